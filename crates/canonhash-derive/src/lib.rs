@@ -0,0 +1,91 @@
+//! `#[derive(CanonicalHash)]`, re-exported by `canonhash` under its `derive`
+//! feature. Use it from there rather than depending on this crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Error, Ident, LitStr};
+
+/// Adds inherent `canonical_sha256` and `canonical_hash` methods to a type
+/// that implements `Serialize`.
+///
+/// `canonical_hash` uses SHA-256 unless the type has
+/// `#[canonical(alg = "...")]`, which takes the names `HashAlgorithm`
+/// parses: `sha256`, `sha384`, `sha512`, `blake2b` and `blake3`, ignoring
+/// case and hyphens. The last two need the matching `canonhash` features.
+#[proc_macro_derive(CanonicalHash, attributes(canonical))]
+pub fn derive_canonical_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let alg = algorithm(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Returns the SHA-256 of the canonical form of `self`, as
+            /// [`canonhash::canonical_sha256`].
+            pub fn canonical_sha256(&self) -> ::core::result::Result<[u8; 32], ::canonhash::CanonError>
+            where
+                Self: ::canonhash::__private::Serialize,
+            {
+                ::canonhash::canonical_sha256(self)
+            }
+
+            /// Returns the digest of the canonical form of `self` under the
+            /// algorithm chosen with `#[canonical(alg = "...")]`, SHA-256 by
+            /// default.
+            pub fn canonical_hash(&self) -> ::core::result::Result<::canonhash::__private::Vec<u8>, ::canonhash::CanonError>
+            where
+                Self: ::canonhash::__private::Serialize,
+            {
+                ::canonhash::hash_with(::canonhash::HashAlgorithm::#alg, self)
+            }
+        }
+    })
+}
+
+/// The `HashAlgorithm` variant named by the type's `#[canonical]`
+/// attribute, if it has one.
+fn algorithm(input: &DeriveInput) -> syn::Result<Ident> {
+    let mut alg = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("canonical"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("alg") {
+                return Err(meta.error("expected `alg = \"...\"`"));
+            }
+            if alg.is_some() {
+                return Err(meta.error("duplicate `alg`"));
+            }
+            let name: LitStr = meta.value()?.parse()?;
+            alg = Some(variant(&name)?);
+            Ok(())
+        })?;
+    }
+    Ok(alg.unwrap_or_else(|| Ident::new("Sha256", Span::call_site())))
+}
+
+fn variant(name: &LitStr) -> syn::Result<Ident> {
+    let variant = match name.value().to_ascii_lowercase().replace('-', "").as_str() {
+        "sha256" => "Sha256",
+        "sha384" => "Sha384",
+        "sha512" => "Sha512",
+        "blake2b" | "blake2b512" => "Blake2b",
+        "blake3" => "Blake3",
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                format!("unknown hash algorithm `{}`", name.value()),
+            ))
+        }
+    };
+    Ok(Ident::new(variant, name.span()))
+}
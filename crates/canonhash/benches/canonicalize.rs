@@ -0,0 +1,232 @@
+//! Canonicalization throughput on realistic and adversarial payloads.
+//!
+//! Run with `cargo bench --bench canonicalize`. Compare `btreemap_ascii`
+//! (keys arrive sorted, so members are never reordered) with `hashmap_ascii`
+//! (same keys, arbitrary order, so every object is sorted) for the cost of
+//! the sort. `btreemap_non_ascii` has keys whose `str` order differs from
+//! their UTF-16 order and must fall back to sorting. `typed` compares the
+//! generic path with a [`TypedCanonicalizer`] reused across iterations, and
+//! `value` the generic path with [`canonicalize_value`] on a parsed document.
+//!
+//! `floats` canonicalizes a document of mostly non-integral doubles. To
+//! compare the float backends, save a baseline without the `ryu` feature and
+//! measure against it with the feature on:
+//!
+//! ```text
+//! cargo bench --bench canonicalize -- floats --save-baseline fmt
+//! cargo bench --bench canonicalize --features ryu -- floats --baseline fmt
+//! ```
+//!
+//! `hex` encodes 100,000 SHA-256 digests and compares the same way against
+//! the `faster-hex` feature.
+//!
+//! [`TypedCanonicalizer`]: canonhash::TypedCanonicalizer
+//! [`canonicalize_value`]: canonhash::canonicalize_value
+
+use std::collections::{BTreeMap, HashMap};
+use std::hint::black_box;
+
+use canonhash::HashAlgorithm;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct TestData {
+    #[serde(rename = "testId")]
+    test_id: String,
+    timestamp: String,
+    data: DataContent,
+}
+
+#[derive(Serialize)]
+struct DataContent {
+    modules: Vec<String>,
+    nested: Nested,
+}
+
+#[derive(Serialize)]
+struct Nested {
+    array: Vec<u32>,
+    object: HashMap<String, String>,
+}
+
+fn test_data() -> TestData {
+    TestData {
+        test_id: "golden-hash-test-v1".to_owned(),
+        timestamp: "2026-02-12T15:50:12Z".to_owned(),
+        data: DataContent {
+            modules: ["marketIntelligence", "notifications", "automation"]
+                .map(String::from)
+                .to_vec(),
+            nested: Nested {
+                array: vec![3, 1, 2],
+                object: HashMap::from([
+                    ("zeta".to_owned(), "last".to_owned()),
+                    ("alpha".to_owned(), "first".to_owned()),
+                ]),
+            },
+        },
+    }
+}
+
+fn ascii_keys(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("field_{i:05}")).collect()
+}
+
+/// Keys alternating between a U+E000 and a U+1F600 prefix. `str` order puts
+/// every U+E000 key first, UTF-16 order puts every U+1F600 key first.
+fn non_ascii_keys(n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| {
+            let prefix = if i % 2 == 0 { '\u{e000}' } else { '\u{1f600}' };
+            format!("{prefix}{i:05}")
+        })
+        .collect()
+}
+
+fn bench_test_data(c: &mut Criterion) {
+    let data = test_data();
+    let len = canonhash::canonical_len(&data).unwrap();
+    let mut group = c.benchmark_group("test_data");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("canonicalize", |b| {
+        b.iter(|| canonhash::canonicalize(black_box(&data)).unwrap())
+    });
+    group.bench_function("canonical_sha256", |b| {
+        b.iter(|| canonhash::canonical_sha256(black_box(&data)).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_typed(c: &mut Criterion) {
+    let data = test_data();
+    let typed = canonhash::TypedCanonicalizer::<TestData>::new();
+    let mut group = c.benchmark_group("typed");
+    group.bench_function("generic_canonicalize", |b| {
+        b.iter(|| canonhash::canonicalize(black_box(&data)).unwrap())
+    });
+    group.bench_function("typed_canonicalize", |b| {
+        b.iter(|| typed.canonicalize(black_box(&data)).unwrap())
+    });
+    group.bench_function("generic_sha256", |b| {
+        b.iter(|| canonhash::hash_with(HashAlgorithm::Sha256, black_box(&data)).unwrap())
+    });
+    group.bench_function("typed_sha256", |b| {
+        b.iter(|| {
+            typed
+                .hash_with(HashAlgorithm::Sha256, black_box(&data))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("value");
+    for n in [16, 1024] {
+        let map: HashMap<String, Vec<u64>> =
+            ascii_keys(n).into_iter().map(|k| (k, vec![1, 2])).collect();
+        let value = serde_json::to_value(map).unwrap();
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("generic", n), &value, |b, v| {
+            b.iter(|| canonhash::canonicalize(black_box(v)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("direct", n), &value, |b, v| {
+            b.iter(|| canonhash::canonicalize_value(black_box(v)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Readings with one integral and three fractional fields each, spread over
+/// the plain and exponent ranges of the ES layout.
+fn float_records(n: usize) -> Vec<BTreeMap<&'static str, f64>> {
+    let mut state = 0x2545f4914f6cdd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    (0..n)
+        .map(|i| {
+            BTreeMap::from([
+                ("id", i as f64),
+                ("lat", next() * 180.0 - 90.0),
+                ("tiny", next() * 1e-9),
+                ("huge", next() * 1e25),
+            ])
+        })
+        .collect()
+}
+
+fn bench_floats(c: &mut Criterion) {
+    let records = float_records(1024);
+    let mut group = c.benchmark_group("floats");
+    group.throughput(Throughput::Elements(4 * records.len() as u64));
+    group.bench_function("canonicalize", |b| {
+        b.iter(|| canonhash::canonicalize(black_box(&records)).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_hex(c: &mut Criterion) {
+    let digests: Vec<[u8; 32]> = (0u32..100_000)
+        .map(|i| canonhash::canonical_sha256(&i).unwrap())
+        .collect();
+    let mut group = c.benchmark_group("hex");
+    group.throughput(Throughput::Elements(digests.len() as u64));
+    group.bench_function("encode_digest", |b| {
+        b.iter(|| {
+            for digest in &digests {
+                black_box(canonhash::encode_digest(
+                    black_box(digest),
+                    canonhash::DigestEncoding::Hex,
+                ));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_maps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("maps");
+    for n in [16, 1024] {
+        let btree_ascii: BTreeMap<String, u64> = ascii_keys(n).into_iter().zip(0..).collect();
+        let hash_ascii: HashMap<String, u64> = btree_ascii.clone().into_iter().collect();
+        let btree_non_ascii: BTreeMap<String, u64> =
+            non_ascii_keys(n).into_iter().zip(0..).collect();
+
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(
+            BenchmarkId::new("btreemap_ascii", n),
+            &btree_ascii,
+            |b, m| b.iter(|| canonhash::canonicalize(black_box(m)).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("hashmap_ascii", n), &hash_ascii, |b, m| {
+            b.iter(|| canonhash::canonicalize(black_box(m)).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("btreemap_non_ascii", n),
+            &btree_non_ascii,
+            |b, m| b.iter(|| canonhash::canonicalize(black_box(m)).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("btreemap_ascii_sha256", n),
+            &btree_ascii,
+            |b, m| b.iter(|| canonhash::canonical_sha256(black_box(m)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_test_data,
+    bench_typed,
+    bench_value,
+    bench_floats,
+    bench_hex,
+    bench_maps
+);
+criterion_main!(benches);
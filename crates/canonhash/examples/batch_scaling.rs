@@ -0,0 +1,45 @@
+//! Measures `hash_batch` throughput at increasing thread counts.
+//!
+//! Run with `cargo run --release --features rayon --example batch_scaling`.
+
+use std::time::Instant;
+
+use canonhash::{hash_batch, HashAlgorithm};
+use serde_json::{json, Value};
+
+fn main() {
+    let records: Vec<Value> = (0..100_000)
+        .map(|i| {
+            json!({
+                "id": i,
+                "source": "ingest",
+                "tags": ["alpha", "beta", "gamma"],
+                "metrics": {"latency_ms": i % 97, "bytes": i * 31, "ratio": 0.5 + f64::from(i % 7)},
+            })
+        })
+        .collect();
+
+    let max_threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut threads = 1;
+    let mut baseline = None;
+    while threads <= max_threads {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("thread pool");
+        let start = Instant::now();
+        let digests = pool
+            .install(|| hash_batch(&records, HashAlgorithm::Sha256))
+            .expect("records hash");
+        let elapsed = start.elapsed().as_secs_f64();
+        assert_eq!(digests.len(), records.len());
+
+        let base = *baseline.get_or_insert(elapsed);
+        println!(
+            "{threads:>3} threads: {:>8.0} records/s  speedup {:.2}x",
+            records.len() as f64 / elapsed,
+            base / elapsed,
+        );
+        threads *= 2;
+    }
+}
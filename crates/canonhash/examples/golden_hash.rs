@@ -0,0 +1,53 @@
+//! Computes the golden hash of the ops-stack fixture record.
+//!
+//! Run with `cargo run --example golden_hash`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct TestData {
+    #[serde(rename = "testId")]
+    test_id: String,
+    timestamp: String,
+    data: DataContent,
+}
+
+#[derive(Serialize)]
+struct DataContent {
+    modules: Vec<String>,
+    nested: Nested,
+}
+
+#[derive(Serialize)]
+struct Nested {
+    array: Vec<u32>,
+    object: HashMap<String, String>,
+}
+
+fn main() -> Result<(), canonhash::CanonError> {
+    let data = TestData {
+        test_id: "golden-hash-test-v1".to_owned(),
+        timestamp: "2026-02-12T15:50:12Z".to_owned(),
+        data: DataContent {
+            modules: ["marketIntelligence", "notifications", "automation"]
+                .map(String::from)
+                .to_vec(),
+            nested: Nested {
+                array: vec![3, 1, 2],
+                object: HashMap::from([
+                    ("zeta".to_owned(), "last".to_owned()),
+                    ("alpha".to_owned(), "first".to_owned()),
+                ]),
+            },
+        },
+    };
+
+    let (canonical, digest) =
+        canonhash::canonicalize_and_hash(&data, canonhash::HashAlgorithm::Sha256)?;
+
+    println!("canonical: {canonical}");
+    println!("sha256:    {}", hex::encode(digest));
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Arbitrary bytes through the JSON text entry points. They must return
+//! `Ok` or `Err`, never panic, and anything they accept must come out in
+//! canonical form.
+
+#![no_main]
+
+use canonhash::{canonicalize_reader, canonicalize_str_strict, is_canonical, is_canonical_str};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let from_reader = canonicalize_reader(data);
+    if let Ok(canonical) = &from_reader {
+        assert!(is_canonical(canonical), "not canonical: {canonical:?}");
+        assert_eq!(canonicalize_reader(&canonical[..]).unwrap(), *canonical);
+    }
+
+    let Ok(text) = core::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(canonical) = canonicalize_str_strict(text) {
+        // The strict parse accepts a subset of what the reader does, and
+        // produces the same bytes for it.
+        assert_eq!(Some(&canonical), from_reader.as_ref().ok());
+    }
+    let _ = is_canonical_str(text);
+});
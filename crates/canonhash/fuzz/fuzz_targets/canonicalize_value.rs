@@ -0,0 +1,59 @@
+//! Arbitrary `serde_json::Value`s through `canonicalize`. Any value a
+//! parser could produce canonicalizes without error, and the output is
+//! accepted by `is_canonical` and reproduces itself.
+
+#![no_main]
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use canonhash::{canonicalize, canonicalize_value, is_canonical};
+use libfuzzer_sys::fuzz_target;
+use serde_json::{Map, Number, Value};
+
+/// Below the 128 levels `serde_json` and `canonicalize` allow.
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug)]
+struct ArbitraryValue(Value);
+
+impl<'a> Arbitrary<'a> for ArbitraryValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        value(u, 0).map(ArbitraryValue)
+    }
+}
+
+fn value(u: &mut Unstructured<'_>, depth: usize) -> Result<Value> {
+    let kinds = if depth < MAX_DEPTH { 8 } else { 6 };
+    Ok(match u.choose_index(kinds)? {
+        0 => Value::Null,
+        1 => Value::Bool(u.arbitrary()?),
+        2 => Value::Number(u.arbitrary::<u64>()?.into()),
+        3 => Value::Number(u.arbitrary::<i64>()?.into()),
+        4 => Number::from_f64(u.arbitrary()?).map_or(Value::Null, Value::Number),
+        5 => Value::String(u.arbitrary()?),
+        6 => {
+            let len = u.arbitrary_len::<u8>()?;
+            let mut elements = Vec::with_capacity(len);
+            for _ in 0..len {
+                elements.push(value(u, depth + 1)?);
+            }
+            Value::Array(elements)
+        }
+        _ => {
+            let len = u.arbitrary_len::<u8>()?;
+            let mut members = Map::new();
+            for _ in 0..len {
+                members.insert(u.arbitrary()?, value(u, depth + 1)?);
+            }
+            Value::Object(members)
+        }
+    })
+}
+
+fuzz_target!(|input: ArbitraryValue| {
+    let canonical = canonicalize(&input.0).expect("parsable values always canonicalize");
+    assert!(is_canonical(&canonical), "not canonical: {canonical:?}");
+    assert_eq!(canonicalize_value(&input.0).unwrap(), canonical);
+
+    let reparsed: Value = serde_json::from_slice(&canonical).unwrap();
+    assert_eq!(canonicalize(&reparsed).unwrap(), canonical);
+});
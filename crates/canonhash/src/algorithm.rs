@@ -0,0 +1,174 @@
+//! Runtime-selectable hash algorithms.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// A digest algorithm that canonical forms can be hashed with.
+///
+/// The SHA-2 family is always available. `Blake2b` and `Blake3` are behind the
+/// `blake2` and `blake3` features so callers that only need SHA-256 don't pull
+/// those crates in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+    /// BLAKE2b with a 512-bit output.
+    #[cfg(feature = "blake2")]
+    Blake2b,
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The lowercase name used by `Display` and accepted by `FromStr`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha384 => "sha384",
+            HashAlgorithm::Sha512 => "sha512",
+            #[cfg(feature = "blake2")]
+            HashAlgorithm::Blake2b => "blake2b",
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The digest length in bytes.
+    pub fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+            #[cfg(feature = "blake2")]
+            HashAlgorithm::Blake2b => 64,
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => 32,
+        }
+    }
+}
+
+/// The process-wide default, as [`HashAlgorithm::to_tag`] gives it.
+static DEFAULT: AtomicU8 = AtomicU8::new(0);
+
+impl HashAlgorithm {
+    fn to_tag(self) -> u8 {
+        match self {
+            HashAlgorithm::Sha256 => 0,
+            HashAlgorithm::Sha384 => 1,
+            HashAlgorithm::Sha512 => 2,
+            #[cfg(feature = "blake2")]
+            HashAlgorithm::Blake2b => 3,
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => 4,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Self {
+        match tag {
+            1 => HashAlgorithm::Sha384,
+            2 => HashAlgorithm::Sha512,
+            #[cfg(feature = "blake2")]
+            3 => HashAlgorithm::Blake2b,
+            #[cfg(feature = "blake3")]
+            4 => HashAlgorithm::Blake3,
+            _ => HashAlgorithm::Sha256,
+        }
+    }
+}
+
+/// Sets the algorithm that [`default_algorithm`] returns, and so the one
+/// [`hash_default`](crate::hash_default) uses, for the whole process.
+///
+/// Intended to be called once at startup. Threads spawned afterwards see the
+/// new value. Changing it while other threads are hashing is safe but
+/// affects only the calls that start after it, and nothing recorded which
+/// algorithm earlier digests used: prefer [`ContentId`](crate::ContentId)
+/// for digests that outlive the process.
+pub fn set_default_algorithm(alg: HashAlgorithm) {
+    DEFAULT.store(alg.to_tag(), Ordering::Relaxed);
+}
+
+/// Returns the algorithm set by [`set_default_algorithm`], or
+/// [`HashAlgorithm::Sha256`] if it hasn't been called.
+pub fn default_algorithm() -> HashAlgorithm {
+    HashAlgorithm::from_tag(DEFAULT.load(Ordering::Relaxed))
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = UnknownAlgorithm;
+
+    /// Parses an algorithm name, ignoring ASCII case and an optional hyphen
+    /// (`"SHA-256"` and `"sha256"` are equivalent).
+    fn from_str(s: &str) -> Result<Self, UnknownAlgorithm> {
+        let normalized = s.to_ascii_lowercase().replace('-', "");
+        match normalized.as_str() {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha384" => Ok(HashAlgorithm::Sha384),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            #[cfg(feature = "blake2")]
+            "blake2b" | "blake2b512" => Ok(HashAlgorithm::Blake2b),
+            #[cfg(feature = "blake3")]
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(UnknownAlgorithm(s.to_owned())),
+        }
+    }
+}
+
+/// Returned when parsing a name that is not a known (or enabled) algorithm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownAlgorithm(pub String);
+
+impl fmt::Display for UnknownAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown or disabled hash algorithm `{}`", self.0)
+    }
+}
+
+impl core::error::Error for UnknownAlgorithm {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_case_insensitively() {
+        assert_eq!("sha256".parse(), Ok(HashAlgorithm::Sha256));
+        assert_eq!("SHA-384".parse(), Ok(HashAlgorithm::Sha384));
+        assert_eq!("Sha512".parse(), Ok(HashAlgorithm::Sha512));
+    }
+
+    #[test]
+    fn display_round_trips_through_from_str() {
+        for alg in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            assert_eq!(alg.to_string().parse(), Ok(alg));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let err = "md5".parse::<HashAlgorithm>().unwrap_err();
+        assert_eq!(err, UnknownAlgorithm("md5".to_owned()));
+        assert_eq!(err.to_string(), "unknown or disabled hash algorithm `md5`");
+    }
+
+    #[cfg(not(feature = "blake3"))]
+    #[test]
+    fn disabled_algorithms_are_unknown() {
+        assert!("blake3".parse::<HashAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Hashing JSON text that arrives through a `tokio::io::AsyncRead`.
+//!
+//! The reader is drained into memory and parsed once it ends. Peak memory is
+//! therefore the whole body plus its parsed `serde_json::Value`, about the
+//! same as reading the body into a `String` yourself; what this saves is the
+//! blocking read. Key sorting means an object can't be emitted before its
+//! last member is seen, so a streaming version would still buffer each
+//! object, but it could hash arrays and scalars as they arrive. The signature
+//! does not depend on how the input is buffered, so that can change without
+//! breaking callers.
+
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+
+/// Reads JSON text from `reader` to the end and returns the SHA-256 of its
+/// canonical form.
+///
+/// The result equals [`canonical_sha256_from_str`] on the same text, as raw
+/// bytes rather than hex. Read failures are [`CanonError::Io`] and invalid
+/// JSON is [`CanonError::Parse`].
+///
+/// [`canonical_sha256_from_str`]: crate::canonical_sha256_from_str
+pub async fn canonical_sha256_async<R>(mut reader: R) -> Result<[u8; 32], CanonError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body).await?;
+    let value: Value = serde_json::from_slice(&body)?;
+    canonical_sha256(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::hash::canonical_sha256_from_str;
+
+    #[tokio::test]
+    async fn matches_the_synchronous_hash() {
+        let text = r#"{ "b": [1.0, 2], "a": {"z": null, "y": "é"} }"#;
+        let digest = canonical_sha256_async(text.as_bytes()).await.unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            canonical_sha256_from_str(text).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_input_split_across_reads() {
+        let reader = b"{\"b\":[1,".chain(&b" 2], \"a\": 3}"[..]);
+        assert_eq!(
+            canonical_sha256_async(reader).await.unwrap(),
+            canonical_sha256(&json!({"a": 3, "b": [1, 2]})).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_json_is_a_parse_error() {
+        assert!(matches!(
+            canonical_sha256_async(&b"{\"a\":"[..]).await,
+            Err(CanonError::Parse(_))
+        ));
+    }
+}
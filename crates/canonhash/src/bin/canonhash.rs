@@ -0,0 +1,160 @@
+//! Command-line canonicalization and hashing of JSON documents.
+//!
+//! Run `canonhash --help` for usage. Built with the `cli` feature.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process::ExitCode;
+
+use canonhash::multihash::{encode_multihash, parse_multihash, Multibase};
+use canonhash::verify::verify_digest;
+use canonhash::{
+    decode_digest, encode_digest, hash_with, to_writer, DigestEncoding, HashAlgorithm,
+};
+use serde_json::Value;
+
+const USAGE: &str = "\
+Usage: canonhash [OPTIONS] [FILE]
+
+Reads a JSON document from FILE (or stdin if FILE is absent or `-`) and prints
+the hash of its RFC 8785 canonical form.
+
+Options:
+      --alg <ALG>       Hash algorithm: sha256 (default), sha384, sha512,
+                        blake2b, blake3
+      --canonical-only  Print the canonical JSON instead of a hash. The output
+                        is exactly the hashed bytes, with no trailing newline.
+      --hex             Print the digest as lowercase hex (default)
+      --base64          Print the digest as standard padded base64
+      --multibase       Print a base58btc multibase multihash
+      --verify <DIGEST> Compare the digest with DIGEST in the chosen encoding
+                        and exit with status 1 if they differ
+  -h, --help            Print this help
+
+Exit status: 0 on success, 1 on a --verify mismatch, 2 on any other error.";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Digest(DigestEncoding),
+    Multibase,
+}
+
+struct Args {
+    alg: HashAlgorithm,
+    canonical_only: bool,
+    encoding: Encoding,
+    verify: Option<String>,
+    input: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        alg: HashAlgorithm::Sha256,
+        canonical_only: false,
+        encoding: Encoding::Digest(DigestEncoding::Hex),
+        verify: None,
+        input: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--alg" => {
+                let name = args.next().ok_or("--alg needs a value")?;
+                parsed.alg = name.parse().map_err(|err| format!("{err}"))?;
+            }
+            "--canonical-only" => parsed.canonical_only = true,
+            "--hex" => parsed.encoding = Encoding::Digest(DigestEncoding::Hex),
+            "--base64" => parsed.encoding = Encoding::Digest(DigestEncoding::Base64),
+            "--multibase" => parsed.encoding = Encoding::Multibase,
+            "--verify" => parsed.verify = Some(args.next().ok_or("--verify needs a value")?),
+            "-" => parsed.input = Some(arg),
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            _ if parsed.input.is_some() => return Err("only one input file is allowed".into()),
+            _ => parsed.input = Some(arg),
+        }
+    }
+    if parsed.canonical_only && parsed.verify.is_some() {
+        return Err("--verify cannot be combined with --canonical-only".into());
+    }
+    Ok(Some(parsed))
+}
+
+fn read_input(path: Option<&str>) -> Result<Value, String> {
+    // `from_reader` parses as bytes arrive instead of buffering the whole
+    // text first.
+    let reader: Box<dyn Read> = match path {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => Box::new(File::open(path).map_err(|err| format!("{path}: {err}"))?),
+    };
+    serde_json::from_reader(BufReader::new(reader)).map_err(|err| format!("invalid JSON: {err}"))
+}
+
+fn encode(encoding: Encoding, alg: HashAlgorithm, digest: &[u8]) -> String {
+    match encoding {
+        Encoding::Digest(enc) => encode_digest(digest, enc),
+        Encoding::Multibase => Multibase::Base58Btc.encode(&encode_multihash(alg, digest)),
+    }
+}
+
+/// Decodes an expected digest given on the command line.
+fn decode(encoding: Encoding, alg: HashAlgorithm, s: &str) -> Result<Vec<u8>, String> {
+    match encoding {
+        Encoding::Digest(enc) => {
+            decode_digest(s, enc).map_err(|err| format!("--verify value: {err}"))
+        }
+        Encoding::Multibase => {
+            let (_, bytes) = Multibase::decode(s).map_err(|err| err.to_string())?;
+            let (declared, digest) = parse_multihash(&bytes).map_err(|err| err.to_string())?;
+            if declared != alg {
+                return Err(format!(
+                    "--verify value is a {declared} multihash, not {alg}"
+                ));
+            }
+            Ok(digest)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<ExitCode, String> {
+    let value = read_input(args.input.as_deref())?;
+
+    if args.canonical_only {
+        let mut out = BufWriter::new(io::stdout().lock());
+        to_writer(&mut out, &value).map_err(|err| err.to_string())?;
+        out.flush().map_err(|err| err.to_string())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let digest = hash_with(args.alg, &value).map_err(|err| err.to_string())?;
+    println!("{}", encode(args.encoding, args.alg, &digest));
+
+    if let Some(expected) = &args.verify {
+        let expected = decode(args.encoding, args.alg, expected)?;
+        // Accept only a digest of the right length; verify_digest compares in
+        // constant time.
+        let matches = verify_digest(args.alg, &value, &expected).map_err(|err| err.to_string())?;
+        if !matches {
+            eprintln!("canonhash: digest does not match --verify value");
+            return Ok(ExitCode::from(1));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("canonhash: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    run(args).unwrap_or_else(|err| {
+        eprintln!("canonhash: {err}");
+        ExitCode::from(2)
+    })
+}
@@ -0,0 +1,1240 @@
+//! RFC 8785 canonical serialization.
+//!
+//! These functions produce the JSON Canonicalization Scheme byte sequence for
+//! any `Serialize` value. The bytes are what callers should feed to a digest;
+//! the `String` form exists for display and storage.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::error::CanonError;
+use crate::ser::{self, Options, Scope, Serializer};
+#[cfg(feature = "std")]
+use crate::sink::IoSink;
+use crate::sink::{CompareSink, CountingSink};
+
+/// Serializes `value` into its RFC 8785 canonical byte sequence.
+pub fn canonicalize<T>(value: &T) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buf = Vec::with_capacity(128);
+    canonicalize_into(value, &mut buf)?;
+    Ok(buf)
+}
+
+/// Appends the canonical form of `value` to `buf`.
+///
+/// The buffer is not cleared first, so callers hashing many records can reuse
+/// one allocation by calling `buf.clear()` between records. On error `buf` is
+/// truncated back to its original length.
+pub fn canonicalize_into<T>(value: &T, buf: &mut Vec<u8>) -> Result<(), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    canonicalize_into_with(value, buf, Options::default(), Scope::default())
+}
+
+pub(crate) fn canonicalize_into_with<T>(
+    value: &T,
+    buf: &mut Vec<u8>,
+    opts: Options,
+    scope: Scope<'_>,
+) -> Result<(), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let start = buf.len();
+    let result = ser::serialize_scoped(value, buf, opts, scope);
+    if result.is_err() {
+        buf.truncate(start);
+    }
+    result
+}
+
+/// Serializes `value` into its canonical form as a `String`.
+///
+/// This is [`canonicalize`] followed by a UTF-8 conversion, which cannot fail
+/// for output produced by this crate.
+pub fn to_string<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    String::from_utf8(canonicalize(value)?).map_err(|_| CanonError::InvalidUtf8)
+}
+
+/// Writes the canonical form of `value` to `writer`.
+///
+/// Arrays and scalars are streamed to the writer as they are serialized.
+/// Objects have to be buffered so their members can be sorted, but the buffer
+/// is per object: peak memory is bounded by the largest single object rather
+/// than by the whole document. Many small writes are issued, so wrap unbuffered
+/// writers such as `File` in a `BufWriter`.
+#[cfg(feature = "std")]
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), CanonError>
+where
+    W: std::io::Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_with(writer, value, Options::default(), Scope::default())
+}
+
+/// Output settings for [`to_writer_with_options`].
+///
+/// These change only the bytes written to the writer. **None of them are part
+/// of the canonical form**: a file written with them is not the input to
+/// hash. Hash the value itself, or [`canonicalize`] its bytes.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WriteOptions {
+    trailing_newline: bool,
+    html_safe: bool,
+}
+
+#[cfg(feature = "std")]
+impl WriteOptions {
+    /// Returns settings that write the canonical form and nothing else.
+    pub fn new() -> Self {
+        WriteOptions::default()
+    }
+
+    /// Ends the output with `\n`, for files that tools like git and diff
+    /// treat as text.
+    #[must_use]
+    pub fn trailing_newline(mut self, on: bool) -> Self {
+        self.trailing_newline = on;
+        self
+    }
+
+    /// Escapes `<`, `>`, `&`, U+2028 and U+2029 as `\u003c`, `\u003e`,
+    /// `\u0026`, `\u2028` and `\u2029`, so the output can be embedded in an
+    /// HTML `<script>` element. It still parses as the same value, but it
+    /// is **not** canonical: never hash it or store it as the canonical
+    /// form.
+    #[must_use]
+    pub fn html_safe(mut self, on: bool) -> Self {
+        self.html_safe = on;
+        self
+    }
+}
+
+/// Writes the canonical form of `value` to `writer`, followed by whatever
+/// `options` add.
+///
+/// **Neither the trailing newline nor the HTML-safe escapes are part of the
+/// canonical form, and output with them must not be hashed.** Anything
+/// reading such a file back for verification has to re-canonicalize the
+/// parsed value.
+///
+/// ```
+/// use canonhash::WriteOptions;
+///
+/// let mut file = Vec::new();
+/// let options = WriteOptions::new().trailing_newline(true);
+/// canonhash::to_writer_with_options(&mut file, &[2, 1], options).unwrap();
+/// assert_eq!(file, b"[2,1]\n");
+///
+/// let mut page = Vec::new();
+/// let options = WriteOptions::new().html_safe(true);
+/// canonhash::to_writer_with_options(&mut page, &"</script>", options).unwrap();
+/// assert_eq!(page, br#""\u003c/script\u003e""#);
+/// ```
+#[cfg(feature = "std")]
+pub fn to_writer_with_options<W, T>(
+    mut writer: W,
+    value: &T,
+    options: WriteOptions,
+) -> Result<(), CanonError>
+where
+    W: std::io::Write,
+    T: ?Sized + Serialize,
+{
+    if options.html_safe {
+        let mut escaped = HtmlSafe {
+            inner: &mut writer,
+            held: 0,
+        };
+        to_writer_with(&mut escaped, value, Options::default(), Scope::default())?;
+        escaped.release()?;
+    } else {
+        to_writer_with(&mut writer, value, Options::default(), Scope::default())?;
+    }
+    if options.trailing_newline {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// A writer that applies [`WriteOptions::html_safe`] to canonical JSON on
+/// its way to `inner`.
+///
+/// The characters it escapes can only occur inside strings in canonical
+/// JSON, so it needs no parsing. U+2028 and U+2029 are three bytes each,
+/// and `held` counts how many of their two-byte prefix, `E2 80`, the last
+/// write ended with.
+#[cfg(feature = "std")]
+struct HtmlSafe<W> {
+    inner: W,
+    held: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> HtmlSafe<W> {
+    /// Writes out a prefix held back at the end of the output.
+    fn release(&mut self) -> std::io::Result<()> {
+        let held = core::mem::take(&mut self.held);
+        self.inner.write_all(&[0xE2, 0x80][..held])
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> std::io::Write for HtmlSafe<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut out = Vec::with_capacity(buf.len());
+        for &b in buf {
+            match (self.held, b) {
+                (0, 0xE2) | (1, 0x80) => {
+                    self.held += 1;
+                    continue;
+                }
+                (2, 0xA8) => out.extend_from_slice(b"\\u2028"),
+                (2, 0xA9) => out.extend_from_slice(b"\\u2029"),
+                _ => {
+                    out.extend_from_slice(&[0xE2, 0x80][..self.held]);
+                    match b {
+                        0xE2 => {
+                            self.held = 1;
+                            continue;
+                        }
+                        b'<' => out.extend_from_slice(b"\\u003c"),
+                        b'>' => out.extend_from_slice(b"\\u003e"),
+                        b'&' => out.extend_from_slice(b"\\u0026"),
+                        _ => out.push(b),
+                    }
+                }
+            }
+            self.held = 0;
+        }
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn to_writer_with<W, T>(
+    writer: W,
+    value: &T,
+    opts: Options,
+    scope: Scope<'_>,
+) -> Result<(), CanonError>
+where
+    W: std::io::Write,
+    T: ?Sized + Serialize,
+{
+    ser::serialize_scoped(value, &mut IoSink(writer), opts, scope)
+}
+
+/// Reads JSON text from `reader` and returns its canonical form.
+///
+/// Invalid JSON is a [`CanonError::Parse`], as is a read error, since
+/// `serde_json` reports both the same way. The reader is read unbuffered, so
+/// wrap a `File` in a `BufReader`. Together with [`to_writer`] this
+/// canonicalizes one file into another:
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let canonical = canonhash::canonicalize_reader(BufReader::new(File::open("in.json")?))?;
+/// std::fs::write("out.json", canonical)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn canonicalize_reader<R: std::io::Read>(reader: R) -> Result<Vec<u8>, CanonError> {
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    canonicalize(&value)
+}
+
+/// Parses JSON text and returns its canonical form, rejecting objects that
+/// repeat a key and escapes that are not characters.
+///
+/// Parsing into a `serde_json::Value` keeps the last of any repeated keys,
+/// so `{"a":1,"a":2}` would silently canonicalize as `{"a":2}`. Here it is
+/// [`CanonError::DuplicateKey`] instead. Keys are compared after unescaping,
+/// so `"a"` and `"\u0061"` are the same key.
+///
+/// A `\u` escape of a lone surrogate, such as `"\uD800"`, is
+/// [`CanonError::InvalidSurrogate`]; an escaped pair such as
+/// `"\uD83D\uDE00"` is the one character it encodes and is written as raw
+/// UTF-8. (Parsing elsewhere rejects lone surrogates too, as a
+/// [`CanonError::Parse`].)
+///
+/// The text is read three times: for surrogates, for keys and to parse.
+pub fn canonicalize_str_strict(json: &str) -> Result<Vec<u8>, CanonError> {
+    crate::strict::check_surrogates(json)?;
+    crate::strict::check_duplicates(json)?;
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    canonicalize(&value)
+}
+
+/// Returns the length in bytes of the canonical form of `value` without
+/// keeping the output.
+///
+/// Useful for length-prefixed framing and for pre-sizing buffers. As with
+/// [`to_writer`], object members are still buffered while each object is
+/// sorted, but the document as a whole is never held.
+pub fn canonical_len<T>(value: &T) -> Result<usize, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut sink = CountingSink::default();
+    value.serialize(Serializer::with_options(&mut sink, Options::default()))?;
+    Ok(sink.0)
+}
+
+/// Returns whether `a` and `b` have the same canonical form.
+///
+/// Only `a` is canonicalized in full. The output for `b` is compared as it
+/// is produced and abandoned at the first byte that differs, so documents
+/// that differ early are cheap to tell apart. As with [`to_writer`], object
+/// members are buffered while each object is sorted, so a difference inside
+/// an object is found once that object is complete. Unlike comparing
+/// digests, there is no chance of a collision.
+///
+/// Errors from `a` are returned, as are errors from `b` that come before any
+/// difference. Once the outputs differ the answer is `false` whatever the
+/// rest of `b` holds.
+pub fn canonical_eq<A, B>(a: &A, b: &B) -> Result<bool, CanonError>
+where
+    A: ?Sized + Serialize,
+    B: ?Sized + Serialize,
+{
+    let expected = canonicalize(a)?;
+    let mut sink = CompareSink {
+        expected: &expected,
+        matched: 0,
+        differs: false,
+    };
+    match b.serialize(Serializer::with_options(&mut sink, Options::default())) {
+        Ok(()) => Ok(sink.matched == expected.len()),
+        Err(_) if sink.differs => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        zeta: u32,
+        alpha: &'static str,
+        middle: Vec<i32>,
+    }
+
+    #[test]
+    fn sorts_struct_fields_and_strips_whitespace() {
+        let record = Record {
+            zeta: 1,
+            alpha: "a",
+            middle: vec![3, 1, 2],
+        };
+        assert_eq!(
+            to_string(&record).unwrap(),
+            r#"{"alpha":"a","middle":[3,1,2],"zeta":1}"#
+        );
+    }
+
+    #[test]
+    fn sorts_nested_map_keys() {
+        let mut inner = HashMap::new();
+        inner.insert("b", 2);
+        inner.insert("a", 1);
+        let mut outer = HashMap::new();
+        outer.insert("y", inner.clone());
+        outer.insert("x", inner);
+        assert_eq!(
+            to_string(&outer).unwrap(),
+            r#"{"x":{"a":1,"b":2},"y":{"a":1,"b":2}}"#
+        );
+    }
+
+    #[test]
+    fn flattened_keys_sort_with_the_parents() {
+        #[derive(Serialize)]
+        struct Parent {
+            d: u8,
+            #[serde(flatten)]
+            extra: HashMap<&'static str, u8>,
+            b: u8,
+            #[serde(flatten)]
+            inner: Inner,
+        }
+        #[derive(Serialize)]
+        struct Inner {
+            e: u8,
+            c: u8,
+        }
+
+        let value = Parent {
+            d: 4,
+            extra: HashMap::from([("a", 1), ("f", 6)]),
+            b: 2,
+            inner: Inner { e: 5, c: 3 },
+        };
+        let expected = r#"{"a":1,"b":2,"c":3,"d":4,"e":5,"f":6}"#;
+        assert_eq!(to_string(&value).unwrap(), expected);
+        assert_eq!(
+            crate::TypedCanonicalizer::new()
+                .canonicalize(&value)
+                .unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn flattened_keys_that_collide_are_an_error() {
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize)]
+        struct Parent {
+            a: u8,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, u8>,
+        }
+
+        let value = Parent {
+            a: 1,
+            extra: BTreeMap::from([("a", 2)]),
+        };
+        assert!(matches!(
+            canonicalize(&value),
+            Err(CanonError::DuplicateKey { ref key }) if key == "a"
+        ));
+        let value = Parent {
+            a: 1,
+            extra: BTreeMap::from([("0", 0), ("a", 2)]),
+        };
+        assert!(matches!(
+            canonicalize(&value),
+            Err(CanonError::DuplicateKey { ref key }) if key == "a"
+        ));
+    }
+
+    #[test]
+    fn object_members_come_back_sorted_and_distinct() {
+        use crate::ser::object_members;
+
+        let members = object_members(r#"{"b":[1, 2],"😀":{},"a":"x"}"#).unwrap();
+        let members: Vec<_> = members.iter().map(|(k, v)| (k.as_str(), v.get())).collect();
+        assert_eq!(members, [("a", r#""x""#), ("b", "[1, 2]"), ("😀", "{}")]);
+        assert!(matches!(
+            object_members(r#"{"b":1,"a":2,"b":3}"#),
+            Err(CanonError::DuplicateKey { ref key }) if key == "b"
+        ));
+    }
+
+    #[test]
+    fn sorts_keys_by_utf16_code_units() {
+        // UTF-8 byte order would put U+FFFF (EF BF BF) before the emoji
+        // (F0 9F 98 80); UTF-16 puts the emoji's high surrogate 0xD83D first.
+        let value = json!({"\u{FFFF}": 1, "😀": 2});
+        assert_eq!(to_string(&value).unwrap(), "{\"😀\":2,\"\u{FFFF}\":1}");
+    }
+
+    #[test]
+    fn utf16_comparison_matches_code_unit_order() {
+        let chars = [
+            'a',
+            '\u{7f}',
+            'é',
+            '\u{7ff}',
+            '\u{800}',
+            '\u{d7ff}',
+            '\u{e000}',
+            '\u{ffff}',
+            '\u{10000}',
+            '\u{1f600}',
+            '\u{10ffff}',
+        ];
+        let strings: Vec<String> = chars
+            .iter()
+            .flat_map(|&x| chars.iter().map(move |&y| format!("{x}{y}")))
+            .chain(chars.iter().map(|c| c.to_string()))
+            .collect();
+        for a in &strings {
+            for b in &strings {
+                assert_eq!(
+                    crate::ser::cmp_utf16(a, b),
+                    a.encode_utf16().cmp(b.encode_utf16()),
+                    "{a:?} vs {b:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sorts_rfc8785_example_keys() {
+        // RFC 8785, section 3.2.3.
+        let value = json!({
+            "\u{20ac}": "Euro Sign",
+            "\r": "Carriage Return",
+            "\u{fb33}": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "\u{1f600}": "Emoji: Grinning Face",
+            "\u{80}": "Control",
+            "\u{f6}": "Latin Small Letter O With Diaeresis"
+        });
+        assert_eq!(
+            to_string(&value).unwrap(),
+            concat!(
+                "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",",
+                "\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\"\u{20ac}\":\"Euro Sign\",",
+                "\"\u{1f600}\":\"Emoji: Grinning Face\",",
+                "\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}"
+            )
+        );
+    }
+
+    #[test]
+    fn integer_keys_sort_as_strings() {
+        let mut map = HashMap::new();
+        map.insert(2, "two");
+        map.insert(-1, "minus one");
+        map.insert(10, "ten");
+        assert_eq!(
+            to_string(&map).unwrap(),
+            r#"{"-1":"minus one","10":"ten","2":"two"}"#
+        );
+    }
+
+    #[test]
+    fn enum_and_bool_keys_sort_by_their_strings() {
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Region {
+            West,
+            East,
+            #[serde(rename = "central")]
+            Central,
+        }
+
+        // BTreeMap iterates in `Ord` order (West, East, Central); the output
+        // must be in key-string order instead.
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(Region::West, 1);
+        map.insert(Region::East, 2);
+        map.insert(Region::Central, 3);
+        assert_eq!(
+            to_string(&map).unwrap(),
+            r#"{"East":2,"West":1,"central":3}"#
+        );
+
+        let mut flags = HashMap::new();
+        flags.insert(true, 1);
+        flags.insert(false, 0);
+        assert_eq!(to_string(&flags).unwrap(), r#"{"false":0,"true":1}"#);
+    }
+
+    #[test]
+    fn value_objects_are_sorted_regardless_of_insertion_order() {
+        let mut inner = serde_json::Map::new();
+        for key in ["z", "m", "a"] {
+            inner.insert(key.to_owned(), json!(key));
+        }
+        let mut outer = serde_json::Map::new();
+        outer.insert("zeta".to_owned(), serde_json::Value::Object(inner));
+        outer.insert("alpha".to_owned(), json!([3, 1, 2]));
+        let value = serde_json::Value::Object(outer);
+        assert_eq!(
+            to_string(&value).unwrap(),
+            r#"{"alpha":[3,1,2],"zeta":{"a":"a","m":"m","z":"z"}}"#
+        );
+    }
+
+    #[test]
+    fn parsed_value_matches_typed_struct() {
+        let record = Record {
+            zeta: 1,
+            alpha: "a",
+            middle: vec![3, 1, 2],
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(r#"{ "zeta": 1.0, "middle": [3, 1, 2], "alpha": "a" }"#).unwrap();
+        assert_eq!(
+            canonicalize(&value).unwrap(),
+            canonicalize(&record).unwrap()
+        );
+    }
+
+    #[test]
+    fn skipped_fields_are_absent_not_null() {
+        #[derive(Serialize)]
+        struct WithNote {
+            name: &'static str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            note: Option<&'static str>,
+            count: u32,
+        }
+
+        #[derive(Serialize)]
+        struct WithoutNote {
+            name: &'static str,
+            count: u32,
+        }
+
+        let skipped = WithNote {
+            name: "x",
+            note: None,
+            count: 1,
+        };
+        let canonical = canonicalize(&skipped).unwrap();
+        assert_eq!(canonical, br#"{"count":1,"name":"x"}"#);
+        assert_eq!(
+            canonical,
+            canonicalize(&serde_json::to_value(&skipped).unwrap()).unwrap()
+        );
+        assert_eq!(
+            crate::canonical_sha256(&skipped).unwrap(),
+            crate::canonical_sha256(&WithoutNote {
+                name: "x",
+                count: 1
+            })
+            .unwrap()
+        );
+
+        let present = WithNote {
+            note: Some("n"),
+            ..skipped
+        };
+        assert_eq!(
+            canonicalize(&present).unwrap(),
+            br#"{"count":1,"name":"x","note":"n"}"#
+        );
+    }
+
+    #[test]
+    fn string_form_matches_bytes() {
+        let value = json!({"b": [true, null], "a": "text"});
+        assert_eq!(
+            to_string(&value).unwrap().into_bytes(),
+            canonicalize(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn canonicalize_into_appends_to_existing_buffer() {
+        let mut buf = b"prefix:".to_vec();
+        canonicalize_into(&json!({"k": 1}), &mut buf).unwrap();
+        assert_eq!(buf, br#"prefix:{"k":1}"#);
+
+        buf.clear();
+        canonicalize_into(&json!([1, 2]), &mut buf).unwrap();
+        assert_eq!(buf, b"[1,2]");
+    }
+
+    #[test]
+    fn canonicalize_into_restores_buffer_on_error() {
+        let mut bad = HashMap::new();
+        bad.insert(vec![1u8], 1);
+        let mut buf = b"keep".to_vec();
+        assert!(canonicalize_into(&vec![bad], &mut buf).is_err());
+        assert_eq!(buf, b"keep");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn to_writer_matches_canonicalize() {
+        let value = json!({"b": [1, {"d": 4, "c": 3}], "a": null});
+        let mut out = Vec::new();
+        to_writer(&mut out, &value).unwrap();
+        assert_eq!(out, canonicalize(&value).unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn canonicalize_reader_parses_and_canonicalizes() {
+        let text = br#" {"b": [1.0, 2], "a": "\u00e9"} "#;
+        assert_eq!(
+            canonicalize_reader(&text[..]).unwrap(),
+            "{\"a\":\"\u{e9}\",\"b\":[1,2]}".as_bytes()
+        );
+        assert!(matches!(
+            canonicalize_reader(&b"{\"a\": 1"[..]),
+            Err(CanonError::Parse(_))
+        ));
+        assert!(matches!(
+            canonicalize_reader(&b"[1] [2]"[..]),
+            Err(CanonError::Parse(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn trailing_newline_is_the_only_difference() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        let canonical = canonicalize(&value).unwrap();
+
+        let mut plain = Vec::new();
+        to_writer_with_options(&mut plain, &value, WriteOptions::default()).unwrap();
+        assert_eq!(plain, canonical);
+
+        let mut file = Vec::new();
+        let options = WriteOptions::new().trailing_newline(true);
+        to_writer_with_options(&mut file, &value, options).unwrap();
+        assert_eq!(file.len(), canonical.len() + 1);
+        assert_eq!(file.strip_suffix(b"\n"), Some(canonical.as_slice()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn html_safe_output_differs_only_in_its_escapes() {
+        let value =
+            json!({"html": "<script>alert('&')</script>", "ls": "a\u{2028}b\u{2029}", "n": 1});
+        let strict = to_string(&value).unwrap();
+        let mut html = Vec::new();
+        let options = WriteOptions::new().html_safe(true);
+        to_writer_with_options(&mut html, &value, options).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(
+            html,
+            r#"{"html":"\u003cscript\u003ealert('\u0026')\u003c/script\u003e","ls":"a\u2028b\u2029","n":1}"#
+        );
+        let unescaped = html
+            .replace("\\u003c", "<")
+            .replace("\\u003e", ">")
+            .replace("\\u0026", "&")
+            .replace("\\u2028", "\u{2028}")
+            .replace("\\u2029", "\u{2029}");
+        assert_eq!(unescaped, strict);
+        assert!(!crate::is_canonical(html.as_bytes()));
+        let reparsed: serde_json::Value = serde_json::from_str(&html).unwrap();
+        assert_eq!(to_string(&reparsed).unwrap(), strict);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn html_safe_escapes_separators_split_across_writes() {
+        use std::io::Write;
+
+        let text = "\u{2028}\u{e9}\u{2029}\u{2030}<".as_bytes();
+        let mut out = Vec::new();
+        let mut escaped = HtmlSafe {
+            inner: &mut out,
+            held: 0,
+        };
+        for byte in text {
+            escaped.write_all(core::slice::from_ref(byte)).unwrap();
+        }
+        escaped.write_all(&[0xE2, 0x80]).unwrap();
+        escaped.release().unwrap();
+        let mut expected = b"\\u2028\xC3\xA9\\u2029\xE2\x80\xB0\\u003c".to_vec();
+        expected.extend_from_slice(&[0xE2, 0x80]);
+        assert_eq!(out, expected);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn to_writer_streams_arrays_before_they_complete() {
+        use std::io::Write;
+
+        struct FailAfter {
+            written: Vec<u8>,
+            limit: usize,
+        }
+
+        impl Write for FailAfter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.written.len() + buf.len() > self.limit {
+                    return Err(std::io::Error::other("full"));
+                }
+                self.written.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut sink = FailAfter {
+            written: Vec::new(),
+            limit: 15,
+        };
+        let err = to_writer(&mut sink, &json!([{"b": 1, "a": 2}, "tail"])).unwrap_err();
+        assert!(matches!(err, CanonError::Io(_)));
+        assert_eq!(sink.written, br#"[{"a":2,"b":1},"#);
+    }
+
+    #[test]
+    fn sorted_and_unsorted_maps_agree() {
+        use std::collections::BTreeMap;
+
+        let sorted: BTreeMap<&str, u8> = [("a", 1), ("b", 2), ("c", 3)].into();
+        let unsorted = json!({"c": 3, "a": 1, "b": 2});
+        assert_eq!(to_string(&sorted).unwrap(), r#"{"a":1,"b":2,"c":3}"#);
+        assert_eq!(to_string(&unsorted).unwrap(), r#"{"a":1,"b":2,"c":3}"#);
+
+        // `str` order puts U+E000 before U+1F600; UTF-16 order doesn't.
+        let non_ascii: BTreeMap<&str, u8> = [("\u{e000}", 1), ("\u{1f600}", 2)].into();
+        assert_eq!(
+            to_string(&non_ascii).unwrap(),
+            "{\"\u{1f600}\":2,\"\u{e000}\":1}"
+        );
+    }
+
+    #[test]
+    fn unsorted_objects_are_reordered_in_place() {
+        #[derive(Serialize)]
+        struct Inner {
+            z: u8,
+            a: Vec<Inner>,
+        }
+
+        let value = (
+            "prefix",
+            Inner {
+                z: 1,
+                a: vec![Inner { z: 2, a: vec![] }],
+            },
+        );
+        let expected = r#"["prefix",{"a":[{"a":[],"z":2}],"z":1}]"#;
+        let mut buf = b"kept".to_vec();
+        canonicalize_into(&value, &mut buf).unwrap();
+        assert_eq!(buf, [b"kept".as_slice(), expected.as_bytes()].concat());
+
+        #[cfg(feature = "std")]
+        {
+            let mut written = Vec::new();
+            to_writer(&mut written, &value).unwrap();
+            assert_eq!(written, expected.as_bytes());
+        }
+        assert_eq!(canonical_len(&value).unwrap(), expected.len());
+    }
+
+    #[test]
+    fn canonical_eq_compares_canonical_forms() {
+        #[derive(Serialize)]
+        struct Record {
+            b: Vec<f64>,
+            a: &'static str,
+        }
+
+        let record = Record {
+            b: vec![1.0, 2.5],
+            a: "x",
+        };
+        assert!(canonical_eq(&record, &json!({"a": "x", "b": [1, 2.5]})).unwrap());
+        assert!(!canonical_eq(&record, &json!({"a": "x", "b": [1, 2.6]})).unwrap());
+        assert!(!canonical_eq(&record, &json!({"a": "x"})).unwrap());
+        assert!(!canonical_eq(&json!([1]), &json!([1, 2])).unwrap());
+        assert!(!canonical_eq(&json!([1, 2]), &json!([1])).unwrap());
+        assert!(matches!(
+            canonical_eq(&[f64::NAN], &record),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        assert!(matches!(
+            canonical_eq(&[1.0], &[f64::NAN]),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        assert!(!canonical_eq(&[1.0], &[2.0, f64::NAN]).unwrap());
+    }
+
+    #[test]
+    fn canonical_eq_stops_at_the_first_difference() {
+        struct Counted<'a>(&'a core::cell::Cell<usize>);
+        impl Serialize for Counted<'_> {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_seq((0..1_000_000).inspect(|_| self.0.set(self.0.get() + 1)))
+            }
+        }
+
+        let produced = core::cell::Cell::new(0);
+        assert!(!canonical_eq(&[0, 1, 7], &Counted(&produced)).unwrap());
+        assert_eq!(produced.get(), 3);
+    }
+
+    #[test]
+    fn canonical_len_matches_output_length() {
+        let value = json!({"é": ["\u{1}", 1.5e300, {"b": null, "a": true}], "z": ""});
+        assert_eq!(
+            canonical_len(&value).unwrap(),
+            canonicalize(&value).unwrap().len()
+        );
+        assert_eq!(canonical_len(&()).unwrap(), 4);
+        assert!(matches!(
+            canonical_len(&[f64::NAN]),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn strict_text_input_rejects_duplicate_keys() {
+        assert!(matches!(
+            canonicalize_str_strict(r#"{"a":1,"a":2}"#),
+            Err(CanonError::DuplicateKey { ref key }) if key == "a"
+        ));
+        assert_eq!(
+            canonicalize_str_strict(r#"{ "b": [1.0], "a": {"a": null} }"#).unwrap(),
+            br#"{"a":{"a":null},"b":[1]}"#
+        );
+        assert!(matches!(
+            canonicalize_str_strict("[1,"),
+            Err(CanonError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn strict_text_input_rejects_lone_surrogates() {
+        for (json, offset) in [(r#""\uD800""#, 1), (r#"["\uDC00"]"#, 2)] {
+            assert!(
+                matches!(
+                    canonicalize_str_strict(json),
+                    Err(CanonError::InvalidSurrogate { offset: o }) if o == offset
+                ),
+                "{json}"
+            );
+            assert!(
+                matches!(
+                    crate::canonical_sha256_from_str(json),
+                    Err(CanonError::Parse(_))
+                ),
+                "{json}"
+            );
+        }
+        assert_eq!(
+            canonicalize_str_strict(r#""\uD83D\uDE00""#).unwrap(),
+            "\"\u{1f600}\"".as_bytes()
+        );
+    }
+
+    #[test]
+    fn numbers_use_ecmascript_form() {
+        let value = json!([1.0, 1e21, 5e-324, -0.0, 0.5, u64::MAX]);
+        assert_eq!(
+            to_string(&value).unwrap(),
+            "[1,1e+21,5e-324,0,0.5,18446744073709551615]"
+        );
+    }
+
+    #[test]
+    fn negative_zero_canonicalizes_and_hashes_as_zero() {
+        #[derive(Serialize)]
+        struct Record {
+            balance: f64,
+            delta: f32,
+        }
+
+        let negative = Record {
+            balance: -0.0,
+            delta: -0.0,
+        };
+        let positive = Record {
+            balance: 0.0,
+            delta: 0.0,
+        };
+        assert_eq!(to_string(&negative).unwrap(), r#"{"balance":0,"delta":0}"#);
+        assert_eq!(
+            crate::canonical_sha256(&negative).unwrap(),
+            crate::canonical_sha256(&positive).unwrap()
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str("[-0.0, -0, -0e5]").unwrap();
+        assert_eq!(to_string(&parsed).unwrap(), "[0,0,0]");
+        let marked = crate::Canonicalizer::new().preserve_float_marker(true);
+        assert_eq!(marked.to_string(&-0.0).unwrap(), "0.0");
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn big_integers_survive_byte_for_byte() {
+        let text = r#"{"nonce":123456789012345678901234567890,"neg":-98765432109876543210}"#;
+        let value: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(
+            to_string(&value).unwrap(),
+            r#"{"neg":-98765432109876543210,"nonce":123456789012345678901234567890}"#
+        );
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn arbitrary_precision_non_integers_use_ecmascript_form() {
+        let value: serde_json::Value =
+            serde_json::from_str("[1.50, 1e2, -0, 0.1E1, 5e-7]").unwrap();
+        assert_eq!(to_string(&value).unwrap(), "[1.5,100,0,1,5e-7]");
+
+        let value: serde_json::Value = serde_json::from_str("[1e400]").unwrap();
+        assert!(matches!(
+            canonicalize(&value).unwrap_err(),
+            CanonError::NonFiniteNumber { ref path } if path == "/0"
+        ));
+    }
+
+    /// `n` arrays, each holding the next, built while serializing so the
+    /// test doesn't need a 10,000-level value in memory.
+    struct Deep(usize);
+
+    impl Serialize for Deep {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+
+            let mut seq = serializer.serialize_seq(Some(usize::from(self.0 > 1)))?;
+            if self.0 > 1 {
+                seq.serialize_element(&Deep(self.0 - 1))?;
+            }
+            seq.end()
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_stack_overflow() {
+        for result in [
+            canonicalize(&Deep(10_000)).map(drop),
+            crate::canonical_sha256(&Deep(10_000)).map(drop),
+            canonical_len(&Deep(129)).map(drop),
+            crate::cbor::to_canonical_cbor(&Deep(10_000)).map(drop),
+            crate::cbor::to_canonical_cbor(&Deep(129)).map(drop),
+        ] {
+            assert!(matches!(
+                result,
+                Err(CanonError::DepthExceeded { limit: 128, .. })
+            ));
+        }
+        assert_eq!(canonical_len(&Deep(128)).unwrap(), 256);
+        assert_eq!(
+            crate::cbor::to_canonical_cbor(&Deep(128)).unwrap().len(),
+            128
+        );
+    }
+
+    #[test]
+    fn cbor_depth_errors_match_json() {
+        #[derive(Serialize)]
+        enum Wrap {
+            Tuple(Deep, u8),
+            Newtype(Deep),
+        }
+        let cbor = crate::cbor::to_canonical_cbor;
+        assert!(cbor(&Wrap::Newtype(Deep(127))).is_ok());
+        assert!(cbor(&Wrap::Tuple(Deep(126), 0)).is_ok());
+        for value in [Wrap::Newtype(Deep(128)), Wrap::Tuple(Deep(127), 0)] {
+            let json = canonicalize(&value).unwrap_err();
+            let cbor = cbor(&value).unwrap_err();
+            match (json, cbor) {
+                (
+                    CanonError::DepthExceeded { limit: 128, path },
+                    CanonError::DepthExceeded {
+                        limit: 128,
+                        path: cbor_path,
+                    },
+                ) => assert_eq!(path, cbor_path),
+                other => panic!("unexpected errors: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn enum_wrappers_count_towards_depth() {
+        #[derive(Serialize)]
+        enum Wrap {
+            Tuple(Deep, u8),
+            Newtype(Deep),
+        }
+        assert!(canonicalize(&Wrap::Newtype(Deep(127))).is_ok());
+        assert!(canonicalize(&Wrap::Newtype(Deep(128))).is_err());
+        assert!(canonicalize(&Wrap::Tuple(Deep(126), 0)).is_ok());
+        assert!(canonicalize(&Wrap::Tuple(Deep(127), 0)).is_err());
+    }
+
+    fn non_finite_path<T: Serialize>(value: &T) -> String {
+        match canonicalize(value).unwrap_err() {
+            CanonError::NonFiniteNumber { path } => path.to_pointer(),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_floats_at_root() {
+        assert_eq!(non_finite_path(&f64::NAN), "");
+        assert_eq!(non_finite_path(&f32::INFINITY), "");
+        assert_eq!(non_finite_path(&Some(f64::NEG_INFINITY)), "");
+    }
+
+    #[test]
+    fn non_finite_error_names_nested_path() {
+        #[derive(Serialize)]
+        struct Reading {
+            label: &'static str,
+            values: Vec<f64>,
+        }
+        #[derive(Serialize)]
+        struct Report {
+            ok: bool,
+            readings: Vec<Reading>,
+        }
+
+        let report = Report {
+            ok: true,
+            readings: vec![
+                Reading {
+                    label: "a",
+                    values: vec![1.0],
+                },
+                Reading {
+                    label: "b",
+                    values: vec![2.0, f64::NAN],
+                },
+            ],
+        };
+        assert_eq!(non_finite_path(&report), "/readings/1/values/1");
+
+        let mut map = HashMap::new();
+        map.insert("temp", vec![f64::INFINITY]);
+        assert_eq!(non_finite_path(&vec![map]), "/0/temp/0");
+        assert_eq!(non_finite_path(&(1, f64::NEG_INFINITY)), "/1");
+    }
+
+    #[test]
+    fn non_finite_path_escapes_keys_and_names_variants() {
+        #[derive(Serialize)]
+        enum Sample {
+            Scalar(f64),
+            Pair(f64, f64),
+            Named { value: f64 },
+        }
+
+        let mut map = HashMap::new();
+        map.insert("a/b~c", f64::NAN);
+        assert_eq!(non_finite_path(&map), "/a~1b~0c");
+        assert_eq!(non_finite_path(&Sample::Scalar(f64::NAN)), "/Scalar");
+        assert_eq!(
+            non_finite_path(&Sample::Pair(0.0, f64::INFINITY)),
+            "/Pair/1"
+        );
+        assert_eq!(
+            non_finite_path(&Sample::Named {
+                value: f64::NEG_INFINITY
+            }),
+            "/Named/value"
+        );
+    }
+
+    #[test]
+    fn non_finite_error_display_includes_path() {
+        let err = canonicalize(&vec![f64::NAN]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "non-finite number at `/0` has no JSON representation"
+        );
+    }
+
+    #[test]
+    fn serializer_errors_all_carry_paths() {
+        use std::collections::BTreeMap;
+
+        use crate::PathSegment;
+
+        fn nest<T: Serialize>(inner: T) -> BTreeMap<&'static str, BTreeMap<&'static str, T>> {
+            BTreeMap::from([("data", BTreeMap::from([("nested", inner)]))])
+        }
+        let path_of = |err: CanonError| err.path().unwrap().to_pointer();
+
+        let deep = nest(BTreeMap::from([("array", json!([1, 2, [[]]]))]));
+        let c = crate::Canonicalizer::new();
+        assert_eq!(
+            path_of(c.clone().max_depth(4).canonicalize(&deep).unwrap_err()),
+            "/data/nested/array/2"
+        );
+        let wide = nest(json!({"array": [], "more": 0}));
+        assert_eq!(
+            path_of(
+                c.clone()
+                    .max_object_keys(1)
+                    .canonicalize(&wide)
+                    .unwrap_err()
+            ),
+            "/data/nested"
+        );
+        assert_eq!(
+            path_of(c.max_total_bytes(30).canonicalize(&deep).unwrap_err()),
+            "/data/nested/array/1"
+        );
+        let keys = nest(BTreeMap::from([(Some(1), 0)]));
+        let err = canonicalize(&keys).unwrap_err();
+        assert!(matches!(err, CanonError::KeyMustBeAString { .. }));
+        assert_eq!(
+            err.path().unwrap().segments(),
+            [
+                PathSegment::Key("data".into()),
+                PathSegment::Key("nested".into())
+            ]
+        );
+
+        struct Refuses;
+        impl Serialize for Refuses {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("refused"))
+            }
+        }
+        let err = canonicalize(&nest(vec![Refuses])).unwrap_err();
+        assert!(matches!(err, CanonError::Custom { ref message, .. } if message == "refused"));
+        assert_eq!(err.to_string(), "refused at `/data/nested/0`");
+        assert!(CanonError::InvalidUtf8.path().is_none());
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        assert_eq!(to_string("a\"b\\c\n\u{1}").unwrap(), r#""a\"b\\c\n\u0001""#);
+    }
+
+    #[test]
+    fn escapes_only_what_rfc8785_requires() {
+        assert_eq!(to_string("a/b").unwrap(), r#""a/b""#);
+        assert_eq!(to_string("\t").unwrap(), r#""\t""#);
+        assert_eq!(to_string("\u{7f}").unwrap(), "\"\u{7f}\"");
+        assert_eq!(to_string("\u{fc}").unwrap(), "\"\u{fc}\"");
+        assert_eq!(to_string("\u{2028}😀").unwrap(), "\"\u{2028}😀\"");
+    }
+
+    #[test]
+    fn escapes_every_control_character() {
+        for b in 0u8..0x20 {
+            let expected = match b {
+                0x08 => r#""\b""#.to_owned(),
+                0x09 => r#""\t""#.to_owned(),
+                0x0a => r#""\n""#.to_owned(),
+                0x0c => r#""\f""#.to_owned(),
+                0x0d => r#""\r""#.to_owned(),
+                _ => format!("\"\\u{b:04x}\""),
+            };
+            assert_eq!(to_string(&char::from(b)).unwrap(), expected, "{b:#04x}");
+        }
+    }
+
+    #[test]
+    fn enums_use_externally_tagged_form() {
+        #[derive(Serialize)]
+        enum Shape {
+            Unit,
+            Circle(u32),
+            Rect { w: u32, h: u32 },
+        }
+        assert_eq!(to_string(&Shape::Unit).unwrap(), r#""Unit""#);
+        assert_eq!(to_string(&Shape::Circle(2)).unwrap(), r#"{"Circle":2}"#);
+        assert_eq!(
+            to_string(&Shape::Rect { w: 1, h: 2 }).unwrap(),
+            r#"{"Rect":{"h":2,"w":1}}"#
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! A `#[serde(with = "canonhash::canon_bytes")]` helper that writes byte
+//! fields as base64url strings.
+//!
+//! JSON has no byte string type. Left alone, both a `Vec<u8>` field and one
+//! marked `#[serde(with = "serde_bytes")]` canonicalize as an array of
+//! numbers, `[104,105]`, just as `serde_json` writes them: this crate's
+//! serializer handles `serialize_bytes` the same way. That is deterministic
+//! but takes up to four bytes of output per byte of input. With this module
+//! the field is instead the unpadded base64url (RFC 4648 §5) string, `"aGk"`.
+//! Either form hashes stably; the two just hash differently, so pick one per
+//! field and keep it.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Blob {
+//!     #[serde(with = "canonhash::canon_bytes")]
+//!     data: Vec<u8>,
+//! }
+//!
+//! let blob = Blob { data: b"hi".to_vec() };
+//! assert_eq!(canonhash::canonicalize(&blob).unwrap(), br#"{"data":"aGk"}"#);
+//! assert_eq!(serde_json::from_str::<Blob>(r#"{"data":"aGk"}"#).unwrap(), blob);
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Serializes `bytes` as an unpadded base64url string.
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_str(&URL_SAFE_NO_PAD.encode(bytes.as_ref()))
+}
+
+/// Deserializes bytes from an unpadded base64url string. Padding and other
+/// base64 alphabets are rejected, so each value has only one spelling.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    URL_SAFE_NO_PAD
+        .decode(text.as_bytes())
+        .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::canon::to_string;
+
+    /// What `#[serde(with = "serde_bytes")]` does: hand the serializer a
+    /// byte slice rather than a sequence.
+    struct AsBytes<'a>(&'a [u8]);
+
+    impl Serialize for AsBytes<'_> {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(self.0)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        plain: Vec<u8>,
+        #[serde(with = "super")]
+        encoded: Vec<u8>,
+    }
+
+    #[test]
+    fn plain_and_serde_bytes_fields_are_number_arrays() {
+        assert_eq!(to_string(&vec![0u8, 255, 7]).unwrap(), "[0,255,7]");
+        assert_eq!(to_string(&AsBytes(&[0, 255, 7])).unwrap(), "[0,255,7]");
+        assert_eq!(
+            serde_json::to_string(&AsBytes(&[0, 255, 7])).unwrap(),
+            "[0,255,7]"
+        );
+        assert_eq!(to_string(&AsBytes(&[])).unwrap(), "[]");
+    }
+
+    #[test]
+    fn encoded_fields_are_base64url_strings() {
+        let record = Record {
+            plain: vec![1, 2],
+            encoded: vec![0xfb, 0xff, 0x00],
+        };
+        let canonical = to_string(&record).unwrap();
+        assert_eq!(canonical, r#"{"encoded":"-_8A","plain":[1,2]}"#);
+        assert_eq!(serde_json::from_str::<Record>(&canonical).unwrap(), record);
+
+        #[derive(Serialize)]
+        struct Fixed {
+            #[serde(serialize_with = "super::serialize")]
+            key: [u8; 4],
+        }
+        assert_eq!(
+            to_string(&Fixed { key: [0; 4] }).unwrap(),
+            r#"{"key":"AAAAAA"}"#
+        );
+    }
+
+    #[test]
+    fn other_spellings_are_rejected() {
+        for text in [r#""-_8A==""#, r#""+/8A""#, r#"[251,255,0]"#] {
+            let json = format!(r#"{{"plain":[],"encoded":{text}}}"#);
+            assert!(serde_json::from_str::<Record>(&json).is_err(), "{text}");
+        }
+    }
+}
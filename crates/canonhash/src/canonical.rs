@@ -0,0 +1,246 @@
+//! Canonical JSON as a value that can be compared, sorted and used as a
+//! key.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+use serde::Serialize;
+
+use crate::canon::to_string;
+use crate::error::CanonError;
+
+/// The canonical form of a value, ordered by its bytes.
+///
+/// Two values that canonicalize the same are equal, whatever their types,
+/// and the order is byte-lexicographic over the canonical text, so it is
+/// total and doesn't depend on how any type derives `Ord`. That makes a
+/// `BTreeSet<Canonical>` a sorted, deduplicated set of documents. `Display`
+/// writes the canonical text.
+///
+/// ```
+/// use std::collections::BTreeSet;
+///
+/// use canonhash::Canonical;
+/// use serde_json::json;
+///
+/// let docs: BTreeSet<Canonical> = [json!({"b": 1, "a": 2}), json!([]), json!({"a": 2, "b": 1.0})]
+///     .iter()
+///     .map(|doc| Canonical::new(doc).unwrap())
+///     .collect();
+/// let sorted: Vec<String> = docs.iter().map(ToString::to_string).collect();
+/// assert_eq!(sorted, [r#"[]"#, r#"{"a":2,"b":1}"#]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Canonical(String);
+
+impl Canonical {
+    /// Canonicalizes `value`.
+    pub fn new<T>(value: &T) -> Result<Self, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        to_string(value).map(Canonical)
+    }
+
+    /// The canonical bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// The canonical text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the canonical bytes, without copying them.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0.into_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Canonical {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Display for Canonical {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A value that hashes and compares by its canonical form, for keying a
+/// `HashMap` or `HashSet` by canonical equality.
+///
+/// The canonical form is computed once, in [`new`](Self::new), and every
+/// hash and comparison after that uses the stored bytes, so probing a map
+/// doesn't serialize anything. The value can be read through `Deref` but
+/// not changed, which would leave the stored form stale.
+///
+/// A key also borrows as its [`Canonical`], so a map can be probed with
+/// the canonical form of a value of any type.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use canonhash::CanonicalKey;
+/// use serde_json::json;
+///
+/// let mut seen = HashMap::new();
+/// seen.insert(CanonicalKey::new(json!({"b": 1, "a": [2.0]})).unwrap(), "first");
+/// seen.insert(CanonicalKey::new(json!({"a": [2], "b": 1})).unwrap(), "second");
+/// assert_eq!(seen.len(), 1);
+/// assert_eq!(seen.values().next(), Some(&"second"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CanonicalKey<T> {
+    value: T,
+    canonical: Canonical,
+}
+
+impl<T: Serialize> CanonicalKey<T> {
+    /// Canonicalizes `value` and keeps both.
+    pub fn new(value: T) -> Result<Self, CanonError> {
+        let canonical = Canonical::new(&value)?;
+        Ok(CanonicalKey { value, canonical })
+    }
+}
+
+impl<T> CanonicalKey<T> {
+    /// The canonical form the key hashes and compares by.
+    pub fn canonical(&self) -> &Canonical {
+        &self.canonical
+    }
+
+    /// Returns the value, dropping its canonical form.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CanonicalKey<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> PartialEq for CanonicalKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical == other.canonical
+    }
+}
+
+impl<T> Eq for CanonicalKey<T> {}
+
+/// Hashes as the [`Canonical`] it borrows as.
+impl<T> Hash for CanonicalKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical.hash(state);
+    }
+}
+
+impl<T> Borrow<Canonical> for CanonicalKey<T> {
+    fn borrow(&self) -> &Canonical {
+        &self.canonical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn equal_canonical_forms_are_equal_across_types() {
+        let map = BTreeMap::from([("a", 1), ("b", 2)]);
+        let hash_map = HashMap::from([("b", 2.0), ("a", 1.0)]);
+        let json = json!({"b": 2, "a": 1});
+        let (map, hash_map, json) = (
+            Canonical::new(&map).unwrap(),
+            Canonical::new(&hash_map).unwrap(),
+            Canonical::new(&json).unwrap(),
+        );
+        assert_eq!(map, hash_map);
+        assert_eq!(map.cmp(&json), core::cmp::Ordering::Equal);
+        assert_eq!(map.to_string(), r#"{"a":1,"b":2}"#);
+        assert_eq!(map.as_bytes(), map.as_str().as_bytes());
+        assert_eq!(json.into_bytes(), br#"{"a":1,"b":2}"#);
+    }
+
+    #[test]
+    fn orders_by_canonical_bytes() {
+        let mut docs: Vec<Canonical> =
+            [json!("b"), json!(10), json!({"z": 0}), json!(9), json!([])]
+                .iter()
+                .map(|doc| Canonical::new(doc).unwrap())
+                .collect();
+        docs.sort();
+        let sorted: Vec<&str> = docs.iter().map(Canonical::as_str).collect();
+        // Byte order, not numeric order: `1` sorts before `9`.
+        assert_eq!(sorted, [r#""b""#, "10", "9", "[]", r#"{"z":0}"#]);
+    }
+
+    #[test]
+    fn keys_dedup_by_canonical_form() {
+        #[derive(Serialize)]
+        struct Point {
+            y: f64,
+            x: u32,
+        }
+
+        let mut map = HashMap::new();
+        map.insert(CanonicalKey::new(Point { y: 2.0, x: 1 }).unwrap(), 1);
+        map.insert(CanonicalKey::new(Point { y: 2.0, x: 1 }).unwrap(), 2);
+        map.insert(CanonicalKey::new(Point { y: 2.5, x: 1 }).unwrap(), 3);
+        assert_eq!(map.len(), 2);
+
+        let probe = Canonical::new(&json!({"x": 1, "y": 2})).unwrap();
+        assert_eq!(map.get(&probe), Some(&2));
+        let (key, _) = map.get_key_value(&probe).unwrap();
+        assert_eq!(key.x, 1);
+        assert_eq!(key.canonical().as_str(), r#"{"x":1,"y":2}"#);
+    }
+
+    #[test]
+    fn keys_serialize_only_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static SERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Serialize for Counted {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                SERIALIZED.fetch_add(1, Ordering::Relaxed);
+                s.serialize_str("k")
+            }
+        }
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(CanonicalKey::new(Counted).unwrap());
+        for _ in 0..10 {
+            assert!(set.contains(&Canonical::new("k").unwrap()));
+        }
+        assert!(!set.insert(CanonicalKey::new(Counted).unwrap()));
+        assert_eq!(SERIALIZED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn fails_like_canonicalize() {
+        assert!(matches!(
+            Canonical::new(&f64::NAN),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        assert!(CanonicalKey::new(f64::NAN).is_err());
+    }
+}
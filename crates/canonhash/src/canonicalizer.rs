@@ -0,0 +1,1042 @@
+//! Opt-in departures from RFC 8785 for internal use.
+//!
+//! A [`Canonicalizer`] with no options set produces exactly the RFC 8785
+//! output of [`canonicalize`](crate::canonicalize). Each relaxation keeps the
+//! output deterministic but makes it non-standard, and the builder reports
+//! this through [`CanonMode`] so callers can refuse relaxed hashes where
+//! interoperability matters.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::sync::atomic::AtomicBool;
+
+use serde::Serialize;
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon;
+use crate::error::CanonError;
+use crate::hash;
+use crate::interop::InteropWarning;
+use crate::pointer::pointer_tokens;
+use crate::ser::{self, Options, Scope, SortPaths};
+
+/// Whether a [`Canonicalizer`] produces standard output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanonMode {
+    /// Exactly RFC 8785. Output and hashes interoperate with any other JCS
+    /// implementation.
+    Strict,
+    /// At least one relaxation is enabled. Output is deterministic for this
+    /// crate's settings but is not RFC 8785.
+    Relaxed,
+}
+
+/// How a [`Canonicalizer`] orders object keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum KeySortOrder {
+    /// By UTF-16 code units, as RFC 8785 requires.
+    #[default]
+    Utf16CodeUnit,
+    /// By UTF-8 bytes, which is code point order, as some canonical JSON
+    /// schemes that predate RFC 8785 sort.
+    ///
+    /// The two orders differ only when a character above U+FFFF is compared
+    /// with one in U+E000..U+FFFF: `"\u{1f600}"` sorts after `"\u{e000}"`
+    /// here but before it in UTF-16.
+    Utf8Bytes,
+}
+
+impl KeySortOrder {
+    pub(crate) fn cmp(self, a: &str, b: &str) -> core::cmp::Ordering {
+        match self {
+            KeySortOrder::Utf16CodeUnit => crate::ser::cmp_utf16(a, b),
+            KeySortOrder::Utf8Bytes => a.cmp(b),
+        }
+    }
+}
+
+/// A Unicode normalization form, for [`Canonicalizer::normalize_strings`].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalizationForm {
+    /// Canonical composition: `e` followed by U+0301 becomes `é`.
+    Nfc,
+    /// Canonical decomposition: `é` becomes `e` followed by U+0301.
+    Nfd,
+    /// Compatibility composition, which also folds forms such as `ﬁ` into
+    /// `fi`.
+    Nfkc,
+    /// Compatibility decomposition.
+    Nfkd,
+}
+
+#[cfg(feature = "unicode-normalization")]
+impl NormalizationForm {
+    /// Returns `s` in this form, or `None` if it already is.
+    pub(crate) fn apply(self, s: &str) -> Option<String> {
+        use unicode_normalization::{
+            is_nfc_quick, is_nfd_quick, is_nfkc_quick, is_nfkd_quick, IsNormalized,
+            UnicodeNormalization,
+        };
+
+        let quick = match self {
+            NormalizationForm::Nfc => is_nfc_quick(s.chars()),
+            NormalizationForm::Nfd => is_nfd_quick(s.chars()),
+            NormalizationForm::Nfkc => is_nfkc_quick(s.chars()),
+            NormalizationForm::Nfkd => is_nfkd_quick(s.chars()),
+        };
+        if quick == IsNormalized::Yes {
+            return None;
+        }
+        let normalized: String = match self {
+            NormalizationForm::Nfc => s.nfc().collect(),
+            NormalizationForm::Nfd => s.nfd().collect(),
+            NormalizationForm::Nfkc => s.nfkc().collect(),
+            NormalizationForm::Nfkd => s.nfkd().collect(),
+        };
+        (normalized != s).then_some(normalized)
+    }
+}
+
+/// A builder for canonicalization with optional relaxations.
+///
+/// ```
+/// use canonhash::{CanonMode, Canonicalizer};
+/// use serde_json::json;
+///
+/// let relaxed = Canonicalizer::new().omit_nulls(true).sort_arrays(true);
+/// assert_eq!(relaxed.mode(), CanonMode::Relaxed);
+///
+/// let value = json!({"tags": ["b", "a"], "note": null});
+/// assert_eq!(relaxed.to_string(&value).unwrap(), r#"{"tags":["a","b"]}"#);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Canonicalizer {
+    opts: Options,
+    sort_paths: SortPaths,
+    warn_non_ijson: bool,
+}
+
+impl Canonicalizer {
+    /// Returns a strict RFC 8785 canonicalizer.
+    pub fn new() -> Self {
+        Canonicalizer::default()
+    }
+
+    /// Drops object members whose value is `null`, so that an absent field
+    /// and an explicit `null` canonicalize the same. Array elements are kept.
+    #[must_use]
+    pub fn omit_nulls(mut self, omit: bool) -> Self {
+        self.opts.omit_nulls = omit;
+        self
+    }
+
+    /// Sorts array elements by their canonical bytes, for arrays that
+    /// represent sets. This applies to every array in the value, at any
+    /// depth.
+    #[must_use]
+    pub fn sort_arrays(mut self, sort: bool) -> Self {
+        self.opts.sort_arrays = sort;
+        self
+    }
+
+    /// Sorts only the arrays at `pointers` by their elements' canonical
+    /// bytes, as [`sort_arrays`](Self::sort_arrays) does everywhere. The
+    /// pointers are RFC 6901 JSON Pointers into the value, with `""` for the
+    /// value itself, and add to those from earlier calls. Arrays inside a
+    /// sorted array keep their order unless they are named too.
+    ///
+    /// A pointer matches one position, with no wildcards: an array nested
+    /// in another is named by its index, such as `/groups/0/members`. A
+    /// pointer to something that isn't an array, or to nothing, has no
+    /// effect. Keys are matched as they are written, after
+    /// [`lowercase_keys`](Self::lowercase_keys).
+    ///
+    /// ```
+    /// use canonhash::Canonicalizer;
+    /// use serde_json::json;
+    ///
+    /// let canon = Canonicalizer::new().sort_arrays_at(&["/data/modules"]);
+    /// let value = json!({"data": {"modules": ["b", "a"], "steps": ["b", "a"]}});
+    /// assert_eq!(
+    ///     canon.to_string(&value).unwrap(),
+    ///     r#"{"data":{"modules":["a","b"],"steps":["b","a"]}}"#
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If a pointer is not valid: one that is not empty must start with `/`,
+    /// and `~` must be followed by `0` or `1`.
+    #[must_use]
+    pub fn sort_arrays_at(mut self, pointers: &[&str]) -> Self {
+        for pointer in pointers {
+            let tokens = pointer_tokens(pointer).unwrap_or_else(|err| panic!("{err}"));
+            self.sort_paths.insert(tokens);
+        }
+        self
+    }
+
+    /// Writes float-typed values that are integral as `2.0` rather than `2`,
+    /// so that `2.0` and `2` hash differently. Exponent forms such as `1e+21`
+    /// are left as they are, since they already read back as floats.
+    ///
+    /// Integer-typed values are unaffected. For parsed JSON this follows how
+    /// `serde_json` stored each number: `2.0` in the text is a float, `2` is
+    /// an integer.
+    #[must_use]
+    pub fn preserve_float_marker(mut self, preserve: bool) -> Self {
+        self.opts.preserve_float_marker = preserve;
+        self
+    }
+
+    /// Sorts the members of objects whose keys are all integers, such as
+    /// `"2"`, `"10"` and `"-1"`, by numeric value instead of as strings. An
+    /// object with any other key is sorted as usual, so mixed keys stay
+    /// deterministic. Keys with the same value but different text, such as
+    /// `"1"` and `"01"`, are ordered as strings.
+    ///
+    /// This is **not** RFC 8785: `{"10":0,"2":0}` becomes `{"2":0,"10":0}`.
+    #[must_use]
+    pub fn numeric_key_sort(mut self, numeric: bool) -> Self {
+        self.opts.numeric_key_sort = numeric;
+        self
+    }
+
+    /// Sets the order of object keys. The default is the RFC 8785
+    /// [`KeySortOrder::Utf16CodeUnit`]; [`KeySortOrder::Utf8Bytes`] is a
+    /// relaxation, for verifying hashes made by tools that sort by bytes.
+    ///
+    /// With [`numeric_key_sort`](Self::numeric_key_sort), this still orders
+    /// the objects that have a non-integer key.
+    #[must_use]
+    pub fn key_sort(mut self, order: KeySortOrder) -> Self {
+        self.opts.key_sort = order;
+        self
+    }
+
+    /// Lowercases every object key before sorting, for checking hashes from
+    /// systems that treated keys case-insensitively. Off by default.
+    ///
+    /// **Non-standard**: RFC 8785 keeps keys as they are, and this makes the
+    /// mode [`CanonMode::Relaxed`]. Only ASCII letters are lowercased, one
+    /// for one, as Java's `toLowerCase(Locale.ROOT)` does for ASCII input.
+    /// Other characters, such as `É`, are left alone, with no Unicode case
+    /// folding.
+    ///
+    /// Two keys of one object that lowercase to the same key fail with
+    /// [`CanonError::DuplicateKey`] naming the lowercased key, rather than
+    /// one of them being dropped silently.
+    #[must_use]
+    pub fn lowercase_keys(mut self, lowercase: bool) -> Self {
+        self.opts.lowercase_keys = lowercase;
+        self
+    }
+
+    /// Normalizes every string value and object key to `form` before it is
+    /// written, so that strings which differ only in how their characters
+    /// are composed, such as `"é"` as one code point or as `e` plus a
+    /// combining accent, canonicalize the same. Off by default.
+    ///
+    /// **Non-standard**: RFC 8785 deliberately leaves strings alone, so
+    /// this makes the mode [`CanonMode::Relaxed`] and the hashes won't
+    /// match any other JCS implementation's for non-normalized input. Use it
+    /// only where both sides agree to normalize.
+    ///
+    /// Like [`lowercase_keys`](Self::lowercase_keys), two keys of one object
+    /// that normalize to the same key fail with [`CanonError::DuplicateKey`].
+    #[cfg(feature = "unicode-normalization")]
+    #[must_use]
+    pub fn normalize_strings(mut self, form: NormalizationForm) -> Self {
+        self.opts.normalize_strings = Some(form);
+        self
+    }
+
+    /// Sets how deeply arrays and objects may nest before serialization
+    /// fails with [`CanonError::DepthExceeded`]. The default is 128.
+    ///
+    /// Serialization recurses once per level, so the limit is what keeps a
+    /// deeply nested value from overflowing the stack. Raise it only as far
+    /// as the thread's stack allows; this is not a relaxation and doesn't
+    /// change [`mode`](Self::mode).
+    #[must_use]
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.opts.max_depth = limit;
+        self
+    }
+
+    /// Sets how many members any one object may have before serialization
+    /// fails with [`CanonError::LimitExceeded`]. There is no limit by default.
+    ///
+    /// Like [`max_depth`](Self::max_depth), this bounds the work done for
+    /// untrusted input and doesn't change [`mode`](Self::mode). Members
+    /// dropped by [`omit_nulls`](Self::omit_nulls) don't count.
+    #[must_use]
+    pub fn max_object_keys(mut self, limit: usize) -> Self {
+        self.opts.max_object_keys = limit;
+        self
+    }
+
+    /// Sets how many bytes the canonical output may have before
+    /// serialization fails with [`CanonError::LimitExceeded`]. There is no
+    /// limit by default.
+    ///
+    /// The count is kept as the output is produced, so an oversized value
+    /// fails once the limit is passed rather than after it has been buffered
+    /// in full. Output of exactly `limit` bytes is allowed.
+    #[must_use]
+    pub fn max_total_bytes(mut self, limit: usize) -> Self {
+        self.opts.max_total_bytes = limit;
+        self
+    }
+
+    /// Has [`canonicalize_with_warnings`](Self::canonicalize_with_warnings)
+    /// report values outside I-JSON (RFC 7493), which other implementations
+    /// may read back differently: for now, integers beyond
+    /// ±[`MAX_SAFE_INTEGER`](crate::MAX_SAFE_INTEGER).
+    ///
+    /// The output is unchanged, so this is not a relaxation and doesn't
+    /// change [`mode`](Self::mode). The other methods ignore it.
+    #[must_use]
+    pub fn warn_non_ijson(mut self, warn: bool) -> Self {
+        self.warn_non_ijson = warn;
+        self
+    }
+
+    /// Returns [`CanonMode::Strict`] unless a relaxation is enabled.
+    pub fn mode(&self) -> CanonMode {
+        if self.opts.is_strict() && self.sort_paths.is_empty() {
+            CanonMode::Strict
+        } else {
+            CanonMode::Relaxed
+        }
+    }
+
+    /// Serializes `value` into its canonical byte sequence under these
+    /// settings.
+    pub fn canonicalize<T>(&self, value: &T) -> Result<Vec<u8>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut buf = Vec::with_capacity(128);
+        self.canonicalize_into(value, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Like [`canonicalize`](Self::canonicalize), but gives up with
+    /// [`CanonError::Cancelled`] once `cancel` is set, for bounding the time
+    /// spent on untrusted input from a watchdog thread.
+    ///
+    /// The flag is read before the first byte is written and then once
+    /// every 64 KiB of output, so the check costs next to nothing. Time
+    /// spent in a `Serialize` implementation that writes nothing, and in
+    /// parsing JSON text beforehand, isn't covered.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// use canonhash::{CanonError, Canonicalizer};
+    ///
+    /// let cancel = AtomicBool::new(false);
+    /// let canon = Canonicalizer::new();
+    /// assert!(canon.canonicalize_cancellable(&[1, 2], &cancel).is_ok());
+    ///
+    /// cancel.store(true, Ordering::Relaxed);
+    /// assert!(matches!(
+    ///     canon.canonicalize_cancellable(&[1, 2], &cancel),
+    ///     Err(CanonError::Cancelled)
+    /// ));
+    /// ```
+    pub fn canonicalize_cancellable<T>(
+        &self,
+        value: &T,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<u8>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut buf = Vec::with_capacity(128);
+        let scope = Scope {
+            cancel: Some(cancel),
+            ..self.scope()
+        };
+        ser::serialize_scoped(value, &mut buf, self.opts, scope)?;
+        Ok(buf)
+    }
+
+    /// Like [`canonicalize`](Self::canonicalize), but also returns an
+    /// [`InteropWarning`] for each value outside I-JSON when
+    /// [`warn_non_ijson`](Self::warn_non_ijson) is set, in output order.
+    /// Without it the list is always empty.
+    ///
+    /// ```
+    /// use canonhash::{Canonicalizer, InteropWarning};
+    /// use serde_json::json;
+    ///
+    /// let canon = Canonicalizer::new().warn_non_ijson(true);
+    /// let (canonical, warnings) = canon
+    ///     .canonicalize_with_warnings(&json!({"id": 9007199254740993u64, "n": 1}))
+    ///     .unwrap();
+    /// assert_eq!(canonical, br#"{"id":9007199254740993,"n":1}"#);
+    /// assert!(matches!(
+    ///     &warnings[..],
+    ///     [InteropWarning::UnsafeInteger { path, value }]
+    ///         if path == "/id" && value == "9007199254740993"
+    /// ));
+    /// ```
+    pub fn canonicalize_with_warnings<T>(
+        &self,
+        value: &T,
+    ) -> Result<(Vec<u8>, Vec<InteropWarning>), CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let warnings = RefCell::new(Vec::new());
+        let mut buf = Vec::with_capacity(128);
+        let scope = Scope {
+            warnings: self.warn_non_ijson.then_some(&warnings),
+            ..self.scope()
+        };
+        ser::serialize_scoped(value, &mut buf, self.opts, scope)?;
+        Ok((buf, warnings.into_inner()))
+    }
+
+    /// Appends the canonical form of `value` to `buf`, as
+    /// [`canonicalize_into`](crate::canonicalize_into).
+    pub fn canonicalize_into<T>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        canon::canonicalize_into_with(value, buf, self.opts, self.scope())
+    }
+
+    /// Serializes `value` into its canonical form as a `String`.
+    pub fn to_string<T>(&self, value: &T) -> Result<String, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        String::from_utf8(self.canonicalize(value)?).map_err(|_| CanonError::InvalidUtf8)
+    }
+
+    /// Writes the canonical form of `value` to `writer`, as
+    /// [`to_writer`](crate::to_writer).
+    #[cfg(feature = "std")]
+    pub fn to_writer<W, T>(&self, writer: W, value: &T) -> Result<(), CanonError>
+    where
+        W: std::io::Write,
+        T: ?Sized + Serialize,
+    {
+        canon::to_writer_with(writer, value, self.opts, self.scope())
+    }
+
+    /// Returns the digest of the canonical form of `value` under `alg`.
+    pub fn hash_with<T>(&self, alg: HashAlgorithm, value: &T) -> Result<Vec<u8>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        hash::hash_with_options(alg, value, self.opts, self.scope())
+    }
+
+    fn scope(&self) -> Scope<'_> {
+        Scope {
+            sort_paths: Some(&self.sort_paths),
+            ..Scope::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn default_is_strict_rfc8785() {
+        let c = Canonicalizer::new();
+        assert_eq!(c.mode(), CanonMode::Strict);
+        let value = json!({"b": [3, null, 1], "a": null, "c": {"z": null}});
+        assert_eq!(
+            c.canonicalize(&value).unwrap(),
+            crate::canonicalize(&value).unwrap()
+        );
+        assert_eq!(
+            c.hash_with(HashAlgorithm::Sha256, &value).unwrap(),
+            crate::hash_with(HashAlgorithm::Sha256, &value).unwrap()
+        );
+    }
+
+    #[test]
+    fn any_relaxation_is_relaxed() {
+        assert_eq!(
+            Canonicalizer::new().omit_nulls(true).mode(),
+            CanonMode::Relaxed
+        );
+        assert_eq!(
+            Canonicalizer::new().sort_arrays(true).mode(),
+            CanonMode::Relaxed
+        );
+        assert_eq!(
+            Canonicalizer::new()
+                .sort_arrays(true)
+                .sort_arrays(false)
+                .mode(),
+            CanonMode::Strict
+        );
+    }
+
+    #[test]
+    fn omit_nulls_drops_members_at_any_depth() {
+        #[derive(Serialize)]
+        struct Record {
+            id: u32,
+            parent: Option<u32>,
+            tags: Vec<Option<&'static str>>,
+        }
+
+        let c = Canonicalizer::new().omit_nulls(true);
+        let record = Record {
+            id: 7,
+            parent: None,
+            tags: vec![Some("x"), None],
+        };
+        assert_eq!(
+            c.to_string(&record).unwrap(),
+            r#"{"id":7,"tags":["x",null]}"#
+        );
+        assert_eq!(
+            c.to_string(&json!({"a": {"b": null}, "c": null})).unwrap(),
+            r#"{"a":{}}"#
+        );
+        assert_eq!(
+            c.to_string(&json!({"a": null, "b": 1})).unwrap(),
+            r#"{"b":1}"#
+        );
+        assert_eq!(c.to_string(&json!(null)).unwrap(), "null");
+    }
+
+    #[test]
+    fn sort_arrays_orders_by_canonical_bytes() {
+        let c = Canonicalizer::new().sort_arrays(true);
+        assert_eq!(
+            c.to_string(&json!([3, 1, [2, 1], {"b": 1, "a": 2}, "a"]))
+                .unwrap(),
+            r#"["a",1,3,[1,2],{"a":2,"b":1}]"#
+        );
+        assert_eq!(
+            c.canonicalize(&json!({"set": ["b", "a"]})).unwrap(),
+            c.canonicalize(&json!({"set": ["a", "b"]})).unwrap()
+        );
+    }
+
+    #[test]
+    fn sort_arrays_at_sorts_only_the_named_arrays() {
+        let c = Canonicalizer::new().sort_arrays_at(&["/groups/1/ids", "/a~1b", ""]);
+        assert_eq!(c.mode(), CanonMode::Relaxed);
+        let value = json!({
+            "groups": [{"ids": [2, 1]}, {"ids": [[4, 3], {"b": 1, "a": 0}, 1]}],
+            "a/b": ["y", "x"],
+            "ab": ["y", "x"],
+        });
+        assert_eq!(
+            c.to_string(&value).unwrap(),
+            r#"{"a/b":["x","y"],"ab":["y","x"],"groups":[{"ids":[2,1]},{"ids":[1,[4,3],{"a":0,"b":1}]}]}"#
+        );
+        assert_eq!(
+            c.to_string(&json!([{"z": [2, 1]}, 0])).unwrap(),
+            r#"[0,{"z":[2,1]}]"#
+        );
+        assert_eq!(
+            c.to_string(&json!({"groups": {"1": {"ids": "ba"}}}))
+                .unwrap(),
+            r#"{"groups":{"1":{"ids":"ba"}}}"#
+        );
+    }
+
+    #[test]
+    fn sort_arrays_at_follows_enum_variants() {
+        #[derive(Serialize)]
+        enum Event {
+            Tagged(Vec<u8>),
+            Pair(u8, Vec<u8>),
+            Named { tags: Vec<u8> },
+        }
+
+        let c = Canonicalizer::new().sort_arrays_at(&["/Tagged", "/Pair", "/Named/tags"]);
+        assert_eq!(
+            c.to_string(&Event::Tagged(vec![2, 1])).unwrap(),
+            r#"{"Tagged":[1,2]}"#
+        );
+        assert_eq!(
+            c.to_string(&Event::Pair(9, vec![2, 1])).unwrap(),
+            r#"{"Pair":[9,[2,1]]}"#
+        );
+        assert_eq!(
+            c.to_string(&Event::Named { tags: vec![2, 1] }).unwrap(),
+            r#"{"Named":{"tags":[1,2]}}"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JSON Pointer `/a~2`")]
+    fn sort_arrays_at_rejects_invalid_pointers() {
+        let _ = Canonicalizer::new().sort_arrays_at(&["/ok", "/a~2"]);
+    }
+
+    #[test]
+    fn preserve_float_marker_distinguishes_integral_floats() {
+        let c = Canonicalizer::new().preserve_float_marker(true);
+        assert_eq!(c.mode(), CanonMode::Relaxed);
+        assert_eq!(
+            c.to_string(&(2.0, 2, -0.0, 1.5, 1e21, 1e20, 3.0f32))
+                .unwrap(),
+            "[2.0,2,0.0,1.5,1e+21,100000000000000000000.0,3.0]"
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str("[2.0, 2]").unwrap();
+        assert_eq!(c.to_string(&parsed).unwrap(), "[2.0,2]");
+        assert_ne!(
+            c.canonicalize(&json!({"n": 2.0})).unwrap(),
+            c.canonicalize(&json!({"n": 2})).unwrap()
+        );
+    }
+
+    #[test]
+    fn max_depth_is_configurable_and_not_a_relaxation() {
+        let shallow = Canonicalizer::new().max_depth(2);
+        assert_eq!(shallow.mode(), CanonMode::Strict);
+        assert_eq!(shallow.to_string(&json!([[]])).unwrap(), "[[]]");
+        assert!(matches!(
+            shallow.canonicalize(&json!([{"a": []}])),
+            Err(CanonError::DepthExceeded { limit: 2, ref path }) if path == "/0/a"
+        ));
+
+        let mut deep = json!([]);
+        for _ in 1..200 {
+            deep = json!([deep]);
+        }
+        assert!(crate::canonicalize(&deep).is_err());
+        assert!(Canonicalizer::new()
+            .max_depth(200)
+            .canonicalize(&deep)
+            .is_ok());
+    }
+
+    #[test]
+    fn numeric_key_sort_orders_integer_keys_by_value() {
+        let c = Canonicalizer::new().numeric_key_sort(true);
+        assert_eq!(c.mode(), CanonMode::Relaxed);
+        let value = json!({"10": "a", "2": "b", "-3": "c", "1": {"20": 0, "3": 0}});
+        assert_eq!(
+            c.to_string(&value).unwrap(),
+            r#"{"-3":"c","1":{"3":0,"20":0},"2":"b","10":"a"}"#
+        );
+        assert_eq!(
+            Canonicalizer::new().to_string(&value).unwrap(),
+            r#"{"-3":"c","1":{"20":0,"3":0},"10":"a","2":"b"}"#
+        );
+        assert_eq!(
+            c.to_string(&json!({"123456789012345678901234567890": 0, "99": 0}))
+                .unwrap(),
+            r#"{"99":0,"123456789012345678901234567890":0}"#
+        );
+    }
+
+    #[test]
+    fn numeric_key_sort_falls_back_for_mixed_keys() {
+        let c = Canonicalizer::new().numeric_key_sort(true);
+        let mixed = json!({"10": 0, "2": 0, "x": 0});
+        assert_eq!(
+            c.canonicalize(&mixed).unwrap(),
+            crate::canonicalize(&mixed).unwrap()
+        );
+        assert_eq!(
+            c.to_string(&json!({"01": 0, "1": 0, "-0": 0, "0": 0}))
+                .unwrap(),
+            r#"{"-0":0,"0":0,"01":0,"1":0}"#
+        );
+    }
+
+    #[test]
+    fn utf8_key_sort_differs_above_the_surrogate_range() {
+        let c = Canonicalizer::new().key_sort(KeySortOrder::Utf8Bytes);
+        assert_eq!(c.mode(), CanonMode::Relaxed);
+        let value = json!({"\u{1f600}": 0, "\u{e000}": 1, "a": {"\u{ffff}": 2, "\u{10000}": 3}});
+        assert_eq!(
+            c.to_string(&value).unwrap(),
+            "{\"a\":{\"\u{ffff}\":2,\"\u{10000}\":3},\"\u{e000}\":1,\"\u{1f600}\":0}"
+        );
+        assert_eq!(
+            Canonicalizer::new().to_string(&value).unwrap(),
+            "{\"a\":{\"\u{10000}\":3,\"\u{ffff}\":2},\"\u{1f600}\":0,\"\u{e000}\":1}"
+        );
+    }
+
+    #[test]
+    fn utf8_key_sort_applies_to_structs() {
+        #[derive(serde::Serialize)]
+        struct Keys {
+            #[serde(rename = "\u{e000}")]
+            private_use: u8,
+            #[serde(rename = "\u{1f600}")]
+            emoji: u8,
+        }
+
+        let keys = Keys {
+            private_use: 1,
+            emoji: 0,
+        };
+        let c = Canonicalizer::new().key_sort(KeySortOrder::Utf8Bytes);
+        assert_eq!(
+            c.to_string(&keys).unwrap(),
+            "{\"\u{e000}\":1,\"\u{1f600}\":0}"
+        );
+        assert_eq!(
+            Canonicalizer::new().to_string(&keys).unwrap(),
+            "{\"\u{1f600}\":0,\"\u{e000}\":1}"
+        );
+    }
+
+    #[test]
+    fn lowercase_keys_folds_ascii_only() {
+        let c = Canonicalizer::new().lowercase_keys(true);
+        assert_eq!(c.mode(), CanonMode::Relaxed);
+        assert_eq!(
+            c.to_string(&json!({"b": 1, "Apple": {"ZIP": 2}, "\u{c9}T\u{c9}": 3}))
+                .unwrap(),
+            "{\"apple\":{\"zip\":2},\"b\":1,\"\u{c9}t\u{c9}\":3}"
+        );
+
+        #[derive(Serialize)]
+        #[allow(non_snake_case)]
+        struct Legacy {
+            userId: u32,
+            Name: &'static str,
+        }
+        assert_eq!(
+            c.to_string(&Legacy {
+                userId: 1,
+                Name: "x"
+            })
+            .unwrap(),
+            r#"{"name":"x","userid":1}"#
+        );
+    }
+
+    #[test]
+    fn lowercase_key_collisions_are_errors() {
+        let c = Canonicalizer::new().lowercase_keys(true);
+        let err = c
+            .canonicalize(&json!({"outer": {"id": 1, "ID": 2}}))
+            .unwrap_err();
+        assert!(matches!(err, CanonError::DuplicateKey { ref key } if key == "id"));
+        // Without the flag the keys are distinct.
+        assert_eq!(
+            Canonicalizer::new()
+                .to_string(&json!({"id": 1, "ID": 2}))
+                .unwrap(),
+            r#"{"ID":2,"id":1}"#
+        );
+    }
+
+    /// An array that never ends, setting `flag` after `set_after` elements.
+    struct Endless<'a> {
+        flag: &'a AtomicBool,
+        set_after: usize,
+    }
+
+    impl Serialize for Endless<'_> {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+
+            let mut seq = s.serialize_seq(None)?;
+            for i in 0.. {
+                if i == self.set_after {
+                    self.flag.store(true, core::sync::atomic::Ordering::Relaxed);
+                }
+                seq.serialize_element(&json!({"i": i, "pad": "xxxxxxxxxxxxxxxx"}))?;
+            }
+            seq.end()
+        }
+    }
+
+    #[test]
+    fn cancellation_stops_an_endless_value() {
+        let flag = AtomicBool::new(false);
+        let endless = Endless {
+            flag: &flag,
+            set_after: 1000,
+        };
+        assert!(matches!(
+            Canonicalizer::new().canonicalize_cancellable(&endless, &flag),
+            Err(CanonError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn cancellation_from_a_watchdog_thread() {
+        let flag = AtomicBool::new(false);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                flag.store(true, core::sync::atomic::Ordering::Relaxed);
+            });
+            let endless = Endless {
+                flag: &flag,
+                set_after: usize::MAX,
+            };
+            Canonicalizer::new().canonicalize_cancellable(&endless, &flag)
+        });
+        assert!(matches!(result, Err(CanonError::Cancelled)));
+    }
+
+    #[test]
+    fn unset_flag_changes_nothing() {
+        let flag = AtomicBool::new(false);
+        let value = json!({"b": [null, 1.5], "a": "x".repeat(200_000)});
+        let c = Canonicalizer::new().omit_nulls(true);
+        assert_eq!(
+            c.canonicalize_cancellable(&value, &flag).unwrap(),
+            c.canonicalize(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn max_object_keys_counts_members_per_object() {
+        let c = Canonicalizer::new().max_object_keys(2);
+        assert_eq!(c.mode(), CanonMode::Strict);
+        assert_eq!(
+            c.to_string(&json!({"b": {"y": 1, "x": 2}, "a": [{"z": 3}]}))
+                .unwrap(),
+            r#"{"a":[{"z":3}],"b":{"x":2,"y":1}}"#
+        );
+        assert!(matches!(
+            c.canonicalize(&json!({"a": {"x": 1, "y": 2, "z": 3}})),
+            Err(CanonError::LimitExceeded {
+                limit: crate::Limit::ObjectKeys(2),
+                ref path,
+            }) if path == "/a"
+        ));
+        assert_eq!(
+            c.omit_nulls(true)
+                .to_string(&json!({"a": 1, "b": null, "c": 2}))
+                .unwrap(),
+            r#"{"a":1,"c":2}"#
+        );
+    }
+
+    #[test]
+    fn max_total_bytes_allows_output_of_exactly_the_limit() {
+        let value = json!({"b": [1, "x\ny"], "a": {"d": null, "c": 2.5}});
+        for c in [
+            Canonicalizer::new(),
+            Canonicalizer::new().sort_arrays(true),
+            Canonicalizer::new().omit_nulls(true),
+        ] {
+            let expected = c.canonicalize(&value).unwrap();
+            let limit = expected.len();
+            assert_eq!(
+                c.clone()
+                    .max_total_bytes(limit)
+                    .canonicalize(&value)
+                    .unwrap(),
+                expected
+            );
+            assert!(matches!(
+                c.clone().max_total_bytes(limit - 1).canonicalize(&value),
+                Err(CanonError::LimitExceeded {
+                    limit: crate::Limit::TotalBytes(l),
+                    ..
+                }) if l == limit - 1
+            ));
+            assert!(c
+                .max_total_bytes(limit - 1)
+                .hash_with(HashAlgorithm::Sha256, &value)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn max_total_bytes_stops_before_buffering_everything() {
+        // Members of an unsorted map are buffered until the map ends, so the
+        // limit must trip while they are being written.
+        struct Members(core::cell::Cell<usize>);
+        impl Serialize for Members {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_map((0..1_000_000).rev().map(|i| {
+                    self.0.set(self.0.get() + 1);
+                    (i, "v".repeat(100))
+                }))
+            }
+        }
+
+        let members = Members(core::cell::Cell::new(0));
+        let c = Canonicalizer::new().max_total_bytes(1000);
+        let mut buf = Vec::new();
+        assert!(matches!(
+            c.canonicalize_into(&members, &mut buf),
+            Err(CanonError::LimitExceeded {
+                limit: crate::Limit::TotalBytes(1000),
+                ..
+            })
+        ));
+        assert!(buf.is_empty());
+        assert!(members.0.get() < 20);
+    }
+
+    #[test]
+    fn sort_arrays_keeps_error_paths() {
+        let c = Canonicalizer::new().sort_arrays(true);
+        let err = c.canonicalize(&[[1.0, f64::NAN]]).unwrap_err();
+        assert!(matches!(err, CanonError::NonFiniteNumber { ref path } if path == "/0/1"));
+    }
+
+    #[test]
+    fn sort_arrays_closes_tuple_variants() {
+        #[derive(Serialize)]
+        enum Shape {
+            Pair(u8, u8),
+        }
+
+        let c = Canonicalizer::new().sort_arrays(true);
+        assert_eq!(
+            c.to_string(&Shape::Pair(2, 1)).unwrap(),
+            r#"{"Pair":[1,2]}"#
+        );
+    }
+
+    fn warned_pointers<T: ?Sized + Serialize>(c: &Canonicalizer, value: &T) -> Vec<String> {
+        let (canonical, warnings) = c.canonicalize_with_warnings(value).unwrap();
+        assert_eq!(canonical, c.canonicalize(value).unwrap());
+        warnings
+            .iter()
+            .map(|warning| warning.path().to_pointer())
+            .collect()
+    }
+
+    #[test]
+    fn warn_non_ijson_reports_unsafe_integers_by_path() {
+        #[derive(Serialize)]
+        enum Event {
+            Id(u64),
+            Pair(i64, i128),
+            Record { big: u128, small: i64 },
+        }
+
+        let c = Canonicalizer::new().warn_non_ijson(true);
+        assert_eq!(c.mode(), CanonMode::Strict);
+        let value = (
+            crate::MAX_SAFE_INTEGER,
+            crate::MAX_SAFE_INTEGER + 1,
+            -(crate::MAX_SAFE_INTEGER as i64) - 1,
+            json!({"a": {"b~/": [1, u64::MAX]}}),
+            [
+                Event::Id(1 << 60),
+                Event::Pair(1, -(1 << 60)),
+                Event::Record {
+                    big: 1 << 100,
+                    small: 2,
+                },
+            ],
+            1e300,
+        );
+        assert_eq!(
+            warned_pointers(&c, &value),
+            [
+                "/1",
+                "/2",
+                "/3/a/b~0~1/1",
+                "/4/0/Id",
+                "/4/1/Pair/1",
+                "/4/2/Record/big"
+            ]
+        );
+
+        let (_, warnings) = Canonicalizer::new()
+            .canonicalize_with_warnings(&value)
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn warn_non_ijson_paths_match_error_paths() {
+        // As with errors, a sorted array's indices are those before sorting,
+        // and keys are those written out.
+        let c = Canonicalizer::new()
+            .warn_non_ijson(true)
+            .sort_arrays(true)
+            .lowercase_keys(true);
+        assert_eq!(
+            warned_pointers(&c, &json!({"Ids": [u64::MAX, 3]})),
+            ["/ids/0"]
+        );
+        assert_eq!(warned_pointers(&c, &[3, i64::MIN]), ["/1"]);
+        assert_eq!(
+            warned_pointers(
+                &c.clone().omit_nulls(true),
+                &json!({"z": null, "n": 1u64 << 54})
+            ),
+            ["/n"]
+        );
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn warn_non_ijson_checks_arbitrary_precision_integers() {
+        let c = Canonicalizer::new().warn_non_ijson(true);
+        let parsed: serde_json::Value =
+            serde_json::from_str("[9007199254740991, -123456789012345678901234, 1e300, 0.5]")
+                .unwrap();
+        let (_, warnings) = c.canonicalize_with_warnings(&parsed).unwrap();
+        assert!(matches!(
+            &warnings[..],
+            [crate::InteropWarning::UnsafeInteger { path, value }]
+                if path == "/1" && value == "-123456789012345678901234"
+        ));
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn normalize_strings_equates_composed_and_decomposed_text() {
+        let composed = json!({"caf\u{e9}": "caf\u{e9}"});
+        let decomposed = json!({"cafe\u{301}": "cafe\u{301}"});
+        let strict = Canonicalizer::new();
+        assert_ne!(
+            strict.hash_with(HashAlgorithm::Sha256, &composed).unwrap(),
+            strict
+                .hash_with(HashAlgorithm::Sha256, &decomposed)
+                .unwrap()
+        );
+
+        let nfc = Canonicalizer::new().normalize_strings(NormalizationForm::Nfc);
+        assert_eq!(nfc.mode(), CanonMode::Relaxed);
+        assert_eq!(
+            nfc.hash_with(HashAlgorithm::Sha256, &composed).unwrap(),
+            nfc.hash_with(HashAlgorithm::Sha256, &decomposed).unwrap()
+        );
+        assert_eq!(
+            nfc.canonicalize(&decomposed).unwrap(),
+            strict.canonicalize(&composed).unwrap()
+        );
+        let nfd = Canonicalizer::new().normalize_strings(NormalizationForm::Nfd);
+        assert_eq!(
+            nfd.canonicalize(&composed).unwrap(),
+            strict.canonicalize(&decomposed).unwrap()
+        );
+        let nfkc = Canonicalizer::new().normalize_strings(NormalizationForm::Nfkc);
+        assert_eq!(nfkc.to_string(&['\u{fb01}']).unwrap(), r#"["fi"]"#);
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn normalize_strings_rejects_keys_that_become_equal() {
+        let c = Canonicalizer::new().normalize_strings(NormalizationForm::Nfc);
+        let value = json!({"caf\u{e9}": 1, "cafe\u{301}": 2});
+        assert!(matches!(
+            c.canonicalize(&value),
+            Err(CanonError::DuplicateKey { key }) if key == "caf\u{e9}"
+        ));
+    }
+}
@@ -0,0 +1,1003 @@
+//! Deterministic CBOR (RFC 8949 §4.2.1) for the same serde types as the JSON
+//! path.
+//!
+//! Core deterministic encoding means:
+//!
+//! - integers, lengths and tags use the shortest head that fits;
+//! - floats use the shortest of half, single or double precision that keeps
+//!   the value exactly, and every NaN is written as `0xf97e00`;
+//! - arrays, maps and strings are always definite-length;
+//! - map entries are sorted by the bytewise order of their encoded keys,
+//!   and a map that repeats a key is [`CanonError::DuplicateKey`].
+//!
+//! Rust data maps onto CBOR the way `serde_json` maps it onto JSON, except
+//! that byte slices become byte strings and map keys keep their own type.
+//! Enums are externally tagged: a unit variant is its name as a text string,
+//! any other variant a one-entry map from the name to its content. Integers
+//! outside the 64-bit range are bignums (tags 2 and 3). A `serde_json`
+//! `RawValue` is parsed and encoded as the value its text holds. Nesting is limited
+//! to 128 arrays and maps, as in [`canonicalize`](crate::canonicalize), and
+//! deeper values are [`CanonError::DepthExceeded`].
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Pair {
+//!     b: bool,
+//!     a: u8,
+//! }
+//!
+//! let cbor = canonhash::cbor::to_canonical_cbor(&Pair { b: true, a: 1 }).unwrap();
+//! assert_eq!(cbor, [0xa2, 0x61, b'a', 0x01, 0x61, b'b', 0xf5]);
+//! ```
+
+use alloc::borrow::ToOwned;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::ser::{self, Error as _, Serialize};
+
+use crate::error::{CanonError, JsonPath};
+use crate::ser::{parse_raw_value, DEFAULT_MAX_DEPTH, NUMBER_TOKEN, RAW_VALUE_TOKEN};
+use crate::sink::Sink;
+
+const UNSIGNED: u8 = 0;
+const NEGATIVE: u8 = 1;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const TAG: u8 = 6;
+
+const TAG_POSITIVE_BIGNUM: u64 = 2;
+const TAG_NEGATIVE_BIGNUM: u64 = 3;
+
+/// Serializes `value` into deterministically encoded CBOR.
+pub fn to_canonical_cbor<T>(value: &T) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buf = Vec::with_capacity(128);
+    value.serialize(Encoder {
+        out: &mut buf,
+        depth: 0,
+    })?;
+    Ok(buf)
+}
+
+/// Returns the depth inside `levels` more arrays or maps, or
+/// [`CanonError::DepthExceeded`] past the same limit the JSON path applies by
+/// default.
+fn descend(depth: usize, levels: usize) -> Result<usize, CanonError> {
+    let depth = depth + levels;
+    if depth > DEFAULT_MAX_DEPTH {
+        return Err(CanonError::DepthExceeded {
+            limit: DEFAULT_MAX_DEPTH,
+            path: JsonPath::default(),
+        });
+    }
+    Ok(depth)
+}
+
+/// Writes a data item head: the major type and the shortest argument
+/// encoding for `n`.
+fn write_head<S: ?Sized + Sink>(out: &mut S, major: u8, n: u64) -> Result<(), CanonError> {
+    let major = major << 5;
+    if n < 24 {
+        out.write(&[major | n as u8])
+    } else if let Ok(n) = u8::try_from(n) {
+        out.write(&[major | 24, n])
+    } else if let Ok(n) = u16::try_from(n) {
+        out.write(&[major | 25])?;
+        out.write(&n.to_be_bytes())
+    } else if let Ok(n) = u32::try_from(n) {
+        out.write(&[major | 26])?;
+        out.write(&n.to_be_bytes())
+    } else {
+        out.write(&[major | 27])?;
+        out.write(&n.to_be_bytes())
+    }
+}
+
+fn write_text<S: ?Sized + Sink>(out: &mut S, s: &str) -> Result<(), CanonError> {
+    write_head(out, TEXT, s.len() as u64)?;
+    out.write(s.as_bytes())
+}
+
+/// Writes a non-negative integer given as big-endian bytes: as a plain
+/// integer of major type `major` if it fits in 64 bits, otherwise as a
+/// bignum.
+fn write_big_endian<S: ?Sized + Sink>(
+    out: &mut S,
+    major: u8,
+    bytes: &[u8],
+) -> Result<(), CanonError> {
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let bytes = &bytes[first..];
+    if bytes.len() <= 8 {
+        let mut word = [0; 8];
+        word[8 - bytes.len()..].copy_from_slice(bytes);
+        return write_head(out, major, u64::from_be_bytes(word));
+    }
+    let tag = if major == UNSIGNED {
+        TAG_POSITIVE_BIGNUM
+    } else {
+        TAG_NEGATIVE_BIGNUM
+    };
+    write_head(out, TAG, tag)?;
+    write_head(out, BYTES, bytes.len() as u64)?;
+    out.write(bytes)
+}
+
+fn write_i128<S: ?Sized + Sink>(out: &mut S, v: i128) -> Result<(), CanonError> {
+    if v >= 0 {
+        write_big_endian(out, UNSIGNED, &(v as u128).to_be_bytes())
+    } else {
+        // A negative integer n is encoded as the argument -1 - n.
+        write_big_endian(out, NEGATIVE, &(!(v as u128)).to_be_bytes())
+    }
+}
+
+fn write_f64<S: ?Sized + Sink>(out: &mut S, v: f64) -> Result<(), CanonError> {
+    if v.is_nan() {
+        return out.write(&[0xf9, 0x7e, 0x00]);
+    }
+    let single = v as f32;
+    if f64::from(single) != v {
+        out.write(&[0xfb])?;
+        return out.write(&v.to_be_bytes());
+    }
+    match f32_to_f16_exact(single) {
+        Some(half) => {
+            out.write(&[0xf9])?;
+            out.write(&half.to_be_bytes())
+        }
+        None => {
+            out.write(&[0xfa])?;
+            out.write(&single.to_be_bytes())
+        }
+    }
+}
+
+/// Returns the IEEE 754 half-precision bits for `v` if it converts exactly.
+fn f32_to_f16_exact(v: f32) -> Option<u16> {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exp == 0xff {
+        // Infinities; NaN is handled by the caller.
+        return (mantissa == 0).then_some(sign | 0x7c00);
+    }
+    if exp == 0 {
+        // Zero converts; single-precision subnormals are far below the
+        // half-precision range.
+        return (mantissa == 0).then_some(sign);
+    }
+    let e = exp - 127;
+    if (-14..=15).contains(&e) {
+        // Normal in half precision if the low 13 mantissa bits are zero.
+        return (mantissa & 0x1fff == 0)
+            .then(|| sign | (((e + 15) as u16) << 10) | (mantissa >> 13) as u16);
+    }
+    if (-24..-14).contains(&e) {
+        // Subnormal in half precision: the value is m × 2^-24.
+        let full = mantissa | 0x80_0000;
+        let shift = (-1 - e) as u32;
+        return (full & ((1 << shift) - 1) == 0).then(|| sign | (full >> shift) as u16);
+    }
+    None
+}
+
+/// Writes the decimal text of an arbitrary-precision `serde_json::Number`:
+/// integers exactly, as bignums if need be, and anything else as a float.
+fn write_number_text<S: ?Sized + Sink>(out: &mut S, text: &str) -> Result<(), CanonError> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        let v: f64 = text
+            .parse()
+            .map_err(|_| CanonError::custom(format_args!("invalid JSON number `{text}`")))?;
+        return write_f64(out, v);
+    }
+
+    // Convert the decimal digits to big-endian bytes.
+    let mut bytes: Vec<u8> = vec![0];
+    for d in digits.bytes() {
+        let mut carry = u16::from(d - b'0');
+        for byte in bytes.iter_mut().rev() {
+            let n = u16::from(*byte) * 10 + carry;
+            *byte = n as u8;
+            carry = n >> 8;
+        }
+        if carry > 0 {
+            bytes.insert(0, carry as u8);
+        }
+    }
+    let is_zero = bytes.iter().all(|&b| b == 0);
+    if !negative || is_zero {
+        return write_big_endian(out, UNSIGNED, &bytes);
+    }
+    // -n is encoded with the argument n - 1.
+    for byte in bytes.iter_mut().rev() {
+        let (n, borrow) = byte.overflowing_sub(1);
+        *byte = n;
+        if !borrow {
+            break;
+        }
+    }
+    write_big_endian(out, NEGATIVE, &bytes)
+}
+
+struct Encoder<'a, S: ?Sized> {
+    out: &'a mut S,
+    /// How many arrays and maps enclose the value.
+    depth: usize,
+}
+
+impl<'a, S: ?Sized + Sink> ser::Serializer for Encoder<'a, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    type SerializeSeq = Seq<'a, S>;
+    type SerializeTuple = Seq<'a, S>;
+    type SerializeTupleStruct = Seq<'a, S>;
+    type SerializeTupleVariant = Seq<'a, S>;
+    type SerializeMap = Map<'a, S>;
+    type SerializeStruct = Map<'a, S>;
+    type SerializeStructVariant = Map<'a, S>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CanonError> {
+        self.out.write(&[if v { 0xf5 } else { 0xf4 }])
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CanonError> {
+        if v >= 0 {
+            write_head(self.out, UNSIGNED, v as u64)
+        } else {
+            write_head(self.out, NEGATIVE, !(v as u64))
+        }
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), CanonError> {
+        write_i128(self.out, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CanonError> {
+        write_head(self.out, UNSIGNED, v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), CanonError> {
+        write_big_endian(self.out, UNSIGNED, &v.to_be_bytes())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CanonError> {
+        write_f64(self.out, f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CanonError> {
+        write_f64(self.out, v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CanonError> {
+        write_text(self.out, v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CanonError> {
+        write_text(self.out, v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CanonError> {
+        write_head(self.out, BYTES, v.len() as u64)?;
+        self.out.write(v)
+    }
+
+    fn serialize_none(self) -> Result<(), CanonError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CanonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CanonError> {
+        self.out.write(&[0xf6])
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CanonError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), CanonError> {
+        write_text(self.out, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        let depth = descend(self.depth, 1)?;
+        write_head(self.out, MAP, 1)?;
+        write_text(self.out, variant)?;
+        value
+            .serialize(Encoder {
+                out: self.out,
+                depth,
+            })
+            .map_err(|err| err.within_key(variant))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Seq<'a, S>, CanonError> {
+        let depth = descend(self.depth, 1)?;
+        Seq::new(self.out, depth, len, None)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Seq<'a, S>, CanonError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Seq<'a, S>, CanonError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Seq<'a, S>, CanonError> {
+        let depth = descend(self.depth, 2)?;
+        write_head(self.out, MAP, 1)?;
+        write_text(self.out, variant)?;
+        Seq::new(self.out, depth, Some(len), Some(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Map<'a, S>, CanonError> {
+        let depth = descend(self.depth, 1)?;
+        Ok(Map::new(self.out, depth, Kind::Map, None))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Map<'a, S>, CanonError> {
+        match name {
+            NUMBER_TOKEN => return Ok(Map::new(self.out, self.depth, Kind::Number, None)),
+            RAW_VALUE_TOKEN => return Ok(Map::new(self.out, self.depth, Kind::RawValue, None)),
+            _ => {}
+        }
+        let depth = descend(self.depth, 1)?;
+        Ok(Map::new(self.out, depth, Kind::Map, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Map<'a, S>, CanonError> {
+        let depth = descend(self.depth, 2)?;
+        write_head(self.out, MAP, 1)?;
+        write_text(self.out, variant)?;
+        Ok(Map::new(self.out, depth, Kind::Map, Some(variant)))
+    }
+}
+
+/// An array in progress. With a known length the head is written up front
+/// and elements stream to the output; otherwise they are buffered and
+/// counted so the array can still be definite-length.
+///
+/// `depth` counts the array itself. `variant` is set for tuple variants, to
+/// be named in error paths.
+struct Seq<'a, S: ?Sized> {
+    out: &'a mut S,
+    depth: usize,
+    expected: Option<usize>,
+    buf: Vec<u8>,
+    count: usize,
+    variant: Option<&'static str>,
+}
+
+impl<'a, S: ?Sized + Sink> Seq<'a, S> {
+    fn new(
+        out: &'a mut S,
+        depth: usize,
+        len: Option<usize>,
+        variant: Option<&'static str>,
+    ) -> Result<Self, CanonError> {
+        if let Some(len) = len {
+            write_head(out, ARRAY, len as u64)?;
+        }
+        Ok(Seq {
+            out,
+            depth,
+            expected: len,
+            buf: Vec::new(),
+            count: 0,
+            variant,
+        })
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        let (index, depth) = (self.count, self.depth);
+        self.count += 1;
+        let result = match self.expected {
+            Some(_) => value.serialize(Encoder {
+                out: &mut *self.out,
+                depth,
+            }),
+            None => value.serialize(Encoder {
+                out: &mut self.buf,
+                depth,
+            }),
+        };
+        result.map_err(|err| within_variant(err.within_index(index), self.variant))
+    }
+
+    fn finish(self) -> Result<(), CanonError> {
+        match self.expected {
+            Some(len) if len == self.count => Ok(()),
+            Some(len) => Err(CanonError::custom(format_args!(
+                "sequence declared {len} elements but produced {}",
+                self.count
+            ))),
+            None => {
+                write_head(self.out, ARRAY, self.count as u64)?;
+                self.out.write(&self.buf)
+            }
+        }
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeSeq for Seq<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTuple for Seq<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTupleStruct for Seq<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTupleVariant for Seq<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+/// A map in progress. Keys and values are encoded into their own buffers
+/// and sorted by the encoded key bytes at `end`.
+///
+/// `depth` and `variant` are as for [`Seq`].
+struct Map<'a, S: ?Sized> {
+    out: &'a mut S,
+    depth: usize,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    key: Option<Vec<u8>>,
+    kind: Kind,
+    variant: Option<&'static str>,
+}
+
+/// What a [`Map`] is written as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Map,
+    /// An arbitrary-precision `serde_json::Number`, written as the number
+    /// itself.
+    Number,
+    /// A `serde_json` `RawValue`, written as the value its text holds.
+    RawValue,
+}
+
+impl<'a, S: ?Sized + Sink> Map<'a, S> {
+    fn new(out: &'a mut S, depth: usize, kind: Kind, variant: Option<&'static str>) -> Self {
+        Map {
+            out,
+            depth,
+            entries: Vec::new(),
+            key: None,
+            kind,
+            variant,
+        }
+    }
+
+    fn entry<T: ?Sized + Serialize>(&mut self, key: Vec<u8>, value: &T) -> Result<(), CanonError> {
+        let mut buf = Vec::new();
+        let encoder = Encoder {
+            out: &mut buf,
+            depth: self.depth,
+        };
+        if let Err(err) = value.serialize(encoder) {
+            // Text keys are named in the path, as in JSON; others can't be.
+            let err = match text_string(&key) {
+                Some(key) => err.within_key(key),
+                None => err,
+            };
+            return Err(within_variant(err, self.variant));
+        }
+        self.entries.push((key, buf));
+        Ok(())
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), CanonError> {
+        let mut encoded = Vec::with_capacity(key.len() + 1);
+        write_text(&mut encoded, key)?;
+        self.entry(encoded, value)
+    }
+
+    fn finish(mut self) -> Result<(), CanonError> {
+        if self.kind != Kind::Map {
+            let text = match self.entries.as_slice() {
+                [(_, value)] => text_string(value),
+                _ => None,
+            };
+            if self.kind == Kind::RawValue {
+                let text = text.ok_or_else(|| CanonError::custom("malformed RawValue"))?;
+                return parse_raw_value(text)?.serialize(Encoder {
+                    out: self.out,
+                    depth: self.depth,
+                });
+            }
+            let text = text.ok_or_else(|| CanonError::custom("malformed serde_json::Number"))?;
+            return write_number_text(self.out, text);
+        }
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        // RFC 8949 §5.6: a map with a repeated key isn't valid CBOR.
+        if let Some(pair) = self.entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            let key = &pair[0].0;
+            let key = match text_string(key) {
+                Some(text) => text.to_owned(),
+                None => hex::encode(key),
+            };
+            return Err(CanonError::DuplicateKey { key });
+        }
+        write_head(self.out, MAP, self.entries.len() as u64)?;
+        for (key, value) in &self.entries {
+            self.out.write(key)?;
+            self.out.write(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prefixes the path of an error from inside a variant's content with the
+/// variant's name.
+fn within_variant(err: CanonError, variant: Option<&'static str>) -> CanonError {
+    match variant {
+        Some(variant) => err.within_key(variant),
+        None => err,
+    }
+}
+
+/// Decodes an encoded text string item, as written by [`write_text`].
+fn text_string(item: &[u8]) -> Option<&str> {
+    let (&initial, rest) = item.split_first()?;
+    if initial >> 5 != TEXT {
+        return None;
+    }
+    let (len, rest) = match initial & 0x1f {
+        n @ 0..=23 => (usize::from(n), rest),
+        24 => (usize::from(*rest.first()?), rest.get(1..)?),
+        25 => (
+            usize::from(u16::from_be_bytes(rest.get(..2)?.try_into().ok()?)),
+            rest.get(2..)?,
+        ),
+        26 => (
+            usize::try_from(u32::from_be_bytes(rest.get(..4)?.try_into().ok()?)).ok()?,
+            rest.get(4..)?,
+        ),
+        27 => (
+            usize::try_from(u64::from_be_bytes(rest.get(..8)?.try_into().ok()?)).ok()?,
+            rest.get(8..)?,
+        ),
+        _ => return None,
+    };
+    if rest.len() != len {
+        return None;
+    }
+    core::str::from_utf8(rest).ok()
+}
+
+impl<S: ?Sized + Sink> ser::SerializeMap for Map<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CanonError> {
+        let mut encoded = Vec::new();
+        key.serialize(Encoder {
+            out: &mut encoded,
+            depth: self.depth,
+        })?;
+        self.key = Some(encoded);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| CanonError::custom("serialize_value called before serialize_key"))?;
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeStruct for Map<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeStructVariant for Map<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+
+    fn hex_of<T: ?Sized + Serialize>(value: &T) -> String {
+        hex::encode(to_canonical_cbor(value).unwrap())
+    }
+
+    #[test]
+    fn documented_record_encoding() {
+        #[derive(Serialize)]
+        struct Record {
+            version: u32,
+            name: &'static str,
+            tags: Vec<&'static str>,
+            ratio: f64,
+        }
+
+        let record = Record {
+            version: 1,
+            name: "ops",
+            tags: vec!["a", "b"],
+            ratio: 0.5,
+        };
+        // Keys sort by their encoded bytes: the length in the head byte puts
+        // shorter keys first, then equal lengths compare by content.
+        assert_eq!(
+            hex_of(&record),
+            concat!(
+                "a4",
+                "646e616d65",
+                "636f7073",
+                "6474616773",
+                "8261616162",
+                "65726174696f",
+                "f93800",
+                "6776657273696f6e",
+                "01",
+            )
+        );
+    }
+
+    #[test]
+    fn rfc8949_appendix_a_integers() {
+        let cases: &[(i128, &str)] = &[
+            (0, "00"),
+            (23, "17"),
+            (24, "1818"),
+            (100, "1864"),
+            (1000, "1903e8"),
+            (1_000_000, "1a000f4240"),
+            (1_000_000_000_000, "1b000000e8d4a51000"),
+            (18_446_744_073_709_551_615, "1bffffffffffffffff"),
+            (18_446_744_073_709_551_616, "c249010000000000000000"),
+            (-18_446_744_073_709_551_616, "3bffffffffffffffff"),
+            (-18_446_744_073_709_551_617, "c349010000000000000000"),
+            (-1, "20"),
+            (-10, "29"),
+            (-100, "3863"),
+            (-1000, "3903e7"),
+        ];
+        for &(v, expected) in cases {
+            assert_eq!(hex_of(&v), expected, "{v}");
+        }
+        assert_eq!(hex_of(&u64::MAX), "1bffffffffffffffff");
+        assert_eq!(hex_of(&i64::MIN), "3b7fffffffffffffff");
+    }
+
+    #[test]
+    fn rfc8949_appendix_a_floats_use_shortest_form() {
+        let cases: &[(f64, &str)] = &[
+            (0.0, "f90000"),
+            (-0.0, "f98000"),
+            (1.0, "f93c00"),
+            (1.1, "fb3ff199999999999a"),
+            (1.5, "f93e00"),
+            (65504.0, "f97bff"),
+            (100000.0, "fa47c35000"),
+            (3.4028234663852886e+38, "fa7f7fffff"),
+            (1.0e+300, "fb7e37e43c8800759c"),
+            (5.960464477539063e-8, "f90001"),
+            (0.00006103515625, "f90400"),
+            (-4.0, "f9c400"),
+            (-4.1, "fbc010666666666666"),
+            (f64::INFINITY, "f97c00"),
+            (f64::NEG_INFINITY, "f9fc00"),
+            (f64::NAN, "f97e00"),
+            (-f64::NAN, "f97e00"),
+        ];
+        for &(v, expected) in cases {
+            assert_eq!(hex_of(&v), expected, "{v}");
+        }
+        assert_eq!(hex_of(&1.5f32), "f93e00");
+    }
+
+    #[test]
+    fn rfc8949_appendix_a_simple_strings_and_containers() {
+        assert_eq!(hex_of(&false), "f4");
+        assert_eq!(hex_of(&true), "f5");
+        assert_eq!(hex_of(&()), "f6");
+        assert_eq!(hex_of(""), "60");
+        assert_eq!(hex_of("IETF"), "6449455446");
+        assert_eq!(hex_of("\"\\"), "62225c");
+        assert_eq!(hex_of("ü"), "62c3bc");
+        assert_eq!(hex_of(&[0u8; 0]), "80");
+        assert_eq!(hex_of(&[1, 2, 3]), "83010203");
+        assert_eq!(hex_of(&BTreeMap::<u8, u8>::new()), "a0");
+        assert_eq!(hex_of(&BTreeMap::from([(3, 4), (1, 2)])), "a201020304");
+        assert_eq!(hex_of(&json!({"b": [2, 3], "a": 1})), "a26161016162820203");
+        assert_eq!(hex_of(&Bytes(&[1, 2, 3, 4])), "4401020304");
+    }
+
+    /// A byte string, which serde otherwise only produces through a helper
+    /// crate.
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn keys_sort_by_encoded_bytes() {
+        // "b" encodes as 61 62, "aa" as 62 61 61: the shorter key sorts first.
+        let map = HashMap::from([("aa", 1), ("b", 2)]);
+        assert_eq!(hex_of(&map), "a261620262616101");
+        // Integer keys sort before text keys, negative after positive.
+        let value = HashMap::from([(-1i8, "n"), (10, "p")]);
+        assert_eq!(hex_of(&value), "a20a617020616e");
+    }
+
+    #[test]
+    fn repeated_keys_are_an_error() {
+        #[derive(Serialize)]
+        struct Parent {
+            a: u8,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, u8>,
+        }
+        let value = Parent {
+            a: 1,
+            extra: BTreeMap::from([("a", 2)]),
+        };
+        assert!(matches!(
+            to_canonical_cbor(&value),
+            Err(CanonError::DuplicateKey { ref key }) if key == "a"
+        ));
+
+        struct Repeats;
+        impl Serialize for Repeats {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeMap;
+                let mut map = s.serialize_map(None)?;
+                map.serialize_entry(&1, &"x")?;
+                map.serialize_entry(&1, &"y")?;
+                map.end()
+            }
+        }
+        // Keys that aren't text are named by their encoding, in hex.
+        assert!(matches!(
+            to_canonical_cbor(&Repeats),
+            Err(CanonError::DuplicateKey { ref key }) if key == "01"
+        ));
+    }
+
+    #[test]
+    fn unsized_sequences_are_definite_length() {
+        struct Unsized;
+        impl Serialize for Unsized {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+                let mut seq = s.serialize_seq(None)?;
+                seq.serialize_element(&1)?;
+                seq.serialize_element(&2)?;
+                seq.end()
+            }
+        }
+        assert_eq!(hex_of(&Unsized), "820102");
+    }
+
+    #[test]
+    fn enums_are_externally_tagged() {
+        #[derive(Serialize)]
+        enum Shape {
+            Empty,
+            Circle(u8),
+            Pair(u8, u8),
+            Rect { w: u8, h: u8 },
+        }
+
+        assert_eq!(hex_of(&Shape::Empty), "65456d707479");
+        assert_eq!(hex_of(&Shape::Circle(1)), "a166436972636c6501");
+        assert_eq!(hex_of(&Shape::Pair(1, 2)), "a16450616972820102");
+        assert_eq!(
+            hex_of(&Shape::Rect { w: 1, h: 2 }),
+            "a16452656374a2616802617701"
+        );
+    }
+
+    #[test]
+    fn json_values_match_typed_values() {
+        let value = json!({"name": "ops", "n": [1, -2, 2.5, null, true]});
+        assert_eq!(
+            hex_of(&value),
+            hex_of(&BTreeMap::from([
+                ("n", json!([1, -2, 2.5, null, true])),
+                ("name", json!("ops")),
+            ]))
+        );
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn arbitrary_precision_integers_become_bignums() {
+        let value: serde_json::Value =
+            serde_json::from_str("[18446744073709551616, -18446744073709551617, -0, 1.5, 7]")
+                .unwrap();
+        assert_eq!(
+            hex_of(&value),
+            "85c249010000000000000000c34901000000000000000000f93e0007"
+        );
+    }
+
+    #[test]
+    fn raw_values_are_encoded_as_the_value_they_hold() {
+        use serde_json::value::RawValue;
+
+        #[derive(Serialize)]
+        struct Envelope {
+            kind: &'static str,
+            payload: Box<RawValue>,
+        }
+
+        let raw = |text: &str| RawValue::from_string(text.into()).unwrap();
+        let envelope = Envelope {
+            kind: "event",
+            payload: raw(r#"{ "b":1 , "a":2 }"#),
+        };
+        assert_eq!(
+            to_canonical_cbor(&envelope).unwrap(),
+            to_canonical_cbor(&json!({"kind": "event", "payload": {"a": 2, "b": 1}})).unwrap()
+        );
+        // Long enough for a four-byte length head.
+        let long = format!("[{}0]", "0,".repeat(40_000));
+        assert_eq!(
+            to_canonical_cbor(&raw(&long)).unwrap(),
+            to_canonical_cbor(&vec![0; 40_001]).unwrap()
+        );
+        assert!(matches!(
+            to_canonical_cbor(&raw(r#"{"a":1,"a":2}"#)),
+            Err(CanonError::DuplicateKey { .. })
+        ));
+    }
+}
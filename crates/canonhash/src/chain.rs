@@ -0,0 +1,253 @@
+//! Hash chains for append-only logs.
+//!
+//! Each record is hashed with the previous head embedded in it, so changing,
+//! removing or reordering any record changes every later head.
+//!
+//! # Link contract
+//!
+//! Records must serialize to JSON objects. Before hashing, a member named
+//! after the chain's link field (chosen at construction, e.g. `"prev_hash"`)
+//! is added whose value is the previous head as 64 lowercase hex digits. The
+//! first record links to the all-zero head. The record must not already have
+//! that member. The new head is the SHA-256 of the canonical form of the
+//! record with the link included, so anyone can recompute it with any RFC 8785
+//! implementation.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::canon::to_string;
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::ser::{object_members, write_object, write_str};
+
+/// The head before any record has been appended.
+pub const GENESIS: [u8; 32] = [0; 32];
+
+/// A running hash chain. See the [module docs](self) for how records are
+/// linked.
+///
+/// ```
+/// use canonhash::chain::{verify_chain, HashChain};
+/// use serde_json::json;
+///
+/// let records = [json!({"op": "open"}), json!({"op": "close"})];
+/// let mut chain = HashChain::new("prev_hash");
+/// for record in &records {
+///     chain.append(record).unwrap();
+/// }
+/// assert!(verify_chain("prev_hash", &records, &chain.head()).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashChain {
+    field: String,
+    head: [u8; 32],
+}
+
+impl HashChain {
+    /// Starts an empty chain whose records are linked through `field`.
+    pub fn new(field: impl Into<String>) -> Self {
+        Self::resume(field, GENESIS)
+    }
+
+    /// Continues a chain from a previously stored `head`.
+    pub fn resume(field: impl Into<String>, head: [u8; 32]) -> Self {
+        HashChain {
+            field: field.into(),
+            head,
+        }
+    }
+
+    /// The name of the member that carries the previous head.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The hash of the most recent record, or [`GENESIS`].
+    pub fn head(&self) -> [u8; 32] {
+        self.head
+    }
+
+    /// Links `record` to the current head, hashes it and returns the new head.
+    ///
+    /// On error the head is unchanged.
+    pub fn append<T>(&mut self, record: &T) -> Result<[u8; 32], CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let linked = self.linked_form(record)?;
+        self.head = Sha256::digest(&linked).into();
+        Ok(self.head)
+    }
+
+    /// Returns the canonical bytes that [`append`](Self::append) would hash
+    /// for `record`: its canonical form with the link member added.
+    pub fn linked_form<T>(&self, record: &T) -> Result<Vec<u8>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let canonical = to_string(record)?;
+        if !canonical.starts_with('{') {
+            return Err(CanonError::NotAnObject);
+        }
+        // Canonical output is valid JSON, and `RawValue` keeps each member's
+        // canonical text as-is, so only the new member needs serializing.
+        let members = object_members(&canonical)?;
+        if members.iter().any(|(key, _)| *key == self.field) {
+            return Err(CanonError::ChainFieldConflict {
+                field: self.field.clone(),
+            });
+        }
+
+        let mut link = Vec::with_capacity(66);
+        write_str(&mut link, &encode_digest(&self.head, DigestEncoding::Hex))?;
+        let link = core::str::from_utf8(&link).expect("hex is ASCII");
+        let mut members: Vec<(&str, &str)> = members
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.get()))
+            .collect();
+        members.push((&self.field, link));
+
+        let mut out = Vec::with_capacity(canonical.len() + self.field.len() + 70);
+        write_object(&mut out, members, |out, value| {
+            out.extend_from_slice(value.as_bytes());
+            Ok(())
+        })?;
+        Ok(out)
+    }
+}
+
+/// Replays `records` from [`GENESIS`] through `field` and returns whether the
+/// final head equals `expected_head`.
+///
+/// Errors from individual records are returned rather than reported as a
+/// mismatch. The head comparison is constant-time.
+pub fn verify_chain<T>(
+    field: &str,
+    records: &[T],
+    expected_head: &[u8; 32],
+) -> Result<bool, CanonError>
+where
+    T: Serialize,
+{
+    let mut chain = HashChain::new(field.to_owned());
+    for record in records {
+        chain.append(record)?;
+    }
+    Ok(chain.head.ct_eq(expected_head).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::to_string;
+
+    #[test]
+    fn link_member_is_inserted_in_canonical_order() {
+        let chain = HashChain::resume("prev", [0xab; 32]);
+        let linked = chain
+            .linked_form(&json!({"z": [1.5, {"b": 1, "a": 2}], "a": "x"}))
+            .unwrap();
+        let expected = alloc::format!(
+            r#"{{"a":"x","prev":"{}","z":[1.5,{{"a":2,"b":1}}]}}"#,
+            "ab".repeat(32)
+        );
+        assert_eq!(String::from_utf8(linked).unwrap(), expected);
+    }
+
+    #[test]
+    fn linked_form_matches_canonicalizing_the_injected_object() {
+        let mut chain = HashChain::new("prev_hash");
+        chain.append(&json!({"seq": 0})).unwrap();
+        let record = json!({"seq": 1, "body": {"k": [true, null]}});
+
+        let mut injected = record.clone();
+        injected["prev_hash"] = json!(hex::encode(chain.head()));
+        assert_eq!(
+            String::from_utf8(chain.linked_form(&record).unwrap()).unwrap(),
+            to_string(&injected).unwrap()
+        );
+    }
+
+    #[test]
+    fn first_record_links_to_genesis() {
+        let mut chain = HashChain::new("prev_hash");
+        let head = chain.append(&json!({"seq": 0})).unwrap();
+        let expected = Sha256::digest(
+            alloc::format!(r#"{{"prev_hash":"{}","seq":0}}"#, "0".repeat(64)).as_bytes(),
+        );
+        assert_eq!(head, expected.as_slice());
+        assert_eq!(chain.head(), head);
+    }
+
+    #[test]
+    fn verify_chain_detects_tampering_and_reordering() {
+        let records: Vec<_> = (0..5).map(|i| json!({"seq": i})).collect();
+        let mut chain = HashChain::new("prev_hash");
+        for record in &records {
+            chain.append(record).unwrap();
+        }
+        let head = chain.head();
+        assert!(verify_chain("prev_hash", &records, &head).unwrap());
+
+        let mut tampered = records.clone();
+        tampered[2] = json!({"seq": 20});
+        assert!(!verify_chain("prev_hash", &tampered, &head).unwrap());
+
+        let mut reordered = records.clone();
+        reordered.swap(1, 3);
+        assert!(!verify_chain("prev_hash", &reordered, &head).unwrap());
+
+        assert!(!verify_chain("prev", &records, &head).unwrap());
+    }
+
+    #[test]
+    fn resume_continues_from_stored_head() {
+        let mut full = HashChain::new("prev");
+        full.append(&json!({"n": 1})).unwrap();
+        let stored = full.head();
+        full.append(&json!({"n": 2})).unwrap();
+
+        let mut resumed = HashChain::resume("prev", stored);
+        resumed.append(&json!({"n": 2})).unwrap();
+        assert_eq!(resumed, full);
+    }
+
+    #[test]
+    fn rejects_non_objects_and_conflicting_fields() {
+        let mut chain = HashChain::new("prev");
+        assert!(matches!(
+            chain.append(&json!([1, 2])).unwrap_err(),
+            CanonError::NotAnObject
+        ));
+        assert!(matches!(
+            chain.append(&json!({"prev": "mine"})).unwrap_err(),
+            CanonError::ChainFieldConflict { field } if field == "prev"
+        ));
+        assert!(matches!(
+            chain.append(&vec![f64::NAN]).unwrap_err(),
+            CanonError::NonFiniteNumber { .. }
+        ));
+        assert_eq!(chain.head(), GENESIS);
+    }
+
+    #[test]
+    fn preserves_number_text_beyond_f64() {
+        #[derive(Serialize)]
+        struct Big {
+            value: u128,
+        }
+        let chain = HashChain::new("prev");
+        let linked = chain.linked_form(&Big { value: u128::MAX }).unwrap();
+        assert!(String::from_utf8(linked)
+            .unwrap()
+            .contains("340282366920938463463374607431768211455"));
+    }
+}
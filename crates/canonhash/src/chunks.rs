@@ -0,0 +1,136 @@
+//! The canonical form as a sequence of bounded string chunks, for chunked
+//! HTTP bodies and rope-like buffers.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::error::CanonError;
+use crate::ser::{Options, Serializer};
+use crate::sink::Sink;
+
+/// The most bytes a chunk holds. A chunk ends early rather than split a
+/// character.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The canonical form of a value in pieces, from [`canonical_chunks`].
+///
+/// Where chunks begin and end means nothing: concatenated in order, they
+/// are exactly the output of [`canonicalize`](crate::canonicalize). Each is
+/// at most 8 KiB of whole characters.
+#[derive(Clone, Debug)]
+pub struct CanonChunks {
+    chunks: alloc::vec::IntoIter<String>,
+}
+
+impl Iterator for CanonChunks {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.chunks.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for CanonChunks {}
+
+/// Returns the canonical form of `value` as a sequence of chunks.
+///
+/// The output is never held in one allocation, but all of it is produced
+/// before the first chunk is returned, since a serde `Serialize` can't be
+/// paused. As with [`to_writer`](crate::to_writer), each object is buffered
+/// while it is sorted, so peak memory also includes the largest object.
+///
+/// ```
+/// let value = serde_json::json!({"b": [1, 2], "a": "x"});
+/// let chunks = canonhash::canonical_chunks(&value).unwrap();
+/// assert_eq!(chunks.collect::<String>(), r#"{"a":"x","b":[1,2]}"#);
+/// ```
+pub fn canonical_chunks<T>(value: &T) -> Result<CanonChunks, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut sink = ChunkSink::default();
+    value.serialize(Serializer::with_options(&mut sink, Options::default()))?;
+    let mut chunks = sink.full;
+    if !sink.current.is_empty() {
+        chunks.push(chunk(sink.current)?);
+    }
+    Ok(CanonChunks {
+        chunks: chunks.into_iter(),
+    })
+}
+
+#[derive(Default)]
+struct ChunkSink {
+    full: Vec<String>,
+    current: Vec<u8>,
+}
+
+impl Sink for ChunkSink {
+    fn write(&mut self, mut bytes: &[u8]) -> Result<(), CanonError> {
+        // Every write starts on a character boundary, so cutting at one
+        // keeps each chunk valid UTF-8 and never leaves a chunk empty.
+        while self.current.len() + bytes.len() > CHUNK_SIZE {
+            let mut cut = CHUNK_SIZE - self.current.len();
+            while is_continuation(bytes[cut]) {
+                cut -= 1;
+            }
+            self.current.extend_from_slice(&bytes[..cut]);
+            self.full.push(chunk(core::mem::take(&mut self.current))?);
+            bytes = &bytes[cut..];
+        }
+        self.current.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+fn chunk(bytes: Vec<u8>) -> Result<String, CanonError> {
+    String::from_utf8(bytes).map_err(|_| CanonError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    #[test]
+    fn concatenation_is_the_canonical_form() {
+        let value = json!({
+            "z": "\u{1f600}".repeat(5000),
+            "a": (0..3000).collect::<Vec<_>>(),
+            "m": {"y": "é".repeat(9000), "x": null},
+        });
+        let chunks: Vec<String> = canonical_chunks(&value).unwrap().collect();
+        assert!(chunks.len() > 3);
+        assert!(chunks
+            .iter()
+            .all(|c| !c.is_empty() && c.len() <= CHUNK_SIZE));
+        assert_eq!(chunks.concat().as_bytes(), canonicalize(&value).unwrap());
+    }
+
+    #[test]
+    fn small_values_are_one_chunk() {
+        let chunks: Vec<String> = canonical_chunks(&[1, 2]).unwrap().collect();
+        assert_eq!(chunks, ["[1,2]"]);
+        assert_eq!(canonical_chunks(&()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn errors_come_before_any_chunk() {
+        assert!(matches!(
+            canonical_chunks(&[f64::NAN]),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+}
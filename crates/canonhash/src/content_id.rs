@@ -0,0 +1,231 @@
+//! Digests that carry the algorithm that produced them.
+//!
+//! A [`ContentId`] is written as `<algorithm>:<lowercase hex>`, for example
+//! `sha256:9f86…`, and serializes as that string. Two ids are only equal
+//! when both the algorithm and the digest match, so a SHA-256 id can't be
+//! mistaken for a SHA-512 one.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::algorithm::{HashAlgorithm, UnknownAlgorithm};
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+/// A digest together with its [`HashAlgorithm`].
+///
+/// Ordering is by algorithm and then by digest bytes, so ids can key a
+/// `BTreeMap` as well as a `HashMap`.
+///
+/// ```
+/// use canonhash::{ContentId, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let id = ContentId::of(HashAlgorithm::Sha256, &json!({"b": 1, "a": 2})).unwrap();
+/// assert!(id.to_string().starts_with("sha256:"));
+/// assert_eq!(id.to_string().parse::<ContentId>().unwrap(), id);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContentId {
+    alg: HashAlgorithm,
+    digest: Box<[u8]>,
+}
+
+impl ContentId {
+    /// Wraps an existing digest. Fails if `digest` is not `alg`'s output
+    /// length.
+    pub fn new(alg: HashAlgorithm, digest: impl Into<Box<[u8]>>) -> Result<Self, ContentIdError> {
+        let digest = digest.into();
+        if digest.len() != alg.output_len() {
+            return Err(ContentIdError::DigestLength {
+                expected: alg.output_len(),
+                actual: digest.len(),
+            });
+        }
+        Ok(ContentId { alg, digest })
+    }
+
+    /// Canonicalizes `value` and hashes it under `alg`.
+    pub fn of<T>(alg: HashAlgorithm, value: &T) -> Result<Self, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let digest = hash_with(alg, value)?;
+        Ok(ContentId {
+            alg,
+            digest: digest.into_boxed_slice(),
+        })
+    }
+
+    /// The algorithm that produced the digest.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.alg
+    }
+
+    /// The raw digest bytes.
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+impl fmt::Display for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.alg,
+            encode_digest(&self.digest, DigestEncoding::Hex)
+        )
+    }
+}
+
+impl FromStr for ContentId {
+    type Err = ContentIdError;
+
+    /// Parses `<algorithm>:<hex>`. The algorithm name is parsed as by
+    /// [`HashAlgorithm`]'s `FromStr`, and the hex may be either case.
+    fn from_str(s: &str) -> Result<Self, ContentIdError> {
+        let (alg, digest) = s.split_once(':').ok_or(ContentIdError::MissingAlgorithm)?;
+        let alg = alg.parse().map_err(ContentIdError::UnknownAlgorithm)?;
+        let digest = hex::decode(digest).map_err(|_| ContentIdError::InvalidHex)?;
+        ContentId::new(alg, digest)
+    }
+}
+
+impl Serialize for ContentId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Errors from building or parsing a [`ContentId`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContentIdError {
+    /// The text had no `algorithm:` prefix.
+    MissingAlgorithm,
+    /// The prefix is not a known (or enabled) algorithm.
+    UnknownAlgorithm(UnknownAlgorithm),
+    /// The digest was not valid hex.
+    InvalidHex,
+    /// The digest has the wrong length for its algorithm.
+    DigestLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ContentIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentIdError::MissingAlgorithm => {
+                f.write_str("content id must have the form `algorithm:hex`")
+            }
+            ContentIdError::UnknownAlgorithm(err) => err.fmt(f),
+            ContentIdError::InvalidHex => f.write_str("content id digest is not valid hex"),
+            ContentIdError::DigestLength { expected, actual } => write!(
+                f,
+                "content id digest is {actual} bytes but the algorithm produces {expected}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ContentIdError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ContentIdError::UnknownAlgorithm(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn text_form_is_algorithm_and_hex() {
+        let value = json!({"b": 1, "a": 2});
+        let id = ContentId::of(HashAlgorithm::Sha256, &value).unwrap();
+        assert_eq!(
+            id.to_string(),
+            format!("sha256:{}", crate::sha256_hex(&value).unwrap())
+        );
+        assert_eq!(id.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(
+            id.digest(),
+            crate::canonical_sha256(&value).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn parses_its_own_display() {
+        let id = ContentId::of(HashAlgorithm::Sha512, &json!([1])).unwrap();
+        assert_eq!(id.to_string().parse::<ContentId>(), Ok(id.clone()));
+        let upper = id.to_string().to_uppercase();
+        assert_eq!(upper.parse::<ContentId>(), Ok(id));
+    }
+
+    #[test]
+    fn algorithm_is_part_of_identity() {
+        let digest = [7_u8; 32];
+        let sha256 = ContentId::new(HashAlgorithm::Sha256, digest).unwrap();
+        assert_eq!(
+            ContentId::new(HashAlgorithm::Sha512, digest),
+            Err(ContentIdError::DigestLength {
+                expected: 64,
+                actual: 32
+            })
+        );
+        assert_ne!(
+            sha256,
+            ContentId::of(HashAlgorithm::Sha512, &json!(null)).unwrap()
+        );
+    }
+
+    #[test]
+    fn serializes_as_a_string_and_keys_maps() {
+        let a = ContentId::of(HashAlgorithm::Sha256, &json!("a")).unwrap();
+        let b = ContentId::of(HashAlgorithm::Sha384, &json!("a")).unwrap();
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, format!("\"{a}\""));
+        assert_eq!(serde_json::from_str::<ContentId>(&json).unwrap(), a);
+
+        let map = BTreeMap::from([(b.clone(), 2), (a.clone(), 1)]);
+        assert_eq!(map.keys().collect::<Vec<_>>(), [&a, &b]);
+        assert_eq!(
+            crate::canonicalize(&map).unwrap(),
+            format!(r#"{{"{a}":1,"{b}":2}}"#).into_bytes()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        assert_eq!(
+            "abcd".parse::<ContentId>(),
+            Err(ContentIdError::MissingAlgorithm)
+        );
+        assert!(matches!(
+            "md5:abcd".parse::<ContentId>(),
+            Err(ContentIdError::UnknownAlgorithm(_))
+        ));
+        assert_eq!(
+            "sha256:zz".parse::<ContentId>(),
+            Err(ContentIdError::InvalidHex)
+        );
+        assert!(serde_json::from_str::<ContentId>(r#""sha256:00""#).is_err());
+    }
+}
@@ -0,0 +1,845 @@
+//! Explaining why two documents hash differently.
+//!
+//! [`canonical_diff`] compares the canonical forms of two values, so it
+//! reports exactly the differences that change the hash and nothing else: `1`
+//! and `1.0`, or two objects with the same members in a different order, are
+//! equal here, while precomposed and decomposed spellings of the same accented
+//! character are not. [`canonical_patch`] compares the same way and returns
+//! the differences as JSON Patch operations instead. [`compare_documents`]
+//! hashes both sides as well and sums the result up in a report for logs.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon::{canonical_len, to_string};
+use crate::content_id::ContentId;
+use crate::error::{push_pointer_segment, CanonError};
+use crate::hash::digest_bytes;
+use crate::ser::cmp_utf16;
+
+/// How many characters of each side a [`Divergence`] keeps.
+pub const SNIPPET_CHARS: usize = 80;
+
+/// One divergent subtree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonDiff {
+    /// JSON Pointer (RFC 6901) to the subtree; empty for the whole document.
+    pub path: String,
+    /// Canonical form of the left subtree, or `None` if the left side has no
+    /// member or element at `path`.
+    pub left: Option<String>,
+    /// Canonical form of the right subtree, as for `left`.
+    pub right: Option<String>,
+}
+
+/// Returns the places where the canonical forms of `a` and `b` differ.
+///
+/// Objects are compared member by member and arrays element by element, so a
+/// difference deep in a document is reported at its own path rather than as a
+/// change to the whole document. Paths are returned in canonical order. An
+/// empty result means `a` and `b` have the same canonical form and hash.
+///
+/// Either value nesting deeper than the default limit of 128 is
+/// [`CanonError::DepthExceeded`], as it is for hashing.
+pub fn canonical_diff(a: &Value, b: &Value) -> Result<Vec<CanonDiff>, CanonError> {
+    let (a, b) = (to_string(a)?, to_string(b)?);
+    let mut diffs = Vec::new();
+    diff_at(
+        &mut String::new(),
+        &Node::parse(&a),
+        &Node::parse(&b),
+        &mut diffs,
+    );
+    Ok(diffs)
+}
+
+/// One RFC 6902 JSON Patch operation, from [`canonical_patch`].
+///
+/// Serializes as the operation object RFC 6902 defines, such as
+/// `{"op":"replace","path":"/a","value":1}`, so a `Vec<PatchOp>` serializes
+/// as a patch document.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    /// Inserts `value` at `path`: a new object member, or an array element
+    /// that shifts the ones after it.
+    Add { path: String, value: Value },
+    /// Removes the member or element at `path`.
+    Remove { path: String },
+    /// Replaces the value at `path`.
+    Replace { path: String, value: Value },
+}
+
+/// Returns JSON Patch (RFC 6902) operations that turn `from` into `to`, up to
+/// canonical form.
+///
+/// Applied in order, the operations give a document with the same canonical
+/// form and hash as `to`. Only canonical differences produce operations, as
+/// with [`canonical_diff`]. Objects are compared by key, in canonical key
+/// order. Arrays are compared by index: elements present on both sides are
+/// patched in place, then extra elements are added in ascending order or
+/// removed from the end in descending order, so each path is valid when its
+/// operation runs. Inserting near the start of an array therefore patches
+/// every later element rather than adding one, and `move` and `copy` are
+/// never used. A value whose type changes is replaced whole, and so is the
+/// whole document, at the empty path, when either side is a scalar. Either
+/// value nesting deeper than the default limit of 128 is
+/// [`CanonError::DepthExceeded`].
+///
+/// ```
+/// use canonhash::diff::{canonical_patch, PatchOp};
+/// use serde_json::json;
+///
+/// let from = json!({"tags": ["a", "b"], "n": 1});
+/// let to = json!({"tags": ["a"], "n": 2.0, "new": true});
+/// assert_eq!(
+///     serde_json::to_value(canonical_patch(&from, &to).unwrap()).unwrap(),
+///     json!([
+///         {"op": "replace", "path": "/n", "value": 2.0},
+///         {"op": "add", "path": "/new", "value": true},
+///         {"op": "remove", "path": "/tags/1"},
+///     ])
+/// );
+/// ```
+pub fn canonical_patch(from: &Value, to: &Value) -> Result<Vec<PatchOp>, CanonError> {
+    // `patch_at` recurses as deep as the values go, so they are held to the
+    // limit before it starts.
+    canonical_len(from)?;
+    canonical_len(to)?;
+    let mut ops = Vec::new();
+    patch_at(&mut String::new(), from, to, &mut ops);
+    Ok(ops)
+}
+
+/// The outcome of [`compare_documents`]: both documents' hashes and, when
+/// they differ, where.
+///
+/// `Display` writes a few lines for a CI log. The report also serializes,
+/// with each [`ContentId`] as its `sha256:<hex>` string, so it can be kept
+/// as a JSON artifact.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    pub left: ComparedDocument,
+    pub right: ComparedDocument,
+    /// Whether the two canonical forms, and so their hashes, are the same.
+    pub matches: bool,
+    /// How many subtrees differ, as counted by [`canonical_diff`].
+    pub differences: usize,
+    /// The first difference in canonical order, or `None` if they match.
+    pub first_divergence: Option<Divergence>,
+}
+
+/// One side of a [`ComparisonReport`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComparedDocument {
+    /// The name the caller gave the document, such as an environment.
+    pub label: String,
+    /// The SHA-256 of its canonical form.
+    pub id: ContentId,
+    /// Length of its canonical form in bytes.
+    pub byte_len: usize,
+}
+
+/// Where two documents first differ, with the start of each side there.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// JSON Pointer (RFC 6901) to the subtree; empty for the whole document.
+    pub path: String,
+    /// The left subtree's canonical form, cut to [`SNIPPET_CHARS`]
+    /// characters with a trailing `…`. `None` if the left side has nothing
+    /// at `path`.
+    pub left: Option<String>,
+    /// The right subtree, as for `left`.
+    pub right: Option<String>,
+}
+
+/// Canonicalizes and hashes `a` and `b`, and reports whether they match and,
+/// if not, where they first differ.
+///
+/// The labels only name the two sides in the report. Differences are found
+/// as by [`canonical_diff`], so only those that change the hash count.
+///
+/// ```
+/// use canonhash::compare_documents;
+/// use serde_json::json;
+///
+/// let staging = json!({"db": {"host": "db", "port": 5432}, "debug": false});
+/// let production = json!({"debug": false, "db": {"host": "db"}});
+/// let report = compare_documents("staging", &staging, "production", &production).unwrap();
+/// assert!(!report.matches);
+/// let divergence = report.first_divergence.as_ref().unwrap();
+/// assert_eq!(divergence.path, "/db/port");
+/// assert_eq!(divergence.left.as_deref(), Some("5432"));
+/// assert_eq!(divergence.right, None);
+///
+/// let text = report.to_string();
+/// assert!(text.starts_with("staging and production differ in 1 place\n"));
+/// assert!(text.ends_with(
+///     "first difference at `/db/port`\n  staging:    5432\n  production: (absent)"
+/// ));
+/// ```
+pub fn compare_documents<A, B>(
+    label_a: &str,
+    a: &A,
+    label_b: &str,
+    b: &B,
+) -> Result<ComparisonReport, CanonError>
+where
+    A: ?Sized + Serialize,
+    B: ?Sized + Serialize,
+{
+    let (left, a) = compared(label_a, a)?;
+    let (right, b) = compared(label_b, b)?;
+    let matches = left.id == right.id;
+    let mut diffs = Vec::new();
+    if !matches {
+        diff_at(
+            &mut String::new(),
+            &Node::parse(&a),
+            &Node::parse(&b),
+            &mut diffs,
+        );
+    }
+    let first_divergence = diffs.first().map(|diff| Divergence {
+        path: diff.path.clone(),
+        left: diff.left.as_deref().map(snippet),
+        right: diff.right.as_deref().map(snippet),
+    });
+    Ok(ComparisonReport {
+        left,
+        right,
+        matches,
+        differences: diffs.len(),
+        first_divergence,
+    })
+}
+
+/// Summarizes one side and returns its canonical text, which is compared as
+/// it is rather than parsed back into a `Value` that might not hold it.
+fn compared<T>(label: &str, value: &T) -> Result<(ComparedDocument, String), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = to_string(value)?;
+    let alg = HashAlgorithm::Sha256;
+    let id = ContentId::new(alg, digest_bytes(alg, canonical.as_bytes()))
+        .expect("digest has the algorithm's length");
+    let document = ComparedDocument {
+        label: label.into(),
+        id,
+        byte_len: canonical.len(),
+    };
+    Ok((document, canonical))
+}
+
+fn snippet(text: &str) -> String {
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => alloc::format!("{}…", &text[..end]),
+        None => text.into(),
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    /// For example:
+    ///
+    /// ```text
+    /// staging and production differ in 1 place
+    ///   staging:    sha256:…
+    ///   production: sha256:…
+    /// first difference at `/db/port`
+    ///   staging:    5432
+    ///   production: (absent)
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (left, right) = (&self.left.label, &self.right.label);
+        if self.matches {
+            return write!(f, "{left} and {right} match ({})", self.left.id);
+        }
+        let places = if self.differences == 1 {
+            "place"
+        } else {
+            "places"
+        };
+        writeln!(
+            f,
+            "{left} and {right} differ in {} {places}",
+            self.differences
+        )?;
+        let width = left.chars().count().max(right.chars().count()) + 1;
+        let side = |f: &mut fmt::Formatter<'_>, label: &str, text: &dyn fmt::Display| {
+            write!(f, "  {:<width$} {text}", alloc::format!("{label}:"))
+        };
+        side(f, left, &self.left.id)?;
+        f.write_str("\n")?;
+        side(f, right, &self.right.id)?;
+        if let Some(divergence) = &self.first_divergence {
+            if divergence.path.is_empty() {
+                f.write_str("\nfirst difference at the document root\n")?;
+            } else {
+                write!(f, "\nfirst difference at `{}`\n", divergence.path)?;
+            }
+            side(f, left, &divergence.left.as_deref().unwrap_or("(absent)"))?;
+            f.write_str("\n")?;
+            side(f, right, &divergence.right.as_deref().unwrap_or("(absent)"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The canonical form of a scalar, which always has one.
+fn render(value: &Value) -> String {
+    to_string(value).expect("a scalar `Value` canonicalizes")
+}
+
+/// A canonical form split into its members and elements, each holding its
+/// own slice of the text, so that comparing and rendering a subtree costs
+/// nothing beyond the one serialization.
+enum Node<'a> {
+    Object(Vec<(String, Node<'a>)>, &'a str),
+    Array(Vec<Node<'a>>, &'a str),
+    Scalar(&'a str),
+}
+
+impl<'a> Node<'a> {
+    /// Splits `text`, which must be canonical output of this crate.
+    fn parse(text: &'a str) -> Self {
+        let mut pos = 0;
+        let node = Node::parse_at(text, &mut pos);
+        debug_assert_eq!(pos, text.len(), "trailing text after the value");
+        node
+    }
+
+    fn parse_at(text: &'a str, pos: &mut usize) -> Self {
+        let bytes = text.as_bytes();
+        let start = *pos;
+        match bytes[start] {
+            b'{' => {
+                *pos += 1;
+                let mut members = Vec::new();
+                while bytes[*pos] != b'}' {
+                    let key_start = *pos;
+                    skip_string(bytes, pos);
+                    let key = serde_json::from_str(&text[key_start..*pos])
+                        .expect("canonical keys are JSON strings");
+                    *pos += 1;
+                    members.push((key, Node::parse_at(text, pos)));
+                    if bytes[*pos] == b',' {
+                        *pos += 1;
+                    }
+                }
+                *pos += 1;
+                Node::Object(members, &text[start..*pos])
+            }
+            b'[' => {
+                *pos += 1;
+                let mut elements = Vec::new();
+                while bytes[*pos] != b']' {
+                    elements.push(Node::parse_at(text, pos));
+                    if bytes[*pos] == b',' {
+                        *pos += 1;
+                    }
+                }
+                *pos += 1;
+                Node::Array(elements, &text[start..*pos])
+            }
+            b'"' => {
+                skip_string(bytes, pos);
+                Node::Scalar(&text[start..*pos])
+            }
+            _ => {
+                // A number or literal runs to the next delimiter; canonical
+                // text has no whitespace.
+                while *pos < bytes.len() && !matches!(bytes[*pos], b',' | b']' | b'}') {
+                    *pos += 1;
+                }
+                Node::Scalar(&text[start..*pos])
+            }
+        }
+    }
+
+    fn text(&self) -> &'a str {
+        match self {
+            Node::Object(_, text) | Node::Array(_, text) | Node::Scalar(text) => text,
+        }
+    }
+}
+
+/// Moves `pos` past the string literal starting there.
+fn skip_string(bytes: &[u8], pos: &mut usize) {
+    *pos += 1;
+    loop {
+        match bytes[*pos] {
+            b'\\' => *pos += 2,
+            b'"' => break,
+            _ => *pos += 1,
+        }
+    }
+    *pos += 1;
+}
+
+/// Compares two canonical forms member by member and element by element.
+/// Only leaves are compared by text, so each byte is looked at once.
+fn diff_at(path: &mut String, a: &Node<'_>, b: &Node<'_>, diffs: &mut Vec<CanonDiff>) {
+    match (a, b) {
+        (Node::Object(a, _), Node::Object(b, _)) => {
+            // Both sides are already in canonical key order.
+            let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+            loop {
+                let order = match (a.peek(), b.peek()) {
+                    (Some(x), Some(y)) => cmp_utf16(&x.0, &y.0),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => break,
+                };
+                let (key, left, right) = match order {
+                    Ordering::Less => {
+                        let (key, left) = a.next().expect("peeked");
+                        (key, Some(left), None)
+                    }
+                    Ordering::Greater => {
+                        let (key, right) = b.next().expect("peeked");
+                        (key, None, Some(right))
+                    }
+                    Ordering::Equal => {
+                        let (key, left) = a.next().expect("peeked");
+                        let (_, right) = b.next().expect("peeked");
+                        (key, Some(left), Some(right))
+                    }
+                };
+                let len = path.len();
+                push_pointer_segment(path, key);
+                child(path, left, right, diffs);
+                path.truncate(len);
+            }
+        }
+        (Node::Array(a, _), Node::Array(b, _)) => {
+            for i in 0..a.len().max(b.len()) {
+                let len = path.len();
+                push_pointer_segment(path, itoa::Buffer::new().format(i));
+                child(path, a.get(i), b.get(i), diffs);
+                path.truncate(len);
+            }
+        }
+        _ if a.text() == b.text() => {}
+        _ => diffs.push(CanonDiff {
+            path: path.clone(),
+            left: Some(a.text().into()),
+            right: Some(b.text().into()),
+        }),
+    }
+}
+
+fn child(
+    path: &mut String,
+    a: Option<&Node<'_>>,
+    b: Option<&Node<'_>>,
+    diffs: &mut Vec<CanonDiff>,
+) {
+    match (a, b) {
+        (Some(a), Some(b)) => diff_at(path, a, b, diffs),
+        (a, b) => diffs.push(CanonDiff {
+            path: path.clone(),
+            left: a.map(|node| node.text().into()),
+            right: b.map(|node| node.text().into()),
+        }),
+    }
+}
+
+/// Only containers of the same kind are recursed into, and only scalars are
+/// rendered to be compared, so the work is proportional to the documents.
+fn patch_at(path: &mut String, from: &Value, to: &Value, ops: &mut Vec<PatchOp>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut keys: Vec<&String> = from.keys().chain(to.keys()).collect();
+            keys.sort_by(|x, y| cmp_utf16(x, y));
+            keys.dedup();
+            for key in keys {
+                let len = path.len();
+                push_pointer_segment(path, key);
+                match (from.get(key), to.get(key)) {
+                    (Some(from), Some(to)) => patch_at(path, from, to, ops),
+                    (Some(_), None) => ops.push(PatchOp::Remove { path: path.clone() }),
+                    (None, Some(to)) => ops.push(PatchOp::Add {
+                        path: path.clone(),
+                        value: to.clone(),
+                    }),
+                    (None, None) => unreachable!("key came from one of the objects"),
+                }
+                path.truncate(len);
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            for (i, (from, to)) in from.iter().zip(to).enumerate() {
+                let len = path.len();
+                push_pointer_segment(path, itoa::Buffer::new().format(i));
+                patch_at(path, from, to, ops);
+                path.truncate(len);
+            }
+            let common = from.len().min(to.len());
+            for (i, value) in to.iter().enumerate().skip(common) {
+                ops.push(PatchOp::Add {
+                    path: element_path(path, i),
+                    value: value.clone(),
+                });
+            }
+            for i in (common..from.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: element_path(path, i),
+                });
+            }
+        }
+        (Value::Object(_) | Value::Array(_), _) | (_, Value::Object(_) | Value::Array(_)) => ops
+            .push(PatchOp::Replace {
+                path: path.clone(),
+                value: to.clone(),
+            }),
+        _ if render(from) == render(to) => {}
+        _ => ops.push(PatchOp::Replace {
+            path: path.clone(),
+            value: to.clone(),
+        }),
+    }
+}
+
+fn element_path(path: &str, index: usize) -> String {
+    let mut path = String::from(path);
+    push_pointer_segment(&mut path, itoa::Buffer::new().format(index));
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn diff(path: &str, left: Option<&str>, right: Option<&str>) -> CanonDiff {
+        CanonDiff {
+            path: path.into(),
+            left: left.map(Into::into),
+            right: right.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn equal_canonical_forms_have_no_diff() {
+        let a = json!({"n": 1.0, "list": [1, 2], "s": "x"});
+        let b = json!({"s": "x", "list": [1.0, 2], "n": 1});
+        assert!(canonical_diff(&a, &b).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_nested_scalar_change() {
+        let a = json!({"outer": {"same": true, "n": 1}});
+        let b = json!({"outer": {"same": true, "n": 1.5}});
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            vec![diff("/outer/n", Some("1"), Some("1.5"))]
+        );
+    }
+
+    #[test]
+    fn distinguishes_unicode_normalization_forms() {
+        let a = json!({"name": "caf\u{e9}"});
+        let b = json!({"name": "cafe\u{301}"});
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            vec![diff(
+                "/name",
+                Some("\"caf\u{e9}\""),
+                Some("\"cafe\u{301}\"")
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_missing_members_and_elements() {
+        let a = json!({"a/b": 1, "list": [1, 2, 3]});
+        let b = json!({"c": {"d": null}, "list": [1, 2]});
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            vec![
+                diff("/a~1b", Some("1"), None),
+                diff("/c", None, Some(r#"{"d":null}"#)),
+                diff("/list/2", Some("3"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_change_is_reported_at_its_path() {
+        let a = json!({"v": [1]});
+        let b = json!({"v": {"0": 1}});
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            vec![diff("/v", Some("[1]"), Some(r#"{"0":1}"#))]
+        );
+        assert_eq!(
+            canonical_diff(&json!(1), &json!("1")).unwrap(),
+            vec![diff("", Some("1"), Some("\"1\""))]
+        );
+    }
+
+    /// Applies `ops` as RFC 6902 describes, for the three operations used.
+    fn apply(mut doc: Value, ops: &[PatchOp]) -> Value {
+        for op in ops {
+            let (path, value) = match op {
+                PatchOp::Add { path, value } | PatchOp::Replace { path, value } => {
+                    (path, Some(value.clone()))
+                }
+                PatchOp::Remove { path } => (path, None),
+            };
+            if path.is_empty() {
+                doc = value.unwrap();
+                continue;
+            }
+            let (parent, last) = path.rsplit_once('/').unwrap();
+            let last = last.replace("~1", "/").replace("~0", "~");
+            match (doc.pointer_mut(parent).unwrap(), op, value) {
+                (Value::Object(map), _, Some(value)) => {
+                    map.insert(last, value);
+                }
+                (Value::Object(map), _, None) => {
+                    map.remove(&last).unwrap();
+                }
+                (Value::Array(items), PatchOp::Add { .. }, Some(value)) => {
+                    items.insert(last.parse().unwrap(), value)
+                }
+                (Value::Array(items), PatchOp::Replace { .. }, Some(value)) => {
+                    items[last.parse::<usize>().unwrap()] = value
+                }
+                (Value::Array(items), _, None) => {
+                    items.remove(last.parse().unwrap());
+                }
+                (parent, _, _) => panic!("no container at {parent}"),
+            }
+        }
+        doc
+    }
+
+    #[test]
+    fn patch_turns_one_document_into_the_other() {
+        let pairs = [
+            (
+                json!({"a/b": 1, "list": [1, 2, 3], "keep": {"x": [true]}}),
+                json!({"c": {"d": null}, "list": [1, 2.5], "keep": {"x": [true]}}),
+            ),
+            (json!([1, [2, 3]]), json!([1, [2, 3, 4, 5], {"k": "v"}])),
+            (json!({"v": [1]}), json!({"v": {"0": 1}})),
+            (json!([{"a": 1}, 2]), json!([])),
+            (json!(1), json!("1")),
+        ];
+        for (from, to) in pairs {
+            let ops = canonical_patch(&from, &to).unwrap();
+            let patched = apply(from.clone(), &ops);
+            assert!(
+                canonical_diff(&patched, &to).unwrap().is_empty(),
+                "{from} -> {to}: {ops:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn patch_is_empty_for_equal_canonical_forms() {
+        let a = json!({"n": 1.0, "list": [1, 2]});
+        let b = json!({"list": [1.0, 2], "n": 1});
+        assert!(canonical_patch(&a, &b).unwrap().is_empty());
+    }
+
+    #[test]
+    fn patch_uses_key_and_index_paths() {
+        let from = json!({"z": 0, "a~b": [0, 1, 2, 3], "m": {"k": 1}});
+        let to = json!({"a~b": [0, 9], "m": {"k": 1, "j": 2}});
+        assert_eq!(
+            canonical_patch(&from, &to).unwrap(),
+            vec![
+                PatchOp::Replace {
+                    path: "/a~0b/1".into(),
+                    value: json!(9),
+                },
+                PatchOp::Remove {
+                    path: "/a~0b/3".into(),
+                },
+                PatchOp::Remove {
+                    path: "/a~0b/2".into(),
+                },
+                PatchOp::Add {
+                    path: "/m/j".into(),
+                    value: json!(2),
+                },
+                PatchOp::Remove { path: "/z".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_on_matching_documents() {
+        let a = json!({"b": 1, "a": [1.0, "x"]});
+        let b = json!({"a": [1, "x"], "b": 1});
+        let report = compare_documents("local", &a, "ci", &b).unwrap();
+        assert!(report.matches);
+        assert_eq!(report.differences, 0);
+        assert_eq!(report.first_divergence, None);
+        assert_eq!(report.left.id, report.right.id);
+        assert_eq!(report.left.byte_len, br#"{"a":[1,"x"],"b":1}"#.len());
+        assert_eq!(
+            report.left.id,
+            ContentId::of(HashAlgorithm::Sha256, &a).unwrap()
+        );
+        assert_eq!(
+            report.to_string(),
+            alloc::format!("local and ci match ({})", report.left.id)
+        );
+    }
+
+    #[test]
+    fn report_names_the_first_divergence() {
+        let staging = json!({"db": {"host": "db", "port": 5432}, "debug": false, "z": 1});
+        let production = json!({"debug": true, "db": {"host": "db"}, "z": 1});
+        let report = compare_documents("staging", &staging, "production", &production).unwrap();
+        assert!(!report.matches);
+        assert_ne!(report.left.id, report.right.id);
+        assert_eq!(report.differences, 2);
+        assert_eq!(
+            report.first_divergence,
+            Some(Divergence {
+                path: "/db/port".into(),
+                left: Some("5432".into()),
+                right: None,
+            })
+        );
+        assert_eq!(
+            report.to_string(),
+            alloc::format!(
+                "staging and production differ in 2 places\n  \
+                 staging:    {}\n  \
+                 production: {}\n\
+                 first difference at `/db/port`\n  \
+                 staging:    5432\n  \
+                 production: (absent)",
+                report.left.id,
+                report.right.id
+            )
+        );
+    }
+
+    #[test]
+    fn snippets_are_cut_on_character_boundaries() {
+        let long = "é".repeat(200);
+        let report = compare_documents("a", &json!([long]), "b", &json!(1)).unwrap();
+        let divergence = report.first_divergence.unwrap();
+        assert_eq!(divergence.path, "");
+        let left = divergence.left.unwrap();
+        assert_eq!(left.chars().count(), SNIPPET_CHARS + 1);
+        assert!(left.starts_with("[\"éé") && left.ends_with("é…"));
+        assert_eq!(divergence.right.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn report_round_trips_through_json() {
+        let report = compare_documents("a", &json!({"k": [1]}), "b", &json!({"k": [2]})).unwrap();
+        let text = serde_json::to_string(&report).unwrap();
+        let value: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["first_divergence"]["path"], "/k/0");
+        assert_eq!(value["left"]["label"], "a");
+        assert_eq!(value["left"]["id"], report.left.id.to_string());
+        assert_eq!(
+            serde_json::from_str::<ComparisonReport>(&text).unwrap(),
+            report
+        );
+        assert!(matches!(
+            compare_documents("a", &f64::NAN, "b", &json!(1)),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn values_deeper_than_the_default_limit_are_errors() {
+        fn nested(depth: usize, leaf: Value) -> Value {
+            (0..depth).fold(leaf, |inner, _| json!([inner]))
+        }
+        let (a, b) = (nested(128, json!(1)), nested(128, json!(2)));
+        let path = "/0".repeat(128);
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            [diff(&path, Some("1"), Some("2"))]
+        );
+        assert_eq!(
+            canonical_patch(&a, &b).unwrap(),
+            [PatchOp::Replace {
+                path,
+                value: json!(2)
+            }]
+        );
+
+        let deep = nested(129, json!(1));
+        let too_deep = |result: Result<(), CanonError>| {
+            matches!(result, Err(CanonError::DepthExceeded { limit: 128, .. }))
+        };
+        assert!(too_deep(canonical_diff(&deep, &a).map(drop)));
+        assert!(too_deep(canonical_diff(&a, &deep).map(drop)));
+        assert!(too_deep(canonical_patch(&deep, &a).map(drop)));
+        assert!(too_deep(canonical_patch(&a, &deep).map(drop)));
+        assert!(too_deep(compare_documents("a", &deep, "b", &a).map(drop)));
+    }
+
+    #[test]
+    fn members_are_matched_in_canonical_key_order() {
+        // U+1F600 sorts before U+E000 in UTF-16 but after it in UTF-8.
+        let a = json!({"\u{e000}": 1, "\u{1f600}": 2, "a": [1, {"k": "v"}]});
+        let b = json!({"\u{e000}": 1, "\u{1f600}": 3, "b": true, "a": [1.0, {"k": "w"}]});
+        assert_eq!(
+            canonical_diff(&a, &b).unwrap(),
+            [
+                diff("/a/1/k", Some(r#""v""#), Some(r#""w""#)),
+                diff("/b", None, Some("true")),
+                diff("/\u{1f600}", Some("2"), Some("3")),
+            ]
+        );
+        let escaped = json!({"q\"\\": ["\n", {"\u{1}": null}]});
+        assert!(canonical_diff(&escaped, &escaped.clone())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            canonical_diff(&escaped, &json!({"q\"\\": []})).unwrap(),
+            [
+                diff("/q\"\\/0", Some(r#""\n""#), None),
+                diff("/q\"\\/1", Some(r#"{"\u0001":null}"#), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_keeps_integers_beyond_64_bits() {
+        #[derive(Serialize)]
+        struct Config {
+            id: u128,
+        }
+
+        let a = Config { id: u128::MAX };
+        let b = Config { id: u128::MAX - 1 };
+        let report = compare_documents("a", &a, "b", &b).unwrap();
+        assert!(!report.matches);
+        assert_eq!(report.differences, 1);
+        let divergence = report.first_divergence.unwrap();
+        assert_eq!(divergence.path, "/id");
+        assert_eq!(divergence.left, Some(u128::MAX.to_string()));
+        assert_eq!(divergence.right, Some((u128::MAX - 1).to_string()));
+    }
+
+    #[test]
+    fn report_covers_the_full_depth_limit() {
+        let nested = |leaf: Value| (0..127).fold(leaf, |inner, _| json!([inner]));
+        let (a, b) = (nested(json!([1])), nested(json!([2])));
+        let report = compare_documents("a", &a, "b", &b).unwrap();
+        let divergence = report.first_divergence.unwrap();
+        assert_eq!(divergence.path, "/0".repeat(128));
+        assert_eq!(divergence.left.as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Hashes bound to the protocol or purpose they were made for.
+//!
+//! The same document may be hashed by more than one protocol. Without a tag,
+//! a digest computed for one of them (say, a signed receipt) is also a valid
+//! digest under the others (say, a cache key). Putting a domain string in
+//! front of the canonical bytes keeps the digests apart.
+//!
+//! # Framing
+//!
+//! [`hash_domain_separated`] digests these bytes under the chosen
+//! algorithm:
+//!
+//! ```text
+//! len(domain) as an unsigned 64-bit big-endian integer (8 bytes)
+//! domain, as UTF-8
+//! the canonical form of the value
+//! ```
+//!
+//! Because the domain's length comes first, the split between domain and
+//! payload is never ambiguous. Domain `"ab"` with payload `P` and domain `"a"`
+//! with payload `"b" || P` hash different bytes. An empty domain still gets
+//! its 8-byte length, so it doesn't produce the plain [`hash_with`] digest.
+//!
+//! [`hash_with`]: crate::hash_with
+
+use alloc::vec::Vec;
+
+use digest::{Digest, Update};
+use serde::Serialize;
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::algorithm::HashAlgorithm;
+use crate::error::CanonError;
+use crate::hash::feed;
+use crate::ser::{Options, Scope};
+
+/// Returns the digest under `alg` of `domain`, framed as the
+/// [module docs](self) describe, followed by the canonical form of `value`.
+///
+/// Pick a domain that names the protocol and its version, such as
+/// `"example.com/receipt/v1"`, and use it everywhere the digest is computed
+/// or checked.
+///
+/// ```
+/// use canonhash::{hash_domain_separated, hash_with, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let value = json!({"amount": 5});
+/// let receipt = hash_domain_separated("example.com/receipt/v1", &value, HashAlgorithm::Sha256).unwrap();
+/// let cache = hash_domain_separated("example.com/cache/v1", &value, HashAlgorithm::Sha256).unwrap();
+/// assert_ne!(receipt, cache);
+/// assert_ne!(receipt, hash_with(HashAlgorithm::Sha256, &value).unwrap());
+/// ```
+pub fn hash_domain_separated<T>(
+    domain: &str,
+    value: &T,
+    alg: HashAlgorithm,
+) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    fn framed<D: Digest + Update, T: ?Sized + Serialize>(
+        domain: &str,
+        value: &T,
+    ) -> Result<Vec<u8>, CanonError> {
+        let mut digest = D::new();
+        Update::update(&mut digest, &frame_len(domain));
+        Update::update(&mut digest, domain.as_bytes());
+        feed(&mut digest, value, Options::default(), Scope::default())?;
+        Ok(digest.finalize().to_vec())
+    }
+
+    match alg {
+        HashAlgorithm::Sha256 => framed::<Sha256, T>(domain, value),
+        HashAlgorithm::Sha384 => framed::<Sha384, T>(domain, value),
+        HashAlgorithm::Sha512 => framed::<Sha512, T>(domain, value),
+        #[cfg(feature = "blake2")]
+        HashAlgorithm::Blake2b => framed::<blake2::Blake2b512, T>(domain, value),
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&frame_len(domain));
+            hasher.update(domain.as_bytes());
+            feed(
+                &mut crate::hash::Blake3(&mut hasher),
+                value,
+                Options::default(),
+                Scope::default(),
+            )?;
+            Ok(hasher.finalize().as_bytes().to_vec())
+        }
+    }
+}
+
+/// The 8-byte length prefix of `domain`.
+fn frame_len(domain: &str) -> [u8; 8] {
+    (domain.len() as u64).to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+    use crate::hash::{digest_bytes, hash_with};
+
+    fn manual(domain: &str, value: &serde_json::Value, alg: HashAlgorithm) -> Vec<u8> {
+        let mut framed = (domain.len() as u64).to_be_bytes().to_vec();
+        framed.extend_from_slice(domain.as_bytes());
+        framed.extend_from_slice(&canonicalize(value).unwrap());
+        digest_bytes(alg, &framed)
+    }
+
+    #[test]
+    fn digests_the_documented_framing() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        for alg in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+            #[cfg(feature = "blake2")]
+            HashAlgorithm::Blake2b,
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3,
+        ] {
+            assert_eq!(
+                hash_domain_separated("proto/v1", &value, alg).unwrap(),
+                manual("proto/v1", &value, alg),
+                "{alg}"
+            );
+        }
+        // A fixed vector, so other implementations can check their framing.
+        assert_eq!(
+            hex::encode(hash_domain_separated("d", &json!(1), HashAlgorithm::Sha256).unwrap()),
+            hex::encode(Sha256::digest(b"\0\0\0\0\0\0\0\x01d1"))
+        );
+    }
+
+    #[test]
+    fn split_between_domain_and_payload_is_unambiguous() {
+        // Plain concatenation gives "123" both ways.
+        let a = hash_domain_separated("1", &json!(23), HashAlgorithm::Sha256).unwrap();
+        let b = hash_domain_separated("12", &json!(3), HashAlgorithm::Sha256).unwrap();
+        assert_ne!(a, b);
+
+        let empty = hash_domain_separated("", &json!(3), HashAlgorithm::Sha256).unwrap();
+        assert_ne!(empty, hash_with(HashAlgorithm::Sha256, &json!(3)).unwrap());
+    }
+
+    #[test]
+    fn canonicalization_errors_are_returned() {
+        assert!(matches!(
+            hash_domain_separated("d", &f64::NAN, HashAlgorithm::Sha256),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+}
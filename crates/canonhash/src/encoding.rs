@@ -0,0 +1,149 @@
+//! Text encodings for digests.
+//!
+//! All digest formatting goes through [`encode_digest`] and
+//! [`decode_digest`], so callers choose an encoding by value instead of
+//! calling `hex` or `base64` directly.
+//!
+//! With the `faster-hex` feature, hex is encoded by the SIMD routines of the
+//! `faster-hex` crate. The text is the same either way.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+
+use crate::error::CanonError;
+
+/// How a digest is written as text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DigestEncoding {
+    /// Lowercase hex.
+    Hex,
+    /// Uppercase hex.
+    HexUpper,
+    /// Standard base64 (RFC 4648 §4) with padding.
+    Base64,
+    /// URL-safe base64 (RFC 4648 §5) with padding.
+    Base64Url,
+    /// URL-safe base64 without padding, as used by JOSE and most web
+    /// protocols.
+    Base64UrlNoPad,
+}
+
+/// Encodes `bytes` as text in `enc`.
+pub fn encode_digest(bytes: &[u8], enc: DigestEncoding) -> String {
+    match enc {
+        DigestEncoding::Hex => hex_lower(bytes),
+        DigestEncoding::HexUpper => hex_upper(bytes),
+        DigestEncoding::Base64 => STANDARD.encode(bytes),
+        DigestEncoding::Base64Url => URL_SAFE.encode(bytes),
+        DigestEncoding::Base64UrlNoPad => URL_SAFE_NO_PAD.encode(bytes),
+    }
+}
+
+#[cfg(feature = "faster-hex")]
+fn hex_lower(bytes: &[u8]) -> String {
+    faster_hex::hex_string(bytes)
+}
+
+#[cfg(not(feature = "faster-hex"))]
+fn hex_lower(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+#[cfg(feature = "faster-hex")]
+fn hex_upper(bytes: &[u8]) -> String {
+    faster_hex::hex_string_upper(bytes)
+}
+
+#[cfg(not(feature = "faster-hex"))]
+fn hex_upper(bytes: &[u8]) -> String {
+    hex::encode_upper(bytes)
+}
+
+/// Decodes a digest written in `enc`.
+///
+/// Hex is accepted in either case. The base64 variants accept only their own
+/// alphabet and padding rule, so a digest in the wrong variant is an error
+/// rather than silently decoding to different bytes.
+pub fn decode_digest(s: &str, enc: DigestEncoding) -> Result<Vec<u8>, CanonError> {
+    match enc {
+        DigestEncoding::Hex | DigestEncoding::HexUpper => {
+            hex::decode(s).map_err(|_| CanonError::InvalidHex)
+        }
+        DigestEncoding::Base64 => STANDARD.decode(s).map_err(|_| CanonError::InvalidBase64),
+        DigestEncoding::Base64Url => URL_SAFE.decode(s).map_err(|_| CanonError::InvalidBase64),
+        DigestEncoding::Base64UrlNoPad => URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| CanonError::InvalidBase64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [DigestEncoding; 5] = [
+        DigestEncoding::Hex,
+        DigestEncoding::HexUpper,
+        DigestEncoding::Base64,
+        DigestEncoding::Base64Url,
+        DigestEncoding::Base64UrlNoPad,
+    ];
+
+    #[test]
+    fn encodes_each_variant() {
+        let bytes = [0xfb, 0xff, 0x01];
+        let encoded: Vec<String> = ALL.iter().map(|&e| encode_digest(&bytes, e)).collect();
+        assert_eq!(encoded, ["fbff01", "FBFF01", "+/8B", "-_8B", "-_8B"]);
+        assert_eq!(encode_digest(&[0xfb], DigestEncoding::Base64Url), "-w==");
+        assert_eq!(encode_digest(&[0xfb], DigestEncoding::Base64UrlNoPad), "-w");
+    }
+
+    #[test]
+    fn hex_matches_the_hex_crate_at_every_length() {
+        // Covers the SIMD block sizes and the scalar tails after them.
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+        for len in 0..bytes.len() {
+            let bytes = &bytes[..len];
+            assert_eq!(
+                encode_digest(bytes, DigestEncoding::Hex),
+                hex::encode(bytes)
+            );
+            assert_eq!(
+                encode_digest(bytes, DigestEncoding::HexUpper),
+                hex::encode_upper(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_every_variant() {
+        let digest: Vec<u8> = (0..=255).step_by(7).collect();
+        for enc in ALL {
+            let text = encode_digest(&digest, enc);
+            assert_eq!(decode_digest(&text, enc).unwrap(), digest, "{enc:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_the_wrong_variant() {
+        assert!(matches!(
+            decode_digest("+/8B", DigestEncoding::Base64Url),
+            Err(CanonError::InvalidBase64)
+        ));
+        assert!(matches!(
+            decode_digest("-w==", DigestEncoding::Base64UrlNoPad),
+            Err(CanonError::InvalidBase64)
+        ));
+        assert!(matches!(
+            decode_digest("xyz", DigestEncoding::Hex),
+            Err(CanonError::InvalidHex)
+        ));
+        assert_eq!(
+            decode_digest("FbfF01", DigestEncoding::Hex).unwrap(),
+            [0xfb, 0xff, 0x01]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Receipts: a value hashed together with a server-supplied timestamp and
+//! nonce.
+//!
+//! # Envelope contract
+//!
+//! The hashed document is a JSON object with the value under `"payload"`,
+//! the timestamp under `"ts"` and the nonce under `"nonce"`, both as
+//! strings, plus any [`Envelope::extra`] members. A `ts` or `nonce` that is
+//! `None` is left out, not written as `null`. The digest is that of the
+//! canonical form of the object, so a verifier holding the same payload and
+//! envelope fields can rebuild it with any RFC 8785 implementation:
+//!
+//! ```text
+//! {"nonce":"n-1","payload":{"amount":5},"ts":"2024-05-01T12:00:00Z"}
+//! ```
+//!
+//! The timestamp and nonce are taken as given: choosing their format, and
+//! checking them when verifying, is up to the caller.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+/// The member holding the wrapped value.
+pub const PAYLOAD_KEY: &str = "payload";
+/// The member holding [`Envelope::timestamp`].
+pub const TIMESTAMP_KEY: &str = "ts";
+/// The member holding [`Envelope::nonce`].
+pub const NONCE_KEY: &str = "nonce";
+
+/// The fields wrapped around a value by [`hash_with_envelope`]. See the
+/// [module docs](self) for the layout.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Envelope {
+    /// Written as [`TIMESTAMP_KEY`] when set.
+    pub timestamp: Option<String>,
+    /// Written as [`NONCE_KEY`] when set.
+    pub nonce: Option<String>,
+    /// Further members of the envelope. None of them may use one of the
+    /// three keys above.
+    pub extra: Map<String, Value>,
+}
+
+impl Envelope {
+    /// Returns the canonical bytes that [`hash_with_envelope`] hashes for
+    /// `value`, or [`CanonError::EnvelopeFieldConflict`] if
+    /// [`extra`](Self::extra) uses a reserved key.
+    pub fn canonical_form<T>(&self, value: &T) -> Result<Vec<u8>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.check()?;
+        canonicalize(&Wrapped {
+            envelope: self,
+            payload: value,
+        })
+    }
+
+    fn check(&self) -> Result<(), CanonError> {
+        match [PAYLOAD_KEY, TIMESTAMP_KEY, NONCE_KEY]
+            .into_iter()
+            .find(|key| self.extra.contains_key(*key))
+        {
+            Some(key) => Err(CanonError::EnvelopeFieldConflict { field: key.into() }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Wraps `value` in `envelope` and returns the digest of the canonical form
+/// of the result under `alg`.
+///
+/// ```
+/// use canonhash::envelope::{hash_with_envelope, Envelope};
+/// use canonhash::{hash_with, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let envelope = Envelope {
+///     timestamp: Some("2024-05-01T12:00:00Z".into()),
+///     nonce: Some("n-1".into()),
+///     ..Envelope::default()
+/// };
+/// let receipt = hash_with_envelope(&json!({"amount": 5}), envelope, HashAlgorithm::Sha256).unwrap();
+/// let rebuilt = json!({
+///     "payload": {"amount": 5},
+///     "ts": "2024-05-01T12:00:00Z",
+///     "nonce": "n-1",
+/// });
+/// assert_eq!(receipt, hash_with(HashAlgorithm::Sha256, &rebuilt).unwrap());
+/// ```
+pub fn hash_with_envelope<T>(
+    value: &T,
+    envelope: Envelope,
+    alg: HashAlgorithm,
+) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    envelope.check()?;
+    hash_with(
+        alg,
+        &Wrapped {
+            envelope: &envelope,
+            payload: value,
+        },
+    )
+}
+
+/// The envelope object, serialized without building a `Value`. The
+/// canonical serializer puts the members in order.
+struct Wrapped<'a, T: ?Sized> {
+    envelope: &'a Envelope,
+    payload: &'a T,
+}
+
+impl<T: ?Sized + Serialize> Serialize for Wrapped<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(PAYLOAD_KEY, self.payload)?;
+        if let Some(timestamp) = &self.envelope.timestamp {
+            map.serialize_entry(TIMESTAMP_KEY, timestamp)?;
+        }
+        if let Some(nonce) = &self.envelope.nonce {
+            map.serialize_entry(NONCE_KEY, nonce)?;
+        }
+        for (key, value) in &self.envelope.extra {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn layout_matches_the_contract() {
+        let mut extra = Map::new();
+        extra.insert("issuer".into(), json!("svc-a"));
+        let envelope = Envelope {
+            timestamp: Some("2024-05-01T12:00:00Z".into()),
+            nonce: Some("n-1".into()),
+            extra,
+        };
+        let bytes = envelope
+            .canonical_form(&json!({"b": 1.0, "a": null}))
+            .unwrap();
+        assert_eq!(
+            bytes,
+            br#"{"issuer":"svc-a","nonce":"n-1","payload":{"a":null,"b":1},"ts":"2024-05-01T12:00:00Z"}"#
+        );
+        assert_eq!(
+            hash_with_envelope(&json!({"a": null, "b": 1}), envelope, HashAlgorithm::Sha256)
+                .unwrap(),
+            crate::hash::digest_bytes(HashAlgorithm::Sha256, &bytes)
+        );
+    }
+
+    #[test]
+    fn unset_fields_are_left_out() {
+        let envelope = Envelope::default();
+        assert_eq!(
+            envelope.canonical_form(&[1]).unwrap(),
+            br#"{"payload":[1]}"#
+        );
+        let nonce_only = Envelope {
+            nonce: Some("x".into()),
+            ..Envelope::default()
+        };
+        assert_eq!(
+            nonce_only.canonical_form(&()).unwrap(),
+            br#"{"nonce":"x","payload":null}"#
+        );
+    }
+
+    #[test]
+    fn extra_members_cannot_use_reserved_keys() {
+        for key in [PAYLOAD_KEY, TIMESTAMP_KEY, NONCE_KEY] {
+            let mut extra = Map::new();
+            extra.insert(key.into(), json!(0));
+            let envelope = Envelope {
+                extra,
+                ..Envelope::default()
+            };
+            assert!(matches!(
+                hash_with_envelope(&1, envelope, HashAlgorithm::Sha256),
+                Err(CanonError::EnvelopeFieldConflict { field }) if field == key
+            ));
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Errors produced while canonicalizing or hashing a value.
+///
+/// `source()` chains to the underlying `serde_json` or I/O error, and both
+/// convert with `?`.
+#[derive(Debug)]
+pub enum CanonError {
+    /// A map key serialized to something other than a string or integer.
+    /// `path` locates the map.
+    KeyMustBeAString { path: JsonPath },
+    /// The canonical output was not valid UTF-8. This is checked on the
+    /// finished output, so it has no path.
+    InvalidUtf8,
+    /// A float was NaN or infinite, which JSON cannot represent. `path`
+    /// locates the offending value; it is empty when the value itself is
+    /// the number.
+    NonFiniteNumber { path: JsonPath },
+    /// Arrays and objects nested deeper than `limit`, which defaults to 128
+    /// and is set with [`Canonicalizer::max_depth`]. `path` locates the
+    /// array or object that went one level too deep.
+    ///
+    /// [`Canonicalizer::max_depth`]: crate::Canonicalizer::max_depth
+    DepthExceeded { limit: usize, path: JsonPath },
+    /// A size limit set on a [`Canonicalizer`] was reached. `path` locates
+    /// the object with too many members, or the value being written when
+    /// the output grew too long.
+    ///
+    /// [`Canonicalizer`]: crate::Canonicalizer
+    LimitExceeded { limit: Limit, path: JsonPath },
+    /// A JSON text input could not be parsed. The `serde_json` error carries
+    /// the line and column of the problem, which [`CanonError::line`],
+    /// [`CanonError::column`] and [`CanonError::classify`] pass on.
+    Parse(serde_json::Error),
+    /// An object in JSON text input repeated a key. Only the `_strict` text
+    /// entry points check for this. Also raised when
+    /// [`Canonicalizer::lowercase_keys`] makes two keys of one object equal.
+    ///
+    /// [`Canonicalizer::lowercase_keys`]: crate::Canonicalizer::lowercase_keys
+    DuplicateKey { key: String },
+    /// JSON text input had a `\u` escape for a lone surrogate at byte
+    /// `offset`. Only the `_strict` text entry points report this; elsewhere
+    /// it is a [`CanonError::Parse`].
+    InvalidSurrogate { offset: usize },
+    /// The token at `position`, counting from 0, couldn't come next in a
+    /// [`CanonTokenSink`], or the stream ended there before its value did.
+    ///
+    /// [`CanonTokenSink`]: crate::token_sink::CanonTokenSink
+    TokenStream {
+        position: usize,
+        message: &'static str,
+    },
+    /// An expected digest was not valid hex.
+    InvalidHex,
+    /// An expected digest was not valid in the chosen base64 variant.
+    InvalidBase64,
+    /// An expected digest had the wrong length for its algorithm.
+    DigestLength { expected: usize, actual: usize },
+    /// Writing canonical output to an `io::Write` failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// A JSON Pointer was not valid RFC 6901 syntax: it must be empty or start
+    /// with `/`, and `~` must be followed by `0` or `1`.
+    InvalidPointer(String),
+    /// A projection kept no members, either because none were named or
+    /// because none of them were present.
+    EmptyProjection,
+    /// An error raised by a `Serialize` implementation, or a value this
+    /// crate couldn't handle for a reason no other variant covers. `path`
+    /// locates the value that raised it.
+    Custom { message: String, path: JsonPath },
+    /// A value that must be a JSON object, such as a [`HashChain`] record,
+    /// serialized to something else.
+    ///
+    /// [`HashChain`]: crate::chain::HashChain
+    NotAnObject,
+    /// A [`HashChain`] record already has a member named like the chain's
+    /// link field.
+    ///
+    /// [`HashChain`]: crate::chain::HashChain
+    ChainFieldConflict { field: String },
+    /// The [`extra`] members of an [`Envelope`] used one of the keys the
+    /// envelope reserves for itself.
+    ///
+    /// [`extra`]: crate::envelope::Envelope::extra
+    /// [`Envelope`]: crate::envelope::Envelope
+    EnvelopeFieldConflict { field: String },
+    /// A JWS could not be produced or did not verify.
+    #[cfg(feature = "jws")]
+    Jws(crate::jws::JwsError),
+    /// A value given to [`validate_and_hash`] failed its schema. Holds every
+    /// violation found, in the order `jsonschema` reported them.
+    ///
+    /// [`validate_and_hash`]: crate::schema::validate_and_hash
+    #[cfg(feature = "jsonschema")]
+    SchemaViolation(Vec<crate::schema::SchemaViolation>),
+    /// The item at `index` of a batch failed.
+    BatchItem {
+        index: usize,
+        source: Box<CanonError>,
+    },
+    /// Line `line` of a JSON Lines input, counting from 1, failed.
+    Line {
+        line: usize,
+        source: Box<CanonError>,
+    },
+    /// The cancellation flag passed to
+    /// [`Canonicalizer::canonicalize_cancellable`] was set.
+    ///
+    /// [`Canonicalizer::canonicalize_cancellable`]: crate::Canonicalizer::canonicalize_cancellable
+    Cancelled,
+    /// Bytes given to [`from_canonical_slice`](crate::from_canonical_slice)
+    /// were not in canonical form.
+    NotCanonical,
+    /// [`hash_optional`](crate::hash_optional) was given `None` under
+    /// [`NoneBehavior::Error`](crate::NoneBehavior::Error).
+    MissingValue,
+}
+
+/// Which size limit a [`CanonError::LimitExceeded`] tripped, with the limit
+/// that was set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// Set with [`Canonicalizer::max_object_keys`].
+    ///
+    /// [`Canonicalizer::max_object_keys`]: crate::Canonicalizer::max_object_keys
+    ObjectKeys(usize),
+    /// Set with [`Canonicalizer::max_total_bytes`].
+    ///
+    /// [`Canonicalizer::max_total_bytes`]: crate::Canonicalizer::max_total_bytes
+    TotalBytes(usize),
+}
+
+/// Where in a value an error occurred, as the keys and indices leading to
+/// it from the root.
+///
+/// Object members and externally tagged enum variants are
+/// [`Key`](PathSegment::Key) segments; array and tuple elements are
+/// [`Index`](PathSegment::Index) segments. Displays as a JSON Pointer.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// #[derive(serde::Serialize)]
+/// struct Nested {
+///     array: Vec<f64>,
+/// }
+///
+/// let nested = Nested { array: vec![1.0, 2.0, f64::NAN] };
+/// let value = BTreeMap::from([("data", BTreeMap::from([("nested", nested)]))]);
+/// let err = canonhash::canonicalize(&value).unwrap_err();
+/// assert_eq!(err.path().unwrap().to_pointer(), "/data/nested/array/2");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct JsonPath {
+    segments: Vec<PathSegment>,
+}
+
+/// One step of a [`JsonPath`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// An object member or enum variant name.
+    Key(String),
+    /// An array or tuple element.
+    Index(usize),
+}
+
+impl JsonPath {
+    /// Returns the segments from the root down.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Returns true for the path of the root value.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the path as an RFC 6901 JSON Pointer, such as
+    /// `/data/nested/array/2`. The root is the empty string.
+    pub fn to_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.segments {
+            match segment {
+                PathSegment::Key(key) => push_pointer_segment(&mut pointer, key),
+                PathSegment::Index(index) => {
+                    push_pointer_segment(&mut pointer, itoa::Buffer::new().format(*index))
+                }
+            }
+        }
+        pointer
+    }
+
+    /// Adds `segment` above the current root, for a path built while
+    /// unwinding out of nested values.
+    pub(crate) fn push_front(&mut self, segment: PathSegment) {
+        self.segments.insert(0, segment);
+    }
+}
+
+impl From<Vec<PathSegment>> for JsonPath {
+    fn from(segments: Vec<PathSegment>) -> Self {
+        JsonPath { segments }
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_pointer())
+    }
+}
+
+/// Compares against the JSON Pointer form.
+impl PartialEq<str> for JsonPath {
+    fn eq(&self, pointer: &str) -> bool {
+        self.to_pointer() == pointer
+    }
+}
+
+/// Displays as ` at `path`` after a message, or nothing at the root.
+struct At<'a>(&'a JsonPath);
+
+impl fmt::Display for At<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, " at `{}`", self.0)
+    }
+}
+
+impl fmt::Display for CanonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanonError::KeyMustBeAString { path } => {
+                write!(f, "object key{} must be a string", At(path))
+            }
+            CanonError::InvalidUtf8 => f.write_str("canonical output is not valid UTF-8"),
+            CanonError::NonFiniteNumber { path } => {
+                write!(
+                    f,
+                    "non-finite number{} has no JSON representation",
+                    At(path)
+                )
+            }
+            CanonError::DepthExceeded { limit, path } => write!(
+                f,
+                "value nests more than {limit} arrays and objects deep{}",
+                At(path)
+            ),
+            CanonError::LimitExceeded {
+                limit: Limit::ObjectKeys(limit),
+                path,
+            } => write!(f, "object{} has more than {limit} members", At(path)),
+            CanonError::LimitExceeded {
+                limit: Limit::TotalBytes(limit),
+                path,
+            } => write!(f, "canonical output exceeds {limit} bytes{}", At(path)),
+            CanonError::Parse(err) => write!(f, "invalid JSON input: {err}"),
+            CanonError::DuplicateKey { key } => write!(f, "duplicate object key `{key}`"),
+            CanonError::InvalidSurrogate { offset } => {
+                write!(f, "lone surrogate escape at byte {offset}")
+            }
+            CanonError::TokenStream { position, message } => {
+                write!(f, "invalid token stream at token {position}: {message}")
+            }
+            CanonError::InvalidHex => f.write_str("expected digest is not valid hex"),
+            CanonError::InvalidBase64 => f.write_str("expected digest is not valid base64"),
+            CanonError::DigestLength { expected, actual } => write!(
+                f,
+                "expected digest is {actual} bytes but the algorithm produces {expected}"
+            ),
+            #[cfg(feature = "std")]
+            CanonError::Io(err) => write!(f, "failed to write canonical output: {err}"),
+            CanonError::InvalidPointer(pointer) => write!(f, "invalid JSON Pointer `{pointer}`"),
+            CanonError::EmptyProjection => f.write_str("projection keeps no members"),
+            CanonError::Custom { message, path } => write!(f, "{message}{}", At(path)),
+            CanonError::NotAnObject => f.write_str("value must serialize to a JSON object"),
+            CanonError::ChainFieldConflict { field } => {
+                write!(f, "record already has a `{field}` member")
+            }
+            CanonError::EnvelopeFieldConflict { field } => {
+                write!(f, "envelope key `{field}` is reserved")
+            }
+            #[cfg(feature = "jws")]
+            CanonError::Jws(err) => write!(f, "JWS error: {err}"),
+            #[cfg(feature = "jsonschema")]
+            CanonError::SchemaViolation(violations) => {
+                f.write_str("value fails its schema: ")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    write!(f, "{violation}")?;
+                }
+                Ok(())
+            }
+            CanonError::BatchItem { index, source } => {
+                write!(f, "batch item {index} failed: {source}")
+            }
+            CanonError::Line { line, source } => write!(f, "line {line}: {source}"),
+            CanonError::Cancelled => f.write_str("canonicalization was cancelled"),
+            CanonError::NotCanonical => f.write_str("input is not in canonical form"),
+            CanonError::MissingValue => f.write_str("no value to hash"),
+        }
+    }
+}
+
+impl CanonError {
+    /// Returns where in the value the error occurred, for the errors raised
+    /// while serializing one.
+    pub fn path(&self) -> Option<&JsonPath> {
+        match self {
+            CanonError::KeyMustBeAString { path }
+            | CanonError::NonFiniteNumber { path }
+            | CanonError::DepthExceeded { path, .. }
+            | CanonError::LimitExceeded { path, .. }
+            | CanonError::Custom { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the line of JSON text input where parsing failed, counting
+    /// from 1, for a [`CanonError::Parse`] that came from parsing.
+    pub fn line(&self) -> Option<usize> {
+        self.input_position().map(|(line, _)| line)
+    }
+
+    /// Returns the column, counting from 1, that goes with
+    /// [`line`](Self::line). `serde_json` counts it in bytes.
+    pub fn column(&self) -> Option<usize> {
+        self.input_position().map(|(_, column)| column)
+    }
+
+    /// Returns what kind of `serde_json` error a [`CanonError::Parse`] is:
+    /// bad syntax, input that ended too soon, and so on.
+    pub fn classify(&self) -> Option<serde_json::error::Category> {
+        match self {
+            CanonError::Parse(err) => Some(err.classify()),
+            _ => None,
+        }
+    }
+
+    /// `serde_json` reports line 0 for errors that did not come from input,
+    /// such as a failed conversion to a `Value`.
+    fn input_position(&self) -> Option<(usize, usize)> {
+        match self {
+            CanonError::Parse(err) if err.line() > 0 => Some((err.line(), err.column())),
+            _ => None,
+        }
+    }
+
+    /// Prefixes the path of an error from inside an object member or enum
+    /// variant with its key. Errors without a path pass through.
+    pub(crate) fn within_key(mut self, key: &str) -> Self {
+        if let Some(path) = self.path_mut() {
+            path.segments.insert(0, PathSegment::Key(key.into()));
+        }
+        self
+    }
+
+    /// Prefixes the path of an error from inside an array element with its
+    /// index. Errors without a path pass through.
+    pub(crate) fn within_index(mut self, index: usize) -> Self {
+        if let Some(path) = self.path_mut() {
+            path.segments.insert(0, PathSegment::Index(index));
+        }
+        self
+    }
+
+    fn path_mut(&mut self) -> Option<&mut JsonPath> {
+        match self {
+            CanonError::KeyMustBeAString { path }
+            | CanonError::NonFiniteNumber { path }
+            | CanonError::DepthExceeded { path, .. }
+            | CanonError::LimitExceeded { path, .. }
+            | CanonError::Custom { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Appends `/segment` to a JSON Pointer, escaping `~` and `/` per RFC 6901.
+pub(crate) fn push_pointer_segment(path: &mut String, segment: &str) {
+    path.push('/');
+    for c in segment.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+}
+
+impl core::error::Error for CanonError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CanonError::Parse(err) => Some(err),
+            #[cfg(feature = "std")]
+            CanonError::Io(err) => Some(err),
+            CanonError::BatchItem { source, .. } | CanonError::Line { source, .. } => Some(source),
+            #[cfg(feature = "jws")]
+            CanonError::Jws(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for CanonError {
+    fn from(err: serde_json::Error) -> Self {
+        CanonError::Parse(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for CanonError {
+    fn from(err: std::io::Error) -> Self {
+        CanonError::Io(err)
+    }
+}
+
+impl serde::ser::Error for CanonError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CanonError::Custom {
+            message: msg.to_string(),
+            path: JsonPath::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn parse_errors_convert_and_chain() {
+        let json_err = serde_json::from_str::<serde_json::Value>("[1,").unwrap_err();
+        let message = json_err.to_string();
+        let err = CanonError::from(json_err);
+        assert!(matches!(err, CanonError::Parse(_)));
+        assert_eq!(err.source().unwrap().to_string(), message);
+    }
+
+    #[test]
+    fn parse_errors_report_their_position() {
+        use serde_json::error::Category;
+
+        let err = crate::canonicalize_str_strict("{\n  \"a\": [1,\n    2,").unwrap_err();
+        assert_eq!((err.line(), err.column()), (Some(3), Some(6)));
+        assert_eq!(err.classify(), Some(Category::Eof));
+
+        let err = crate::canonical_sha256_from_str("[1, 2}").unwrap_err();
+        assert_eq!((err.line(), err.column()), (Some(1), Some(6)));
+        assert_eq!(err.classify(), Some(Category::Syntax));
+
+        let err = CanonError::InvalidSurrogate { offset: 3 };
+        assert_eq!(
+            (err.line(), err.column(), err.classify()),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn conversion_errors_have_no_position() {
+        use serde::ser::Error as _;
+
+        let err = CanonError::from(serde_json::Error::custom("not from input"));
+        assert_eq!(err.line(), None);
+        assert_eq!(err.column(), None);
+        assert_eq!(err.classify(), Some(serde_json::error::Category::Data));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_errors_convert_and_chain() {
+        let err: CanonError = std::io::Error::other("disk full").into();
+        assert!(matches!(err, CanonError::Io(_)));
+        assert_eq!(err.source().unwrap().to_string(), "disk full");
+    }
+
+    #[test]
+    fn leaf_errors_have_no_source() {
+        assert!(CanonError::InvalidUtf8.source().is_none());
+        let err = CanonError::NonFiniteNumber {
+            path: JsonPath::default(),
+        };
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn within_prefixes_paths() {
+        let err = CanonError::NonFiniteNumber {
+            path: JsonPath::default(),
+        }
+        .within_index(0)
+        .within_key("a/b");
+        assert!(matches!(err, CanonError::NonFiniteNumber { ref path } if path == "/a~1b/0"));
+        assert_eq!(
+            err.path().unwrap().segments(),
+            [PathSegment::Key("a/b".into()), PathSegment::Index(0)]
+        );
+        let err = CanonError::InvalidUtf8.within_key("x");
+        assert!(matches!(err, CanonError::InvalidUtf8));
+        assert!(err.path().is_none());
+    }
+
+    #[test]
+    fn messages_name_the_path() {
+        let path = JsonPath::from(vec![PathSegment::Key("a".into()), PathSegment::Index(3)]);
+        let err = CanonError::DepthExceeded { limit: 2, path };
+        assert_eq!(
+            err.to_string(),
+            "value nests more than 2 arrays and objects deep at `/a/3`"
+        );
+        let err = CanonError::LimitExceeded {
+            limit: Limit::ObjectKeys(1),
+            path: JsonPath::default(),
+        };
+        assert_eq!(err.to_string(), "object has more than 1 members");
+    }
+}
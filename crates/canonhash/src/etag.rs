@@ -0,0 +1,139 @@
+//! HTTP entity tags derived from the canonical form, so that the same
+//! content gets the same tag however its fields were ordered.
+//!
+//! Tags are `"sha256-<base64>"`, the SHA-256 of the canonical form in
+//! standard padded base64 as Subresource Integrity writes it, in quotes.
+//! [`weak_etag`] adds the `W/` prefix.
+
+use alloc::format;
+use alloc::string::String;
+
+use serde::Serialize;
+
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+
+/// Returns the strong ETag of `value`, quotes included, ready for the
+/// `ETag` header.
+///
+/// Canonical forms are byte-for-byte identical for equal content, which is
+/// what a strong validator promises.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let a = canonhash::etag(&json!({"id": 1, "name": "x"})).unwrap();
+/// let b = canonhash::etag(&json!({"name": "x", "id": 1})).unwrap();
+/// assert_eq!(a, b);
+/// assert!(a.starts_with("\"sha256-") && a.ends_with('"'));
+/// ```
+pub fn etag<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let digest = encode_digest(&canonical_sha256(value)?, DigestEncoding::Base64);
+    Ok(format!("\"sha256-{digest}\""))
+}
+
+/// Returns the weak ETag of `value`: [`etag`] with a `W/` prefix, for
+/// responses whose bytes may differ, for example by compression or
+/// pretty-printing, while the content is the same.
+pub fn weak_etag<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(format!("W/{}", etag(value)?))
+}
+
+/// Returns whether an `If-None-Match` header value matches the ETag of
+/// `value`, in which case the response should be `304 Not Modified`.
+///
+/// As RFC 9110 §13.1.2 specifies, the comparison is weak, so `W/"…"` and
+/// `"…"` match the same value, and `*` matches anything. The header may
+/// list several tags separated by commas. Text that isn't a list of entity
+/// tags matches nothing.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let body = json!({"id": 1});
+/// let tag = canonhash::etag(&body).unwrap();
+/// assert!(canonhash::if_none_match(&format!("\"other\", W/{tag}"), &body).unwrap());
+/// assert!(!canonhash::if_none_match("\"other\"", &body).unwrap());
+/// ```
+pub fn if_none_match<T>(header: &str, value: &T) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    if header.trim() == "*" {
+        return Ok(true);
+    }
+    let tag = etag(value)?;
+    Ok(EntityTags(header).any(|opaque| opaque == tag))
+}
+
+/// The opaque tags, quotes included, of a comma-separated list of entity
+/// tags. Stops at the first thing that isn't one.
+struct EntityTags<'a>(&'a str);
+
+impl<'a> Iterator for EntityTags<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.0.trim_start_matches([' ', '\t', ',']);
+        let rest = rest.strip_prefix("W/").unwrap_or(rest);
+        let end = rest.strip_prefix('"')?.find('"')? + 2;
+        let (opaque, rest) = rest.split_at(end);
+        self.0 = rest;
+        Some(opaque)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn tags_are_quoted_sha256_base64() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        let digest = canonical_sha256(&value).unwrap();
+        let expected = format!(
+            "\"sha256-{}\"",
+            encode_digest(&digest, DigestEncoding::Base64)
+        );
+        assert_eq!(etag(&value).unwrap(), expected);
+        assert_eq!(weak_etag(&value).unwrap(), format!("W/{expected}"));
+        assert_ne!(etag(&value).unwrap(), etag(&json!({"a": "y"})).unwrap());
+    }
+
+    #[test]
+    fn entity_tag_lists_are_parsed() {
+        let tags: Vec<_> = EntityTags(r#" "a", W/"b",,"c,d"  "e""#).collect();
+        assert_eq!(tags, [r#""a""#, r#""b""#, r#""c,d""#, r#""e""#]);
+        assert_eq!(EntityTags(r#""a", bare, "b""#).count(), 1);
+        assert_eq!(EntityTags(r#""unterminated"#).count(), 0);
+        assert_eq!(EntityTags("").count(), 0);
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let value = json!([1, 2, 3]);
+        let strong = etag(&value).unwrap();
+        let weak = weak_etag(&value).unwrap();
+        for header in [&*strong, &*weak, " * ", &format!("\"x\",{weak}")] {
+            assert!(if_none_match(header, &value).unwrap(), "{header}");
+        }
+        for header in ["", "\"x\"", &strong[1..], "*, \"x\""] {
+            assert!(!if_none_match(header, &value).unwrap(), "{header}");
+        }
+        assert!(matches!(
+            if_none_match("\"x\"", &f64::NAN),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+}
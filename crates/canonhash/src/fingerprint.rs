@@ -0,0 +1,243 @@
+//! Short digests for people to compare by eye.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+
+/// How [`fingerprint_with`] writes out the start of the digest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FingerprintEncoding {
+    /// Lowercase hex in groups of four digits (two bytes) joined by `-`, as
+    /// in `a1b2-c3d4-e5f6`.
+    #[default]
+    HexGroups,
+    /// One word of the PGP word list per byte, joined by spaces, as in
+    /// `topmost Istanbul Pluto`. Bytes at even positions use the two-syllable
+    /// list and odd ones the three-syllable list, so a swapped pair of words
+    /// reads wrong.
+    PgpWords,
+}
+
+/// Settings for [`fingerprint_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FingerprintOptions {
+    /// How many groups to write: four hex digits each with
+    /// [`FingerprintEncoding::HexGroups`], one word each with
+    /// [`FingerprintEncoding::PgpWords`]. Counts past the end of the 32-byte
+    /// digest stop at its end.
+    pub groups: usize,
+    /// How to write them.
+    pub encoding: FingerprintEncoding,
+}
+
+impl Default for FingerprintOptions {
+    /// Three hex groups, 48 bits of the digest.
+    fn default() -> Self {
+        FingerprintOptions {
+            groups: 3,
+            encoding: FingerprintEncoding::HexGroups,
+        }
+    }
+}
+
+/// Returns a short fingerprint of the canonical SHA-256 of `value`, three
+/// groups of hex such as `a1b2-c3d4-e5f6`.
+///
+/// A fingerprint is for people checking at a glance that two values match,
+/// such as the same config in two environments. It keeps only 48 bits of
+/// the digest, which is plenty to spot an accidental difference but far too
+/// few to resist a deliberate collision, so compare full digests wherever a
+/// program decides.
+///
+/// ```
+/// let prod = serde_json::json!({"replicas": 3, "image": "api:1.4"});
+/// let staging = serde_json::json!({"image": "api:1.4", "replicas": 3});
+/// let fingerprint = canonhash::fingerprint(&prod).unwrap();
+/// assert_eq!(fingerprint.len(), 14);
+/// assert_eq!(fingerprint, canonhash::fingerprint(&staging).unwrap());
+/// ```
+pub fn fingerprint<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    fingerprint_with(value, FingerprintOptions::default())
+}
+
+/// Returns a fingerprint of the canonical SHA-256 of `value` laid out by
+/// `options`.
+///
+/// ```
+/// use canonhash::{fingerprint_with, FingerprintEncoding, FingerprintOptions};
+///
+/// let options = FingerprintOptions {
+///     groups: 4,
+///     encoding: FingerprintEncoding::PgpWords,
+/// };
+/// let words = fingerprint_with(&serde_json::json!({"replicas": 3}), options).unwrap();
+/// assert_eq!(words.split(' ').count(), 4);
+/// ```
+pub fn fingerprint_with<T>(value: &T, options: FingerprintOptions) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let digest = canonical_sha256(value)?;
+    Ok(match options.encoding {
+        FingerprintEncoding::HexGroups => {
+            let bytes = &digest[..digest.len().min(options.groups.saturating_mul(2))];
+            let groups: Vec<String> = bytes
+                .chunks(2)
+                .map(|group| encode_digest(group, DigestEncoding::Hex))
+                .collect();
+            groups.join("-")
+        }
+        FingerprintEncoding::PgpWords => {
+            let bytes = &digest[..digest.len().min(options.groups)];
+            pgp_words(bytes).join(" ")
+        }
+    })
+}
+
+/// Spells `bytes` in the PGP word list.
+fn pgp_words(bytes: &[u8]) -> Vec<&'static str> {
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            let list = if i % 2 == 0 { &PGP_EVEN } else { &PGP_ODD };
+            list[usize::from(b)]
+        })
+        .collect()
+}
+
+/// The two-syllable words of the PGP word list, for bytes at even
+/// positions.
+#[rustfmt::skip]
+const PGP_EVEN: [&str; 256] = [
+    "aardvark", "absurd", "accrue", "acme", "adrift", "adult", "afflict", "ahead",
+    "aimless", "Algol", "allow", "alone", "ammo", "ancient", "apple", "artist",
+    "assume", "Athens", "atlas", "Aztec", "baboon", "backfield", "backward", "banjo",
+    "beaming", "bedlamp", "beehive", "beeswax", "befriend", "Belfast", "berserk", "billiard",
+    "bison", "blackjack", "blockade", "blowtorch", "bluebird", "bombast", "bookshelf", "brackish",
+    "breadline", "breakup", "brickyard", "briefcase", "Burbank", "button", "buzzard", "cement",
+    "chairlift", "chatter", "checkup", "chisel", "choking", "chopper", "Christmas", "clamshell",
+    "classic", "classroom", "cleanup", "clockwork", "cobra", "commence", "concert", "cowbell",
+    "crackdown", "cranky", "crowfoot", "crucial", "crumpled", "crusade", "cubic", "dashboard",
+    "deadbolt", "deckhand", "dogsled", "dragnet", "drainage", "dreadful", "drifter", "dropper",
+    "drumbeat", "drunken", "Dupont", "dwelling", "eating", "edict", "egghead", "eightball",
+    "endorse", "endow", "enlist", "erase", "escape", "exceed", "eyeglass", "eyetooth",
+    "facial", "fallout", "flagpole", "flatfoot", "flytrap", "fracture", "framework", "freedom",
+    "frighten", "gazelle", "Geiger", "glitter", "glucose", "goggles", "goldfish", "gremlin",
+    "guidance", "hamlet", "highchair", "hockey", "indoors", "indulge", "inverse", "involve",
+    "island", "jawbone", "keyboard", "kickoff", "kiwi", "klaxon", "locale", "lockup",
+    "merit", "minnow", "miser", "Mohawk", "mural", "music", "necklace", "Neptune",
+    "newborn", "nightbird", "Oakland", "obtuse", "offload", "optic", "orca", "payday",
+    "peachy", "pheasant", "physique", "playhouse", "Pluto", "preclude", "prefer", "preshrunk",
+    "printer", "prowler", "pupil", "puppy", "python", "quadrant", "quiver", "quota",
+    "ragtime", "ratchet", "rebirth", "reform", "regain", "reindeer", "rematch", "repay",
+    "retouch", "revenge", "reward", "rhythm", "ribcage", "ringbolt", "robust", "rocker",
+    "ruffled", "sailboat", "sawdust", "scallion", "scenic", "scorecard", "Scotland", "seabird",
+    "select", "sentence", "shadow", "shamrock", "showgirl", "skullcap", "skydive", "slingshot",
+    "slowdown", "snapline", "snapshot", "snowcap", "snowslide", "solo", "southward", "soybean",
+    "spaniel", "spearhead", "spellbind", "spheroid", "spigot", "spindle", "spyglass", "stagehand",
+    "stagnate", "stairway", "standard", "stapler", "steamship", "sterling", "stockman", "stopwatch",
+    "stormy", "sugar", "surmount", "suspense", "sweatband", "swelter", "tactics", "talon",
+    "tapeworm", "tempest", "tiger", "tissue", "tonic", "topmost", "tracker", "transit",
+    "trauma", "treadmill", "Trojan", "trouble", "tumor", "tunnel", "tycoon", "uncut",
+    "unearth", "unwind", "uproot", "upset", "upshot", "vapor", "village", "virus",
+    "Vulcan", "waffle", "wallet", "watchword", "wayside", "willow", "woodlark", "Zulu",
+];
+
+/// The three-syllable words of the PGP word list, for bytes at odd
+/// positions.
+#[rustfmt::skip]
+const PGP_ODD: [&str; 256] = [
+    "adroitness", "adviser", "aftermath", "aggregate", "alkali", "almighty", "amulet", "amusement",
+    "antenna", "applicant", "Apollo", "armistice", "article", "asteroid", "Atlantic", "atmosphere",
+    "autopsy", "Babylon", "backwater", "barbecue", "belowground", "bifocals", "bodyguard", "bookseller",
+    "borderline", "bottomless", "Bradbury", "bravado", "Brazilian", "breakaway", "Burlington", "businessman",
+    "butterfat", "Camelot", "candidate", "cannonball", "Capricorn", "caravan", "caretaker", "celebrate",
+    "cellulose", "certify", "chambermaid", "Cherokee", "Chicago", "clergyman", "coherence", "combustion",
+    "commando", "company", "component", "concurrent", "confidence", "conformist", "congregate", "consensus",
+    "consulting", "corporate", "corrosion", "councilman", "crossover", "crucifix", "cumbersome", "customer",
+    "Dakota", "decadence", "December", "decimal", "designing", "detector", "detergent", "determine",
+    "dictator", "dinosaur", "direction", "disable", "disbelief", "disruptive", "distortion", "document",
+    "embezzle", "enchanting", "enrollment", "enterprise", "equation", "equipment", "escapade", "Eskimo",
+    "everyday", "examine", "existence", "exodus", "fascinate", "filament", "finicky", "forever",
+    "fortitude", "frequency", "gadgetry", "Galveston", "getaway", "glossary", "gossamer", "graduate",
+    "gravity", "guitarist", "hamburger", "Hamilton", "handiwork", "hazardous", "headwaters", "hemisphere",
+    "hesitate", "hideaway", "holiness", "hurricane", "hydraulic", "impartial", "impetus", "inception",
+    "indigo", "inertia", "infancy", "inferno", "informant", "insincere", "insurgent", "integrate",
+    "intention", "inventive", "Istanbul", "Jamaica", "Jupiter", "leprosy", "letterhead", "liberty",
+    "maritime", "matchmaker", "maverick", "Medusa", "megaton", "microscope", "microwave", "midsummer",
+    "millionaire", "miracle", "misnomer", "molasses", "molecule", "Montana", "monument", "mosquito",
+    "narrative", "nebula", "newsletter", "Norwegian", "October", "Ohio", "onlooker", "opulent",
+    "Orlando", "outfielder", "Pacific", "pandemic", "Pandora", "paperweight", "paragon", "paragraph",
+    "paramount", "passenger", "pedigree", "Pegasus", "penetrate", "perceptive", "performance", "pharmacy",
+    "phonetic", "photograph", "pioneer", "pocketful", "politeness", "positive", "potato", "processor",
+    "provincial", "proximate", "puberty", "publisher", "pyramid", "quantity", "racketeer", "rebellion",
+    "recipe", "recover", "repellent", "replica", "reproduce", "resistor", "responsive", "retraction",
+    "retrieval", "retrospect", "revenue", "revival", "revolver", "sandalwood", "sardonic", "Saturday",
+    "savagery", "scavenger", "sensation", "sociable", "souvenir", "specialist", "speculate", "stethoscope",
+    "stupendous", "supportive", "surrender", "suspicious", "sympathy", "tambourine", "telephone", "therapist",
+    "tobacco", "tolerance", "tomorrow", "torpedo", "tradition", "travesty", "trombonist", "truncated",
+    "typewriter", "ultimate", "undaunted", "underfoot", "unicorn", "unify", "universe", "unravel",
+    "upcoming", "vacancy", "vagabond", "vertigo", "Virginia", "visitor", "vocalist", "voyager",
+    "warranty", "Waterloo", "whimsical", "Wichita", "Wilmington", "Wyoming", "yesteryear", "Yucatan",
+];
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn pgp_words_match_the_published_example() {
+        let bytes = hex::decode("e58294f2e9a227486e8b061b31cc528fd7fa3f19").unwrap();
+        assert_eq!(
+            pgp_words(&bytes).join(" "),
+            "topmost Istanbul Pluto vagabond treadmill Pacific brackish dictator goldfish \
+             Medusa afflict bravado chatter revolver Dupont midsummer stopwatch whimsical \
+             cowbell bottomless"
+        );
+    }
+
+    #[test]
+    fn hex_groups_are_the_start_of_the_digest() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        let hex = crate::sha256_hex(&value).unwrap();
+        assert_eq!(
+            fingerprint(&value).unwrap(),
+            format!("{}-{}-{}", &hex[0..4], &hex[4..8], &hex[8..12])
+        );
+        let options = |groups| FingerprintOptions {
+            groups,
+            ..FingerprintOptions::default()
+        };
+        assert_eq!(fingerprint_with(&value, options(1)).unwrap(), hex[0..4]);
+        assert_eq!(fingerprint_with(&value, options(0)).unwrap(), "");
+        let all = fingerprint_with(&value, options(usize::MAX)).unwrap();
+        assert_eq!(all.replace('-', ""), hex);
+    }
+
+    #[test]
+    fn word_fingerprints_spell_the_digest() {
+        let value = json!([1.0, "two"]);
+        let digest = canonical_sha256(&value).unwrap();
+        let options = FingerprintOptions {
+            groups: 6,
+            encoding: FingerprintEncoding::PgpWords,
+        };
+        let words = fingerprint_with(&value, options).unwrap();
+        assert_eq!(words, pgp_words(&digest[..6]).join(" "));
+        assert_eq!(
+            words,
+            fingerprint_with(&json!([1, "two"]), options).unwrap()
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Golden fixtures: an input document together with its expected canonical
+//! form and SHA-256, as used by the ops-stack golden hash tests.
+
+use alloc::string::String;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+
+/// An input document and the canonical form and hash it must produce.
+///
+/// Fixtures deserialize from JSON files of the same shape, so a suite of them
+/// can be kept as data next to the tests.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GoldenFixture {
+    pub input: Value,
+    pub canonical: String,
+    pub sha256_hex: String,
+}
+
+/// The first stage at which a fixture diverged.
+#[derive(Debug)]
+pub enum FixtureError {
+    /// The input could not be canonicalized at all.
+    Canonicalize(CanonError),
+    /// The canonical form differs from the expected one, first at byte
+    /// `offset`.
+    CanonicalMismatch {
+        offset: usize,
+        expected: String,
+        actual: String,
+    },
+    /// The canonical form matched but the expected hash is different, which
+    /// means the fixture's `sha256_hex` is stale or wrong.
+    HashMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::Canonicalize(err) => write!(f, "failed to canonicalize input: {err}"),
+            FixtureError::CanonicalMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "canonical form differs at byte {offset}: expected {:?}, got {:?}",
+                excerpt(expected, *offset),
+                excerpt(actual, *offset),
+            ),
+            FixtureError::HashMismatch { expected, actual } => {
+                write!(f, "sha256 mismatch: expected {expected}, got {actual}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for FixtureError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            FixtureError::Canonicalize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Up to 16 bytes either side of `offset`, widened to char boundaries.
+pub(crate) fn excerpt(s: &str, offset: usize) -> &str {
+    let mut start = offset.saturating_sub(16).min(s.len());
+    let mut end = (offset + 16).min(s.len());
+    while !s.is_char_boundary(start) {
+        start -= 1;
+    }
+    while !s.is_char_boundary(end) {
+        end += 1;
+    }
+    &s[start..end]
+}
+
+/// The offset of the first byte where `actual` and `expected` differ, or the
+/// length of the shorter if one is a prefix of the other.
+pub(crate) fn first_difference(actual: &[u8], expected: &[u8]) -> usize {
+    actual
+        .iter()
+        .zip(expected)
+        .position(|(a, e)| a != e)
+        .unwrap_or_else(|| actual.len().min(expected.len()))
+}
+
+/// Canonicalizes `fixture.input` and checks it against the expected canonical
+/// form and then the expected hash.
+pub fn check_fixture(fixture: &GoldenFixture) -> Result<(), FixtureError> {
+    let actual = canonicalize(&fixture.input).map_err(FixtureError::Canonicalize)?;
+    let expected = fixture.canonical.as_bytes();
+    if actual != expected {
+        return Err(FixtureError::CanonicalMismatch {
+            offset: first_difference(&actual, expected),
+            expected: fixture.canonical.clone(),
+            actual: String::from_utf8_lossy(&actual).into_owned(),
+        });
+    }
+
+    let digest = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&actual));
+    if !digest.eq_ignore_ascii_case(&fixture.sha256_hex) {
+        return Err(FixtureError::HashMismatch {
+            expected: fixture.sha256_hex.clone(),
+            actual: digest,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fixture(input: Value, canonical: &str, sha256_hex: &str) -> GoldenFixture {
+        GoldenFixture {
+            input,
+            canonical: canonical.to_owned(),
+            sha256_hex: sha256_hex.to_owned(),
+        }
+    }
+
+    #[test]
+    fn passes_matching_fixture() {
+        let f = fixture(
+            json!({}),
+            "{}",
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+        );
+        check_fixture(&f).unwrap();
+    }
+
+    #[test]
+    fn reports_first_differing_byte() {
+        let f = fixture(json!({"b": 1, "a": 2}), r#"{"a":2,"b":2}"#, "");
+        match check_fixture(&f).unwrap_err() {
+            FixtureError::CanonicalMismatch { offset, actual, .. } => {
+                assert_eq!(offset, 11);
+                assert_eq!(actual, r#"{"a":2,"b":1}"#);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn reports_prefix_mismatch_at_shorter_length() {
+        let f = fixture(json!([1, 2]), "[1,2]extra", "");
+        assert!(matches!(
+            check_fixture(&f).unwrap_err(),
+            FixtureError::CanonicalMismatch { offset: 5, .. }
+        ));
+    }
+
+    #[test]
+    fn reports_hash_mismatch_after_canonical_match() {
+        let f = fixture(json!({}), "{}", &"0".repeat(64));
+        let err = check_fixture(&f).unwrap_err();
+        assert!(matches!(err, FixtureError::HashMismatch { .. }));
+        assert!(err.to_string().contains("44136fa3"));
+    }
+
+    #[test]
+    fn loads_from_json() {
+        let f: GoldenFixture = serde_json::from_str(
+            r#"{"input": [true], "canonical": "[true]",
+                "sha256_hex": "3f5a0d5a6fd2c2b8c1e1a4c7b0b1f7c9a1e4d3b77f4d7d1f5c8a0f7c5b2f1a9e"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            check_fixture(&f).unwrap_err(),
+            FixtureError::HashMismatch { .. }
+        ));
+    }
+}
@@ -0,0 +1,643 @@
+//! Hashing of canonical forms.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use digest::{Digest, DynDigest, FixedOutputReset, Output, Update};
+use serde::Serialize;
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::algorithm::{default_algorithm, HashAlgorithm};
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::ser::{self, Options, Scope};
+use crate::sink::Sink;
+
+/// Feeds canonical output straight into a digest or MAC.
+pub(crate) struct DigestSink<'a, D>(pub(crate) &'a mut D);
+
+impl<D: Update> Sink for DigestSink<'_, D> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        self.0.update(bytes);
+        Ok(())
+    }
+}
+
+/// Feeds the canonical form of `value` into `state`. Every hash and MAC
+/// goes through here, so they all see exactly the bytes [`canonicalize`]
+/// produces.
+///
+/// [`canonicalize`]: crate::canonicalize
+pub(crate) fn feed<U, T>(
+    state: &mut U,
+    value: &T,
+    opts: Options,
+    scope: Scope<'_>,
+) -> Result<(), CanonError>
+where
+    U: Update,
+    T: ?Sized + Serialize,
+{
+    ser::serialize_scoped(value, &mut DigestSink(state), opts, scope)
+}
+
+/// Canonicalizes values and digests the result in a single pass.
+///
+/// The canonical bytes are fed to `D` as they are produced, so the complete
+/// canonical document is never held in memory. Only the members of the object
+/// currently being sorted are buffered.
+///
+/// ```
+/// use canonhash::CanonicalHasher;
+/// use sha2::Sha512;
+///
+/// let mut hasher = CanonicalHasher::<Sha512>::new();
+/// let digest = hasher.hash(&serde_json::json!({"b": 1, "a": 2})).unwrap();
+/// assert_eq!(digest.len(), 64);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CanonicalHasher<D> {
+    digest: D,
+}
+
+impl<D: Digest + FixedOutputReset> CanonicalHasher<D> {
+    pub fn new() -> Self {
+        CanonicalHasher { digest: D::new() }
+    }
+
+    /// Returns the digest of the canonical form of `value`.
+    ///
+    /// The hasher is reset afterwards, whether or not serialization succeeded,
+    /// so it can be reused for the next value.
+    pub fn hash<T>(&mut self, value: &T) -> Result<Output<D>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.hash_with_options(value, Options::default(), Scope::default())
+    }
+
+    pub(crate) fn hash_with_options<T>(
+        &mut self,
+        value: &T,
+        opts: Options,
+        scope: Scope<'_>,
+    ) -> Result<Output<D>, CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let result = feed(&mut self.digest, value, opts, scope);
+        match result {
+            Ok(()) => Ok(Digest::finalize_reset(&mut self.digest)),
+            Err(err) => {
+                Digest::reset(&mut self.digest);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Lets [`feed`] take a BLAKE3 hasher, which doesn't implement `digest`'s
+/// traits.
+#[cfg(feature = "blake3")]
+pub(crate) struct Blake3<'a>(pub(crate) &'a mut blake3::Hasher);
+
+#[cfg(feature = "blake3")]
+impl Update for Blake3<'_> {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
+
+impl Sink for dyn DynDigest + '_ {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        self.update(bytes);
+        Ok(())
+    }
+}
+
+/// Returns the digest of the canonical form of `value` under a hasher chosen
+/// at runtime, such as one from a `Box<dyn DynDigest>` registry.
+///
+/// The canonical bytes are fed to `hasher` as they are produced and the
+/// result is taken with `finalize_into_reset`. As with [`CanonicalHasher::hash`],
+/// `hasher` is left reset whether or not serialization succeeded. Any
+/// `DynDigest` works, including truncated variable-output hashes such as
+/// `Sha512_256`.
+///
+/// ```
+/// use digest::DynDigest;
+///
+/// let mut hasher: Box<dyn DynDigest> = Box::new(sha2::Sha384::default());
+/// let digest = canonhash::hash_dyn(&[1, 2], hasher.as_mut()).unwrap();
+/// assert_eq!(digest, canonhash::hash_with(canonhash::HashAlgorithm::Sha384, &[1, 2]).unwrap());
+/// ```
+pub fn hash_dyn<T>(value: &T, hasher: &mut dyn DynDigest) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    match ser::serialize(value, hasher, Options::default()) {
+        Ok(()) => {
+            let mut digest = alloc::vec![0; hasher.output_size()];
+            hasher
+                .finalize_into_reset(&mut digest)
+                .expect("buffer is output_size() long");
+            Ok(digest)
+        }
+        Err(err) => {
+            hasher.reset();
+            Err(err)
+        }
+    }
+}
+
+/// Returns the digest of the canonical form of `value` under `alg`.
+///
+/// This is the runtime-dispatch counterpart of [`CanonicalHasher`] for when
+/// the algorithm comes from configuration.
+pub fn hash_with<T>(alg: HashAlgorithm, value: &T) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    hash_with_options(alg, value, Options::default(), Scope::default())
+}
+
+pub(crate) fn hash_with_options<T>(
+    alg: HashAlgorithm,
+    value: &T,
+    opts: Options,
+    scope: Scope<'_>,
+) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    fn fixed<D: Digest + FixedOutputReset, T: ?Sized + Serialize>(
+        value: &T,
+        opts: Options,
+        scope: Scope<'_>,
+    ) -> Result<Vec<u8>, CanonError> {
+        Ok(CanonicalHasher::<D>::new()
+            .hash_with_options(value, opts, scope)?
+            .to_vec())
+    }
+
+    match alg {
+        HashAlgorithm::Sha256 => fixed::<Sha256, T>(value, opts, scope),
+        HashAlgorithm::Sha384 => fixed::<Sha384, T>(value, opts, scope),
+        HashAlgorithm::Sha512 => fixed::<Sha512, T>(value, opts, scope),
+        #[cfg(feature = "blake2")]
+        HashAlgorithm::Blake2b => fixed::<blake2::Blake2b512, T>(value, opts, scope),
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            feed(&mut Blake3(&mut hasher), value, opts, scope)?;
+            Ok(hasher.finalize().as_bytes().to_vec())
+        }
+    }
+}
+
+/// Returns the canonical form of `value` as a `String` together with its
+/// digest under `alg`, canonicalizing only once.
+///
+/// ```
+/// use canonhash::{canonicalize_and_hash, HashAlgorithm};
+///
+/// let value = serde_json::json!({"b": 1, "a": 2});
+/// let (canonical, digest) = canonicalize_and_hash(&value, HashAlgorithm::Sha256).unwrap();
+/// assert_eq!(canonical, r#"{"a":2,"b":1}"#);
+/// assert_eq!(digest, canonhash::hash_with(HashAlgorithm::Sha256, &value).unwrap());
+/// ```
+pub fn canonicalize_and_hash<T>(
+    value: &T,
+    alg: HashAlgorithm,
+) -> Result<(String, Vec<u8>), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = crate::canon::to_string(value)?;
+    let digest = digest_bytes(alg, canonical.as_bytes());
+    Ok((canonical, digest))
+}
+
+/// Returns the digest of the canonical form of `value` under `alg`, wiping
+/// the canonical bytes from memory afterwards, for values holding secrets.
+///
+/// The canonical form is built in a `Zeroizing<Vec<u8>>`, so the buffer's
+/// whole allocation is zeroed when it is dropped, whether or not
+/// serialization succeeded. The digest is returned as usual.
+///
+/// This closes the obvious leak, not every one:
+///
+/// - Earlier allocations the buffer outgrew are freed without being wiped.
+///   So are the map keys serde hands over as strings.
+/// - The buffers used to reorder an object's members are wiped, but only
+///   with this feature on, and only on this crate's side.
+/// - Nothing here scrubs `value` itself, wherever it lives.
+#[cfg(feature = "zeroize")]
+pub fn hash_secret<T>(value: &T, alg: HashAlgorithm) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut canonical = zeroize::Zeroizing::new(Vec::with_capacity(1024));
+    crate::canon::canonicalize_into(value, &mut canonical)?;
+    Ok(digest_bytes(alg, &canonical))
+}
+
+/// Digests bytes that are already canonical under `alg`.
+pub(crate) fn digest_bytes(alg: HashAlgorithm, bytes: &[u8]) -> Vec<u8> {
+    match alg {
+        HashAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+        HashAlgorithm::Sha384 => Sha384::digest(bytes).to_vec(),
+        HashAlgorithm::Sha512 => Sha512::digest(bytes).to_vec(),
+        #[cfg(feature = "blake2")]
+        HashAlgorithm::Blake2b => blake2::Blake2b512::digest(bytes).to_vec(),
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => blake3::hash(bytes).as_bytes().to_vec(),
+    }
+}
+
+/// Returns the digest of the canonical form of `value` under the process-wide
+/// [`default_algorithm`], SHA-256 unless
+/// [`set_default_algorithm`](crate::set_default_algorithm) changed it.
+pub fn hash_default<T>(value: &T) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    hash_with(default_algorithm(), value)
+}
+
+/// Returns the SHA-256 digest of the canonical form of `value`.
+pub fn canonical_sha256<T>(value: &T) -> Result<[u8; 32], CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(CanonicalHasher::<Sha256>::new().hash(value)?.into())
+}
+
+/// What [`hash_optional`] does with `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NoneBehavior {
+    /// Hash `None` as `null`, which is what serializing it gives.
+    AsNull,
+    /// Fail with [`CanonError::MissingValue`].
+    Error,
+}
+
+/// Returns the SHA-256 digest of the canonical form of the value in `value`,
+/// with `on_none` deciding what happens when there is none.
+///
+/// `Some(x)` hashes exactly as `x` does. On its own, `None` serializes to
+/// `null` and hashes the same as an explicit null;
+/// [`NoneBehavior::Error`] refuses it instead, so a missing value can't be
+/// hashed by accident.
+///
+/// This is only about the top level. Inside a struct, whether a `None`
+/// field is written as `null` or left out is up to serde:
+/// `#[serde(skip_serializing_if = "Option::is_none")]` leaves it out, which
+/// gives a different hash from writing `null`.
+///
+/// ```
+/// use canonhash::{canonical_sha256, hash_optional, NoneBehavior};
+///
+/// let none: Option<u32> = None;
+/// assert_eq!(
+///     hash_optional(&none, NoneBehavior::AsNull).unwrap(),
+///     canonical_sha256(&()).unwrap()
+/// );
+/// assert!(hash_optional(&none, NoneBehavior::Error).is_err());
+/// assert_eq!(
+///     hash_optional(&Some(7), NoneBehavior::Error).unwrap(),
+///     canonical_sha256(&7).unwrap()
+/// );
+/// ```
+pub fn hash_optional<T>(value: &Option<T>, on_none: NoneBehavior) -> Result<[u8; 32], CanonError>
+where
+    T: Serialize,
+{
+    match (value, on_none) {
+        (Some(value), _) => canonical_sha256(value),
+        (None, NoneBehavior::AsNull) => canonical_sha256(&()),
+        (None, NoneBehavior::Error) => Err(CanonError::MissingValue),
+    }
+}
+
+/// Returns the SHA-256 digest of the canonical form of `value` as lowercase
+/// hex.
+pub fn sha256_hex<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(encode_digest(
+        &canonical_sha256(value)?,
+        DigestEncoding::Hex,
+    ))
+}
+
+/// Returns the SHA-256 digest of the canonical form of `value` as unpadded
+/// base64url.
+pub fn hash_base64url<T>(value: &T) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(encode_digest(
+        &canonical_sha256(value)?,
+        DigestEncoding::Base64UrlNoPad,
+    ))
+}
+
+/// Parses `json` text and returns the SHA-256 of its canonical form as
+/// lowercase hex.
+///
+/// This is the entry point for callers that only have JSON text rather than a
+/// typed value, such as JavaScript via the `wasm` feature. Two texts that
+/// differ only in whitespace, key order or number spelling hash the same.
+pub fn canonical_sha256_from_str(json: &str) -> Result<String, CanonError> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    sha256_hex(&value)
+}
+
+/// Hashes each item of `items` under `alg` in parallel, preserving order.
+///
+/// Items are canonicalized and hashed independently on rayon's global pool.
+/// If any item fails, the error for the lowest failing index is returned as
+/// [`CanonError::BatchItem`].
+#[cfg(feature = "rayon")]
+pub fn hash_batch<T>(items: &[T], alg: HashAlgorithm) -> Result<Vec<Vec<u8>>, CanonError>
+where
+    T: Serialize + Sync,
+{
+    use rayon::prelude::*;
+
+    let results: Vec<Result<Vec<u8>, CanonError>> =
+        items.par_iter().map(|item| hash_with(alg, item)).collect();
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            result.map_err(|err| CanonError::BatchItem {
+                index,
+                source: Box::new(err),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn hash_secret_matches_hash_with() {
+        let value = json!({"token": "s3cr3t", "user": "ops"});
+        for alg in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+            assert_eq!(
+                hash_secret(&value, alg).unwrap(),
+                hash_with(alg, &value).unwrap()
+            );
+        }
+        assert!(matches!(
+            hash_secret(&[f64::NAN], HashAlgorithm::Sha256),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn hash_dyn_matches_the_typed_hashers() {
+        let value = json!({"b": [1.5, null], "a": "x"});
+        let bytes = canonicalize(&value).unwrap();
+        let mut registry: Vec<(Box<dyn DynDigest>, Vec<u8>)> = vec![
+            (Box::new(Sha256::new()), Sha256::digest(&bytes).to_vec()),
+            (
+                Box::new(sha2::Sha512_256::new()),
+                sha2::Sha512_256::digest(&bytes).to_vec(),
+            ),
+            #[cfg(feature = "blake2")]
+            (
+                Box::new(blake2::Blake2b::<digest::consts::U20>::new()),
+                blake2::Blake2b::<digest::consts::U20>::digest(&bytes).to_vec(),
+            ),
+        ];
+        for (hasher, expected) in &mut registry {
+            assert_eq!(hash_dyn(&value, hasher.as_mut()).unwrap(), *expected);
+            // Reset afterwards, so the same hasher gives the same digest.
+            assert_eq!(hash_dyn(&value, hasher.as_mut()).unwrap(), *expected);
+        }
+
+        let hasher = registry[0].0.as_mut();
+        assert!(hash_dyn(&[f64::NAN], hasher).is_err());
+        assert_eq!(
+            hash_dyn(&value, hasher).unwrap(),
+            canonical_sha256(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn digest_matches_hash_of_canonical_bytes() {
+        let value = json!({"z": [1, 2, {"y": true, "x": null}], "a": "text"});
+        let expected = Sha256::digest(canonicalize(&value).unwrap());
+        assert_eq!(canonical_sha256(&value).unwrap(), expected.as_slice());
+    }
+
+    #[test]
+    fn hex_is_lowercase_sha256() {
+        assert_eq!(
+            sha256_hex(&json!({})).unwrap(),
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+    }
+
+    #[test]
+    fn base64url_is_unpadded_sha256() {
+        assert_eq!(
+            hash_base64url(&json!({})).unwrap(),
+            "RBNvo1WzZ4oRRq0W9-hknpT7T8If536DEMBg9hyq_4o"
+        );
+    }
+
+    #[test]
+    fn canonical_text_and_digest_agree() {
+        let value = json!({"z": [1.0, "é"], "a": null});
+        for alg in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+            let (canonical, digest) = canonicalize_and_hash(&value, alg).unwrap();
+            assert_eq!(canonical.as_bytes(), canonicalize(&value).unwrap());
+            assert_eq!(digest, hash_with(alg, &value).unwrap());
+        }
+        assert!(canonicalize_and_hash(&[f64::NAN], HashAlgorithm::Sha256).is_err());
+    }
+
+    #[test]
+    fn hasher_is_generic_over_digest() {
+        let value = json!([1, "two"]);
+        let expected = Sha512::digest(canonicalize(&value).unwrap());
+        let mut hasher = CanonicalHasher::<Sha512>::new();
+        assert_eq!(hasher.hash(&value).unwrap(), expected);
+    }
+
+    #[test]
+    fn hash_with_dispatches_on_algorithm() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        let bytes = canonicalize(&value).unwrap();
+        assert_eq!(
+            hash_with(HashAlgorithm::Sha256, &value).unwrap(),
+            Sha256::digest(&bytes).to_vec()
+        );
+        assert_eq!(
+            hash_with(HashAlgorithm::Sha384, &value).unwrap(),
+            Sha384::digest(&bytes).to_vec()
+        );
+        assert_eq!(
+            hash_with(HashAlgorithm::Sha512, &value).unwrap(),
+            Sha512::digest(&bytes).to_vec()
+        );
+    }
+
+    #[cfg(feature = "blake2")]
+    #[test]
+    fn hash_with_blake2b() {
+        let value = json!({"k": "v"});
+        let expected = blake2::Blake2b512::digest(canonicalize(&value).unwrap());
+        let digest = hash_with(HashAlgorithm::Blake2b, &value).unwrap();
+        assert_eq!(digest, expected.to_vec());
+        assert_eq!(digest.len(), HashAlgorithm::Blake2b.output_len());
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn hash_with_blake3() {
+        let value = json!({"k": "v"});
+        let expected = blake3::hash(&canonicalize(&value).unwrap());
+        let digest = hash_with(HashAlgorithm::Blake3, &value).unwrap();
+        assert_eq!(digest, expected.as_bytes());
+        assert_eq!(digest.len(), HashAlgorithm::Blake3.output_len());
+    }
+
+    #[test]
+    fn from_str_ignores_formatting() {
+        let expected = sha256_hex(&json!({"a": 1, "b": [true, null]})).unwrap();
+        assert_eq!(
+            canonical_sha256_from_str(r#"{"b": [true, null], "a": 1.0}"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            canonical_sha256_from_str("{\n  \"a\": 1,\n  \"b\": [true,null]\n}").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn from_str_reports_parse_position() {
+        match canonical_sha256_from_str("{\n  \"a\": 1,\n  \"b\": }").unwrap_err() {
+            CanonError::Parse(err) => {
+                assert_eq!(err.line(), 3);
+                assert_eq!(err.column(), 8);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn hash_batch_preserves_order() {
+        let items: Vec<_> = (0..1000).map(|i| json!({"i": i, "sq": i * i})).collect();
+        let digests = hash_batch(&items, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(digests.len(), items.len());
+        for (item, digest) in items.iter().zip(&digests) {
+            assert_eq!(digest, &hash_with(HashAlgorithm::Sha256, item).unwrap());
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn hash_batch_reports_first_failing_index() {
+        let mut items = vec![vec![1.0]; 100];
+        items[70] = vec![f64::NAN];
+        items[42] = vec![2.0, f64::INFINITY];
+        match hash_batch(&items, HashAlgorithm::Sha256).unwrap_err() {
+            CanonError::BatchItem { index, source } => {
+                assert_eq!(index, 42);
+                assert!(
+                    matches!(*source, CanonError::NonFiniteNumber { ref path } if path == "/1")
+                );
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn hasher_resets_between_values() {
+        let mut hasher = CanonicalHasher::<Sha256>::new();
+        let first = hasher.hash(&json!({"a": 1})).unwrap();
+        let second = hasher.hash(&json!({"a": 1})).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn hasher_resets_after_error() {
+        let mut bad = HashMap::new();
+        bad.insert(vec![0u8], 1);
+        let mut hasher = CanonicalHasher::<Sha256>::new();
+        assert!(hasher.hash(&("partial", bad)).is_err());
+        assert_eq!(
+            hasher.hash(&json!({"a": 1})).unwrap(),
+            CanonicalHasher::<Sha256>::new()
+                .hash(&json!({"a": 1}))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn hash_optional_distinguishes_absent_from_null() {
+        #[derive(Serialize)]
+        struct Record {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            note: Option<&'static str>,
+        }
+
+        let null = canonical_sha256(&json!(null)).unwrap();
+        assert_eq!(
+            hash_optional::<u8>(&None, NoneBehavior::AsNull).unwrap(),
+            null
+        );
+        assert!(matches!(
+            hash_optional::<u8>(&None, NoneBehavior::Error),
+            Err(CanonError::MissingValue)
+        ));
+        assert_eq!(
+            hash_optional(&Some(Some(1)), NoneBehavior::Error).unwrap(),
+            canonical_sha256(&1).unwrap()
+        );
+        // A present `None` inside the option is still a value, and is null.
+        assert_eq!(
+            hash_optional(&Some(None::<u8>), NoneBehavior::Error).unwrap(),
+            null
+        );
+        assert_eq!(
+            hash_optional(&Some(Record { note: None }), NoneBehavior::Error).unwrap(),
+            canonical_sha256(&json!({})).unwrap()
+        );
+    }
+
+    #[test]
+    fn hash_default_follows_the_default_algorithm() {
+        use crate::algorithm::set_default_algorithm;
+
+        let value = json!({"b": 1, "a": [2]});
+        assert_eq!(default_algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(
+            hash_default(&value).unwrap(),
+            canonical_sha256(&value).unwrap()
+        );
+
+        set_default_algorithm(HashAlgorithm::Sha512);
+        let from_thread = std::thread::spawn(default_algorithm).join().unwrap();
+        let digest = hash_default(&value).unwrap();
+        set_default_algorithm(HashAlgorithm::Sha256);
+        assert_eq!(from_thread, HashAlgorithm::Sha512);
+        assert_eq!(digest, hash_with(HashAlgorithm::Sha512, &value).unwrap());
+    }
+}
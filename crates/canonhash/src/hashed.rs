@@ -0,0 +1,323 @@
+//! A value that remembers its own canonical hash, and the same for JSON text
+//! embedded in the binary.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::canon::canonicalize_str_strict;
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+
+/// Wraps a value and computes its canonical SHA-256 at most once.
+///
+/// The digest is computed on the first call to [`hash`](Self::hash) and
+/// kept until the value is changed through [`get_mut`](Self::get_mut), the
+/// only way to reach it mutably. Reads go through `Deref`. The cache is a
+/// `OnceLock`, so a `Hashed<T>` can be shared between threads and hashed
+/// from any of them.
+///
+/// Equality compares the digests, so two values are equal when their
+/// canonical forms are, even if their types' own `PartialEq` would say
+/// otherwise.
+///
+/// A `T` with interior mutability, such as a `Cell` or `Mutex` field, can
+/// change without `get_mut` and leave the cached digest stale.
+///
+/// ```
+/// use canonhash::Hashed;
+/// use serde_json::json;
+///
+/// let mut config = Hashed::new(json!({"retries": 3}));
+/// let before = *config.hash();
+/// assert_eq!(config["retries"], 3);
+///
+/// config.get_mut()["retries"] = json!(4);
+/// assert_ne!(*config.hash(), before);
+/// ```
+pub struct Hashed<T> {
+    value: T,
+    digest: OnceLock<[u8; 32]>,
+}
+
+impl<T: Serialize> Hashed<T> {
+    /// Wraps `value` without hashing it yet.
+    pub fn new(value: T) -> Self {
+        Hashed {
+            value,
+            digest: OnceLock::new(),
+        }
+    }
+
+    /// Returns the canonical SHA-256 of the value, computing it on first
+    /// use.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be canonicalized, for example because it holds a
+    /// NaN. Use [`try_hash`](Self::try_hash) for values that might not.
+    pub fn hash(&self) -> &[u8; 32] {
+        match self.try_hash() {
+            Ok(digest) => digest,
+            Err(err) => panic!("value cannot be hashed: {err}"),
+        }
+    }
+
+    /// Returns the canonical SHA-256 of the value, or why it can't be
+    /// computed. Errors are not cached.
+    pub fn try_hash(&self) -> Result<&[u8; 32], CanonError> {
+        if let Some(digest) = self.digest.get() {
+            return Ok(digest);
+        }
+        let digest = canonical_sha256(&self.value)?;
+        Ok(self.digest.get_or_init(|| digest))
+    }
+
+    /// Returns the value for changing it, discarding the cached digest.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.digest.take();
+        &mut self.value
+    }
+
+    /// Returns the value, dropping the digest.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Hashed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Serialize> From<T> for Hashed<T> {
+    fn from(value: T) -> Self {
+        Hashed::new(value)
+    }
+}
+
+impl<T: Clone> Clone for Hashed<T> {
+    fn clone(&self) -> Self {
+        Hashed {
+            value: self.value.clone(),
+            digest: self.digest.clone(),
+        }
+    }
+}
+
+/// Compares canonical digests, hashing either side if it hasn't been yet.
+///
+/// # Panics
+///
+/// As [`Hashed::hash`] does.
+impl<T: Serialize> PartialEq for Hashed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash() == other.hash()
+    }
+}
+
+impl<T: Serialize> Eq for Hashed<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Hashed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hashed")
+            .field("value", &self.value)
+            .field("digest", &self.digest.get().map(hex::encode))
+            .finish()
+    }
+}
+
+/// The canonical SHA-256 of JSON text known at compile time, computed on
+/// first use and then kept.
+///
+/// [`new`](Self::new) is `const`, so this can be a `static` next to an
+/// `include_str!` of a baked-in document, and startup does no hashing. The
+/// text is parsed as [`canonicalize_str_strict`] does, so a repeated key is
+/// an error rather than silently dropped.
+///
+/// ```
+/// use canonhash::{canonical_sha256, LazyCanonicalHash};
+/// use serde_json::json;
+///
+/// // In practice: LazyCanonicalHash::new(include_str!("default-config.json")).
+/// static DEFAULT_CONFIG: LazyCanonicalHash =
+///     LazyCanonicalHash::new(r#"{ "retries": 3, "hosts": ["a", "b"] }"#);
+///
+/// let loaded = json!({"hosts": ["a", "b"], "retries": 3});
+/// assert_eq!(canonical_sha256(&loaded).unwrap(), *DEFAULT_CONFIG.hash());
+/// ```
+///
+/// To have the digest without parsing at run time at all, compute it in a
+/// build script with [`canonical_sha256_from_str`] and write it out for an
+/// `include!`:
+///
+/// ```ignore
+/// // build.rs
+/// let text = std::fs::read_to_string("default-config.json").unwrap();
+/// let hex = canonhash::canonical_sha256_from_str(&text).unwrap();
+/// let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("config_hash.rs");
+/// std::fs::write(out, format!("pub const DEFAULT_CONFIG_SHA256: &str = {hex:?};")).unwrap();
+/// println!("cargo:rerun-if-changed=default-config.json");
+///
+/// // src/lib.rs
+/// include!(concat!(env!("OUT_DIR"), "/config_hash.rs"));
+/// ```
+///
+/// [`canonical_sha256_from_str`]: crate::canonical_sha256_from_str
+pub struct LazyCanonicalHash {
+    json: &'static str,
+    digest: OnceLock<[u8; 32]>,
+}
+
+impl LazyCanonicalHash {
+    /// Holds `json` without parsing or hashing it yet.
+    pub const fn new(json: &'static str) -> Self {
+        LazyCanonicalHash {
+            json,
+            digest: OnceLock::new(),
+        }
+    }
+
+    /// Returns the JSON text as given.
+    pub fn source(&self) -> &'static str {
+        self.json
+    }
+
+    /// Returns the canonical SHA-256 of the text, computing it on first use.
+    ///
+    /// # Panics
+    ///
+    /// If the text isn't valid JSON or repeats a key. Embedded text is
+    /// fixed when the binary is built, so a test that calls this once
+    /// catches it; use [`try_hash`](Self::try_hash) to handle it instead.
+    pub fn hash(&self) -> &[u8; 32] {
+        match self.try_hash() {
+            Ok(digest) => digest,
+            Err(err) => panic!("embedded JSON cannot be hashed: {err}"),
+        }
+    }
+
+    /// Returns the canonical SHA-256 of the text, or why it can't be
+    /// computed. Errors are not cached.
+    pub fn try_hash(&self) -> Result<&[u8; 32], CanonError> {
+        if let Some(digest) = self.digest.get() {
+            return Ok(digest);
+        }
+        let digest: [u8; 32] = Sha256::digest(canonicalize_str_strict(self.json)?).into();
+        Ok(self.digest.get_or_init(|| digest))
+    }
+}
+
+impl fmt::Debug for LazyCanonicalHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyCanonicalHash")
+            .field("json", &self.json)
+            .field("digest", &self.digest.get().map(hex::encode))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::ptr;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Counts how many times it is serialized.
+    struct Counted<'a>(&'a Cell<usize>);
+
+    impl Serialize for Counted<'_> {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.0.set(self.0.get() + 1);
+            s.serialize_u8(1)
+        }
+    }
+
+    #[test]
+    fn hashes_once_until_mutated() {
+        let count = Cell::new(0);
+        let mut hashed = Hashed::new(Counted(&count));
+        assert_eq!(count.get(), 0);
+        let digest = *hashed.hash();
+        assert_eq!(*hashed.hash(), digest);
+        assert_eq!(count.get(), 1);
+        assert_eq!(digest, canonical_sha256(&1).unwrap());
+
+        hashed.get_mut();
+        assert_eq!(*hashed.hash(), digest);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn equality_is_canonical() {
+        let a = Hashed::new(json!({"a": 1.0, "b": [true]}));
+        let b = Hashed::new(json!({"b": [true], "a": 1}));
+        assert_eq!(a, b);
+        let mut c = b.clone();
+        c.get_mut()["a"] = json!(2);
+        assert_ne!(a, c);
+        assert_eq!(*c.into_inner().get("a").unwrap(), 2);
+    }
+
+    #[test]
+    fn errors_are_reported_and_not_cached() {
+        let mut hashed = Hashed::new(vec![f64::NAN]);
+        assert!(matches!(
+            hashed.try_hash(),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        hashed.get_mut()[0] = 0.5;
+        assert_eq!(*hashed.hash(), canonical_sha256(&[0.5]).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "value cannot be hashed")]
+    fn hash_panics_on_unhashable_values() {
+        Hashed::new(f64::INFINITY).hash();
+    }
+
+    #[test]
+    fn is_shareable_between_threads() {
+        let hashed = Hashed::new(json!({"k": "v"}));
+        let digests: Vec<[u8; 32]> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(|| *hashed.hash())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(digests.iter().all(|d| d == hashed.hash()));
+    }
+
+    #[test]
+    fn lazy_hash_of_embedded_text() {
+        static GOLDEN: LazyCanonicalHash =
+            LazyCanonicalHash::new(include_str!("../tests/fixtures/golden-hash-test-v1.json"));
+        let value: serde_json::Value = serde_json::from_str(GOLDEN.source()).unwrap();
+        assert_eq!(*GOLDEN.hash(), canonical_sha256(&value).unwrap());
+        assert!(ptr::eq(GOLDEN.hash(), GOLDEN.hash()));
+        assert!(format!("{GOLDEN:?}").contains(&hex::encode(GOLDEN.hash())));
+    }
+
+    #[test]
+    fn lazy_hash_rejects_invalid_text() {
+        let repeated = LazyCanonicalHash::new(r#"{"a": 1, "a": 2}"#);
+        assert!(matches!(
+            repeated.try_hash(),
+            Err(CanonError::DuplicateKey { .. })
+        ));
+        assert!(LazyCanonicalHash::new("{").try_hash().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "embedded JSON cannot be hashed")]
+    fn lazy_hash_panics_on_invalid_text() {
+        LazyCanonicalHash::new("[1,").hash();
+    }
+}
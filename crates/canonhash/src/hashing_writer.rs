@@ -0,0 +1,125 @@
+//! Hashing output as it is written.
+
+use std::io::{self, Write};
+
+use digest::{Digest, Output};
+
+/// Wraps a writer and feeds every byte written through it into a digest.
+///
+/// Handing it to [`to_writer`](crate::to_writer) writes a canonical document
+/// and hashes it in the same pass, for storing a checksum next to the file.
+/// Only bytes the inner writer accepts are hashed, so after a short write or
+/// an error the digest still matches what actually reached it.
+///
+/// ```
+/// use canonhash::HashingWriter;
+/// use sha2::{Digest, Sha256};
+///
+/// let value = serde_json::json!({"b": 1, "a": 2});
+/// let mut writer = HashingWriter::new(Vec::new(), Sha256::new());
+/// canonhash::to_writer(&mut writer, &value).unwrap();
+/// let (file, digest) = writer.into_parts();
+/// assert_eq!(file, br#"{"a":2,"b":1}"#);
+/// assert_eq!(digest.finalize()[..], canonhash::canonical_sha256(&value).unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HashingWriter<W, D> {
+    inner: W,
+    digest: D,
+}
+
+impl<W: Write, D: Digest> HashingWriter<W, D> {
+    /// Wraps `inner`, hashing into `digest`.
+    pub fn new(inner: W, digest: D) -> Self {
+        HashingWriter { inner, digest }
+    }
+
+    /// The wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the digest of everything written so far, dropping the
+    /// writer. Flush it first if it buffers.
+    pub fn finalize(self) -> Output<D> {
+        self.digest.finalize()
+    }
+
+    /// Returns the writer and the digest state, for a caller that needs the
+    /// writer back, to sync a file for example.
+    pub fn into_parts(self) -> (W, D) {
+        (self.inner, self.digest)
+    }
+}
+
+impl<W: Write, D: Digest> Write for HashingWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.digest.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::hash::canonical_sha256;
+
+    #[test]
+    fn sidecar_hash_matches_canonical_sha256() {
+        let dir = std::env::temp_dir().join(format!("canonhash-hw-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("doc.json");
+        let value = json!({"z": [1.5, null], "a": {"nested": "\u{1f600}"}});
+
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = HashingWriter::new(io::BufWriter::new(file), Sha256::new());
+        crate::to_writer(&mut writer, &value).unwrap();
+        writer.flush().unwrap();
+        let sidecar = crate::encode_digest(&writer.finalize(), crate::DigestEncoding::Hex);
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(written, crate::canonicalize(&value).unwrap());
+        assert_eq!(
+            sidecar,
+            crate::encode_digest(
+                &canonical_sha256(&value).unwrap(),
+                crate::DigestEncoding::Hex
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn short_writes_hash_only_what_was_accepted() {
+        /// Accepts at most three bytes a call.
+        struct Trickle(Vec<u8>);
+
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(3);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = HashingWriter::new(Trickle(Vec::new()), Sha256::new());
+        assert_eq!(writer.write(b"hello").unwrap(), 3);
+        assert_eq!(writer.get_ref().0, b"hel");
+        writer.write_all(b"lo, world").unwrap();
+        let (inner, digest) = writer.into_parts();
+        assert_eq!(inner.0, b"hello, world");
+        assert_eq!(digest.finalize(), Sha256::digest(b"hello, world"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Values that canonicalize but that some other JSON implementation may not
+//! read back the same, found by [`Canonicalizer::warn_non_ijson`].
+//!
+//! RFC 8785 assumes the data is I-JSON (RFC 7493), whose numbers an IEEE 754
+//! double holds exactly. This crate writes integer-typed values with all
+//! their digits, which is the same value to a reader with 64-bit integers
+//! but a rounded one to JavaScript, so the two can disagree on the hash.
+//!
+//! [`Canonicalizer::warn_non_ijson`]: crate::Canonicalizer::warn_non_ijson
+
+use alloc::string::String;
+use core::fmt;
+
+use crate::error::JsonPath;
+
+/// The largest `n` such that a double holds every integer from `-n` to `n`
+/// exactly: 2^53 − 1, JavaScript's `Number.MAX_SAFE_INTEGER`.
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Something I-JSON doesn't allow, reported by
+/// [`Canonicalizer::canonicalize_with_warnings`].
+///
+/// [`Canonicalizer::canonicalize_with_warnings`]: crate::Canonicalizer::canonicalize_with_warnings
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InteropWarning {
+    /// An integer beyond ±[`MAX_SAFE_INTEGER`], which a double rounds.
+    /// `value` is the integer as written to the output.
+    UnsafeInteger { path: JsonPath, value: String },
+}
+
+impl InteropWarning {
+    /// Returns where in the value the warning applies.
+    pub fn path(&self) -> &JsonPath {
+        match self {
+            InteropWarning::UnsafeInteger { path, .. } => path,
+        }
+    }
+
+    pub(crate) fn path_mut(&mut self) -> &mut JsonPath {
+        match self {
+            InteropWarning::UnsafeInteger { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for InteropWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InteropWarning::UnsafeInteger { path, value } => {
+                write!(
+                    f,
+                    "integer {value} is not exactly representable as a double"
+                )?;
+                if !path.is_empty() {
+                    write!(f, " at `{path}`")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether `text`, the decimal digits of an integer with an optional `-`,
+/// is beyond ±[`MAX_SAFE_INTEGER`].
+pub(crate) fn is_unsafe_integer_text(text: &str) -> bool {
+    let digits = text
+        .strip_prefix('-')
+        .unwrap_or(text)
+        .trim_start_matches('0');
+    // MAX_SAFE_INTEGER has 16 digits.
+    match digits.len() {
+        0..=15 => false,
+        16 => digits.parse::<u64>().map_or(true, |v| v > MAX_SAFE_INTEGER),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_integer_bounds() {
+        assert!(!is_unsafe_integer_text("9007199254740991"));
+        assert!(!is_unsafe_integer_text("-9007199254740991"));
+        assert!(!is_unsafe_integer_text("0009007199254740991"));
+        assert!(!is_unsafe_integer_text("0"));
+        assert!(is_unsafe_integer_text("9007199254740992"));
+        assert!(is_unsafe_integer_text("-9007199254740993"));
+        assert!(is_unsafe_integer_text("123456789012345678901234567890"));
+    }
+
+    #[test]
+    fn displays_the_value_and_path() {
+        let warning = InteropWarning::UnsafeInteger {
+            path: JsonPath::from(alloc::vec![crate::error::PathSegment::Key("id".into())]),
+            value: "9007199254740993".into(),
+        };
+        assert_eq!(
+            warning.to_string(),
+            "integer 9007199254740993 is not exactly representable as a double at `/id`"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Hashing newline-delimited JSON (JSONL) one record at a time.
+
+use std::io::{BufRead, BufReader, Read};
+
+use crate::algorithm::HashAlgorithm;
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+/// The digests of the records in a JSONL stream, from [`hash_jsonl`].
+#[derive(Debug)]
+pub struct JsonlHashes<R> {
+    reader: BufReader<R>,
+    alg: HashAlgorithm,
+    line: Vec<u8>,
+    line_number: usize,
+    done: bool,
+}
+
+/// Reads JSON Lines from `reader` and yields the canonical digest under
+/// `alg` of each record in turn.
+///
+/// Only one line is held at a time. Lines that are empty or all whitespace
+/// are skipped. A line that is not valid JSON, or not valid UTF-8, yields a
+/// [`CanonError::Line`] with its 1-based line number, and reading carries on
+/// with the next line. A read error is yielded once and ends the iterator.
+///
+/// ```
+/// use canonhash::{hash_jsonl, hash_with, HashAlgorithm};
+///
+/// let input = "{\"b\":1,\"a\":2}\n\nnot json\n[1]\n";
+/// let results: Vec<_> = hash_jsonl(input.as_bytes(), HashAlgorithm::Sha256).collect();
+/// assert_eq!(results.len(), 3);
+/// assert_eq!(
+///     results[0].as_ref().unwrap(),
+///     &hash_with(HashAlgorithm::Sha256, &serde_json::json!({"a": 2, "b": 1})).unwrap()
+/// );
+/// assert!(results[1].is_err());
+/// assert!(results[2].is_ok());
+/// ```
+pub fn hash_jsonl<R: Read>(reader: R, alg: HashAlgorithm) -> JsonlHashes<R> {
+    JsonlHashes {
+        reader: BufReader::new(reader),
+        alg,
+        line: Vec::new(),
+        line_number: 0,
+        done: false,
+    }
+}
+
+impl<R: Read> JsonlHashes<R> {
+    fn hash_line(&self) -> Result<Vec<u8>, CanonError> {
+        let value: serde_json::Value = serde_json::from_slice(&self.line)?;
+        hash_with(self.alg, &value)
+    }
+}
+
+impl<R: Read> Iterator for JsonlHashes<R> {
+    type Item = Result<Vec<u8>, CanonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line_number += 1;
+                    if self.line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+                    return Some(self.hash_line().map_err(|err| CanonError::Line {
+                        line: self.line_number,
+                        source: Box::new(err),
+                    }));
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<R: Read> core::iter::FusedIterator for JsonlHashes<R> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn sha256(value: &serde_json::Value) -> Vec<u8> {
+        hash_with(HashAlgorithm::Sha256, value).unwrap()
+    }
+
+    #[test]
+    fn hashes_each_record_and_skips_blank_lines() {
+        let input = "{\"b\":1,\"a\":[1.0]}\r\n\n   \n\"x\"\n{\"a\":[1],\"b\":1}";
+        let hashes: Vec<Vec<u8>> = hash_jsonl(input.as_bytes(), HashAlgorithm::Sha256)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let first = sha256(&json!({"a": [1], "b": 1}));
+        assert_eq!(hashes, [first.clone(), sha256(&json!("x")), first]);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_and_skipped() {
+        let input = b"1\n{\"a\":\n\xff\n2\n";
+        let results: Vec<_> = hash_jsonl(&input[..], HashAlgorithm::Sha256).collect();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &sha256(&json!(1)));
+        for (result, expected) in [(&results[1], 2), (&results[2], 3)] {
+            match result {
+                Err(CanonError::Line { line, source }) => {
+                    assert_eq!(*line, expected);
+                    assert!(matches!(**source, CanonError::Parse(_)));
+                }
+                other => panic!("unexpected result {other:?}"),
+            }
+        }
+        assert_eq!(results[3].as_ref().unwrap(), &sha256(&json!(2)));
+    }
+
+    #[test]
+    fn read_errors_end_the_stream() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk gone"))
+            }
+        }
+
+        let mut hashes = hash_jsonl(Failing, HashAlgorithm::Sha256);
+        assert!(matches!(hashes.next(), Some(Err(CanonError::Io(_)))));
+        assert!(hashes.next().is_none());
+    }
+}
@@ -0,0 +1,420 @@
+//! JSON Web Signatures (RFC 7515) whose payload is a canonical form,
+//! enabled by the `jws` feature.
+//!
+//! Building the payload from the canonical form means a verifier holding the
+//! same document can recompute the exact signed bytes, which is what makes
+//! [detached](to_detached_jws) signatures work. The protected header is
+//! itself canonical JSON containing only `alg`.
+//!
+//! ```
+//! use canonhash::jws::{to_jws, verify_jws, JwsAlg, JwsKey};
+//! use serde_json::json;
+//!
+//! let key = JwsKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&[7; 32]));
+//! let jws = to_jws(&key, &json!({"b": 2, "a": 1}), JwsAlg::EdDSA).unwrap();
+//! let payload = verify_jws(&key.verifying_key(), &jws).unwrap();
+//! assert_eq!(payload, json!({"a": 1, "b": 2}));
+//! ```
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+use crate::validate::is_canonical;
+
+/// A JWS `alg` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JwsAlg {
+    /// Ed25519 (RFC 8037).
+    EdDSA,
+    /// ECDSA over P-256 with SHA-256.
+    ES256,
+}
+
+impl JwsAlg {
+    /// The registered `alg` name.
+    pub fn name(self) -> &'static str {
+        match self {
+            JwsAlg::EdDSA => "EdDSA",
+            JwsAlg::ES256 => "ES256",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "EdDSA" => Some(JwsAlg::EdDSA),
+            "ES256" => Some(JwsAlg::ES256),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JwsAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A private key for one of the supported algorithms.
+#[derive(Clone, Debug)]
+pub enum JwsKey {
+    Ed25519(ed25519_dalek::SigningKey),
+    P256(p256::ecdsa::SigningKey),
+}
+
+/// A public key for one of the supported algorithms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JwsVerifyingKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl JwsKey {
+    /// The algorithm this key signs with.
+    pub fn alg(&self) -> JwsAlg {
+        match self {
+            JwsKey::Ed25519(_) => JwsAlg::EdDSA,
+            JwsKey::P256(_) => JwsAlg::ES256,
+        }
+    }
+
+    /// The matching public key.
+    pub fn verifying_key(&self) -> JwsVerifyingKey {
+        match self {
+            JwsKey::Ed25519(key) => JwsVerifyingKey::Ed25519(key.verifying_key()),
+            JwsKey::P256(key) => JwsVerifyingKey::P256(*key.verifying_key()),
+        }
+    }
+
+    fn sign(&self, input: &[u8]) -> Vec<u8> {
+        use ed25519_dalek::Signer as _;
+
+        match self {
+            JwsKey::Ed25519(key) => key.sign(input).to_bytes().to_vec(),
+            JwsKey::P256(key) => {
+                let sig: p256::ecdsa::Signature = key.sign(input);
+                sig.to_bytes().to_vec()
+            }
+        }
+    }
+}
+
+impl JwsVerifyingKey {
+    /// The algorithm this key verifies.
+    pub fn alg(&self) -> JwsAlg {
+        match self {
+            JwsVerifyingKey::Ed25519(_) => JwsAlg::EdDSA,
+            JwsVerifyingKey::P256(_) => JwsAlg::ES256,
+        }
+    }
+
+    fn verify(&self, input: &[u8], sig: &[u8]) -> bool {
+        use p256::ecdsa::signature::Verifier as _;
+
+        match self {
+            JwsVerifyingKey::Ed25519(key) => ed25519_dalek::Signature::from_slice(sig)
+                .is_ok_and(|sig| key.verify_strict(input, &sig).is_ok()),
+            JwsVerifyingKey::P256(key) => p256::ecdsa::Signature::from_slice(sig)
+                .is_ok_and(|sig| key.verify(input, &sig).is_ok()),
+        }
+    }
+}
+
+/// Why a JWS could not be produced or verified.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JwsError {
+    /// The requested algorithm doesn't match the key's.
+    KeyMismatch { alg: JwsAlg, key: JwsAlg },
+    /// The input is not three base64url segments with a JSON header.
+    Malformed,
+    /// The header's `alg` is missing, unsupported, or not the key's; or the
+    /// header lists `crit` extensions, none of which are supported.
+    UnsupportedHeader,
+    /// The signature does not verify.
+    InvalidSignature,
+    /// The signature verifies but the payload is not canonical JSON.
+    NonCanonicalPayload,
+}
+
+impl fmt::Display for JwsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwsError::KeyMismatch { alg, key } => {
+                write!(f, "cannot sign {alg} with a {key} key")
+            }
+            JwsError::Malformed => f.write_str("malformed compact JWS"),
+            JwsError::UnsupportedHeader => f.write_str("unsupported JWS protected header"),
+            JwsError::InvalidSignature => f.write_str("JWS signature does not verify"),
+            JwsError::NonCanonicalPayload => f.write_str("JWS payload is not canonical JSON"),
+        }
+    }
+}
+
+impl core::error::Error for JwsError {}
+
+impl From<JwsError> for CanonError {
+    fn from(err: JwsError) -> Self {
+        CanonError::Jws(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header<'a> {
+    alg: &'a str,
+    #[serde(default, skip_serializing)]
+    crit: Option<Value>,
+}
+
+/// Signs `value` and returns the compact serialization
+/// `header.payload.signature`, with the canonical form as the payload.
+pub fn to_jws<T>(key: &JwsKey, value: &T, alg: JwsAlg) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let payload = URL_SAFE_NO_PAD.encode(canonicalize(value)?);
+    sign_parts(key, alg, &payload)
+}
+
+/// Like [`to_jws`] but with the payload segment left empty (RFC 7515,
+/// appendix F). The verifier supplies the document to
+/// [`verify_detached_jws`].
+pub fn to_detached_jws<T>(key: &JwsKey, value: &T, alg: JwsAlg) -> Result<String, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let payload = URL_SAFE_NO_PAD.encode(canonicalize(value)?);
+    let jws = sign_parts(key, alg, &payload)?;
+    let (header, rest) = jws.split_once('.').expect("compact JWS has a header");
+    let signature = &rest[payload.len() + 1..];
+    Ok(alloc::format!("{header}..{signature}"))
+}
+
+fn sign_parts(key: &JwsKey, alg: JwsAlg, payload: &str) -> Result<String, CanonError> {
+    if key.alg() != alg {
+        return Err(JwsError::KeyMismatch {
+            alg,
+            key: key.alg(),
+        }
+        .into());
+    }
+    let header = Header {
+        alg: alg.name(),
+        crit: None,
+    };
+    let mut jws = URL_SAFE_NO_PAD.encode(canonicalize(&header)?);
+    jws.push('.');
+    jws.push_str(payload);
+    let signature = key.sign(jws.as_bytes());
+    jws.push('.');
+    URL_SAFE_NO_PAD.encode_string(signature, &mut jws);
+    Ok(jws)
+}
+
+/// Verifies a compact JWS and returns its payload.
+///
+/// The header must name the key's algorithm, and the payload must be
+/// canonical JSON: a JWS whose payload was serialized some other way is
+/// rejected even if its signature is valid.
+pub fn verify_jws(key: &JwsVerifyingKey, jws: &str) -> Result<Value, CanonError> {
+    let (signing_input, signature) = split_verified(key, jws)?;
+    let (_, payload) = signing_input.split_once('.').ok_or(JwsError::Malformed)?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| JwsError::Malformed)?;
+    if !key.verify(signing_input.as_bytes(), &signature) {
+        return Err(JwsError::InvalidSignature.into());
+    }
+    if !is_canonical(&payload) {
+        return Err(JwsError::NonCanonicalPayload.into());
+    }
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// Verifies a detached JWS (`header..signature`) against the canonical form
+/// of `value`.
+///
+/// An invalid signature is `Ok(false)`; a malformed JWS or unsupported header
+/// is an error.
+pub fn verify_detached_jws<T>(
+    key: &JwsVerifyingKey,
+    jws: &str,
+    value: &T,
+) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let (header, signature) = split_verified(key, jws)?;
+    let Some(header) = header.strip_suffix('.') else {
+        return Err(JwsError::Malformed.into());
+    };
+    let mut input = header.to_owned();
+    input.push('.');
+    URL_SAFE_NO_PAD.encode_string(canonicalize(value)?, &mut input);
+    Ok(key.verify(input.as_bytes(), &signature))
+}
+
+/// Checks the header of `jws` against `key` and returns the signing input
+/// and decoded signature.
+fn split_verified<'a>(key: &JwsVerifyingKey, jws: &'a str) -> Result<(&'a str, Vec<u8>), JwsError> {
+    let (signing_input, signature) = jws.rsplit_once('.').ok_or(JwsError::Malformed)?;
+    let (header, payload) = signing_input.split_once('.').ok_or(JwsError::Malformed)?;
+    if payload.contains('.') {
+        return Err(JwsError::Malformed);
+    }
+    let header = URL_SAFE_NO_PAD
+        .decode(header)
+        .map_err(|_| JwsError::Malformed)?;
+    let header: Header<'_> = serde_json::from_slice(&header).map_err(|_| JwsError::Malformed)?;
+    // Taking the algorithm from the key, never from the header, rules out
+    // algorithm-substitution attacks.
+    if header.crit.is_some() || JwsAlg::from_name(header.alg) != Some(key.alg()) {
+        return Err(JwsError::UnsupportedHeader);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| JwsError::Malformed)?;
+    Ok((signing_input, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ed_key() -> JwsKey {
+        JwsKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&[0x11; 32]))
+    }
+
+    fn p256_key() -> JwsKey {
+        JwsKey::P256(p256::ecdsa::SigningKey::from_slice(&[0x22; 32]).unwrap())
+    }
+
+    fn jws_error(err: CanonError) -> JwsError {
+        match err {
+            CanonError::Jws(err) => err,
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn round_trips_with_both_algorithms() {
+        let value = json!({"z": [1.0, "two"], "a": {"nested": true}});
+        for (key, alg) in [(ed_key(), JwsAlg::EdDSA), (p256_key(), JwsAlg::ES256)] {
+            let jws = to_jws(&key, &value, alg).unwrap();
+            let payload = verify_jws(&key.verifying_key(), &jws).unwrap();
+            assert_eq!(payload, json!({"a": {"nested": true}, "z": [1, "two"]}));
+        }
+    }
+
+    #[test]
+    fn header_and_payload_are_canonical() {
+        let jws = to_jws(&ed_key(), &json!({"b": 1, "a": 2}), JwsAlg::EdDSA).unwrap();
+        let parts: Vec<&str> = jws.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(parts[0]).unwrap(),
+            br#"{"alg":"EdDSA"}"#
+        );
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(parts[1]).unwrap(),
+            br#"{"a":2,"b":1}"#
+        );
+        assert_eq!(URL_SAFE_NO_PAD.decode(parts[2]).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn rejects_key_and_algorithm_mismatch() {
+        let err = to_jws(&ed_key(), &json!(1), JwsAlg::ES256).unwrap_err();
+        assert_eq!(
+            jws_error(err),
+            JwsError::KeyMismatch {
+                alg: JwsAlg::ES256,
+                key: JwsAlg::EdDSA
+            }
+        );
+
+        let jws = to_jws(&ed_key(), &json!(1), JwsAlg::EdDSA).unwrap();
+        let err = verify_jws(&p256_key().verifying_key(), &jws).unwrap_err();
+        assert_eq!(jws_error(err), JwsError::UnsupportedHeader);
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        for (key, alg) in [(ed_key(), JwsAlg::EdDSA), (p256_key(), JwsAlg::ES256)] {
+            let vk = key.verifying_key();
+            let jws = to_jws(&key, &json!({"amount": 1}), alg).unwrap();
+            let parts: Vec<&str> = jws.split('.').collect();
+
+            let forged = URL_SAFE_NO_PAD.encode(br#"{"amount":2}"#);
+            let swapped = alloc::format!("{}.{forged}.{}", parts[0], parts[2]);
+            assert_eq!(
+                jws_error(verify_jws(&vk, &swapped).unwrap_err()),
+                JwsError::InvalidSignature
+            );
+
+            let mut sig = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+            sig[5] ^= 1;
+            let flipped =
+                alloc::format!("{}.{}.{}", parts[0], parts[1], URL_SAFE_NO_PAD.encode(sig));
+            assert_eq!(
+                jws_error(verify_jws(&vk, &flipped).unwrap_err()),
+                JwsError::InvalidSignature
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input_and_crit_headers() {
+        let vk = ed_key().verifying_key();
+        for bad in ["", "a.b", "a.b.c.d", "!!!.e30.AAAA"] {
+            assert_eq!(
+                jws_error(verify_jws(&vk, bad).unwrap_err()),
+                JwsError::Malformed,
+                "{bad}"
+            );
+        }
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"EdDSA","crit":["exp"]}"#);
+        let jws = alloc::format!("{header}.e30.AAAA");
+        assert_eq!(
+            jws_error(verify_jws(&vk, &jws).unwrap_err()),
+            JwsError::UnsupportedHeader
+        );
+    }
+
+    #[test]
+    fn rejects_validly_signed_non_canonical_payload() {
+        let key = ed_key();
+        let payload = URL_SAFE_NO_PAD.encode(br#"{"b":1,"a":2}"#);
+        let jws = sign_parts(&key, JwsAlg::EdDSA, &payload).unwrap();
+        assert_eq!(
+            jws_error(verify_jws(&key.verifying_key(), &jws).unwrap_err()),
+            JwsError::NonCanonicalPayload
+        );
+    }
+
+    #[test]
+    fn detached_signatures_verify_against_the_document() {
+        for (key, alg) in [(ed_key(), JwsAlg::EdDSA), (p256_key(), JwsAlg::ES256)] {
+            let vk = key.verifying_key();
+            let value = json!({"b": [1, 2], "a": null});
+            let jws = to_detached_jws(&key, &value, alg).unwrap();
+            assert!(jws.contains(".."));
+
+            let reordered = json!({"a": null, "b": [1, 2.0]});
+            assert!(verify_detached_jws(&vk, &jws, &reordered).unwrap());
+            assert!(!verify_detached_jws(&vk, &jws, &json!({"a": null})).unwrap());
+
+            let attached = to_jws(&key, &value, alg).unwrap();
+            assert!(verify_detached_jws(&vk, &attached, &value).is_err());
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Keyed hashes of canonical forms, for integrity tokens that can't be
+//! precomputed without the key.
+//!
+//! The MAC input is exactly the canonical form [`canonicalize`] produces;
+//! only the final step differs from the unkeyed hashes.
+//!
+//! [`canonicalize`]: crate::canonicalize
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::error::CanonError;
+use crate::hash::feed;
+use crate::ser::{Options, Scope};
+
+/// Returns the HMAC-SHA256 (RFC 2104) of the canonical form of `value` under
+/// `key`.
+pub fn hmac_sha256<T>(key: &[u8], value: &T) -> Result<[u8; 32], CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut mac = new_hmac(key);
+    feed(&mut mac, value, Options::default(), Scope::default())?;
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// Returns whether `tag` is the HMAC-SHA256 of the canonical form of `value`
+/// under `key`. The comparison is constant-time.
+pub fn verify_hmac_sha256<T>(key: &[u8], value: &T, tag: &[u8]) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut mac = new_hmac(key);
+    feed(&mut mac, value, Options::default(), Scope::default())?;
+    Ok(mac.verify_slice(tag).is_ok())
+}
+
+fn new_hmac(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// Returns BLAKE3 in keyed mode (`blake3::keyed_hash`) over the canonical
+/// form of `value`.
+#[cfg(feature = "blake3")]
+pub fn blake3_keyed<T>(key: &[u8; 32], value: &T) -> Result<[u8; 32], CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut hasher = blake3::Hasher::new_keyed(key);
+    feed(
+        &mut crate::hash::Blake3(&mut hasher),
+        value,
+        Options::default(),
+        Scope::default(),
+    )?;
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    #[test]
+    fn hmac_input_is_the_canonical_form() {
+        let value = json!({"b": [true, null], "a": 1.0});
+        let tag = hmac_sha256(b"secret", &value).unwrap();
+
+        let mut expected = new_hmac(b"secret");
+        expected.update(&canonicalize(&value).unwrap());
+        assert_eq!(tag, expected.finalize().into_bytes().as_slice());
+        assert_eq!(
+            hex::encode(tag),
+            "5c65f9fe9ce6071e8e6765bdbfdde2e9bc6621fc89fffd1fe9ba9e0c39caf755"
+        );
+    }
+
+    #[test]
+    fn tag_depends_on_key_and_value() {
+        let value = json!({"a": 1});
+        let tag = hmac_sha256(b"k1", &value).unwrap();
+        assert_ne!(tag, hmac_sha256(b"k2", &value).unwrap());
+        assert_ne!(tag, hmac_sha256(b"k1", &json!({"a": 2})).unwrap());
+        assert_ne!(tag, crate::canonical_sha256(&value).unwrap());
+    }
+
+    #[test]
+    fn verifies_tags() {
+        let value = json!({"a": 1});
+        let tag = hmac_sha256(b"k", &value).unwrap();
+        assert!(verify_hmac_sha256(b"k", &value, &tag).unwrap());
+        assert!(!verify_hmac_sha256(b"other", &value, &tag).unwrap());
+        assert!(!verify_hmac_sha256(b"k", &value, &tag[..31]).unwrap());
+    }
+
+    #[test]
+    fn errors_surface_before_the_mac() {
+        assert!(matches!(
+            hmac_sha256(b"k", &[f64::NAN]),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_keyed_matches_keyed_hash_of_canonical_form() {
+        let key = [7; 32];
+        let value = json!({"z": 1, "a": [1, 2]});
+        assert_eq!(
+            blake3_keyed(&key, &value).unwrap(),
+            *blake3::keyed_hash(&key, &canonicalize(&value).unwrap()).as_bytes()
+        );
+        assert_ne!(
+            blake3_keyed(&key, &value).unwrap(),
+            blake3_keyed(&[8; 32], &value).unwrap()
+        );
+    }
+}
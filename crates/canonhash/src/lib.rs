@@ -0,0 +1,209 @@
+//! Canonical JSON (RFC 8785, the JSON Canonicalization Scheme) for serde
+//! types, aimed at producing stable hashes of structured data.
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     version: u32,
+//!     name: &'static str,
+//! }
+//!
+//! let bytes = canonhash::canonicalize(&Config { version: 1, name: "ops" }).unwrap();
+//! assert_eq!(bytes, br#"{"name":"ops","version":1}"#);
+//! ```
+//!
+//! Untyped `serde_json::Value`s are first-class input. Object members are
+//! sorted during serialization, so the result doesn't depend on the `Map`'s
+//! own order (insertion order when `serde_json`'s `preserve_order` feature is
+//! on):
+//!
+//! ```
+//! let value: serde_json::Value = serde_json::from_str(r#"{"b": [1.0, 2], "a": {}}"#).unwrap();
+//! assert_eq!(canonhash::canonicalize(&value).unwrap(), br#"{"a":{},"b":[1,2]}"#);
+//! ```
+//!
+//! [`canonicalize_value`] produces the same bytes for a `Value` without going
+//! through serde.
+//!
+//! Every entry point takes `&T` for any `T: ?Sized + Serialize` and
+//! serializes through the reference, so a large value is never cloned or
+//! moved to be hashed. `&T`, `Box<T>` and `Cow<T>` serialize exactly as `T`
+//! does, borrowed or owned. `Rc<T>` and `Arc<T>` implement `Serialize` only
+//! with serde's `rc` feature; without it, pass `&*arc`, which canonicalizes
+//! the same.
+//!
+//! # Features
+//!
+//! - `std` (default): the `io::Write` streaming API ([`to_writer`]). Without
+//!   it the crate is `no_std` and needs only `alloc`.
+//! - `arbitrary_precision`: turns on `serde_json`'s feature of the same name,
+//!   so integers of any size in parsed JSON keep their exact digits.
+//! - `blake2`, `blake3`: the corresponding [`HashAlgorithm`] variants.
+//! - `cli`: the `canonhash` command-line tool.
+//! - `derive`: [`macro@CanonicalHash`], which adds `canonical_sha256()` and
+//!   `canonical_hash()` methods to a `Serialize` type.
+//! - `ed25519`: [`sign`] for Ed25519 signatures over canonical forms.
+//! - `faster-hex`: SIMD hex encoding of digests. The output is unchanged.
+//! - `jsonschema`: [`schema::validate_and_hash`], which hashes a value only
+//!   once it passes a JSON Schema.
+//! - `jws`: [`jws`] compact and detached JWS (RFC 7515) with a canonical
+//!   payload, signed with `EdDSA` or `ES256`.
+//! - `rayon`: [`hash_batch`](hash::hash_batch) for hashing many records in
+//!   parallel.
+//! - `ryu`: float digits from the `ryu` crate rather than `core::fmt`. The
+//!   output is identical; only the speed differs.
+//! - `testing`: [`assert_canonical_snapshot!`] for snapshot tests of
+//!   canonical forms.
+//! - `tokio`: [`canonical_sha256_async`] for hashing JSON read from a
+//!   `tokio::io::AsyncRead`.
+//! - `trace`: [`trace::canonicalize_traced`], which reports every reordered
+//!   object and reformatted number alongside the output.
+//! - `unicode-normalization`: [`Canonicalizer::normalize_strings`], a
+//!   non-standard mode that normalizes strings and keys before writing them.
+//! - `wasm`: a `wasm-bindgen` export of [`canonical_sha256_from_str`] as
+//!   `canonicalSha256`.
+//! - `zeroize`: [`hash_secret`](hash::hash_secret), which wipes the
+//!   canonical bytes of a value holding secrets once they are hashed.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod algorithm;
+#[cfg(feature = "tokio")]
+pub mod async_hash;
+pub mod canon;
+pub mod canon_bytes;
+pub mod canonical;
+pub mod canonicalizer;
+pub mod cbor;
+pub mod chain;
+pub mod chunks;
+pub mod content_id;
+pub mod diff;
+pub mod domain;
+pub mod encoding;
+pub mod envelope;
+mod error;
+pub mod etag;
+pub mod fingerprint;
+pub mod fixture;
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod hashed;
+#[cfg(feature = "std")]
+pub mod hashing_writer;
+pub mod interop;
+#[cfg(feature = "std")]
+pub mod jsonl;
+#[cfg(feature = "jws")]
+pub mod jws;
+pub mod keyed;
+pub mod merkle;
+pub mod multihash;
+mod number;
+pub mod pointer;
+pub mod pretty;
+pub mod projection;
+pub mod redact;
+#[cfg(feature = "jsonschema")]
+pub mod schema;
+pub mod sequence;
+mod ser;
+#[cfg(feature = "std")]
+pub mod serializer;
+pub mod set_hash;
+#[cfg(feature = "ed25519")]
+pub mod sign;
+mod sink;
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod store;
+mod strict;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod token_sink;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod typed;
+mod validate;
+pub mod value;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use algorithm::{default_algorithm, set_default_algorithm, HashAlgorithm, UnknownAlgorithm};
+#[cfg(feature = "tokio")]
+pub use async_hash::canonical_sha256_async;
+pub use canon::{
+    canonical_eq, canonical_len, canonicalize, canonicalize_into, canonicalize_str_strict,
+};
+#[cfg(feature = "std")]
+pub use canon::{canonicalize_reader, to_writer, to_writer_with_options, WriteOptions};
+#[cfg(feature = "derive")]
+pub use canonhash_derive::CanonicalHash;
+pub use canonical::{Canonical, CanonicalKey};
+#[cfg(feature = "unicode-normalization")]
+pub use canonicalizer::NormalizationForm;
+pub use canonicalizer::{CanonMode, Canonicalizer, KeySortOrder};
+pub use chain::{verify_chain, HashChain};
+pub use chunks::{canonical_chunks, CanonChunks};
+pub use content_id::ContentId;
+pub use diff::{
+    canonical_diff, canonical_patch, compare_documents, CanonDiff, ComparisonReport, PatchOp,
+};
+pub use domain::hash_domain_separated;
+pub use encoding::{decode_digest, encode_digest, DigestEncoding};
+pub use envelope::{hash_with_envelope, Envelope};
+pub use error::{CanonError, JsonPath, Limit, PathSegment};
+pub use etag::{etag, if_none_match, weak_etag};
+pub use fingerprint::{fingerprint, fingerprint_with, FingerprintEncoding, FingerprintOptions};
+#[cfg(feature = "rayon")]
+pub use hash::hash_batch;
+#[cfg(feature = "zeroize")]
+pub use hash::hash_secret;
+pub use hash::{
+    canonical_sha256, canonical_sha256_from_str, canonicalize_and_hash, hash_base64url,
+    hash_default, hash_dyn, hash_optional, hash_with, sha256_hex, CanonicalHasher, NoneBehavior,
+};
+#[cfg(feature = "std")]
+pub use hashed::{Hashed, LazyCanonicalHash};
+#[cfg(feature = "std")]
+pub use hashing_writer::HashingWriter;
+pub use interop::{InteropWarning, MAX_SAFE_INTEGER};
+#[cfg(feature = "std")]
+pub use jsonl::{hash_jsonl, JsonlHashes};
+pub use keyed::hmac_sha256;
+pub use merkle::{merkleize_object, MerkleProof, MerkleTree, ObjectMerkle};
+pub use multihash::{multihash, parse_multihash, Multibase};
+pub use pointer::{canonical_at, hash_at};
+pub use pretty::pretty_from_canonical;
+pub use projection::{hash_excluding, hash_projection};
+pub use redact::{commit_with_redactions, Commitment};
+#[cfg(feature = "jsonschema")]
+pub use schema::{validate_and_hash, SchemaViolation};
+pub use sequence::SequenceHasher;
+#[cfg(feature = "std")]
+pub use serializer::CanonicalSerializer;
+pub use set_hash::SetHasher;
+pub use stats::{canonicalize_with_stats, CanonStats};
+#[cfg(feature = "std")]
+pub use store::{store_path, write_content};
+pub use token_sink::{CanonTokenSink, JsonToken};
+pub use typed::TypedCanonicalizer;
+pub use validate::{
+    from_canonical_slice, from_canonical_slice_unchecked, is_canonical, is_canonical_str,
+};
+pub use value::{canonicalize_value, merge_objects, DuplicateKeyPolicy};
+pub use verify::{verify_document, verify_sha256, VerifyError};
+
+/// Paths used by the code `#[derive(CanonicalHash)]` generates. Not public
+/// API.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+    pub use serde::Serialize;
+}
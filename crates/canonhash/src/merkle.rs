@@ -0,0 +1,458 @@
+//! Merkle trees over canonical hashes, using the RFC 6962 (Certificate
+//! Transparency) construction.
+//!
+//! Each leaf is a 32-byte record hash, typically from
+//! [`canonical_sha256`](crate::canonical_sha256). Leaves and interior nodes
+//! are domain-separated with a `0x00` or `0x01` prefix before hashing, so a
+//! leaf can never be passed off as an interior node.
+//!
+//! ```
+//! use canonhash::merkle::{verify_proof, MerkleTree};
+//!
+//! let records = [serde_json::json!({"seq": 0}), serde_json::json!({"seq": 1})];
+//! let leaves = records
+//!     .iter()
+//!     .map(|r| canonhash::canonical_sha256(r).unwrap())
+//!     .collect();
+//! let tree = MerkleTree::new(leaves);
+//!
+//! let proof = tree.proof(1).unwrap();
+//! let leaf = canonhash::canonical_sha256(&records[1]).unwrap();
+//! assert!(verify_proof(&tree.root(), &leaf, &proof));
+//! ```
+//!
+//! [`merkleize_object`] builds such a tree over the members of a JSON
+//! object, so that one field can be proven without revealing the others.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+use crate::ser::sort_members;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// A Merkle tree over a fixed list of leaf hashes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// `levels[0]` is `SHA-256(0x00 || leaf)` for each leaf, in order. Each
+    /// further level pairs up the nodes of the one below, and a last node
+    /// without a partner is carried up unchanged, which gives the RFC 6962
+    /// shape. The last level holds only the root, unless there are no
+    /// leaves.
+    levels: Vec<Vec<[u8; 32]>>,
+    root: [u8; 32],
+}
+
+/// An inclusion proof for one leaf: the RFC 6962 audit path from the leaf to
+/// the root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the leaf in the tree.
+    pub index: usize,
+    /// Number of leaves in the tree the proof was built from.
+    pub tree_size: usize,
+    /// Sibling subtree hashes, from the leaf upwards.
+    pub path: Vec<[u8; 32]>,
+}
+
+impl MerkleTree {
+    /// Builds a tree over `leaves`, which are record hashes in log order.
+    pub fn new(leaves: Vec<[u8; 32]>) -> Self {
+        let mut levels = vec![leaves.iter().map(leaf_hash).collect::<Vec<_>>()];
+        while let Some(below) = levels.last().filter(|level| level.len() > 1) {
+            let above = below
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [odd] => *odd,
+                    _ => unreachable!("chunks of two"),
+                })
+                .collect();
+            levels.push(above);
+        }
+        let root = match levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => Sha256::digest([]).into(),
+        };
+        MerkleTree { levels, root }
+    }
+
+    /// The root hash. An empty tree's root is the SHA-256 of the empty string.
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// The number of leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns whether the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Returns the inclusion proof for the leaf at `index`, or `None` if it is
+    /// out of range. The siblings are read from the stored levels, so this
+    /// is O(log n).
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            // A node carried up unpartnered has no sibling at this level.
+            if let Some(sibling) = level.get(position ^ 1) {
+                path.push(*sibling);
+            }
+            position >>= 1;
+        }
+        Some(MerkleProof {
+            index,
+            tree_size: self.len(),
+            path,
+        })
+    }
+}
+
+/// Returns whether `proof` shows that `leaf` (a record hash, as passed to
+/// [`MerkleTree::new`]) is included in the tree with the given `root`.
+pub fn verify_proof(root: &[u8; 32], leaf: &[u8; 32], proof: &MerkleProof) -> bool {
+    // RFC 9162, section 2.1.3.2.
+    if proof.index >= proof.tree_size {
+        return false;
+    }
+    let mut f = proof.index;
+    let mut s = proof.tree_size - 1;
+    let mut hash = leaf_hash(leaf);
+    for sibling in &proof.path {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            hash = node_hash(sibling, &hash);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && hash == *root
+}
+
+/// A Merkle tree over the members of one JSON object, from
+/// [`merkleize_object`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectMerkle {
+    /// The root of the tree over every member's leaf.
+    pub root: [u8; 32],
+    /// Each member's leaf hash and inclusion proof, by key.
+    pub fields: BTreeMap<String, FieldProof>,
+}
+
+/// One member of an [`ObjectMerkle`]: what to show a verifier along with
+/// the member's key and value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldProof {
+    /// The member's record hash, from [`field_leaf`].
+    pub leaf: [u8; 32],
+    pub proof: MerkleProof,
+}
+
+impl ObjectMerkle {
+    /// Returns whether `proof` shows that the member `key` holding `value`
+    /// is in the object with this root. See [`verify_field`].
+    pub fn verify(&self, key: &str, value: &Value, proof: &MerkleProof) -> bool {
+        verify_field(&self.root, key, value, proof)
+    }
+}
+
+/// Builds a Merkle tree over the members of the object `value`.
+///
+/// Each member's leaf is [`field_leaf`], the SHA-256 of the canonical form
+/// of the two-element array `[key, value]`, so a proof commits to the key as
+/// well as the value and one field can't be presented as another. Leaves are
+/// in canonical key order (by UTF-16 code units, as RFC 8785 sorts members),
+/// so the root depends only on the object's contents. A value that isn't an
+/// object is [`CanonError::NotAnObject`]; an empty object has the root of
+/// an empty tree.
+///
+/// ```
+/// use canonhash::merkle::{merkleize_object, verify_field};
+/// use serde_json::json;
+///
+/// let document = json!({"name": "Ada", "salary": 100, "team": "core"});
+/// let merkle = merkleize_object(&document).unwrap();
+///
+/// // Reveal only `team`: the verifier needs the root, the member and its proof.
+/// let proof = &merkle.fields["team"].proof;
+/// assert!(verify_field(&merkle.root, "team", &json!("core"), proof));
+/// assert!(!verify_field(&merkle.root, "name", &json!("core"), proof));
+/// ```
+pub fn merkleize_object(value: &Value) -> Result<ObjectMerkle, CanonError> {
+    let Value::Object(members) = value else {
+        return Err(CanonError::NotAnObject);
+    };
+    let mut members: Vec<(&String, &Value)> = members.iter().collect();
+    sort_members(&mut members)?;
+    let leaves = members
+        .iter()
+        .map(|(key, value)| field_leaf(key, value))
+        .collect::<Result<Vec<_>, _>>()?;
+    let tree = MerkleTree::new(leaves.clone());
+    let fields = members
+        .into_iter()
+        .zip(leaves)
+        .enumerate()
+        .map(|(index, ((key, _), leaf))| {
+            let proof = tree.proof(index).expect("index is in range");
+            (key.clone(), FieldProof { leaf, proof })
+        })
+        .collect();
+    Ok(ObjectMerkle {
+        root: tree.root(),
+        fields,
+    })
+}
+
+/// The record hash of the object member `key` holding `value`: the SHA-256
+/// of the canonical form of `[key, value]`.
+pub fn field_leaf(key: &str, value: &Value) -> Result<[u8; 32], CanonError> {
+    canonical_sha256(&(key, value))
+}
+
+/// Returns whether `proof` shows that the member `key` holding `value` is in
+/// the object whose [`merkleize_object`] root is `root`.
+///
+/// A value that can't be canonicalized, such as one with a non-finite
+/// number, was never a leaf, so it isn't verified.
+pub fn verify_field(root: &[u8; 32], key: &str, value: &Value, proof: &MerkleProof) -> bool {
+    field_leaf(key, value).is_ok_and(|leaf| verify_proof(root, &leaf, proof))
+}
+
+fn leaf_hash(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update([LEAF_PREFIX]);
+    h.update(leaf);
+    h.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update([NODE_PREFIX]);
+    h.update(left);
+    h.update(right);
+    h.finalize().into()
+}
+
+/// The largest power of two strictly less than `n`, for `n > 1`.
+#[cfg(test)]
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// MTH over already leaf-hashed nodes, straight from RFC 6962's definition,
+/// to check the levels against.
+#[cfg(test)]
+fn subtree_root(nodes: &[[u8; 32]]) -> [u8; 32] {
+    match nodes.len() {
+        0 => Sha256::digest([]).into(),
+        1 => nodes[0],
+        n => {
+            let (left, right) = nodes.split_at(split(n));
+            node_hash(&subtree_root(left), &subtree_root(right))
+        }
+    }
+}
+
+/// PATH from RFC 6962's definition, to check proofs against.
+#[cfg(test)]
+fn audit_path(index: usize, nodes: &[[u8; 32]], path: &mut Vec<[u8; 32]>) {
+    if nodes.len() <= 1 {
+        return;
+    }
+    let (left, right) = nodes.split_at(split(nodes.len()));
+    if index < left.len() {
+        audit_path(index, left, path);
+        path.push(subtree_root(right));
+    } else {
+        audit_path(index - left.len(), right, path);
+        path.push(subtree_root(left));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: u8) -> Vec<[u8; 32]> {
+        (0..n).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn empty_tree_root_is_hash_of_nothing() {
+        let tree = MerkleTree::new(Vec::new());
+        assert!(tree.is_empty());
+        assert_eq!(
+            hex::encode(tree.root()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(tree.proof(0), None);
+    }
+
+    #[test]
+    fn root_follows_rfc6962_shape() {
+        let l = leaves(3);
+        let tree = MerkleTree::new(l.clone());
+        let expected = node_hash(
+            &node_hash(&leaf_hash(&l[0]), &leaf_hash(&l[1])),
+            &leaf_hash(&l[2]),
+        );
+        assert_eq!(tree.root(), expected);
+        assert_eq!(MerkleTree::new(leaves(1)).root(), leaf_hash(&[0; 32]));
+    }
+
+    #[test]
+    fn split_is_largest_power_of_two_below() {
+        assert_eq!(split(2), 1);
+        assert_eq!(split(3), 2);
+        assert_eq!(split(4), 2);
+        assert_eq!(split(5), 4);
+        assert_eq!(split(9), 8);
+    }
+
+    #[test]
+    fn levels_match_the_recursive_definition() {
+        for n in 0..=70 {
+            let l = leaves(n);
+            let tree = MerkleTree::new(l.clone());
+            let hashed: Vec<_> = l.iter().map(leaf_hash).collect();
+            assert_eq!(tree.root(), subtree_root(&hashed), "n={n}");
+            for i in 0..l.len() {
+                let mut path = Vec::new();
+                audit_path(i, &hashed, &mut path);
+                assert_eq!(tree.proof(i).unwrap().path, path, "n={n} i={i}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_proof_verifies() {
+        for n in 1..=17 {
+            let l = leaves(n);
+            let tree = MerkleTree::new(l.clone());
+            for (i, leaf) in l.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify_proof(&tree.root(), leaf, &proof), "n={n} i={i}");
+            }
+            assert_eq!(tree.proof(n as usize), None);
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_fail() {
+        let l = leaves(7);
+        let tree = MerkleTree::new(l.clone());
+        let root = tree.root();
+        let proof = tree.proof(5).unwrap();
+
+        assert!(!verify_proof(&root, &l[4], &proof));
+
+        let mut wrong_index = proof.clone();
+        wrong_index.index = 4;
+        assert!(!verify_proof(&root, &l[5], &wrong_index));
+
+        let mut wrong_size = proof.clone();
+        wrong_size.tree_size = 6;
+        assert!(!verify_proof(&root, &l[5], &wrong_size));
+
+        let mut flipped = proof.clone();
+        flipped.path[0][0] ^= 1;
+        assert!(!verify_proof(&root, &l[5], &flipped));
+
+        let mut short = proof;
+        short.path.pop();
+        assert!(!verify_proof(&root, &l[5], &short));
+    }
+
+    #[test]
+    fn interior_node_is_not_accepted_as_leaf() {
+        // Without domain separation, the left child of a four-leaf root could
+        // be presented as leaf 0 of a two-leaf tree with the same root.
+        let l = leaves(4);
+        let tree = MerkleTree::new(l.clone());
+        let left = node_hash(&leaf_hash(&l[0]), &leaf_hash(&l[1]));
+        let right = node_hash(&leaf_hash(&l[2]), &leaf_hash(&l[3]));
+        let proof = MerkleProof {
+            index: 0,
+            tree_size: 2,
+            path: vec![right],
+        };
+        assert!(!verify_proof(&tree.root(), &left, &proof));
+    }
+
+    #[test]
+    fn every_field_verifies_against_the_object_root() {
+        let document = serde_json::json!({
+            "b": [1, 2],
+            "a": {"nested": true},
+            "\u{e000}": null,
+            "\u{1f600}": "x",
+            "c": 1.5,
+        });
+        let merkle = merkleize_object(&document).unwrap();
+        assert_eq!(merkle.fields.len(), 5);
+        for (key, value) in document.as_object().unwrap() {
+            let field = &merkle.fields[key];
+            assert_eq!(field.leaf, field_leaf(key, value).unwrap());
+            assert_eq!(field.proof.tree_size, 5);
+            assert!(merkle.verify(key, value, &field.proof), "{key}");
+        }
+        // Leaves follow canonical key order, where U+1F600 (a surrogate pair)
+        // sorts before U+E000.
+        assert_eq!(merkle.fields["a"].proof.index, 0);
+        assert_eq!(merkle.fields["\u{1f600}"].proof.index, 3);
+        assert_eq!(merkle.fields["\u{e000}"].proof.index, 4);
+    }
+
+    #[test]
+    fn proofs_bind_the_key_and_the_value() {
+        let document = serde_json::json!({"role": "user", "name": "admin"});
+        let merkle = merkleize_object(&document).unwrap();
+        let name = &merkle.fields["name"].proof;
+        assert!(merkle.verify("name", &"admin".into(), name));
+        assert!(!merkle.verify("role", &"admin".into(), name));
+        assert!(!merkle.verify("name", &"user".into(), name));
+        // Swapping the two members' values changes the root.
+        let swapped = serde_json::json!({"role": "admin", "name": "user"});
+        assert_ne!(merkleize_object(&swapped).unwrap().root, merkle.root);
+    }
+
+    #[test]
+    fn root_ignores_member_order_and_rejects_non_objects() {
+        let a: Value = serde_json::from_str(r#"{"x":1,"y":{"q":2,"p":3}}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"y":{"p":3,"q":2},"x":1.0}"#).unwrap();
+        assert_eq!(merkleize_object(&a).unwrap(), merkleize_object(&b).unwrap());
+        let empty = merkleize_object(&serde_json::json!({})).unwrap();
+        assert!(empty.fields.is_empty());
+        assert_eq!(empty.root, MerkleTree::new(Vec::new()).root());
+        assert!(matches!(
+            merkleize_object(&serde_json::json!([1])),
+            Err(CanonError::NotAnObject)
+        ));
+    }
+}
@@ -0,0 +1,406 @@
+//! Self-describing digests in the multiformats `multihash` and `multibase`
+//! encodings, for use as content identifiers.
+//!
+//! A multihash is `varint(code) || varint(len) || digest`. Codes follow the
+//! multicodec table: `0x12` sha2-256, `0x20` sha2-384, `0x13` sha2-512,
+//! `0xb240` blake2b-512 and `0x1e` blake3.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::algorithm::HashAlgorithm;
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Errors from decoding a multihash or multibase string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultihashError {
+    /// The input ended before the code, length or digest was complete.
+    Truncated,
+    /// The code or length varint used more bytes than its value needs,
+    /// which the unsigned-varint spec forbids so that each multihash has
+    /// exactly one encoding.
+    NonMinimalVarint,
+    /// The multicodec code is not a supported (or enabled) hash algorithm.
+    UnknownCode(u64),
+    /// The declared digest length doesn't match the bytes that follow it or
+    /// the algorithm's output size.
+    LengthMismatch { declared: usize, actual: usize },
+    /// The multibase prefix is unsupported or the payload is malformed.
+    InvalidMultibase,
+}
+
+impl fmt::Display for MultihashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultihashError::Truncated => f.write_str("multihash is truncated"),
+            MultihashError::NonMinimalVarint => {
+                f.write_str("multihash varint is not minimally encoded")
+            }
+            MultihashError::UnknownCode(code) => write!(f, "unknown multihash code 0x{code:x}"),
+            MultihashError::LengthMismatch { declared, actual } => write!(
+                f,
+                "multihash declares {declared} digest bytes but has {actual}"
+            ),
+            MultihashError::InvalidMultibase => f.write_str("invalid multibase string"),
+        }
+    }
+}
+
+impl core::error::Error for MultihashError {}
+
+/// The multicodec code for `alg`.
+pub fn code(alg: HashAlgorithm) -> u64 {
+    match alg {
+        HashAlgorithm::Sha256 => 0x12,
+        HashAlgorithm::Sha384 => 0x20,
+        HashAlgorithm::Sha512 => 0x13,
+        #[cfg(feature = "blake2")]
+        HashAlgorithm::Blake2b => 0xb240,
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => 0x1e,
+    }
+}
+
+fn algorithm_for_code(code: u64) -> Option<HashAlgorithm> {
+    match code {
+        0x12 => Some(HashAlgorithm::Sha256),
+        0x20 => Some(HashAlgorithm::Sha384),
+        0x13 => Some(HashAlgorithm::Sha512),
+        #[cfg(feature = "blake2")]
+        0xb240 => Some(HashAlgorithm::Blake2b),
+        #[cfg(feature = "blake3")]
+        0x1e => Some(HashAlgorithm::Blake3),
+        _ => None,
+    }
+}
+
+/// Hashes the canonical form of `value` and wraps the digest as a multihash.
+pub fn multihash<T>(alg: HashAlgorithm, value: &T) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(encode_multihash(alg, &hash_with(alg, value)?))
+}
+
+/// Prefixes an existing `digest` with the multihash header for `alg`.
+pub fn encode_multihash(alg: HashAlgorithm, digest: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(digest.len() + 4);
+    write_varint(&mut out, code(alg));
+    write_varint(&mut out, digest.len() as u64);
+    out.extend_from_slice(digest);
+    out
+}
+
+/// Splits a multihash into its algorithm and digest.
+///
+/// The declared length must match both the number of remaining bytes and
+/// the algorithm's digest size; truncated digests are rejected.
+pub fn parse_multihash(bytes: &[u8]) -> Result<(HashAlgorithm, Vec<u8>), MultihashError> {
+    let (code, rest) = read_varint(bytes)?;
+    let alg = algorithm_for_code(code).ok_or(MultihashError::UnknownCode(code))?;
+    let (len, digest) = read_varint(rest)?;
+    let declared = usize::try_from(len).map_err(|_| MultihashError::Truncated)?;
+    if declared != digest.len() {
+        return Err(MultihashError::LengthMismatch {
+            declared,
+            actual: digest.len(),
+        });
+    }
+    if declared != alg.output_len() {
+        return Err(MultihashError::LengthMismatch {
+            declared,
+            actual: alg.output_len(),
+        });
+    }
+    Ok((alg, digest.to_vec()))
+}
+
+/// Text encodings from the multibase table, identified by a one-character
+/// prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multibase {
+    /// Bitcoin-alphabet base58, prefix `z`.
+    Base58Btc,
+    /// RFC 4648 lowercase base32 without padding, prefix `b`.
+    Base32,
+}
+
+impl Multibase {
+    fn prefix(self) -> char {
+        match self {
+            Multibase::Base58Btc => 'z',
+            Multibase::Base32 => 'b',
+        }
+    }
+
+    /// Encodes `bytes`, including the multibase prefix.
+    pub fn encode(self, bytes: &[u8]) -> String {
+        let mut out = String::new();
+        out.push(self.prefix());
+        match self {
+            Multibase::Base58Btc => base58_encode(bytes, &mut out),
+            Multibase::Base32 => base32_encode(bytes, &mut out),
+        }
+        out
+    }
+
+    /// Decodes a prefixed multibase string, returning the encoding it used.
+    pub fn decode(s: &str) -> Result<(Multibase, Vec<u8>), MultihashError> {
+        let mut chars = s.chars();
+        let (base, bytes) = match chars.next() {
+            Some('z') => (Multibase::Base58Btc, base58_decode(chars.as_str())),
+            Some('b') => (Multibase::Base32, base32_decode(chars.as_str())),
+            _ => (Multibase::Base58Btc, None),
+        };
+        bytes
+            .map(|bytes| (base, bytes))
+            .ok_or(MultihashError::InvalidMultibase)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8]), MultihashError> {
+    let mut n = 0u64;
+    // The multiformats spec caps varints at 9 bytes (63 bits).
+    for (i, &b) in bytes.iter().enumerate().take(9) {
+        n |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            // A last byte of zero adds nothing the bytes before it didn't.
+            if b == 0 && i > 0 {
+                return Err(MultihashError::NonMinimalVarint);
+            }
+            return Ok((n, &bytes[i + 1..]));
+        }
+    }
+    Err(MultihashError::Truncated)
+}
+
+fn base58_encode(bytes: &[u8], out: &mut String) {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Little-endian base58 digits of the non-zero-prefixed tail.
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in &bytes[zeros..] {
+        let mut carry = u32::from(byte);
+        for digit in &mut digits {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    out.extend(core::iter::repeat_n('1', zeros));
+    out.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|&d| BASE58_ALPHABET[d as usize] as char),
+    );
+}
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len());
+    for c in s.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in &mut bytes {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut out = vec![0; zeros];
+    out.extend(bytes.iter().rev());
+    Some(out)
+}
+
+fn base32_encode(bytes: &[u8], out: &mut String) {
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+}
+
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits must be padding, not a partial byte.
+    if bits >= 5 || buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::hash::canonical_sha256;
+
+    #[test]
+    fn sha256_multihash_has_standard_header() {
+        let value = json!({"a": 1});
+        let mh = multihash(HashAlgorithm::Sha256, &value).unwrap();
+        assert_eq!(&mh[..2], &[0x12, 0x20]);
+        assert_eq!(&mh[2..], canonical_sha256(&value).unwrap());
+    }
+
+    #[test]
+    fn parse_round_trips() {
+        for alg in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            let mh = multihash(alg, &json!([1, 2, 3])).unwrap();
+            let (parsed, digest) = parse_multihash(&mh).unwrap();
+            assert_eq!(parsed, alg);
+            assert_eq!(digest, hash_with(alg, &json!([1, 2, 3])).unwrap());
+        }
+    }
+
+    #[cfg(feature = "blake2")]
+    #[test]
+    fn blake2b_code_needs_two_varint_bytes() {
+        let mh = multihash(HashAlgorithm::Blake2b, &json!(null)).unwrap();
+        assert_eq!(&mh[..4], &[0xc0, 0xe4, 0x02, 0x40]);
+        assert_eq!(parse_multihash(&mh).unwrap().0, HashAlgorithm::Blake2b);
+    }
+
+    #[test]
+    fn parse_rejects_length_mismatch() {
+        let mut mh = multihash(HashAlgorithm::Sha256, &json!(1)).unwrap();
+        mh.pop();
+        assert_eq!(
+            parse_multihash(&mh),
+            Err(MultihashError::LengthMismatch {
+                declared: 32,
+                actual: 31
+            })
+        );
+
+        let short = encode_multihash(HashAlgorithm::Sha256, &[0xab; 20]);
+        assert_eq!(
+            parse_multihash(&short),
+            Err(MultihashError::LengthMismatch {
+                declared: 20,
+                actual: 32
+            })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_code_and_truncation() {
+        assert_eq!(
+            parse_multihash(&[0x11, 0x14]),
+            Err(MultihashError::UnknownCode(0x11))
+        );
+        assert_eq!(parse_multihash(&[]), Err(MultihashError::Truncated));
+        assert_eq!(parse_multihash(&[0x12]), Err(MultihashError::Truncated));
+    }
+
+    #[test]
+    fn parse_rejects_non_minimal_varints() {
+        let mh = multihash(HashAlgorithm::Sha256, &json!(1)).unwrap();
+        // 0x92 0x00 also reads as 0x12, and 0xa0 0x00 as 0x20.
+        let mut padded_code = vec![0x92, 0x00];
+        padded_code.extend_from_slice(&mh[1..]);
+        assert_eq!(
+            parse_multihash(&padded_code),
+            Err(MultihashError::NonMinimalVarint)
+        );
+        let mut padded_len = vec![mh[0], 0xa0, 0x00];
+        padded_len.extend_from_slice(&mh[2..]);
+        assert_eq!(
+            parse_multihash(&padded_len),
+            Err(MultihashError::NonMinimalVarint)
+        );
+        // A lone zero byte is the minimal encoding of 0.
+        assert_eq!(
+            parse_multihash(&[0x00, 0x00]),
+            Err(MultihashError::UnknownCode(0))
+        );
+    }
+
+    #[test]
+    fn base58btc_matches_reference_vectors() {
+        assert_eq!(
+            Multibase::Base58Btc.encode(b"hello world"),
+            "zStV1DL6CwTryKyV"
+        );
+        assert_eq!(Multibase::Base58Btc.encode(&[0, 0, 1]), "z112");
+        assert_eq!(
+            Multibase::decode("zStV1DL6CwTryKyV"),
+            Ok((Multibase::Base58Btc, b"hello world".to_vec()))
+        );
+    }
+
+    #[test]
+    fn base32_matches_rfc4648_vectors() {
+        assert_eq!(Multibase::Base32.encode(b"foobar"), "bmzxw6ytboi");
+        assert_eq!(Multibase::Base32.encode(b"f"), "bmy");
+        assert_eq!(
+            Multibase::decode("bmzxw6ytboi"),
+            Ok((Multibase::Base32, b"foobar".to_vec()))
+        );
+    }
+
+    #[test]
+    fn multibase_round_trips_multihash() {
+        let mh = multihash(HashAlgorithm::Sha256, &json!({"k": "v"})).unwrap();
+        for (base, prefix) in [(Multibase::Base58Btc, "zQm"), (Multibase::Base32, "bciq")] {
+            let text = base.encode(&mh);
+            assert!(text.starts_with(prefix), "{text}");
+            assert_eq!(Multibase::decode(&text), Ok((base, mh.clone())));
+        }
+    }
+
+    #[test]
+    fn decode_rejects_bad_input() {
+        assert!(Multibase::decode("").is_err());
+        assert!(Multibase::decode("m0000").is_err());
+        assert!(Multibase::decode("z0OIl").is_err());
+        assert!(Multibase::decode("b1").is_err());
+    }
+}
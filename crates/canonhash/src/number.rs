@@ -0,0 +1,331 @@
+//! Number formatting per ECMAScript `Number.prototype.toString`, which RFC
+//! 8785 mandates for every JSON number.
+//!
+//! Floats are rendered from their shortest round-tripping decimal digits
+//! (taken from `core::fmt`'s `{:e}` output, with exact ties corrected to the
+//! even candidate as ES requires) and then laid out following the ES rules:
+//! plain digits for magnitudes in `[1e-6, 1e21)`, exponent form with an
+//! explicit sign (`1e+21`, `1e-7`) outside it, and no trailing `.0`.
+//!
+//! With the `ryu` feature the digits come from the `ryu` crate instead, which
+//! picks the same closest, ties-to-even candidate directly. Only its digits
+//! and exponent are used; `1e21` and `1.0` are laid out by the same ES rules.
+//!
+//! Integer-typed values (`u64`, `i64`, ...) are written with their exact
+//! decimal digits. Within ±2^53 this is identical to the ES form; beyond it
+//! the digits are preserved rather than rounded to the nearest double.
+
+use core::fmt::Write;
+
+/// A stack buffer holding one formatted number.
+pub(crate) struct Buffer {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Buffer {
+    pub(crate) fn new() -> Self {
+        Buffer {
+            bytes: [0; 32],
+            len: 0,
+        }
+    }
+
+    /// Formats a finite `v`. Negative zero is written as `0`.
+    pub(crate) fn format_finite(&mut self, v: f64) -> &str {
+        debug_assert!(v.is_finite());
+        self.len = 0;
+        if v == 0.0 {
+            self.push(b"0");
+            return self.as_str();
+        }
+        if v < 0.0 {
+            self.push(b"-");
+        }
+
+        let (digits, n) = shortest_digits(v.abs());
+        let mut itoa_buf = itoa::Buffer::new();
+        let digits = itoa_buf.format(digits).as_bytes();
+        // The value is 0.d1d2...dk × 10^n.
+        let k = digits.len() as i32;
+
+        if k <= n && n <= 21 {
+            self.push(digits);
+            for _ in 0..n - k {
+                self.push(b"0");
+            }
+        } else if 0 < n && n <= 21 {
+            let (int, frac) = digits.split_at(n as usize);
+            self.push(int);
+            self.push(b".");
+            self.push(frac);
+        } else if -6 < n && n <= 0 {
+            self.push(b"0.");
+            for _ in 0..-n {
+                self.push(b"0");
+            }
+            self.push(digits);
+        } else {
+            self.push(&digits[..1]);
+            if k > 1 {
+                self.push(b".");
+                self.push(&digits[1..]);
+            }
+            self.push(if n - 1 < 0 { b"e-" } else { b"e+" });
+            self.push(
+                itoa::Buffer::new()
+                    .format((n - 1).unsigned_abs())
+                    .as_bytes(),
+            );
+        }
+        self.as_str()
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).expect("ASCII digits")
+    }
+}
+
+/// Returns the shortest digits `d` and exponent `n` with `v == 0.d × 10^n`,
+/// choosing the closest candidate and, on an exact tie, the even one.
+fn shortest_digits(v: f64) -> (u64, i32) {
+    #[cfg(feature = "ryu")]
+    {
+        ryu_digits(v)
+    }
+    #[cfg(not(feature = "ryu"))]
+    {
+        fmt_digits(v)
+    }
+}
+
+/// [`shortest_digits`] from `ryu`'s output, such as `1.5e21`, `0.0001` or
+/// `12.0`.
+#[cfg(any(feature = "ryu", test))]
+fn ryu_digits(v: f64) -> (u64, i32) {
+    let mut buf = ryu::Buffer::new();
+    let text = buf.format_finite(v);
+    let (mantissa, exp) = match text.split_once('e') {
+        Some((mantissa, exp)) => (mantissa, exp.parse().expect("integer exponent")),
+        None => (text, 0),
+    };
+    let int_len = mantissa.find('.').unwrap_or(mantissa.len()) as i32;
+    let mut d = 0u64;
+    let mut leading_zeros = 0;
+    for b in mantissa.bytes().filter(|&b| b != b'.') {
+        if d == 0 && b == b'0' {
+            leading_zeros += 1;
+        }
+        d = d * 10 + u64::from(b - b'0');
+    }
+    while d.is_multiple_of(10) {
+        d /= 10;
+    }
+    (d, int_len - leading_zeros + exp)
+}
+
+/// [`shortest_digits`] from `core::fmt`, corrected to ES tie-breaking.
+#[cfg(any(not(feature = "ryu"), test))]
+fn fmt_digits(v: f64) -> (u64, i32) {
+    // `{:e}` yields the shortest digits that round-trip, e.g. "1.2345e-7".
+    let mut sci = Buffer::new();
+    write!(sci, "{v:e}").expect("float fits in buffer");
+    let sci = sci.as_str();
+    let (mantissa, exp) = sci.split_once('e').expect("exponent present");
+    let exp: i32 = exp.parse().expect("integer exponent");
+    let mut d = 0u64;
+    let mut k = 0;
+    for b in mantissa.bytes().filter(|&b| b != b'.') {
+        d = d * 10 + u64::from(b - b'0');
+        k += 1;
+    }
+    let mut n = exp + 1;
+
+    // `core::fmt` breaks exact ties upward, ES breaks them to even. A tie is
+    // only possible when `v` is exactly the midpoint between `d` and one of its
+    // neighbours, so check both midpoints exactly when `d` is odd.
+    if d % 2 == 1 {
+        let (m, e) = decompose(v);
+        let t = n - k - 1;
+        if d > 1 && is_exactly(m, e, 10 * d - 5, t) {
+            d -= 1;
+        } else if is_exactly(m, e, 10 * d + 5, t) {
+            d += 1;
+            if d == 10u64.pow(k as u32) {
+                n += 1;
+            }
+        }
+        while d.is_multiple_of(10) {
+            d /= 10;
+        }
+    }
+    (d, n)
+}
+
+/// Splits a positive finite `v` into `m × 2^e` with `m` odd.
+#[cfg(any(not(feature = "ryu"), test))]
+fn decompose(v: f64) -> (u64, i32) {
+    let bits = v.to_bits();
+    let fraction = bits & ((1 << 52) - 1);
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let (m, e) = if biased == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), biased - 1075)
+    };
+    let tz = m.trailing_zeros();
+    (m >> tz, e + tz as i32)
+}
+
+/// Returns whether `m × 2^e` (with `m` odd) equals `c × 10^t` exactly.
+#[cfg(any(not(feature = "ryu"), test))]
+fn is_exactly(m: u64, e: i32, c: u64, t: i32) -> bool {
+    let a = c.trailing_zeros();
+    let odd = c >> a;
+    // c × 10^t = odd × 5^t × 2^(a + t): compare odd parts and powers of two.
+    if e != a as i32 + t {
+        return false;
+    }
+    let Some(pow5) = 5u64.checked_pow(t.unsigned_abs()) else {
+        return false;
+    };
+    if t >= 0 {
+        odd.checked_mul(pow5) == Some(m)
+    } else {
+        m.checked_mul(pow5) == Some(odd)
+    }
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.len + s.len() > self.bytes.len() {
+            return Err(core::fmt::Error);
+        }
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(v: f64) -> String {
+        Buffer::new().format_finite(v).to_owned()
+    }
+
+    #[test]
+    fn integral_floats_have_no_fraction() {
+        assert_eq!(format(1.0), "1");
+        assert_eq!(format(100.0), "100");
+        assert_eq!(format(-42.0), "-42");
+        assert_eq!(format(1e20), "100000000000000000000");
+    }
+
+    #[test]
+    fn exponent_thresholds_follow_es() {
+        assert_eq!(format(1e21), "1e+21");
+        assert_eq!(format(1.5e21), "1.5e+21");
+        assert_eq!(format(0.000001), "0.000001");
+        assert_eq!(format(1e-7), "1e-7");
+        assert_eq!(format(1.25e-7), "1.25e-7");
+        assert_eq!(format(123e-20), "1.23e-18");
+        assert_eq!(format(1e30), "1e+30");
+    }
+
+    #[test]
+    fn fractions_use_shortest_digits() {
+        assert_eq!(format(0.1), "0.1");
+        assert_eq!(format(4.5), "4.5");
+        assert_eq!(format(0.002), "0.002");
+        assert_eq!(format(2.0 / 3.0), "0.6666666666666666");
+        assert_eq!(format(-1.5), "-1.5");
+    }
+
+    #[test]
+    fn negative_zero_is_zero() {
+        assert_eq!(format(-0.0), "0");
+        assert_eq!(format(0.0), "0");
+    }
+
+    #[test]
+    fn beyond_safe_integer_rounds_to_double() {
+        #[allow(clippy::excessive_precision)]
+        let v = 9007199254740993.0;
+        assert_eq!(format(v), "9007199254740992");
+    }
+
+    #[test]
+    fn extremes() {
+        assert_eq!(format(5e-324), "5e-324");
+        assert_eq!(format(-5e-324), "-5e-324");
+        assert_eq!(format(f64::MAX), "1.7976931348623157e+308");
+        assert_eq!(format(f64::MIN), "-1.7976931348623157e+308");
+    }
+
+    #[test]
+    fn ties_round_to_even() {
+        // 1424953923781206.25 is exactly halfway between two 17-digit
+        // candidates; ES picks the even one.
+        assert_eq!(
+            format(f64::from_bits(0x43143ff3c1cb0959)),
+            "1424953923781206.2"
+        );
+        assert_eq!(format(0.5), "0.5");
+    }
+
+    #[test]
+    fn digits_match_ryu_on_random_doubles() {
+        // ryu implements the same closest-shortest, ties-to-even digit
+        // selection, so both backends must agree on the digits and exponent.
+        let mut state = 0x9e3779b97f4a7c15u64;
+        for _ in 0..200_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let v = f64::from_bits(state).abs();
+            if !v.is_finite() || v == 0.0 {
+                continue;
+            }
+            assert_eq!(fmt_digits(v), ryu_digits(v), "{:#018x}", v.to_bits());
+        }
+    }
+
+    #[test]
+    fn rfc8785_appendix_b_vectors() {
+        let vectors: &[(u64, &str)] = &[
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for &(bits, expected) in vectors {
+            assert_eq!(format(f64::from_bits(bits)), expected, "{bits:#018x}");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Hashing a subtree of a document selected by a JSON Pointer (RFC 6901).
+//!
+//! The whole value is canonicalized once and the pointer is then followed
+//! through the canonical text. Every subtree of a canonical document is
+//! itself canonical, so the selected bytes are hashed as they are. Unlike
+//! going through `serde_json::to_value`, this keeps non-finite floats an
+//! error and integers beyond 64 bits exact.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+use serde_json::value::RawValue;
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+use crate::hash::digest_bytes;
+use crate::ser::object_members;
+
+/// Returns the canonical form of the part of `value` at `pointer`, or `None`
+/// if the pointer doesn't resolve.
+///
+/// The empty pointer selects the whole value. A pointer that is not valid
+/// RFC 6901 syntax is [`CanonError::InvalidPointer`].
+pub fn canonical_at<T>(value: &T, pointer: &str) -> Result<Option<Vec<u8>>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = canonicalize(value)?;
+    Ok(select(&canonical, pointer)?.map(|raw| raw.as_bytes().to_vec()))
+}
+
+/// Returns the digest under `alg` of the canonical form of the part of
+/// `value` at `pointer`, or `None` if the pointer doesn't resolve.
+///
+/// ```
+/// use canonhash::{hash_at, hash_with, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let doc = json!({"data": {"nested": {"object": {"b": 1, "a": 2}}}});
+/// let digest = hash_at(&doc, "/data/nested/object", HashAlgorithm::Sha256).unwrap();
+/// let expected = hash_with(HashAlgorithm::Sha256, &json!({"a": 2, "b": 1})).unwrap();
+/// assert_eq!(digest, Some(expected));
+/// ```
+pub fn hash_at<T>(
+    value: &T,
+    pointer: &str,
+    alg: HashAlgorithm,
+) -> Result<Option<Vec<u8>>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = canonicalize(value)?;
+    Ok(select(&canonical, pointer)?.map(|raw| digest_bytes(alg, raw.as_bytes())))
+}
+
+/// Follows `pointer` through canonical JSON text.
+fn select<'a>(canonical: &'a [u8], pointer: &str) -> Result<Option<&'a str>, CanonError> {
+    let tokens = pointer_tokens(pointer)?;
+    let text = core::str::from_utf8(canonical).map_err(|_| CanonError::InvalidUtf8)?;
+
+    let mut current: &'a str = text;
+    for token in tokens {
+        let next = match current.as_bytes().first() {
+            Some(b'{') => object_members(current)?
+                .into_iter()
+                .find(|(key, _)| *key == token)
+                .map(|(_, raw)| raw),
+            Some(b'[') => match array_index(&token) {
+                Some(index) => {
+                    let elements: Vec<&'a RawValue> = serde_json::from_str(current)?;
+                    elements.get(index).copied()
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        match next {
+            Some(raw) => current = raw.get(),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+/// Splits `pointer` into its reference tokens, with `~1` decoded to `/` and
+/// `~0` to `~`. The empty pointer has none. A pointer that is not valid
+/// RFC 6901 syntax is [`CanonError::InvalidPointer`].
+pub(crate) fn pointer_tokens(pointer: &str) -> Result<Vec<String>, CanonError> {
+    match pointer.strip_prefix('/') {
+        None if pointer.is_empty() => Ok(Vec::new()),
+        Some(rest) => rest
+            .split('/')
+            .map(unescape)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| CanonError::InvalidPointer(pointer.to_owned())),
+        None => Err(CanonError::InvalidPointer(pointer.to_owned())),
+    }
+}
+
+/// Decodes `~1` to `/` and `~0` to `~`. Any other `~` is invalid.
+fn unescape(token: &str) -> Option<String> {
+    if !token.contains('~') {
+        return Some(token.to_owned());
+    }
+    let mut out = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' => match chars.next() {
+                Some('0') => out.push('~'),
+                Some('1') => out.push('/'),
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// Parses an RFC 6901 array index: `0` or digits without a leading zero.
+/// `-` (past the end) never resolves.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    let digits_only = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if !digits_only || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::hash::hash_with;
+
+    #[derive(Serialize)]
+    struct Nested {
+        array: Vec<u32>,
+        object: HashMap<String, String>,
+    }
+
+    #[derive(Serialize)]
+    struct DataContent {
+        modules: Vec<&'static str>,
+        nested: Nested,
+    }
+
+    fn data() -> DataContent {
+        DataContent {
+            modules: vec!["marketIntelligence", "notifications"],
+            nested: Nested {
+                array: vec![3, 1, 2],
+                object: HashMap::from([
+                    ("zeta".to_owned(), "last".to_owned()),
+                    ("alpha".to_owned(), "first".to_owned()),
+                ]),
+            },
+        }
+    }
+
+    #[test]
+    fn hashes_only_the_selected_subtree() {
+        let data = data();
+        let expected = hash_with(HashAlgorithm::Sha256, &data.nested.object).unwrap();
+        assert_eq!(
+            hash_at(&data, "/nested/object", HashAlgorithm::Sha256).unwrap(),
+            Some(expected)
+        );
+        assert_eq!(
+            canonical_at(&data, "/nested/object").unwrap().unwrap(),
+            br#"{"alpha":"first","zeta":"last"}"#
+        );
+    }
+
+    #[test]
+    fn empty_pointer_selects_the_whole_value() {
+        let data = data();
+        assert_eq!(
+            hash_at(&data, "", HashAlgorithm::Sha512).unwrap(),
+            Some(hash_with(HashAlgorithm::Sha512, &data).unwrap())
+        );
+    }
+
+    #[test]
+    fn indexes_arrays() {
+        let data = data();
+        assert_eq!(
+            canonical_at(&data, "/nested/array/2").unwrap().unwrap(),
+            b"2"
+        );
+        assert_eq!(
+            canonical_at(&data, "/modules/0").unwrap().unwrap(),
+            br#""marketIntelligence""#
+        );
+        for missing in ["/nested/array/3", "/nested/array/01", "/nested/array/-"] {
+            assert_eq!(canonical_at(&data, missing).unwrap(), None, "{missing}");
+        }
+    }
+
+    #[test]
+    fn unresolved_pointers_are_none() {
+        let data = data();
+        for missing in [
+            "/absent",
+            "/nested/array/0/deeper",
+            "/modules/x",
+            "/nested/",
+        ] {
+            assert_eq!(
+                hash_at(&data, missing, HashAlgorithm::Sha256).unwrap(),
+                None,
+                "{missing}"
+            );
+        }
+    }
+
+    #[test]
+    fn unescapes_tokens() {
+        let doc = json!({"a/b": {"~": 1}, "": {"": 2}});
+        assert_eq!(canonical_at(&doc, "/a~1b/~0").unwrap().unwrap(), b"1");
+        assert_eq!(canonical_at(&doc, "//").unwrap().unwrap(), b"2");
+        assert_eq!(canonical_at(&doc, "/a~1b").unwrap().unwrap(), br#"{"~":1}"#);
+    }
+
+    #[test]
+    fn malformed_pointers_are_errors() {
+        for pointer in ["a", "/x~2", "/x~"] {
+            assert!(
+                matches!(
+                    canonical_at(&json!({}), pointer),
+                    Err(CanonError::InvalidPointer(ref p)) if p == pointer
+                ),
+                "{pointer}"
+            );
+        }
+        // Projection and redaction read pointers the same way.
+        for pointer in ["/x~2", "/x~"] {
+            let errors = [
+                crate::hash_excluding(&json!({}), &[pointer], HashAlgorithm::Sha256).unwrap_err(),
+                crate::commit_with_redactions(&json!({}), &[pointer], b"salt").unwrap_err(),
+            ];
+            for err in errors {
+                assert!(
+                    matches!(err, CanonError::InvalidPointer(ref p) if p == pointer),
+                    "{pointer}: {err}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn serialization_errors_still_surface() {
+        let doc = json!({"ok": 1});
+        assert!(canonical_at(&doc, "/ok").unwrap().is_some());
+        assert!(matches!(
+            hash_at(&[1.0, f64::NAN], "/0", HashAlgorithm::Sha256),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+}
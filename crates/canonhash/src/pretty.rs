@@ -0,0 +1,155 @@
+//! Indented display of canonical JSON that leaves every token as it is.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Indents canonical JSON for reading, without changing anything else.
+///
+/// Unlike parsing and re-serializing with `serde_json::to_string_pretty`,
+/// this only inserts whitespace between tokens: keys stay in canonical
+/// order and numbers and strings keep their exact text, so what is read is
+/// what was hashed. Members and elements go on their own lines, indented by
+/// two spaces a level, with a space after each `:`. Empty arrays and objects
+/// stay `[]` and `{}`. Removing that whitespace again gives back the input.
+///
+/// The input is not checked. Canonical output is always valid UTF-8; any
+/// invalid sequences in other input are replaced with U+FFFD.
+///
+/// ```
+/// let canonical = canonhash::canonicalize(&serde_json::json!({"b": [1e21], "a": {}})).unwrap();
+/// assert_eq!(
+///     canonhash::pretty_from_canonical(&canonical),
+///     "{\n  \"a\": {},\n  \"b\": [\n    1e+21\n  ]\n}"
+/// );
+/// ```
+pub fn pretty_from_canonical(canonical: &[u8]) -> String {
+    let mut out = Vec::with_capacity(canonical.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut bytes = canonical.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        if in_string {
+            out.push(b);
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match b {
+            b'"' => {
+                in_string = true;
+                out.push(b);
+            }
+            b'{' | b'[' => {
+                out.push(b);
+                let close = if b == b'{' { b'}' } else { b']' };
+                if bytes.peek() == Some(&close) {
+                    out.push(close);
+                    bytes.next();
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(b);
+            }
+            b',' => {
+                out.push(b);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            _ => out.push(b),
+        }
+    }
+    match String::from_utf8(out) {
+        Ok(pretty) => pretty,
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+    }
+}
+
+fn newline(out: &mut Vec<u8>, depth: usize) {
+    out.push(b'\n');
+    out.resize(out.len() + 2 * depth, b' ');
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    /// Removes whitespace outside strings, which canonical JSON never has.
+    fn strip(pretty: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        let (mut in_string, mut escaped) = (false, false);
+        for b in pretty.bytes() {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    in_string = false;
+                }
+            } else if b == b'"' {
+                in_string = true;
+            } else if b.is_ascii_whitespace() {
+                continue;
+            }
+            out.push(b);
+        }
+        out
+    }
+
+    #[test]
+    fn removing_the_indentation_restores_the_canonical_form() {
+        let values = [
+            json!({
+                "z": [1.5, -0.0, 1e-7, 123456789012345680000.0, [], {}, [[]]],
+                "a": {"nested": {"k": null, "j": [true, false]}},
+                "tricky": "\"{[,:]}\" \\\" \n\t\u{1}",
+                "\\": "\\",
+                "\u{1f600}": " spaced  out ",
+            }),
+            json!([]),
+            json!("just a string, with: punctuation"),
+            json!(42),
+        ];
+        for value in &values {
+            let canonical = canonicalize(value).unwrap();
+            let pretty = pretty_from_canonical(&canonical);
+            assert_eq!(strip(&pretty), canonical, "{pretty}");
+        }
+    }
+
+    #[test]
+    fn layout_matches_two_space_indentation() {
+        let canonical = canonicalize(&json!({"b": [1, {"c": "x,y"}], "a": []})).unwrap();
+        assert_eq!(
+            pretty_from_canonical(&canonical),
+            "{\n  \"a\": [],\n  \"b\": [\n    1,\n    {\n      \"c\": \"x,y\"\n    }\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn numbers_keep_their_canonical_text() {
+        let canonical = canonicalize(&json!([1e21, 0.000001, 1e-7, 5e-324])).unwrap();
+        let pretty = pretty_from_canonical(&canonical);
+        for token in ["1e+21", "0.000001", "1e-7", "5e-324"] {
+            assert!(pretty.contains(token), "{pretty}");
+        }
+        assert_ne!(
+            pretty,
+            serde_json::to_string_pretty(&json!([1e21, 0.000001, 1e-7, 5e-324])).unwrap()
+        );
+    }
+}
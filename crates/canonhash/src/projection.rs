@@ -0,0 +1,413 @@
+//! Hashing a fixed subset of a record's fields, so that records from
+//! different schema versions hash the same over the fields they share, or
+//! every field but a few, so that records differing only in volatile fields
+//! such as timestamps hash the same.
+//!
+//! As with [`pointer`](crate::pointer), the whole value is canonicalized once
+//! and the members that stay are then copied out of the canonical text, so
+//! every remaining subtree keeps exactly its canonical bytes.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+use crate::hash::digest_bytes;
+use crate::pointer::pointer_tokens;
+use crate::ser::{object_members, write_object};
+
+/// Returns the digest under `alg` of the canonical form of the projection of
+/// `value` onto `include`.
+///
+/// ```
+/// use canonhash::{hash_projection, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let v1 = json!({"id": 7, "body": "hi"});
+/// let v2 = json!({"id": 7, "body": "hi", "metadata": {"source": "import"}});
+/// let stable = ["id", "body"];
+/// assert_eq!(
+///     hash_projection(&v1, &stable, HashAlgorithm::Sha256).unwrap(),
+///     hash_projection(&v2, &stable, HashAlgorithm::Sha256).unwrap()
+/// );
+/// ```
+pub fn hash_projection<T>(
+    value: &T,
+    include: &[&str],
+    alg: HashAlgorithm,
+) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(digest_bytes(alg, &canonical_projection(value, include)?))
+}
+
+/// Returns the canonical form of `value` with every member removed except
+/// those named by `include`.
+///
+/// An entry that starts with `/` is a JSON Pointer (RFC 6901) and keeps that
+/// member, along with the objects leading to it. Any other entry is the name
+/// of a top-level member. Pointers descend through objects only, and entries
+/// that don't resolve are skipped.
+///
+/// `value` must serialize to an object, or the result is
+/// [`CanonError::NotAnObject`]. If `include` is empty or none of its entries
+/// are present the result is [`CanonError::EmptyProjection`] rather than the
+/// canonical form of `{}`, which every record would share.
+pub fn canonical_projection<T>(value: &T, include: &[&str]) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = canonicalize(value)?;
+    if canonical.first() != Some(&b'{') {
+        return Err(CanonError::NotAnObject);
+    }
+    let text = core::str::from_utf8(&canonical).map_err(|_| CanonError::InvalidUtf8)?;
+
+    let mut kept = BTreeMap::new();
+    for entry in include {
+        let tokens = entry_tokens(entry)?;
+        if let Some(selected) = resolve(text, &tokens)? {
+            keep(&mut kept, &tokens, selected);
+        }
+    }
+    if kept.is_empty() {
+        return Err(CanonError::EmptyProjection);
+    }
+
+    let mut out = Vec::with_capacity(canonical.len());
+    write_members(&mut out, &kept)?;
+    Ok(out)
+}
+
+/// Returns the digest under `alg` of the canonical form of `value` without
+/// the members named by `exclude`.
+///
+/// ```
+/// use canonhash::{hash_excluding, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let first = json!({"id": 7, "updated_at": "2024-05-01T12:00:00Z"});
+/// let again = json!({"id": 7, "updated_at": "2024-05-02T08:30:00Z"});
+/// assert_eq!(
+///     hash_excluding(&first, &["updated_at"], HashAlgorithm::Sha256).unwrap(),
+///     hash_excluding(&again, &["updated_at"], HashAlgorithm::Sha256).unwrap()
+/// );
+/// ```
+pub fn hash_excluding<T>(
+    value: &T,
+    exclude: &[&str],
+    alg: HashAlgorithm,
+) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(digest_bytes(alg, &canonical_excluding(value, exclude)?))
+}
+
+/// Returns the canonical form of `value` with the members named by
+/// `exclude` removed, the inverse of [`canonical_projection`].
+///
+/// Entries are written as for [`canonical_projection`]: a JSON Pointer
+/// starting with `/`, or the name of a top-level member. Pointers descend
+/// through objects only. Entries that don't resolve, including every entry
+/// when `value` is not an object, remove nothing. Excluding every member
+/// leaves `{}`.
+pub fn canonical_excluding<T>(value: &T, exclude: &[&str]) -> Result<Vec<u8>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = canonicalize(value)?;
+    let mut excluded = BTreeMap::new();
+    for entry in exclude {
+        drop_member(&mut excluded, &entry_tokens(entry)?);
+    }
+    if excluded.is_empty() {
+        return Ok(canonical);
+    }
+    let text = core::str::from_utf8(&canonical).map_err(|_| CanonError::InvalidUtf8)?;
+    let mut out = Vec::with_capacity(canonical.len());
+    write_excluding(&mut out, text, &excluded)?;
+    Ok(out)
+}
+
+/// Splits an `include` or `exclude` entry into unescaped member names.
+fn entry_tokens(entry: &str) -> Result<Vec<String>, CanonError> {
+    if entry.starts_with('/') {
+        pointer_tokens(entry)
+    } else {
+        Ok(alloc::vec![entry.to_owned()])
+    }
+}
+
+/// A member left out by [`canonical_excluding`]: entirely, or only some of
+/// the members below it.
+enum Excluded {
+    Whole,
+    Members(BTreeMap<String, Excluded>),
+}
+
+/// Adds the member at `tokens` to `excluded`. A member excluded whole
+/// already takes everything below it.
+fn drop_member(excluded: &mut BTreeMap<String, Excluded>, tokens: &[String]) {
+    let Some((first, rest)) = tokens.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        excluded.insert(first.clone(), Excluded::Whole);
+        return;
+    }
+    let entry = excluded
+        .entry(first.clone())
+        .or_insert_with(|| Excluded::Members(BTreeMap::new()));
+    if let Excluded::Members(members) = entry {
+        drop_member(members, rest);
+    }
+}
+
+/// Copies the canonical `text` to `out`, leaving out the members in
+/// `excluded` wherever `text` is an object.
+fn write_excluding(
+    out: &mut Vec<u8>,
+    text: &str,
+    excluded: &BTreeMap<String, Excluded>,
+) -> Result<(), CanonError> {
+    if !text.starts_with('{') {
+        out.extend_from_slice(text.as_bytes());
+        return Ok(());
+    }
+    let members = object_members(text)?
+        .into_iter()
+        .filter_map(|(key, raw)| {
+            let within = match excluded.get(&key) {
+                Some(Excluded::Whole) => return None,
+                Some(Excluded::Members(within)) => Some(within),
+                None => None,
+            };
+            Some((key, (raw, within)))
+        })
+        .collect();
+    write_object(out, members, |out, (raw, within)| match within {
+        Some(within) => write_excluding(out, raw.get(), within),
+        None => {
+            out.extend_from_slice(raw.get().as_bytes());
+            Ok(())
+        }
+    })
+}
+
+/// A member of the projection: a whole subtree, or an object of which only
+/// some members are kept.
+enum Kept<'a> {
+    Whole(&'a str),
+    Members(BTreeMap<String, Kept<'a>>),
+}
+
+/// Follows `tokens` through objects in canonical text.
+fn resolve<'a>(text: &'a str, tokens: &[String]) -> Result<Option<&'a str>, CanonError> {
+    let mut current = text;
+    for token in tokens {
+        if !current.starts_with('{') {
+            return Ok(None);
+        }
+        match object_members(current)?
+            .into_iter()
+            .find(|(key, _)| key == token)
+        {
+            Some((_, raw)) => current = raw.get(),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+/// Adds the subtree at `tokens` to `kept`. A member kept whole already holds
+/// everything below it.
+fn keep<'a>(kept: &mut BTreeMap<String, Kept<'a>>, tokens: &[String], selected: &'a str) {
+    let Some((first, rest)) = tokens.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        kept.insert(first.clone(), Kept::Whole(selected));
+        return;
+    }
+    let entry = kept
+        .entry(first.clone())
+        .or_insert_with(|| Kept::Members(BTreeMap::new()));
+    if let Kept::Members(members) = entry {
+        keep(members, rest, selected);
+    }
+}
+
+fn write_members(out: &mut Vec<u8>, kept: &BTreeMap<String, Kept<'_>>) -> Result<(), CanonError> {
+    write_object(out, kept.iter().collect(), |out, member| match member {
+        Kept::Whole(text) => {
+            out.extend_from_slice(text.as_bytes());
+            Ok(())
+        }
+        Kept::Members(members) => write_members(out, members),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn project(value: &serde_json::Value, include: &[&str]) -> String {
+        String::from_utf8(canonical_projection(value, include).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn new_fields_outside_the_projection_do_not_change_the_hash() {
+        let old = json!({"id": 1, "name": "a"});
+        let new = json!({"id": 1, "name": "a", "metadata": {"v": 2}});
+        let include = ["id", "name"];
+        assert_eq!(
+            hash_projection(&old, &include, HashAlgorithm::Sha256).unwrap(),
+            hash_projection(&new, &include, HashAlgorithm::Sha256).unwrap()
+        );
+        assert_eq!(
+            hash_projection(&old, &include, HashAlgorithm::Sha256).unwrap(),
+            crate::hash_with(HashAlgorithm::Sha256, &old).unwrap()
+        );
+    }
+
+    #[test]
+    fn pointers_keep_nested_members_and_their_parents() {
+        let value = json!({
+            "data": {"nested": {"object": {"b": 1, "a": 2}, "array": [3, 1]}, "x": 0},
+            "other": true
+        });
+        assert_eq!(
+            project(&value, &["/data/nested/object", "other"]),
+            r#"{"data":{"nested":{"object":{"a":2,"b":1}}},"other":true}"#
+        );
+        // A whole member already contains anything below it.
+        assert_eq!(
+            project(&value, &["/data/nested/array", "/data"]),
+            project(&value, &["data"])
+        );
+        assert_eq!(
+            project(&value, &["/data", "/data/x"]),
+            project(&value, &["data"])
+        );
+    }
+
+    #[test]
+    fn missing_entries_are_skipped() {
+        let value = json!({"a": {"b": [1, {"c": 2}]}, "k": 1});
+        assert_eq!(
+            project(&value, &["k", "absent", "/a/zzz", "/a/b/1/c", "/k/deeper"]),
+            r#"{"k":1}"#
+        );
+    }
+
+    #[test]
+    fn keys_are_sorted_by_utf16_and_unescaped() {
+        let value = json!({"\u{e000}": 1, "\u{1f600}": 2, "a/b": 3, "z": 4});
+        assert_eq!(
+            project(&value, &["\u{e000}", "/\u{1f600}", "/a~1b"]),
+            "{\"a/b\":3,\"\u{1f600}\":2,\"\u{e000}\":1}"
+        );
+    }
+
+    #[test]
+    fn empty_projections_are_errors() {
+        let value = json!({"a": 1});
+        for include in [&[][..], &["b"], &["/a/b"]] {
+            assert!(matches!(
+                hash_projection(&value, include, HashAlgorithm::Sha256),
+                Err(CanonError::EmptyProjection)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_non_objects_and_bad_pointers() {
+        assert!(matches!(
+            canonical_projection(&json!([1]), &["a"]),
+            Err(CanonError::NotAnObject)
+        ));
+        assert!(matches!(
+            canonical_projection(&json!({"a": 1}), &["/a~2"]),
+            Err(CanonError::InvalidPointer(ref p)) if p == "/a~2"
+        ));
+    }
+
+    fn excluding(value: &serde_json::Value, exclude: &[&str]) -> String {
+        String::from_utf8(canonical_excluding(value, exclude).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn records_differing_only_in_excluded_fields_hash_equal() {
+        #[derive(Serialize)]
+        struct TestData {
+            #[serde(rename = "testId")]
+            test_id: &'static str,
+            timestamp: &'static str,
+            data: serde_json::Value,
+        }
+
+        let record = |timestamp| TestData {
+            test_id: "golden-hash-test-v1",
+            timestamp,
+            data: json!({"modules": ["automation"], "nested": {"array": [1, 2]}}),
+        };
+        let (first, later) = (
+            record("2026-02-12T15:50:12Z"),
+            record("2026-02-13T09:00:00Z"),
+        );
+        let hash = |r: &TestData| hash_excluding(r, &["/timestamp"], HashAlgorithm::Sha256);
+        assert_eq!(hash(&first).unwrap(), hash(&later).unwrap());
+        assert_ne!(
+            crate::hash_with(HashAlgorithm::Sha256, &first).unwrap(),
+            crate::hash_with(HashAlgorithm::Sha256, &later).unwrap()
+        );
+        assert_eq!(
+            canonical_excluding(&first, &["timestamp", "/data/nested"]).unwrap(),
+            br#"{"data":{"modules":["automation"]},"testId":"golden-hash-test-v1"}"#
+        );
+    }
+
+    #[test]
+    fn missing_exclusions_change_nothing() {
+        let value = json!({"a": {"b": [1, {"c": 2}]}, "\u{e000}": 0, "\u{1f600}": 1});
+        let canonical = String::from_utf8(canonicalize(&value).unwrap()).unwrap();
+        assert_eq!(excluding(&value, &[]), canonical);
+        assert_eq!(
+            excluding(&value, &["absent", "/a/zzz", "/a/b/1/c", "/a/b/c/d"]),
+            canonical
+        );
+        assert_eq!(
+            excluding(&json!([1, {"a": 2}]), &["a", "/1/a"]),
+            r#"[1,{"a":2}]"#
+        );
+    }
+
+    #[test]
+    fn exclusions_nest_and_keep_canonical_order() {
+        let value = json!({
+            "\u{e000}": 1, "\u{1f600}": {"x": 1, "y": 2}, "a/b": 3, "z": {"k": null}
+        });
+        assert_eq!(
+            excluding(&value, &["/\u{1f600}/y", "/a~1b", "/z/k"]),
+            "{\"z\":{},\"\u{1f600}\":{\"x\":1},\"\u{e000}\":1}"
+        );
+        assert_eq!(
+            excluding(&value, &["/\u{1f600}/y", "\u{1f600}"]),
+            excluding(&value, &["\u{1f600}"])
+        );
+        assert_eq!(excluding(&json!({"a": 1}), &["a"]), "{}");
+        assert!(matches!(
+            canonical_excluding(&value, &["/a~2"]),
+            Err(CanonError::InvalidPointer(ref p)) if p == "/a~2"
+        ));
+    }
+}
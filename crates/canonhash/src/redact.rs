@@ -0,0 +1,344 @@
+//! Selective disclosure: a document hashed with some fields replaced by
+//! commitments, which can be opened one at a time later.
+//!
+//! # Commitment contract
+//!
+//! Each redacted value is replaced by the object `{"__commit":"<hex>"}`,
+//! where `<hex>` is the lowercase hex HMAC-SHA256 of the value's canonical
+//! form, keyed by that field's salt. The field's salt is the HMAC-SHA256,
+//! keyed by the caller's salt, of the field's JSON Pointer as a canonical
+//! JSON string. The redacted document is then canonicalized as usual.
+//!
+//! Giving someone a field's [`Commitment`] (its value and field salt) lets
+//! them recompute the placeholder and check it against the document,
+//! without learning the caller's salt. The salt should be at least 16 random
+//! bytes per document and kept secret: anyone holding it can derive every
+//! field's salt and guess at low-entropy values.
+//!
+//! Everything outside the redacted fields is copied from the canonical form
+//! as it is, so an integer too large for a `serde_json::Value` survives
+//! unchanged. A redacted value has to fit in the [`Commitment`]'s `Value`;
+//! one that doesn't, without `serde_json`'s `arbitrary_precision`, is an
+//! error.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::ser::Error as _;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+
+use crate::canon::{canonicalize, to_string};
+use crate::error::CanonError;
+use crate::keyed::hmac_sha256;
+use crate::pointer::{array_index, pointer_tokens};
+use crate::ser::{object_members, write_object};
+
+/// The member of the object that stands in for a redacted value.
+pub const COMMIT_KEY: &str = "__commit";
+
+/// The opening of one redacted field: what it held and the salt its
+/// commitment was made with.
+///
+/// Serializes as `{"salt":"<base64url>","value":…}`, so it can be handed to
+/// whoever the field is revealed to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commitment {
+    /// The redacted value.
+    pub value: Value,
+    /// This field's salt, derived from the caller's.
+    #[serde(with = "crate::canon_bytes")]
+    pub salt: Vec<u8>,
+}
+
+impl Commitment {
+    /// Recomputes the commitment to [`value`](Self::value) under
+    /// [`salt`](Self::salt).
+    pub fn digest(&self) -> Result<[u8; 32], CanonError> {
+        hmac_sha256(&self.salt, &self.value)
+    }
+
+    /// Returns the `{"__commit":"<hex>"}` object that replaced the value in
+    /// the document.
+    pub fn placeholder(&self) -> Result<Value, CanonError> {
+        let mut object = Map::new();
+        object.insert(COMMIT_KEY.to_owned(), hex::encode(self.digest()?).into());
+        Ok(Value::Object(object))
+    }
+}
+
+/// Canonicalizes `value` with the fields at the JSON Pointers in `redact`
+/// replaced by commitments, and returns the result with the opening of each
+/// field keyed by its pointer. See the [module docs](self) for the format.
+///
+/// Pointers go through objects and arrays as RFC 6901 describes, and `""`
+/// redacts the whole value. A pointer that doesn't resolve redacts nothing
+/// and has no opening. When one redacted field contains another, the inner
+/// one is committed first, so opening the outer field reveals only the
+/// inner field's placeholder.
+///
+/// ```
+/// use canonhash::commit_with_redactions;
+/// use serde_json::json;
+///
+/// let receipt = json!({"amount": 5, "card": {"number": "4111", "holder": "A. Lovelace"}});
+/// let (canonical, openings) =
+///     commit_with_redactions(&receipt, &["/card/number"], b"per-receipt random salt").unwrap();
+///
+/// // The verifier later receives the opening and checks it against the
+/// // document.
+/// let opening = &openings["/card/number"];
+/// assert_eq!(opening.value, "4111");
+/// let document: serde_json::Value = serde_json::from_slice(&canonical).unwrap();
+/// assert_eq!(document.pointer("/card/number"), Some(&opening.placeholder().unwrap()));
+/// ```
+pub fn commit_with_redactions<T>(
+    value: &T,
+    redact: &[&str],
+    salt: &[u8],
+) -> Result<(Vec<u8>, BTreeMap<String, Commitment>), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut paths = redact
+        .iter()
+        .map(|pointer| Ok((*pointer, pointer_tokens(pointer)?)))
+        .collect::<Result<Vec<_>, CanonError>>()?;
+    // Deepest first, so that a field is committed before one containing it.
+    paths.sort_by_key(|(_, tokens)| core::cmp::Reverse(tokens.len()));
+
+    let mut document = to_string(value)?;
+    let mut openings = BTreeMap::new();
+    for (pointer, tokens) in paths {
+        if openings.contains_key(pointer) {
+            continue;
+        }
+        let mut opening = None;
+        let redacted = replace_at(&document, &tokens, &mut |field| {
+            let value: Value = serde_json::from_str(field)?;
+            if canonicalize(&value)? != field.as_bytes() {
+                return Err(CanonError::custom(
+                    "a serde_json::Value can't hold this value exactly without \
+                     `arbitrary_precision`, so it can't be redacted",
+                ));
+            }
+            let commitment = Commitment {
+                value,
+                salt: hmac_sha256(salt, pointer)?.to_vec(),
+            };
+            let placeholder = to_string(&commitment.placeholder()?)?;
+            opening = Some(commitment);
+            Ok(placeholder)
+        })?;
+        if let (Some(redacted), Some(opening)) = (redacted, opening) {
+            document = redacted;
+            openings.insert(pointer.to_owned(), opening);
+        }
+    }
+    Ok((document.into_bytes(), openings))
+}
+
+/// Returns the canonical `text` with the subtree at `tokens` replaced by
+/// what `replace` makes of its text, or `None` if there is no such subtree.
+/// Only the objects and arrays on the way down are split up; every other
+/// member and element is copied as it is.
+fn replace_at(
+    text: &str,
+    tokens: &[String],
+    replace: &mut dyn FnMut(&str) -> Result<String, CanonError>,
+) -> Result<Option<String>, CanonError> {
+    let Some((token, rest)) = tokens.split_first() else {
+        return replace(text).map(Some);
+    };
+    let mut out = Vec::with_capacity(text.len());
+    if text.starts_with('{') {
+        let members = object_members(text)?;
+        let Some((_, member)) = members.iter().find(|(key, _)| key == token) else {
+            return Ok(None);
+        };
+        let replaced =
+            replace_at(member.get(), rest, replace).map_err(|err| err.within_key(token))?;
+        let Some(replaced) = replaced else {
+            return Ok(None);
+        };
+        let members = members
+            .into_iter()
+            .map(|(key, raw)| {
+                let text = if key == *token { &replaced } else { raw.get() };
+                (key, text)
+            })
+            .collect();
+        write_object(&mut out, members, |out, text| {
+            out.extend_from_slice(text.as_bytes());
+            Ok(())
+        })?;
+    } else if text.starts_with('[') {
+        let elements: Vec<&RawValue> = serde_json::from_str(text)?;
+        let Some(index) = array_index(token).filter(|&i| i < elements.len()) else {
+            return Ok(None);
+        };
+        let replaced = replace_at(elements[index].get(), rest, replace)
+            .map_err(|err| err.within_index(index))?;
+        let Some(replaced) = replaced else {
+            return Ok(None);
+        };
+        out.push(b'[');
+        for (i, raw) in elements.iter().enumerate() {
+            if i > 0 {
+                out.push(b',');
+            }
+            let element = if i == index { &replaced } else { raw.get() };
+            out.extend_from_slice(element.as_bytes());
+        }
+        out.push(b']');
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8(out).expect("built from UTF-8 pieces"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn receipt() -> Value {
+        json!({
+            "amount": 5,
+            "card": {"number": "4111", "holder": "A. Lovelace"},
+            "items": [{"sku": "a", "price": 2}, {"sku": "b", "price": 3}],
+        })
+    }
+
+    #[test]
+    fn redacted_fields_are_replaced_by_openable_commitments() {
+        let (canonical, openings) =
+            commit_with_redactions(&receipt(), &["/card/number", "/items/1/price"], b"salt")
+                .unwrap();
+        assert_eq!(openings.len(), 2);
+        let document: Value = serde_json::from_slice(&canonical).unwrap();
+        for (pointer, opening) in &openings {
+            assert_eq!(
+                document.pointer(pointer),
+                Some(&opening.placeholder().unwrap())
+            );
+            let hex = document.pointer(pointer).unwrap()[COMMIT_KEY]
+                .as_str()
+                .unwrap();
+            assert_eq!(hex, hex::encode(opening.digest().unwrap()));
+        }
+        assert_eq!(openings["/card/number"].value, "4111");
+        assert_eq!(openings["/items/1/price"].value, 3);
+        assert_eq!(document["card"]["holder"], "A. Lovelace");
+        assert_eq!(document["items"][0]["price"], 2);
+
+        // The output depends on the salt but not on the order of `redact`.
+        let (again, _) =
+            commit_with_redactions(&receipt(), &["/items/1/price", "/card/number"], b"salt")
+                .unwrap();
+        assert_eq!(again, canonical);
+        let (other, _) =
+            commit_with_redactions(&receipt(), &["/card/number", "/items/1/price"], b"pepper")
+                .unwrap();
+        assert_ne!(other, canonical);
+    }
+
+    #[test]
+    fn each_field_gets_its_own_salt() {
+        let value = json!({"a": "same", "b": "same"});
+        let (_, openings) = commit_with_redactions(&value, &["/a", "/b"], b"salt").unwrap();
+        assert_ne!(openings["/a"].salt, openings["/b"].salt);
+        assert_ne!(
+            openings["/a"].digest().unwrap(),
+            openings["/b"].digest().unwrap()
+        );
+    }
+
+    #[test]
+    fn inner_fields_are_committed_before_outer_ones() {
+        let (canonical, openings) =
+            commit_with_redactions(&receipt(), &["/card", "/card/number"], b"salt").unwrap();
+        let inner = &openings["/card/number"];
+        assert_eq!(
+            openings["/card"].value["number"],
+            inner.placeholder().unwrap()
+        );
+        let document: Value = serde_json::from_slice(&canonical).unwrap();
+        assert_eq!(document["card"], openings["/card"].placeholder().unwrap());
+    }
+
+    #[test]
+    fn missing_and_repeated_pointers() {
+        let (canonical, openings) = commit_with_redactions(
+            &receipt(),
+            &["/nope", "/items/7", "/amount", "/amount"],
+            b"s",
+        )
+        .unwrap();
+        assert_eq!(openings.keys().collect::<Vec<_>>(), ["/amount"]);
+        assert_eq!(openings["/amount"].value, 5);
+        let (whole, openings) = commit_with_redactions(&receipt(), &[""], b"s").unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&whole).unwrap(),
+            openings[""].placeholder().unwrap()
+        );
+        assert_ne!(whole, canonical);
+        assert!(matches!(
+            commit_with_redactions(&receipt(), &["card"], b"s"),
+            Err(CanonError::InvalidPointer(p)) if p == "card"
+        ));
+    }
+
+    #[test]
+    fn openings_round_trip_through_json() {
+        let (_, openings) = commit_with_redactions(&receipt(), &["/card"], b"salt").unwrap();
+        let text = serde_json::to_string(&openings["/card"]).unwrap();
+        let opening: Commitment = serde_json::from_str(&text).unwrap();
+        assert_eq!(opening, openings["/card"]);
+        assert_eq!(
+            opening.digest().unwrap(),
+            openings["/card"].digest().unwrap()
+        );
+    }
+
+    #[test]
+    fn unredacted_fields_keep_their_exact_canonical_form() {
+        #[derive(Serialize)]
+        struct Record {
+            id: u128,
+            secret: &'static str,
+        }
+
+        let record = Record {
+            id: u128::MAX,
+            secret: "s",
+        };
+        let (canonical, openings) = commit_with_redactions(&record, &[], b"salt").unwrap();
+        assert!(openings.is_empty());
+        assert_eq!(canonical, canonicalize(&record).unwrap());
+
+        let (canonical, openings) = commit_with_redactions(&record, &["/secret"], b"salt").unwrap();
+        let text = String::from_utf8(canonical).unwrap();
+        let placeholder = to_string(&openings["/secret"].placeholder().unwrap()).unwrap();
+        assert_eq!(
+            text,
+            alloc::format!(r#"{{"id":{},"secret":{placeholder}}}"#, u128::MAX)
+        );
+    }
+
+    #[cfg(not(feature = "arbitrary_precision"))]
+    #[test]
+    fn redacting_a_value_a_value_cannot_hold_is_an_error() {
+        let err = commit_with_redactions(&[u128::MAX], &["/0"], b"salt").unwrap_err();
+        assert!(
+            matches!(err, CanonError::Custom { ref path, .. } if path == "/0"),
+            "{err}"
+        );
+    }
+}
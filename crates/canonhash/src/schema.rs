@@ -0,0 +1,139 @@
+//! Validation against a JSON Schema and canonical hashing as one step, so a
+//! digest is only ever produced for schema-valid data.
+//!
+//! Schemas are compiled with the `jsonschema` crate, re-exported here as
+//! [`validator_for`] and [`Validator`] so that its version matches this
+//! crate's.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+pub use jsonschema::{validator_for, Validator};
+use serde_json::Value;
+
+use crate::algorithm::HashAlgorithm;
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+/// One way a value failed its schema, from
+/// [`CanonError::SchemaViolation`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending part of the value.
+    pub instance_path: String,
+    /// JSON Pointer to the schema keyword it failed.
+    pub schema_path: String,
+    /// What was wrong, as `jsonschema` describes it.
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if !self.instance_path.is_empty() {
+            write!(f, " at `{}`", self.instance_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates `value` against `schema` and, only if it is valid, returns the
+/// digest of its canonical form under `alg`.
+///
+/// A value that fails is [`CanonError::SchemaViolation`] with every
+/// violation found, and is never hashed. The value is validated as given,
+/// before canonicalization, which changes nothing a schema can see.
+///
+/// ```
+/// use canonhash::schema::{validate_and_hash, validator_for};
+/// use canonhash::{CanonError, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let schema = validator_for(&json!({
+///     "type": "object",
+///     "properties": {"amount": {"type": "integer", "minimum": 0}},
+///     "required": ["amount"],
+/// }))
+/// .unwrap();
+///
+/// let digest = validate_and_hash(&json!({"amount": 5}), &schema, HashAlgorithm::Sha256).unwrap();
+/// assert_eq!(digest, canonhash::hash_with(HashAlgorithm::Sha256, &json!({"amount": 5})).unwrap());
+///
+/// let err = validate_and_hash(&json!({"amount": -5}), &schema, HashAlgorithm::Sha256).unwrap_err();
+/// assert!(matches!(
+///     err,
+///     CanonError::SchemaViolation(ref violations) if violations[0].instance_path == "/amount"
+/// ));
+/// ```
+pub fn validate_and_hash(
+    value: &Value,
+    schema: &Validator,
+    alg: HashAlgorithm,
+) -> Result<Vec<u8>, CanonError> {
+    let violations: Vec<SchemaViolation> = schema
+        .iter_errors(value)
+        .map(|err| SchemaViolation {
+            instance_path: err.instance_path().as_str().into(),
+            schema_path: err.schema_path().as_str().into(),
+            message: err.to_string(),
+        })
+        .collect();
+    if !violations.is_empty() {
+        return Err(CanonError::SchemaViolation(violations));
+    }
+    hash_with(alg, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> Validator {
+        validator_for(&json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+            },
+            "required": ["id"],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_values_hash_as_usual() {
+        let value = json!({"tags": ["b", "a"], "id": "x"});
+        for alg in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+            assert_eq!(
+                validate_and_hash(&value, &schema(), alg).unwrap(),
+                hash_with(alg, &value).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        let err = validate_and_hash(
+            &json!({"tags": ["ok", 7, false]}),
+            &schema(),
+            HashAlgorithm::Sha256,
+        )
+        .unwrap_err();
+        let CanonError::SchemaViolation(violations) = &err else {
+            panic!("unexpected error {err:?}");
+        };
+        let mut paths: Vec<_> = violations
+            .iter()
+            .map(|v| v.instance_path.as_str())
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(paths, ["", "/tags/1", "/tags/2"]);
+        let required = violations.iter().find(|v| v.instance_path.is_empty());
+        assert_eq!(required.unwrap().schema_path, "/required");
+        assert!(err.to_string().starts_with("value fails its schema: "));
+        assert!(err.to_string().contains("at `/tags/1`"));
+    }
+}
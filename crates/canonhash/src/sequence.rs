@@ -0,0 +1,145 @@
+//! A running hash over a sequence of values that commits to their order.
+//!
+//! [`hash_batch`](crate::hash::hash_batch) hashes values independently, and
+//! [`HashChain`](crate::chain::HashChain) links records by writing each
+//! one's predecessor into it. A [`SequenceHasher`] leaves the values alone
+//! and keeps only a 32-byte state, so it can follow an unbounded stream.
+
+use digest::Digest;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::error::CanonError;
+use crate::hash::canonical_sha256;
+
+/// An order-sensitive digest of every value pushed so far.
+///
+/// The construction, for reproducing it elsewhere:
+///
+/// - The state starts as 32 zero bytes, which is also the digest of an
+///   empty sequence.
+/// - Pushing `event` sets `state = SHA-256(state || SHA-256(canonical(event)))`,
+///   where `canonical` is the RFC 8785 form and `||` concatenates the two
+///   32-byte values.
+/// - [`digest`](Self::digest) returns the state as it is.
+///
+/// Since each step starts from the one before, the digest changes if any
+/// event is changed, dropped, inserted or moved.
+///
+/// ```
+/// use canonhash::{canonical_sha256, SequenceHasher};
+/// use serde_json::json;
+/// use sha2::{Digest, Sha256};
+///
+/// let mut events = SequenceHasher::new();
+/// events.push(&json!({"type": "open", "id": 1})).unwrap();
+/// let after_one = events.digest();
+///
+/// let mut expected = Sha256::new();
+/// expected.update([0u8; 32]);
+/// expected.update(canonical_sha256(&json!({"id": 1, "type": "open"})).unwrap());
+/// assert_eq!(after_one, <[u8; 32]>::from(expected.finalize()));
+///
+/// // A stream can be picked up again from a stored digest.
+/// let mut resumed = SequenceHasher::resume(after_one);
+/// events.push(&json!({"type": "close", "id": 1})).unwrap();
+/// resumed.push(&json!({"type": "close", "id": 1})).unwrap();
+/// assert_eq!(resumed.digest(), events.digest());
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SequenceHasher {
+    state: [u8; 32],
+}
+
+impl SequenceHasher {
+    /// Returns the hasher of the empty sequence.
+    pub fn new() -> Self {
+        SequenceHasher::default()
+    }
+
+    /// Continues a sequence whose [`digest`](Self::digest) was `state`.
+    pub fn resume(state: [u8; 32]) -> Self {
+        SequenceHasher { state }
+    }
+
+    /// Folds `event` in after everything pushed before it. If `event` can't
+    /// be canonicalized, the state is left as it was.
+    pub fn push<T>(&mut self, event: &T) -> Result<(), CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let event = canonical_sha256(event)?;
+        let mut hasher = Sha256::new();
+        hasher.update(self.state);
+        hasher.update(event);
+        self.state = hasher.finalize().into();
+        Ok(())
+    }
+
+    /// Returns the digest committing to every event pushed so far, in
+    /// order. The hasher is unchanged and can keep being pushed to.
+    pub fn digest(&self) -> [u8; 32] {
+        self.state
+    }
+}
+
+impl core::fmt::Debug for SequenceHasher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("SequenceHasher")
+            .field(&hex::encode(self.state))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn of(events: &[serde_json::Value]) -> [u8; 32] {
+        let mut hasher = SequenceHasher::new();
+        for event in events {
+            hasher.push(event).unwrap();
+        }
+        hasher.digest()
+    }
+
+    #[test]
+    fn empty_sequence_is_zero() {
+        assert_eq!(SequenceHasher::new().digest(), [0; 32]);
+    }
+
+    #[test]
+    fn order_and_multiplicity_matter() {
+        let (a, b) = (json!({"n": 1}), json!({"n": 2}));
+        assert_ne!(of(&[a.clone(), b.clone()]), of(&[b, a.clone()]));
+        let once = of(core::slice::from_ref(&a));
+        assert_ne!(once, of(&[a.clone(), a]));
+        assert_ne!(once, of(&[]));
+    }
+
+    #[test]
+    fn events_are_compared_by_canonical_form() {
+        assert_eq!(
+            of(&[json!({"a": 1.0, "b": [true]})]),
+            of(&[json!({"b": [true], "a": 1})])
+        );
+    }
+
+    #[test]
+    fn failed_push_leaves_state_alone() {
+        let mut hasher = SequenceHasher::new();
+        hasher.push(&"x").unwrap();
+        let before = hasher.clone();
+        assert!(matches!(
+            hasher.push(&f64::NAN),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        assert_eq!(hasher, before);
+        assert_eq!(
+            format!("{hasher:?}"),
+            format!("SequenceHasher({:?})", hex::encode(hasher.digest()))
+        );
+    }
+}
@@ -0,0 +1,1682 @@
+//! The serde `Serializer` that produces canonical JSON.
+//!
+//! Scalars and arrays are written straight to the output. Object members are
+//! serialized into their own buffers so they can be sorted by key before the
+//! object is written out.
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::collections::BTreeMap;
+#[cfg(feature = "trace")]
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::Range;
+use core::sync::atomic::{self, AtomicBool};
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Error as _, Impossible, Serialize};
+use serde_json::value::RawValue;
+
+use crate::canonicalizer::KeySortOrder;
+#[cfg(feature = "unicode-normalization")]
+use crate::canonicalizer::NormalizationForm;
+use crate::error::{CanonError, JsonPath, Limit, PathSegment};
+use crate::interop::{self, InteropWarning};
+use crate::number;
+use crate::sink::Sink;
+use crate::stats::CanonStats;
+#[cfg(feature = "trace")]
+use crate::trace::Tracer;
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// The struct name `serde_json::Number` serializes under when its
+/// `arbitrary_precision` feature is on. The single field holds the number's
+/// decimal text.
+pub(crate) const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// The struct name `serde_json::value::RawValue` serializes under. The
+/// single field holds the JSON text.
+pub(crate) const RAW_VALUE_TOKEN: &str = "$serde_json::private::RawValue";
+
+/// How deeply arrays and objects may nest unless a [`Canonicalizer`] says
+/// otherwise. This is the same limit `serde_json` applies when parsing.
+///
+/// [`Canonicalizer`]: crate::Canonicalizer
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Settings made through [`Canonicalizer`]: departures from RFC 8785, none of
+/// which the default enables, and the nesting limit.
+///
+/// [`Canonicalizer`]: crate::Canonicalizer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Options {
+    /// Drop object members whose value is `null`.
+    pub(crate) omit_nulls: bool,
+    /// Sort array elements by their canonical bytes.
+    pub(crate) sort_arrays: bool,
+    /// Write integral floats with a trailing `.0`.
+    pub(crate) preserve_float_marker: bool,
+    /// Sort objects whose keys are all integers by numeric value.
+    pub(crate) numeric_key_sort: bool,
+    /// How other object keys are ordered.
+    pub(crate) key_sort: KeySortOrder,
+    /// ASCII-lowercase object keys before sorting them.
+    pub(crate) lowercase_keys: bool,
+    /// Normalize string values and object keys to this form.
+    #[cfg(feature = "unicode-normalization")]
+    pub(crate) normalize_strings: Option<NormalizationForm>,
+    /// The deepest nesting of arrays and objects allowed.
+    pub(crate) max_depth: usize,
+    /// The most members any one object may have.
+    pub(crate) max_object_keys: usize,
+    /// The most bytes the whole output may have.
+    pub(crate) max_total_bytes: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            omit_nulls: false,
+            sort_arrays: false,
+            preserve_float_marker: false,
+            numeric_key_sort: false,
+            key_sort: KeySortOrder::Utf16CodeUnit,
+            lowercase_keys: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalize_strings: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_object_keys: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Options {
+    /// Whether the output is plain RFC 8785. The nesting and size limits
+    /// don't change the output, only whether there is any.
+    pub(crate) fn is_strict(&self) -> bool {
+        !(self.omit_nulls
+            || self.sort_arrays
+            || self.preserve_float_marker
+            || self.numeric_key_sort
+            || self.key_sort != KeySortOrder::Utf16CodeUnit
+            || self.rewrites_keys())
+    }
+
+    /// Whether two distinct keys can come out the same.
+    fn rewrites_keys(&self) -> bool {
+        #[cfg(feature = "unicode-normalization")]
+        if self.normalize_strings.is_some() {
+            return true;
+        }
+        self.lowercase_keys
+    }
+}
+
+/// The member order of each struct type seen so far, keyed by struct name.
+///
+/// A derived `Serialize` writes the same fields in the same order every time,
+/// so the permutation that sorts them only needs computing once. Each entry
+/// records the keys it was computed for and is recomputed whenever a struct
+/// arrives with different ones, as happens with `skip_serializing_if` or two
+/// types that share a name.
+#[derive(Debug, Default)]
+pub(crate) struct LayoutCache(RefCell<BTreeMap<&'static str, Layout>>);
+
+#[derive(Debug, Default)]
+struct Layout {
+    keys: Vec<Cow<'static, str>>,
+    /// Indices into `keys` in UTF-16 key order.
+    order: Vec<usize>,
+}
+
+impl Layout {
+    fn of(members: &[Member]) -> Self {
+        let mut order: Vec<usize> = (0..members.len()).collect();
+        order.sort_by(|&a, &b| cmp_utf16(&members[a].key, &members[b].key));
+        Layout {
+            keys: members.iter().map(|member| member.key.clone()).collect(),
+            order,
+        }
+    }
+
+    fn matches(&self, members: &[Member]) -> bool {
+        self.keys.len() == members.len()
+            && self.keys.iter().zip(members).all(|(key, m)| *key == m.key)
+    }
+}
+
+/// State passed down through every level of one serialization, beyond the
+/// options.
+#[derive(Clone, Copy, Default)]
+struct Context<'a> {
+    /// How many arrays and objects enclose the current value.
+    depth: usize,
+    layouts: Option<&'a LayoutCache>,
+    budget: Option<&'a Budget>,
+    cancel: Option<&'a Cancel<'a>>,
+    /// The node of the [`SortPaths`] tree for the current value, if any
+    /// path passes through it.
+    sort_at: Option<&'a SortPaths>,
+    stats: Option<&'a Cell<CanonStats>>,
+    warnings: Option<&'a RefCell<Vec<InteropWarning>>>,
+    #[cfg(feature = "trace")]
+    trace: Option<&'a Tracer>,
+}
+
+/// The arrays that [`Canonicalizer::sort_arrays_at`] sorts, as a tree of
+/// unescaped JSON Pointer reference tokens.
+///
+/// [`Canonicalizer::sort_arrays_at`]: crate::Canonicalizer::sort_arrays_at
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SortPaths {
+    /// Whether the array at this node is sorted.
+    sort: bool,
+    children: BTreeMap<String, SortPaths>,
+}
+
+impl SortPaths {
+    /// Marks the array reached from this node by `tokens`.
+    pub(crate) fn insert(&mut self, tokens: impl IntoIterator<Item = String>) {
+        let node = tokens
+            .into_iter()
+            .fold(self, |node, token| node.children.entry(token).or_default());
+        node.sort = true;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.sort && self.children.is_empty()
+    }
+}
+
+/// What is left of `max_total_bytes`.
+///
+/// Bytes are charged once, as they are produced. Sorting copies members and
+/// elements out of their buffers, but those copies are not charged again,
+/// so the total is the length of the output however deeply it was buffered
+/// and the limit trips before the buffers grow past it.
+struct Budget {
+    limit: usize,
+    remaining: Cell<usize>,
+}
+
+impl Budget {
+    fn spend(&self, bytes: usize) -> Result<(), CanonError> {
+        let remaining =
+            self.remaining
+                .get()
+                .checked_sub(bytes)
+                .ok_or_else(|| CanonError::LimitExceeded {
+                    limit: Limit::TotalBytes(self.limit),
+                    path: JsonPath::default(),
+                })?;
+        self.remaining.set(remaining);
+        Ok(())
+    }
+
+    /// Gives back bytes that were produced but dropped from the output.
+    fn refund(&self, bytes: usize) {
+        self.remaining.set(self.remaining.get() + bytes);
+    }
+}
+
+/// How many bytes of output may be produced between reads of a
+/// cancellation flag.
+const CANCEL_CHECK_INTERVAL: usize = 64 * 1024;
+
+/// A cancellation flag, read once every [`CANCEL_CHECK_INTERVAL`] bytes.
+struct Cancel<'a> {
+    flag: &'a AtomicBool,
+    /// Bytes left before the flag is next read.
+    until_check: Cell<usize>,
+}
+
+impl Cancel<'_> {
+    fn tick(&self, bytes: usize) -> Result<(), CanonError> {
+        match self.until_check.get().checked_sub(bytes) {
+            Some(left) => self.until_check.set(left),
+            None if self.flag.load(atomic::Ordering::Relaxed) => return Err(CanonError::Cancelled),
+            None => self.until_check.set(CANCEL_CHECK_INTERVAL),
+        }
+        Ok(())
+    }
+}
+
+/// A sink that charges the context's budget, if any, for what it writes,
+/// and checks its cancellation flag.
+struct Metered<'m, S: ?Sized> {
+    out: &'m mut S,
+    budget: Option<&'m Budget>,
+    cancel: Option<&'m Cancel<'m>>,
+}
+
+impl<S: ?Sized + Sink> Sink for Metered<'_, S> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        if let Some(budget) = self.budget {
+            budget.spend(bytes.len())?;
+        }
+        if let Some(cancel) = self.cancel {
+            cancel.tick(bytes.len())?;
+        }
+        self.out.write(bytes)
+    }
+}
+
+/// What a [`Canonicalizer`](crate::Canonicalizer) can set for one
+/// serialization besides its [`Options`], which are `Copy` and copied into
+/// every nested serializer.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Scope<'a> {
+    /// Fails the serialization with [`CanonError::Cancelled`] once set.
+    pub(crate) cancel: Option<&'a AtomicBool>,
+    /// Arrays to sort, rooted at the value being serialized.
+    pub(crate) sort_paths: Option<&'a SortPaths>,
+    /// Counts what is written, except `byte_len`.
+    pub(crate) stats: Option<&'a Cell<CanonStats>>,
+    /// Collects values that aren't I-JSON, with paths from the root.
+    pub(crate) warnings: Option<&'a RefCell<Vec<InteropWarning>>>,
+}
+
+/// Serializes `value` into `out` under `opts`, enforcing `max_total_bytes`.
+///
+/// Use this rather than [`Serializer::with_options`] wherever the options
+/// come from a [`Canonicalizer`](crate::Canonicalizer).
+pub(crate) fn serialize<S, T>(value: &T, out: &mut S, opts: Options) -> Result<(), CanonError>
+where
+    S: ?Sized + Sink,
+    T: ?Sized + Serialize,
+{
+    serialize_scoped(value, out, opts, Scope::default())
+}
+
+/// Like [`serialize`], but also applies what `scope` sets. A `cancel` flag
+/// that is set before the first byte or at a later check fails with
+/// [`CanonError::Cancelled`].
+pub(crate) fn serialize_scoped<S, T>(
+    value: &T,
+    out: &mut S,
+    opts: Options,
+    scope: Scope<'_>,
+) -> Result<(), CanonError>
+where
+    S: ?Sized + Sink,
+    T: ?Sized + Serialize,
+{
+    let sort_at = scope.sort_paths.filter(|paths| !paths.is_empty());
+    if opts.max_total_bytes == usize::MAX
+        && scope.cancel.is_none()
+        && sort_at.is_none()
+        && scope.stats.is_none()
+        && scope.warnings.is_none()
+    {
+        return value.serialize(Serializer::with_options(out, opts));
+    }
+    let budget = (opts.max_total_bytes != usize::MAX).then(|| Budget {
+        limit: opts.max_total_bytes,
+        remaining: Cell::new(opts.max_total_bytes),
+    });
+    // Starting with nothing left to spend puts the first check on the
+    // first write.
+    let cancel = scope.cancel.map(|flag| Cancel {
+        flag,
+        until_check: Cell::new(0),
+    });
+    let ctx = Context {
+        budget: budget.as_ref(),
+        cancel: cancel.as_ref(),
+        sort_at,
+        stats: scope.stats,
+        warnings: scope.warnings,
+        ..Context::default()
+    };
+    value.serialize(Serializer::nested(out, opts, ctx))
+}
+
+pub(crate) struct Serializer<'a, S: ?Sized> {
+    out: &'a mut S,
+    opts: Options,
+    ctx: Context<'a>,
+}
+
+impl<'a, S: ?Sized + Sink> Serializer<'a, S> {
+    pub(crate) fn with_options(out: &'a mut S, opts: Options) -> Self {
+        Serializer::nested(out, opts, Context::default())
+    }
+
+    /// Like [`with_options`](Self::with_options), but reorders struct
+    /// members by the layouts in `layouts`, adding any that are missing.
+    pub(crate) fn with_layouts(out: &'a mut S, opts: Options, layouts: &'a LayoutCache) -> Self {
+        let ctx = Context {
+            layouts: Some(layouts),
+            ..Context::default()
+        };
+        Serializer::nested(out, opts, ctx)
+    }
+
+    /// Like [`with_options`](Self::with_options), but records what the
+    /// serializer does in `tracer`.
+    #[cfg(feature = "trace")]
+    pub(crate) fn with_trace(out: &'a mut S, opts: Options, tracer: &'a Tracer) -> Self {
+        let ctx = Context {
+            trace: Some(tracer),
+            ..Context::default()
+        };
+        Serializer::nested(out, opts, ctx)
+    }
+
+    fn nested(out: &'a mut S, opts: Options, ctx: Context<'a>) -> Self {
+        Serializer { out, opts, ctx }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Returns the context inside `levels` more arrays or objects, or
+    /// [`CanonError::DepthExceeded`] if that passes the limit in `opts`.
+    fn descend(self, levels: usize, opts: Options) -> Result<Self, CanonError> {
+        let depth = self.depth + levels;
+        if depth > opts.max_depth {
+            return Err(CanonError::DepthExceeded {
+                limit: opts.max_depth,
+                path: JsonPath::default(),
+            });
+        }
+        self.tally(|stats| stats.max_depth = stats.max_depth.max(depth));
+        Ok(Context { depth, ..self })
+    }
+
+    /// Updates the statistics being gathered, if any.
+    fn tally(&self, update: impl FnOnce(&mut CanonStats)) {
+        if let Some(stats) = self.stats {
+            let mut current = stats.get();
+            update(&mut current);
+            stats.set(current);
+        }
+    }
+
+    /// Warns about the integer written as `text` if a double can't hold it.
+    fn check_integer(&self, text: &str) {
+        if let Some(warnings) = self.warnings {
+            if interop::is_unsafe_integer_text(text) {
+                warnings.borrow_mut().push(InteropWarning::UnsafeInteger {
+                    path: JsonPath::default(),
+                    value: text.into(),
+                });
+            }
+        }
+    }
+
+    /// How many warnings have been collected, to pass to
+    /// [`warned_within`](Self::warned_within) once a child is written.
+    fn warning_mark(&self) -> usize {
+        self.warnings.map_or(0, |warnings| warnings.borrow().len())
+    }
+
+    /// Prefixes the paths of the warnings collected since `mark` with
+    /// `segments`, as errors are prefixed while unwinding.
+    fn warned_within(&self, mark: usize, segments: impl Fn() -> [Option<PathSegment>; 2]) {
+        if let Some(warnings) = self.warnings {
+            for warning in &mut warnings.borrow_mut()[mark..] {
+                let path = warning.path_mut();
+                for segment in segments().into_iter().flatten().rev() {
+                    path.push_front(segment);
+                }
+            }
+        }
+    }
+
+    /// Returns the context for the member or variant named `token`.
+    fn within(self, token: &str) -> Self {
+        Context {
+            sort_at: self.sort_at.and_then(|node| node.children.get(token)),
+            ..self
+        }
+    }
+
+    /// Returns the context for the array element at `index`.
+    fn within_index(self, index: usize) -> Self {
+        match self.sort_at {
+            Some(node) if !node.children.is_empty() => {
+                self.within(itoa::Buffer::new().format(index))
+            }
+            _ => Context {
+                sort_at: None,
+                ..self
+            },
+        }
+    }
+
+    /// Whether the array being opened in this context is sorted.
+    fn sorts_array(&self, opts: Options) -> bool {
+        opts.sort_arrays || self.sort_at.is_some_and(|node| node.sort)
+    }
+
+    /// Wraps `out` so that writes to it are charged to the budget.
+    fn meter<'m, S: ?Sized>(&self, out: &'m mut S) -> Metered<'m, S>
+    where
+        'a: 'm,
+    {
+        Metered {
+            out,
+            budget: self.budget,
+            cancel: self.cancel,
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+impl Context<'_> {
+    /// Extends the traced path, returning the mark to [`leave`](Self::leave)
+    /// back to. Does nothing when not tracing.
+    fn enter<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> Option<usize> {
+        self.trace.map(|tracer| tracer.enter(segments))
+    }
+
+    fn leave(&self, mark: Option<usize>) {
+        if let (Some(tracer), Some(mark)) = (self.trace, mark) {
+            tracer.leave(mark);
+        }
+    }
+}
+
+/// Writes `s` as a JSON string literal, escaping only what JSON requires.
+///
+/// Per RFC 8785 §3.2.2.2 that is `"`, `\` and U+0000..U+001F. Control
+/// characters with a short form (`\b \f \n \r \t`) use it, the rest use
+/// `\u00xx` with lowercase hex. Everything else, including `/`, DEL and all
+/// non-ASCII characters, is written as raw UTF-8.
+pub(crate) fn write_str<S: ?Sized + Sink>(out: &mut S, s: &str) -> Result<(), CanonError> {
+    out.write(b"\"")?;
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b >= 0x20 && b != b'"' && b != b'\\' {
+            continue;
+        }
+        out.write(&bytes[start..i])?;
+        match b {
+            b'"' => out.write(b"\\\"")?,
+            b'\\' => out.write(b"\\\\")?,
+            b'\x08' => out.write(b"\\b")?,
+            b'\x0c' => out.write(b"\\f")?,
+            b'\n' => out.write(b"\\n")?,
+            b'\r' => out.write(b"\\r")?,
+            b'\t' => out.write(b"\\t")?,
+            _ => out.write(&[
+                b'\\',
+                b'u',
+                b'0',
+                b'0',
+                HEX[(b >> 4) as usize],
+                HEX[(b & 0xf) as usize],
+            ])?,
+        }
+        start = i + 1;
+    }
+    out.write(&bytes[start..])?;
+    out.write(b"\"")
+}
+
+/// Orders strings by their UTF-16 code units, as RFC 8785 requires for
+/// object keys.
+///
+/// This differs from `str`'s byte order only when a supplementary-plane
+/// character (encoded as a surrogate pair, 0xD800..0xDFFF) is compared with a
+/// character in U+E000..U+FFFF.
+pub(crate) fn cmp_utf16(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let Some(i) = a.iter().zip(b).position(|(x, y)| x != y) else {
+        return a.len().cmp(&b.len());
+    };
+    // UTF-8 byte order is code point order. The first differing bytes either
+    // both continue the same kind of character or lead two characters, and
+    // only a 4-byte lead (a surrogate pair in UTF-16) against a lead for
+    // U+E000..U+FFFF sorts the other way round.
+    let (x, y) = (a[i], b[i]);
+    match (x >= 0xf0, y >= 0xf0) {
+        (true, false) if y >= 0xee => Ordering::Less,
+        (false, true) if x >= 0xee => Ordering::Greater,
+        _ => x.cmp(&y),
+    }
+}
+
+/// Sorts object members into [`cmp_utf16`] key order, failing with
+/// [`CanonError::DuplicateKey`] if two have the same key.
+pub(crate) fn sort_members<K: AsRef<str>, V>(members: &mut [(K, V)]) -> Result<(), CanonError> {
+    members.sort_by(|a, b| cmp_utf16(a.0.as_ref(), b.0.as_ref()));
+    match members
+        .windows(2)
+        .find(|pair| pair[0].0.as_ref() == pair[1].0.as_ref())
+    {
+        Some(pair) => Err(CanonError::DuplicateKey {
+            key: pair[0].0.as_ref().to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Parses the object in JSON `text` into its members in canonical key
+/// order, keeping each value as its text. A repeated key is
+/// [`CanonError::DuplicateKey`] rather than one of them being dropped.
+pub(crate) fn object_members(text: &str) -> Result<Vec<(String, &RawValue)>, CanonError> {
+    let RawMembers(mut members) = serde_json::from_str(text)?;
+    sort_members(&mut members)?;
+    Ok(members)
+}
+
+/// Writes an object of `members` in canonical key order, with each value
+/// written by `write_value`.
+pub(crate) fn write_object<K: AsRef<str>, V>(
+    out: &mut Vec<u8>,
+    mut members: Vec<(K, V)>,
+    mut write_value: impl FnMut(&mut Vec<u8>, V) -> Result<(), CanonError>,
+) -> Result<(), CanonError> {
+    sort_members(&mut members)?;
+    out.push(b'{');
+    for (i, (key, value)) in members.into_iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        write_str(out, key.as_ref())?;
+        out.push(b':');
+        write_value(out, value)?;
+    }
+    out.push(b'}');
+    Ok(())
+}
+
+/// The members of a JSON object in the order they appear, repeats included.
+struct RawMembers<'a>(Vec<(String, &'a RawValue)>);
+
+impl<'de> Deserialize<'de> for RawMembers<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = RawMembers<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut members = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(member) = map.next_entry()? {
+                    members.push(member);
+                }
+                Ok(RawMembers(members))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// Whether `key` is an optional `-` followed by ASCII digits.
+fn is_integer(key: &str) -> bool {
+    let digits = key.strip_prefix('-').unwrap_or(key);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Orders integer keys, of any length, by value. Keys with the same value
+/// but different text, such as `1` and `01`, fall back to [`cmp_utf16`] so
+/// the order stays total.
+fn cmp_numeric(a: &str, b: &str) -> Ordering {
+    fn parts(key: &str) -> (bool, &str) {
+        let (negative, digits) = match key.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, key),
+        };
+        let digits = digits.trim_start_matches('0');
+        (negative && !digits.is_empty(), digits)
+    }
+    let ((a_neg, a_digits), (b_neg, b_digits)) = (parts(a), parts(b));
+    let magnitude = a_digits
+        .len()
+        .cmp(&b_digits.len())
+        .then_with(|| a_digits.cmp(b_digits));
+    let by_value = match (a_neg, b_neg) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    };
+    by_value.then_with(|| cmp_utf16(a, b))
+}
+
+/// Writes the decimal text of an arbitrary-precision `serde_json::Number`.
+///
+/// Integers keep their exact digits, as integer-typed values do, so values
+/// beyond `u64` survive unchanged. Anything with a fraction or exponent is a
+/// double as far as RFC 8785 is concerned and goes through the ES formatter.
+pub(crate) fn write_number_text<S: ?Sized + Sink>(
+    out: &mut S,
+    text: &str,
+    opts: Options,
+) -> Result<(), CanonError> {
+    if is_integer_text(text) {
+        if text.bytes().all(|b| b == b'0' || b == b'-') {
+            return out.write(b"0");
+        }
+        return out.write(text.as_bytes());
+    }
+    let v: f64 = text
+        .parse()
+        .map_err(|_| CanonError::custom(format_args!("invalid JSON number `{text}`")))?;
+    if !v.is_finite() {
+        return Err(CanonError::NonFiniteNumber {
+            path: JsonPath::default(),
+        });
+    }
+    write_float(out, v, opts)
+}
+
+/// Whether `text` is an integer's digits, with an optional `-`, rather than
+/// a double's.
+fn is_integer_text(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Writes a finite `v` in ES form. With `preserve_float_marker`, a result
+/// that would read back as an integer gets a trailing `.0`.
+pub(crate) fn write_float<S: ?Sized + Sink>(
+    out: &mut S,
+    v: f64,
+    opts: Options,
+) -> Result<(), CanonError> {
+    let mut buf = number::Buffer::new();
+    let text = buf.format_finite(v);
+    out.write(text.as_bytes())?;
+    if opts.preserve_float_marker && !text.contains(['.', 'e']) {
+        out.write(b".0")?;
+    }
+    Ok(())
+}
+
+/// Opens the `{"Variant":` object that wraps a non-unit variant's contents.
+fn write_variant_open<S: ?Sized + Sink>(
+    out: &mut Metered<'_, S>,
+    ctx: &Context<'_>,
+    variant: &str,
+) -> Result<(), CanonError> {
+    ctx.tally(|stats| stats.object_count += 1);
+    out.write(b"{")?;
+    write_str(out, variant)?;
+    out.write(b":")
+}
+
+impl<'a, S: ?Sized + Sink> ser::Serializer for Serializer<'a, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    type SerializeSeq = Array<'a, S>;
+    type SerializeTuple = Array<'a, S>;
+    type SerializeTupleStruct = Array<'a, S>;
+    type SerializeTupleVariant = Array<'a, S>;
+    type SerializeMap = Object<'a, S>;
+    type SerializeStruct = Object<'a, S>;
+    type SerializeStructVariant = Object<'a, S>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CanonError> {
+        self.ctx
+            .meter(self.out)
+            .write(if v { b"true" } else { b"false" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CanonError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CanonError> {
+        self.ctx.tally(|stats| stats.number_count += 1);
+        let mut buf = itoa::Buffer::new();
+        let text = buf.format(v);
+        self.ctx.check_integer(text);
+        self.ctx.meter(self.out).write(text.as_bytes())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), CanonError> {
+        self.ctx.tally(|stats| stats.number_count += 1);
+        let mut buf = itoa::Buffer::new();
+        let text = buf.format(v);
+        self.ctx.check_integer(text);
+        self.ctx.meter(self.out).write(text.as_bytes())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CanonError> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CanonError> {
+        self.ctx.tally(|stats| stats.number_count += 1);
+        let mut buf = itoa::Buffer::new();
+        let text = buf.format(v);
+        self.ctx.check_integer(text);
+        self.ctx.meter(self.out).write(text.as_bytes())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), CanonError> {
+        self.ctx.tally(|stats| stats.number_count += 1);
+        let mut buf = itoa::Buffer::new();
+        let text = buf.format(v);
+        self.ctx.check_integer(text);
+        self.ctx.meter(self.out).write(text.as_bytes())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CanonError> {
+        #[cfg(feature = "trace")]
+        if let (Some(tracer), true) = (self.ctx.trace, v.is_finite()) {
+            self.ctx.tally(|stats| stats.number_count += 1);
+            let opts = self.opts;
+            return tracer.reformat(&mut self.ctx.meter(self.out), &format!("{v:?}"), |to| {
+                write_float(to, f64::from(v), opts)
+            });
+        }
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CanonError> {
+        if !v.is_finite() {
+            return Err(CanonError::NonFiniteNumber {
+                path: JsonPath::default(),
+            });
+        }
+        self.ctx.tally(|stats| stats.number_count += 1);
+        #[cfg(feature = "trace")]
+        if let Some(tracer) = self.ctx.trace {
+            let opts = self.opts;
+            return tracer.reformat(&mut self.ctx.meter(self.out), &format!("{v:?}"), |to| {
+                write_float(to, v, opts)
+            });
+        }
+        write_float(&mut self.ctx.meter(self.out), v, self.opts)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CanonError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CanonError> {
+        self.ctx.tally(|stats| stats.string_count += 1);
+        #[cfg(feature = "unicode-normalization")]
+        if let Some(normalized) = self.opts.normalize_strings.and_then(|form| form.apply(v)) {
+            return write_str(&mut self.ctx.meter(self.out), &normalized);
+        }
+        write_str(&mut self.ctx.meter(self.out), v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CanonError> {
+        use ser::SerializeSeq;
+        let mut seq = self.serialize_seq(Some(v.len()))?;
+        for byte in v {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_none(self) -> Result<(), CanonError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CanonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CanonError> {
+        self.ctx.meter(self.out).write(b"null")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CanonError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), CanonError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        let ctx = self.ctx.descend(1, self.opts)?.within(variant);
+        write_variant_open(&mut self.ctx.meter(self.out), &self.ctx, variant)?;
+        #[cfg(feature = "trace")]
+        let mark = self.ctx.enter([variant]);
+        let warned = self.ctx.warning_mark();
+        let result = value
+            .serialize(Serializer::nested(&mut *self.out, self.opts, ctx))
+            .map_err(|err| err.within_key(variant));
+        #[cfg(feature = "trace")]
+        self.ctx.leave(mark);
+        result?;
+        self.ctx
+            .warned_within(warned, || [Some(PathSegment::Key(variant.into())), None]);
+        self.ctx.meter(self.out).write(b"}")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Array<'a, S>, CanonError> {
+        let ctx = self.ctx.descend(1, self.opts)?;
+        self.ctx.meter(self.out).write(b"[")?;
+        Ok(Array::new(self.out, self.opts, ctx, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Array<'a, S>, CanonError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Array<'a, S>, CanonError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Array<'a, S>, CanonError> {
+        let ctx = self.ctx.descend(2, self.opts)?.within(variant);
+        let mut out = self.ctx.meter(self.out);
+        write_variant_open(&mut out, &self.ctx, variant)?;
+        out.write(b"[")?;
+        Ok(Array::new(self.out, self.opts, ctx, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Object<'a, S>, CanonError> {
+        let ctx = self.ctx.descend(1, self.opts)?;
+        Object::new(self.out, self.opts, ctx, None, None)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Object<'a, S>, CanonError> {
+        if name == NUMBER_TOKEN {
+            return Ok(Object::number(self.out, self.opts, self.ctx));
+        }
+        if name == RAW_VALUE_TOKEN {
+            return Ok(Object::raw(self.out, self.opts, self.ctx));
+        }
+        let ctx = self.ctx.descend(1, self.opts)?;
+        Object::new(self.out, self.opts, ctx, Some(name), None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Object<'a, S>, CanonError> {
+        let ctx = self.ctx.descend(2, self.opts)?.within(variant);
+        write_variant_open(&mut self.ctx.meter(self.out), &self.ctx, variant)?;
+        Object::new(self.out, self.opts, ctx, None, Some(variant))
+    }
+}
+
+/// An array in progress. Elements are written directly to the output, unless
+/// `sort_arrays` is set or the array is on a sorted path, in which case they
+/// are buffered like object members and written in byte order at the end.
+///
+/// `variant` is set for tuple variants, whose `{"Variant":` wrapper must be
+/// closed after the array and named in error paths.
+pub(crate) struct Array<'a, S: ?Sized> {
+    out: &'a mut S,
+    opts: Options,
+    ctx: Context<'a>,
+    len: usize,
+    sorted: Option<Vec<Vec<u8>>>,
+    variant: Option<&'static str>,
+}
+
+impl<'a, S: ?Sized + Sink> Array<'a, S> {
+    fn new(out: &'a mut S, opts: Options, ctx: Context<'a>, variant: Option<&'static str>) -> Self {
+        ctx.tally(|stats| stats.array_count += 1);
+        Array {
+            out,
+            opts,
+            ctx,
+            len: 0,
+            sorted: ctx.sorts_array(opts).then(Vec::new),
+            variant,
+        }
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        let index = self.len;
+        self.len += 1;
+        let variant = self.variant;
+        let ctx = self.ctx.within_index(index);
+        let in_path = |err: CanonError| {
+            let err = err.within_index(index);
+            match variant {
+                Some(variant) => err.within_key(variant),
+                None => err,
+            }
+        };
+        #[cfg(feature = "trace")]
+        let mark = self.ctx.enter(
+            variant
+                .into_iter()
+                .chain([itoa::Buffer::new().format(index)]),
+        );
+        let warned = self.ctx.warning_mark();
+        let result = match &mut self.sorted {
+            Some(elements) => {
+                let mut buf = Vec::new();
+                value
+                    .serialize(Serializer::nested(&mut buf, self.opts, ctx))
+                    .map_err(in_path)
+                    .map(|()| elements.push(buf))
+            }
+            None => {
+                if index > 0 {
+                    self.ctx.meter(self.out).write(b",")?;
+                }
+                value
+                    .serialize(Serializer::nested(&mut *self.out, self.opts, ctx))
+                    .map_err(in_path)
+            }
+        };
+        #[cfg(feature = "trace")]
+        self.ctx.leave(mark);
+        self.ctx.warned_within(warned, || {
+            [
+                variant.map(|variant| PathSegment::Key(variant.into())),
+                Some(PathSegment::Index(index)),
+            ]
+        });
+        result
+    }
+
+    fn finish(self) -> Result<(), CanonError> {
+        if let Some(mut elements) = self.sorted {
+            elements.sort_unstable();
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    self.ctx.meter(self.out).write(b",")?;
+                }
+                self.out.write(element)?;
+            }
+        }
+        let mut out = self.ctx.meter(self.out);
+        out.write(b"]")?;
+        if self.variant.is_some() {
+            out.write(b"}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeSeq for Array<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTuple for Array<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTupleStruct for Array<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeTupleVariant for Array<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+/// An object in progress.
+///
+/// Members are written as `"key":value` one after another, either straight
+/// into the output when it is a `Vec<u8>` or into a buffer of the object's
+/// own. Each member's span is recorded, and if the keys did not arrive in
+/// UTF-16 order the spans are sorted and rewritten at `end`. Maps that are
+/// already sorted, such as a `BTreeMap` with ASCII keys, skip the sort and
+/// are never copied.
+///
+/// `name` is set for plain structs, whose member order is looked up in
+/// the context's layout cache when there is one rather than sorted each
+/// time.
+///
+/// `variant` is set for struct variants, as for [`Array`]. `number` is set
+/// for an arbitrary-precision `serde_json::Number`, which is written as the
+/// bare number rather than as an object. `raw` is set for a `RawValue`, whose
+/// text is parsed and written as the value it holds.
+pub(crate) struct Object<'a, S: ?Sized> {
+    out: &'a mut S,
+    opts: Options,
+    ctx: Context<'a>,
+    /// Whether members are written into `out` itself rather than `buf`.
+    in_place: bool,
+    buf: Vec<u8>,
+    /// Where the first member starts in `out` or `buf`.
+    start: usize,
+    members: Vec<Member>,
+    /// Whether every key so far sorted after the one before it.
+    sorted: bool,
+    key: Option<String>,
+    name: Option<&'static str>,
+    variant: Option<&'static str>,
+    number: bool,
+    /// `Some` for a `RawValue`, holding its text once the field is written.
+    raw: Option<Option<String>>,
+}
+
+struct Member {
+    /// Borrowed for struct fields, owned for map keys.
+    key: Cow<'static, str>,
+    /// The `"key":value` bytes, not including any separating comma.
+    span: Range<usize>,
+    /// Where the value starts within `span`.
+    value: usize,
+}
+
+impl<'a, S: ?Sized + Sink> Object<'a, S> {
+    fn new(
+        out: &'a mut S,
+        opts: Options,
+        ctx: Context<'a>,
+        name: Option<&'static str>,
+        variant: Option<&'static str>,
+    ) -> Result<Self, CanonError> {
+        ctx.tally(|stats| stats.object_count += 1);
+        let in_place = out.as_vec().is_some();
+        if in_place {
+            ctx.meter(out).write(b"{")?;
+        }
+        let start = out.as_vec().map_or(0, |vec| vec.len());
+        Ok(Object {
+            out,
+            opts,
+            ctx,
+            in_place,
+            buf: Vec::new(),
+            start,
+            members: Vec::new(),
+            sorted: true,
+            key: None,
+            name,
+            variant,
+            number: false,
+            raw: None,
+        })
+    }
+
+    fn number(out: &'a mut S, opts: Options, ctx: Context<'a>) -> Self {
+        Object {
+            out,
+            opts,
+            ctx,
+            in_place: false,
+            buf: Vec::new(),
+            start: 0,
+            members: Vec::new(),
+            sorted: true,
+            key: None,
+            name: None,
+            variant: None,
+            number: true,
+            raw: None,
+        }
+    }
+
+    fn raw(out: &'a mut S, opts: Options, ctx: Context<'a>) -> Self {
+        Object {
+            raw: Some(None),
+            number: false,
+            ..Object::number(out, opts, ctx)
+        }
+    }
+
+    fn body(&mut self) -> &mut Vec<u8> {
+        if self.in_place {
+            self.out.as_vec().expect("checked in Object::new")
+        } else {
+            &mut self.buf
+        }
+    }
+
+    fn member<T: ?Sized + Serialize>(
+        &mut self,
+        key: Cow<'static, str>,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        if let Some(text) = &mut self.raw {
+            *text = Some(value.serialize(KeySerializer)?);
+            return Ok(());
+        }
+        let opts = self.opts;
+        let variant = self.variant;
+        let first = self.members.is_empty();
+        let key = if opts.lowercase_keys && key.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(key.to_ascii_lowercase())
+        } else {
+            key
+        };
+        #[cfg(feature = "unicode-normalization")]
+        let key = match opts.normalize_strings.and_then(|form| form.apply(&key)) {
+            Some(normalized) => Cow::Owned(normalized),
+            None => key,
+        };
+        if !self.number && self.members.len() == opts.max_object_keys {
+            return Err(CanonError::LimitExceeded {
+                limit: Limit::ObjectKeys(opts.max_object_keys),
+                path: JsonPath::default(),
+            });
+        }
+        // A number's field is not output, only the text it holds, which
+        // `finish` charges and counts.
+        let ctx = if self.number {
+            Context {
+                budget: None,
+                stats: None,
+                ..self.ctx
+            }
+        } else {
+            self.ctx.within(&key)
+        };
+        let in_order = self
+            .members
+            .last()
+            .is_none_or(|last| opts.key_sort.cmp(&last.key, &key) == Ordering::Less);
+
+        let body = self.body();
+        let restore = body.len();
+        let mut body = ctx.meter(body);
+        if !first {
+            body.write(b",")?;
+        }
+        let span_start = body.out.len();
+        write_str(&mut body, &key)?;
+        body.write(b":")?;
+        let body = body.out;
+        let value_start = body.len();
+        #[cfg(feature = "trace")]
+        let mark = ctx.enter(variant.into_iter().chain([&*key]));
+        let warned = ctx.warning_mark();
+        let result = value
+            .serialize(Serializer::nested(&mut *body, opts, ctx))
+            .map_err(|err| {
+                let err = err.within_key(&key);
+                match variant {
+                    Some(variant) => err.within_key(variant),
+                    None => err,
+                }
+            });
+        #[cfg(feature = "trace")]
+        ctx.leave(mark);
+        result?;
+        ctx.warned_within(warned, || {
+            [
+                variant.map(|variant| PathSegment::Key(variant.into())),
+                Some(PathSegment::Key(key.as_ref().into())),
+            ]
+        });
+        if opts.omit_nulls && body[value_start..] == *b"null" {
+            if let Some(budget) = ctx.budget {
+                budget.refund(body.len() - restore);
+            }
+            body.truncate(restore);
+            return Ok(());
+        }
+        let span = span_start..body.len();
+
+        self.sorted &= in_order;
+        self.members.push(Member {
+            key,
+            span,
+            value: value_start,
+        });
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), CanonError> {
+        if let Some(text) = self.raw {
+            // Embedded text is held to the same rules as
+            // `canonicalize_str_strict`, since nothing parsed it before.
+            let text = text.ok_or_else(|| CanonError::custom("malformed RawValue"))?;
+            let value = parse_raw_value(&text)?;
+            return value.serialize(Serializer::nested(self.out, self.opts, self.ctx));
+        }
+        if self.number {
+            // The field was written as a string literal; decimal text needs
+            // no escaping, so the quotes are all there is to strip.
+            let text = match self.members.as_slice() {
+                [member] => self.buf[member.value..member.span.end]
+                    .strip_prefix(b"\"")
+                    .and_then(|rest| rest.strip_suffix(b"\""))
+                    .and_then(|text| core::str::from_utf8(text).ok()),
+                _ => None,
+            };
+            let text = text.ok_or_else(|| CanonError::custom("malformed serde_json::Number"))?;
+            self.ctx.tally(|stats| stats.number_count += 1);
+            if is_integer_text(text) {
+                self.ctx.check_integer(text);
+            }
+            #[cfg(feature = "trace")]
+            if let Some(tracer) = self.ctx.trace {
+                let opts = self.opts;
+                return tracer.reformat(&mut self.ctx.meter(self.out), text, |to| {
+                    write_number_text(to, text, opts)
+                });
+            }
+            return write_number_text(&mut self.ctx.meter(self.out), text, self.opts);
+        }
+
+        if !self.in_place {
+            self.ctx.meter(self.out).write(b"{")?;
+        }
+        let numeric =
+            self.opts.numeric_key_sort && self.members.iter().all(|member| is_integer(&member.key));
+        if numeric {
+            self.sorted = self
+                .members
+                .windows(2)
+                .all(|pair| cmp_numeric(&pair[0].key, &pair[1].key) == Ordering::Less);
+        }
+        if self.sorted {
+            if !self.in_place {
+                self.out.write(&self.buf)?;
+            }
+        } else {
+            // Keys in strictly increasing order are distinct.
+            check_distinct(&self.members)?;
+            #[cfg(feature = "trace")]
+            if let Some(tracer) = self.ctx.trace {
+                let mark = tracer.enter(self.variant);
+                tracer.keys_sorted(self.members.iter().map(|member| &*member.key));
+                tracer.leave(mark);
+            }
+            let (start, region) = if self.in_place {
+                let vec = self.out.as_vec().expect("checked in Object::new");
+                (self.start, vec.split_off(self.start))
+            } else {
+                (0, core::mem::take(&mut self.buf))
+            };
+            // The commas were charged for as the members were written.
+            let mut write = |i: usize, member: &Member| {
+                if i > 0 {
+                    self.out.write(b",")?;
+                }
+                self.out
+                    .write(&region[member.span.start - start..member.span.end - start])
+            };
+            match (self.ctx.layouts, self.name) {
+                (Some(layouts), Some(name)) if !numeric => {
+                    let mut layouts = layouts.0.borrow_mut();
+                    let layout = layouts.entry(name).or_default();
+                    if !layout.matches(&self.members) {
+                        *layout = Layout::of(&self.members);
+                    }
+                    for (i, &index) in layout.order.iter().enumerate() {
+                        write(i, &self.members[index])?;
+                    }
+                }
+                _ => {
+                    let order = self.opts.key_sort;
+                    self.members.sort_by(|a, b| {
+                        if numeric {
+                            cmp_numeric(&a.key, &b.key)
+                        } else {
+                            order.cmp(&a.key, &b.key)
+                        }
+                    });
+                    for (i, member) in self.members.iter().enumerate() {
+                        write(i, member)?;
+                    }
+                }
+            }
+            // The members may hold secrets for `hash_secret`.
+            #[cfg(feature = "zeroize")]
+            zeroize::Zeroize::zeroize(&mut { region });
+        }
+        let mut out = self.ctx.meter(self.out);
+        out.write(b"}")?;
+        if self.variant.is_some() {
+            out.write(b"}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the text of a `RawValue`, which is held to the same rules as
+/// `canonicalize_str_strict` since nothing parsed it before.
+pub(crate) fn parse_raw_value(text: &str) -> Result<serde_json::Value, CanonError> {
+    crate::strict::check_surrogates(text)?;
+    crate::strict::check_duplicates(text)?;
+    Ok(serde_json::from_str(text)?)
+}
+
+/// Fails with [`CanonError::DuplicateKey`] if two members have the same key
+/// as written, naming the first such key in UTF-16 order as
+/// [`CanonTokenSink`](crate::token_sink::CanonTokenSink) does. A
+/// `Serialize` impl can repeat a key outright, and lowercasing or
+/// normalizing can make two distinct keys equal.
+fn check_distinct(members: &[Member]) -> Result<(), CanonError> {
+    let mut keys: Vec<&str> = members.iter().map(|member| &*member.key).collect();
+    keys.sort_unstable_by(|a, b| cmp_utf16(a, b));
+    match keys.windows(2).find(|pair| pair[0] == pair[1]) {
+        Some(pair) => Err(CanonError::DuplicateKey {
+            key: pair[0].to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeMap for Object<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CanonError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| CanonError::custom("serialize_value called before serialize_key"))?;
+        self.member(Cow::Owned(key), value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeStruct for Object<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.member(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+impl<S: ?Sized + Sink> ser::SerializeStructVariant for Object<'_, S> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.member(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        self.finish()
+    }
+}
+
+/// Turns a map key into the string it will be written as. Integer and bool
+/// keys are stringified the same way `serde_json` does, unit enum variants
+/// become their (possibly renamed) variant name, and newtype structs use their
+/// inner key. Floats, `Option`s, sequences and other compound keys are
+/// rejected with [`CanonError::KeyMustBeAString`].
+///
+/// Members are sorted by this string, never by the key's own ordering, so
+/// `HashMap<i32, _>` keys `-1, 2, 10` come out as `"-1", "10", "2"`.
+struct KeySerializer;
+
+/// The error for a rejected key. Its path is filled in with the map's as it
+/// propagates.
+fn key_must_be_a_string() -> CanonError {
+    CanonError::KeyMustBeAString {
+        path: JsonPath::default(),
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = CanonError;
+
+    type SerializeSeq = Impossible<String, CanonError>;
+    type SerializeTuple = Impossible<String, CanonError>;
+    type SerializeTupleStruct = Impossible<String, CanonError>;
+    type SerializeTupleVariant = Impossible<String, CanonError>;
+    type SerializeMap = Impossible<String, CanonError>;
+    type SerializeStruct = Impossible<String, CanonError>;
+    type SerializeStructVariant = Impossible<String, CanonError>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, CanonError> {
+        Ok(if v { "true" } else { "false" }.to_owned())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<String, CanonError> {
+        Ok(itoa::Buffer::new().format(v).to_owned())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, CanonError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, CanonError> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, CanonError> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, CanonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, CanonError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, CanonError> {
+        Err(key_must_be_a_string())
+    }
+}
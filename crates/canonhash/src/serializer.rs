@@ -0,0 +1,419 @@
+//! The canonical serializer as a `serde::Serializer`, for driving it from
+//! code that expects one.
+
+use std::io;
+
+use serde::ser::{self, Serialize};
+
+use crate::error::CanonError;
+use crate::ser::{Array, Object, Options, Serializer};
+use crate::sink::IoSink;
+
+/// A `serde::Serializer` that writes the RFC 8785 canonical form to an
+/// `io::Write`.
+///
+/// This is the engine behind [`to_writer`](crate::to_writer), for callers
+/// that need a serializer value rather than a function: nested inside
+/// other serde machinery, or behind a type-erased serializer. As with
+/// `serde_json::Serializer`, it is `&mut CanonicalSerializer` that
+/// implements the trait, so one serializer can be reused for several values
+/// written back to back.
+///
+/// Memory: scalars and arrays are written to the writer as they are
+/// produced, but each object is buffered until it ends so its members can
+/// be sorted. Peak memory is bounded by the largest single object, not the
+/// whole document. Many small writes are issued, so wrap unbuffered writers
+/// in a `BufWriter`.
+///
+/// If serialization fails, whatever was written before the error stays in
+/// the writer.
+///
+/// ```
+/// use canonhash::CanonicalSerializer;
+/// use serde::Serialize;
+///
+/// let mut ser = CanonicalSerializer::new(Vec::new());
+/// serde_json::json!({"b": 1, "a": [2.0]}).serialize(&mut ser).unwrap();
+/// assert_eq!(ser.into_inner(), br#"{"a":[2],"b":1}"#);
+/// ```
+pub struct CanonicalSerializer<W> {
+    sink: IoSink<W>,
+}
+
+impl<W: io::Write> CanonicalSerializer<W> {
+    /// Returns a serializer writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        CanonicalSerializer {
+            sink: IoSink(writer),
+        }
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.sink.0
+    }
+
+    fn engine(&mut self) -> Serializer<'_, IoSink<W>> {
+        Serializer::with_options(&mut self.sink, Options::default())
+    }
+}
+
+/// Serializes the elements of an array, from a [`CanonicalSerializer`].
+pub struct SerializeArray<'a, W>(Array<'a, IoSink<W>>);
+
+/// Serializes the members of an object, from a [`CanonicalSerializer`].
+pub struct SerializeObject<'a, W>(Object<'a, IoSink<W>>);
+
+impl<'a, W: io::Write> ser::Serializer for &'a mut CanonicalSerializer<W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    type SerializeSeq = SerializeArray<'a, W>;
+    type SerializeTuple = SerializeArray<'a, W>;
+    type SerializeTupleStruct = SerializeArray<'a, W>;
+    type SerializeTupleVariant = SerializeArray<'a, W>;
+    type SerializeMap = SerializeObject<'a, W>;
+    type SerializeStruct = SerializeObject<'a, W>;
+    type SerializeStructVariant = SerializeObject<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CanonError> {
+        self.engine().serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CanonError> {
+        self.engine().serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CanonError> {
+        self.engine().serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CanonError> {
+        self.engine().serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CanonError> {
+        self.engine().serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), CanonError> {
+        self.engine().serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CanonError> {
+        self.engine().serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CanonError> {
+        self.engine().serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CanonError> {
+        self.engine().serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CanonError> {
+        self.engine().serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), CanonError> {
+        self.engine().serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CanonError> {
+        self.engine().serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CanonError> {
+        self.engine().serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CanonError> {
+        self.engine().serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CanonError> {
+        self.engine().serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CanonError> {
+        self.engine().serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<(), CanonError> {
+        self.engine().serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CanonError> {
+        self.engine().serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<(), CanonError> {
+        self.engine().serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), CanonError> {
+        self.engine().serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<(), CanonError> {
+        self.engine().serialize_unit_variant(name, index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.engine().serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        self.engine()
+            .serialize_newtype_variant(name, index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray<'a, W>, CanonError> {
+        self.engine().serialize_seq(len).map(SerializeArray)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray<'a, W>, CanonError> {
+        self.engine().serialize_tuple(len).map(SerializeArray)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray<'a, W>, CanonError> {
+        self.engine()
+            .serialize_tuple_struct(name, len)
+            .map(SerializeArray)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray<'a, W>, CanonError> {
+        self.engine()
+            .serialize_tuple_variant(name, index, variant, len)
+            .map(SerializeArray)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeObject<'a, W>, CanonError> {
+        self.engine().serialize_map(len).map(SerializeObject)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject<'a, W>, CanonError> {
+        self.engine()
+            .serialize_struct(name, len)
+            .map(SerializeObject)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject<'a, W>, CanonError> {
+        self.engine()
+            .serialize_struct_variant(name, index, variant, len)
+            .map(SerializeObject)
+    }
+}
+
+impl<W: io::Write> ser::SerializeSeq for SerializeArray<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        ser::SerializeSeq::serialize_element(&mut self.0, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeSeq::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTuple for SerializeArray<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        ser::SerializeTuple::serialize_element(&mut self.0, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeTuple::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleStruct for SerializeArray<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        ser::SerializeTupleStruct::serialize_field(&mut self.0, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeTupleStruct::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleVariant for SerializeArray<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        ser::SerializeTupleVariant::serialize_field(&mut self.0, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeTupleVariant::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeMap for SerializeObject<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CanonError> {
+        ser::SerializeMap::serialize_key(&mut self.0, key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CanonError> {
+        ser::SerializeMap::serialize_value(&mut self.0, value)
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), CanonError>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        ser::SerializeMap::serialize_entry(&mut self.0, key, value)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeMap::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeStruct for SerializeObject<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        ser::SerializeStruct::serialize_field(&mut self.0, key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), CanonError> {
+        ser::SerializeStruct::skip_field(&mut self.0, key)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeStruct::end(self.0)
+    }
+}
+
+impl<W: io::Write> ser::SerializeStructVariant for SerializeObject<'_, W> {
+    type Ok = ();
+    type Error = CanonError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CanonError> {
+        ser::SerializeStructVariant::serialize_field(&mut self.0, key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), CanonError> {
+        ser::SerializeStructVariant::skip_field(&mut self.0, key)
+    }
+
+    fn end(self) -> Result<(), CanonError> {
+        ser::SerializeStructVariant::end(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    fn via_serializer<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut ser = CanonicalSerializer::new(Vec::new());
+        value.serialize(&mut ser).unwrap();
+        ser.into_inner()
+    }
+
+    #[test]
+    fn matches_canonicalize() {
+        #[derive(serde::Serialize)]
+        enum Shape {
+            Point(i32, i32),
+            Named { z: f64, a: Option<u8> },
+        }
+
+        let values = [
+            json!({"b": [1.0, "\u{1f600}"], "a": {"y": null, "x": 1e21}}),
+            json!([[1, 2], {"k": "v"}]),
+        ];
+        for value in &values {
+            assert_eq!(via_serializer(value), canonicalize(value).unwrap());
+        }
+        for shape in [Shape::Point(1, 2), Shape::Named { z: 0.5, a: None }] {
+            assert_eq!(via_serializer(&shape), canonicalize(&shape).unwrap());
+        }
+        let map = HashMap::from([(3, 'c'), (1, 'a'), (20, 'b')]);
+        assert_eq!(via_serializer(&map), br#"{"1":"a","20":"b","3":"c"}"#);
+    }
+
+    #[test]
+    fn writes_values_back_to_back() {
+        let mut ser = CanonicalSerializer::new(Vec::new());
+        json!({"b": 1, "a": 2}).serialize(&mut ser).unwrap();
+        [true].serialize(&mut ser).unwrap();
+        assert_eq!(ser.into_inner(), br#"{"a":2,"b":1}[true]"#);
+    }
+
+    #[test]
+    fn errors_keep_their_paths() {
+        let mut ser = CanonicalSerializer::new(Vec::new());
+        1.serialize(&mut ser).unwrap();
+        let err = json!({"a": [0.5]})
+            .serialize(&mut ser)
+            .and_then(|()| [0.0, f64::NAN].serialize(&mut ser))
+            .unwrap_err();
+        assert!(matches!(err, CanonError::NonFiniteNumber { ref path } if path == "/1"));
+        assert_eq!(ser.into_inner(), br#"1{"a":[0.5]}[0,"#);
+    }
+}
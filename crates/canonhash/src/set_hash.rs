@@ -0,0 +1,209 @@
+//! Order-independent hashes of collections that can be updated one element
+//! at a time.
+//!
+//! Sorting the elements' canonical forms and hashing the result also
+//! ignores order, but has to start over whenever an element changes. A
+//! [`SetHasher`] instead keeps a running sum that elements are added to and
+//! subtracted from.
+
+use alloc::boxed::Box;
+
+use digest::Digest;
+use serde::Serialize;
+use sha2::{Sha256, Sha512};
+
+use crate::error::CanonError;
+use crate::hash::feed;
+use crate::ser::{Options, Scope};
+
+/// How many 16-bit lanes the running sum has.
+const LANES: usize = 1024;
+
+/// A commutative hash of a multiset of values.
+///
+/// This is a lattice hash in the style of LtHash (Lewi et al., "Securing
+/// Update Propagation with Homomorphic Hashing", 2019). Each element's
+/// canonical SHA-256 is expanded with SHA-512 into 1024 lanes of 16 bits,
+/// and the state is the lane-wise sum modulo 2^16 over the elements. The
+/// sum doesn't depend on order, and [`remove`](Self::remove) subtracts
+/// exactly what [`insert`](Self::insert) added.
+///
+/// On collisions, honestly:
+///
+/// - XOR of digests, or a sum of them modulo 2^256, would be broken by
+///   generalized birthday attacks: an attacker can find a different multiset
+///   with the same sum with far less than 2^128 work. The 16 Kibit state
+///   here is what puts that out of reach; the LtHash paper estimates over
+///   200 bits of security for these parameters, resting on a lattice
+///   problem rather than on SHA-256 alone.
+/// - It is a multiset hash, not a set hash. Inserting a value twice is
+///   different from inserting it once, and removing a value that was never
+///   inserted is not an error; it just gives a state no insertions alone
+///   would reach.
+/// - The expansion is this crate's own, so digests don't interoperate with
+///   other LtHash implementations.
+///
+/// ```
+/// use canonhash::SetHasher;
+///
+/// let mut a = SetHasher::new();
+/// a.insert(&"x").unwrap();
+/// a.insert(&"y").unwrap();
+///
+/// let mut b = SetHasher::new();
+/// for item in ["y", "z", "x"] {
+///     b.insert(&item).unwrap();
+/// }
+/// b.remove(&"z").unwrap();
+/// assert_eq!(a.finalize(), b.finalize());
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct SetHasher {
+    lanes: Box<[u16; LANES]>,
+}
+
+impl SetHasher {
+    /// Returns the hasher of the empty multiset.
+    pub fn new() -> Self {
+        SetHasher {
+            lanes: Box::new([0; LANES]),
+        }
+    }
+
+    /// Adds one occurrence of `item`.
+    pub fn insert<T>(&mut self, item: &T) -> Result<(), CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let lanes = expand(item)?;
+        for (sum, lane) in self.lanes.iter_mut().zip(lanes.iter()) {
+            *sum = sum.wrapping_add(*lane);
+        }
+        Ok(())
+    }
+
+    /// Removes one occurrence of `item`, undoing an [`insert`](Self::insert)
+    /// of it.
+    pub fn remove<T>(&mut self, item: &T) -> Result<(), CanonError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let lanes = expand(item)?;
+        for (sum, lane) in self.lanes.iter_mut().zip(lanes.iter()) {
+            *sum = sum.wrapping_sub(*lane);
+        }
+        Ok(())
+    }
+
+    /// Returns the SHA-256 of the current state. The hasher is unchanged, so
+    /// it can keep being updated.
+    pub fn finalize(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for lane in self.lanes.iter() {
+            hasher.update(lane.to_le_bytes());
+        }
+        hasher.finalize().into()
+    }
+}
+
+impl Default for SetHasher {
+    fn default() -> Self {
+        SetHasher::new()
+    }
+}
+
+impl core::fmt::Debug for SetHasher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("SetHasher")
+            .field(&hex::encode(self.finalize()))
+            .finish()
+    }
+}
+
+/// The lanes `item` contributes: its canonical SHA-256 followed by a block
+/// counter, hashed with SHA-512 for each 32 lanes.
+fn expand<T>(item: &T) -> Result<Box<[u16; LANES]>, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut digest = Sha256::new();
+    feed(&mut digest, item, Options::default(), Scope::default())?;
+    let digest = digest.finalize();
+
+    let mut lanes = Box::new([0; LANES]);
+    for (block, chunk) in lanes.chunks_mut(32).enumerate() {
+        let mut hasher = Sha512::new();
+        hasher.update(b"canonhash set element");
+        hasher.update(digest);
+        hasher.update((block as u32).to_be_bytes());
+        let bytes = hasher.finalize();
+        for (lane, pair) in chunk.iter_mut().zip(bytes.chunks_exact(2)) {
+            *lane = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+    }
+    Ok(lanes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn of(items: &[serde_json::Value]) -> [u8; 32] {
+        let mut hasher = SetHasher::new();
+        for item in items {
+            hasher.insert(item).unwrap();
+        }
+        hasher.finalize()
+    }
+
+    #[test]
+    fn order_does_not_matter() {
+        let a = json!({"id": 1, "tags": ["x"]});
+        let b = json!({"tags": ["x"], "id": 2});
+        let c = json!([1, 2, 3]);
+        assert_eq!(
+            of(&[a.clone(), b.clone(), c.clone()]),
+            of(&[c.clone(), a.clone(), b.clone()])
+        );
+        assert_ne!(of(&[a.clone(), b.clone()]), of(&[a.clone(), c.clone()]));
+    }
+
+    #[test]
+    fn elements_are_compared_by_canonical_form() {
+        assert_eq!(
+            of(&[json!({"a": 1.0, "b": 2})]),
+            of(&[json!({"b": 2, "a": 1})])
+        );
+    }
+
+    #[test]
+    fn remove_undoes_insert() {
+        let mut hasher = SetHasher::new();
+        let empty = hasher.finalize();
+        hasher.insert(&"x").unwrap();
+        hasher.insert(&"y").unwrap();
+        hasher.remove(&"x").unwrap();
+        assert_eq!(hasher.finalize(), of(&[json!("y")]));
+        hasher.remove(&"y").unwrap();
+        assert_eq!(hasher.finalize(), empty);
+        assert_eq!(hasher, SetHasher::new());
+    }
+
+    #[test]
+    fn multiplicity_counts() {
+        assert_ne!(of(&[json!(1)]), of(&[json!(1), json!(1)]));
+        assert_ne!(of(&[]), of(&[json!(1), json!(1)]));
+    }
+
+    #[test]
+    fn errors_leave_the_state_unchanged() {
+        let mut hasher = SetHasher::new();
+        hasher.insert(&1).unwrap();
+        let before = hasher.clone();
+        assert!(hasher.insert(&[f64::NAN]).is_err());
+        assert!(hasher.remove(&[f64::INFINITY]).is_err());
+        assert_eq!(hasher, before);
+    }
+}
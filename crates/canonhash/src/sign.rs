@@ -0,0 +1,100 @@
+//! Ed25519 signatures over canonical forms, enabled by the `ed25519` feature.
+//!
+//! The signed message is the canonical byte sequence itself, not a digest of
+//! it. Ed25519 hashes its input with SHA-512 internally, so hashing first
+//! would only add a second, redundant hash and make signatures incompatible
+//! with other implementations that sign canonical JSON directly.
+//!
+//! ```
+//! use canonhash::sign::{sign, verify};
+//! use ed25519_dalek::SigningKey;
+//! use serde_json::json;
+//!
+//! let key = SigningKey::from_bytes(&[7; 32]);
+//! let doc = json!({"amount": 10, "to": "alice"});
+//! let sig = sign(&key, &doc).unwrap();
+//! assert!(verify(&key.verifying_key(), &doc, &sig).unwrap());
+//! ```
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::Serialize;
+
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+
+/// Signs the canonical form of `value`.
+pub fn sign<T>(key: &SigningKey, value: &T) -> Result<Signature, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(key.sign(&canonicalize(value)?))
+}
+
+/// Returns whether `sig` is a valid signature by `key` over the canonical
+/// form of `value`.
+///
+/// Verification uses `verify_strict`, which also rejects weak public keys and
+/// non-canonical signature encodings. An invalid signature is `Ok(false)`;
+/// only a value that cannot be canonicalized is an error.
+pub fn verify<T>(key: &VerifyingKey, value: &T, sig: &Signature) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(key.verify_strict(&canonicalize(value)?, sig).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&[0x42; 32])
+    }
+
+    #[test]
+    fn signs_canonical_bytes_directly() {
+        let value = json!({"b": [1.0, 2], "a": "x"});
+        let sig = sign(&key(), &value).unwrap();
+        assert_eq!(sig, key().sign(br#"{"a":"x","b":[1,2]}"#));
+    }
+
+    #[test]
+    fn verifies_equivalent_documents() {
+        let sig = sign(&key(), &json!({"b": 1, "a": 2})).unwrap();
+        let reordered = json!({"a": 2, "b": 1.0});
+        assert!(verify(&key().verifying_key(), &reordered, &sig).unwrap());
+    }
+
+    #[test]
+    fn rejects_tampered_values() {
+        let value = json!({"amount": 100, "to": "alice"});
+        let sig = sign(&key(), &value).unwrap();
+        let vk = key().verifying_key();
+        assert!(!verify(&vk, &json!({"amount": 101, "to": "alice"}), &sig).unwrap());
+        assert!(!verify(&vk, &json!({"amount": 100, "to": "alicf"}), &sig).unwrap());
+        assert!(!verify(&vk, &json!({"amount": 100}), &sig).unwrap());
+    }
+
+    #[test]
+    fn rejects_tampered_signature_and_wrong_key() {
+        let value = json!([1, 2, 3]);
+        let sig = sign(&key(), &value).unwrap();
+        let mut bytes = sig.to_bytes();
+        bytes[10] ^= 0x01;
+        let tampered = Signature::from_bytes(&bytes);
+        assert!(!verify(&key().verifying_key(), &value, &tampered).unwrap());
+
+        let other = SigningKey::from_bytes(&[0x43; 32]).verifying_key();
+        assert!(!verify(&other, &value, &sig).unwrap());
+    }
+
+    #[test]
+    fn non_finite_numbers_are_errors() {
+        assert!(matches!(
+            sign(&key(), &[f64::NAN]).unwrap_err(),
+            CanonError::NonFiniteNumber { .. }
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+//! Destinations for canonical output.
+
+use alloc::vec::Vec;
+
+use serde::ser::Error as _;
+
+use crate::error::CanonError;
+
+/// Something the canonical serializer can write bytes to.
+///
+/// The serializer calls `write` with small slices in output order, so an
+/// implementation can forward them straight into a digest without keeping
+/// the document around.
+pub(crate) trait Sink {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError>;
+
+    /// The underlying buffer, if output can be revised after it is written.
+    /// Objects use it to write members in place and reorder them only if
+    /// they turn out to be unsorted.
+    fn as_vec(&mut self) -> Option<&mut Vec<u8>> {
+        None
+    }
+}
+
+impl Sink for Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn as_vec(&mut self) -> Option<&mut Vec<u8>> {
+        Some(self)
+    }
+}
+
+/// Discards output, keeping only its length.
+#[derive(Default)]
+pub(crate) struct CountingSink(pub(crate) usize);
+
+impl Sink for CountingSink {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        self.0 += bytes.len();
+        Ok(())
+    }
+}
+
+/// Checks output against `expected` as it is written, failing at the first
+/// byte that differs.
+pub(crate) struct CompareSink<'a> {
+    pub(crate) expected: &'a [u8],
+    /// How much of `expected` has been matched.
+    pub(crate) matched: usize,
+    /// Whether the writes stopped because of a difference rather than an
+    /// error of the value's own.
+    pub(crate) differs: bool,
+}
+
+impl Sink for CompareSink<'_> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        let end = self.matched + bytes.len();
+        if self.expected.get(self.matched..end) != Some(bytes) {
+            self.differs = true;
+            return Err(CanonError::custom("canonical forms differ"));
+        }
+        self.matched = end;
+        Ok(())
+    }
+}
+
+/// Adapts an `io::Write` into a [`Sink`].
+#[cfg(feature = "std")]
+pub(crate) struct IoSink<W>(pub(crate) W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Sink for IoSink<W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CanonError> {
+        self.0.write_all(bytes).map_err(CanonError::Io)
+    }
+}
@@ -0,0 +1,143 @@
+//! Counts of what a canonical form contains, gathered while it is produced.
+
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use serde::Serialize;
+
+use crate::error::CanonError;
+use crate::ser::{self, Options, Scope};
+
+/// The shape of a canonical form, from [`canonicalize_with_stats`].
+///
+/// Everything is counted as it appears in the output, so an enum variant's
+/// `{"Variant":…}` wrapper is an object. Object keys are not counted as
+/// strings, only string values are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CanonStats {
+    /// Length of the canonical form in bytes.
+    pub byte_len: usize,
+    pub object_count: usize,
+    pub array_count: usize,
+    /// How deeply arrays and objects nest: 0 for a scalar, 1 for `[1]` or
+    /// `{}`, 2 for `[[]]`.
+    pub max_depth: usize,
+    pub string_count: usize,
+    pub number_count: usize,
+}
+
+/// Serializes `value` into its canonical form, as
+/// [`canonicalize`](crate::canonicalize), and reports its [`CanonStats`].
+///
+/// The counts are kept during the one serialization, so this costs a few
+/// additions per value rather than a second pass over the output.
+///
+/// ```
+/// use canonhash::{canonicalize_with_stats, CanonStats};
+/// use serde_json::json;
+///
+/// let (canonical, stats) =
+///     canonicalize_with_stats(&json!({"tags": ["a", "b"], "meta": {"n": 1.5}})).unwrap();
+/// assert_eq!(canonical, br#"{"meta":{"n":1.5},"tags":["a","b"]}"#);
+/// assert_eq!(
+///     stats,
+///     CanonStats {
+///         byte_len: canonical.len(),
+///         object_count: 2,
+///         array_count: 1,
+///         max_depth: 2,
+///         string_count: 2,
+///         number_count: 1,
+///     }
+/// );
+/// ```
+pub fn canonicalize_with_stats<T>(value: &T) -> Result<(Vec<u8>, CanonStats), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let stats = Cell::new(CanonStats::default());
+    let mut buf = Vec::with_capacity(128);
+    let scope = Scope {
+        stats: Some(&stats),
+        ..Scope::default()
+    };
+    ser::serialize_scoped(value, &mut buf, Options::default(), scope)?;
+    let stats = CanonStats {
+        byte_len: buf.len(),
+        ..stats.get()
+    };
+    Ok((buf, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    fn stats_of<T: ?Sized + Serialize>(value: &T) -> CanonStats {
+        let (canonical, stats) = canonicalize_with_stats(value).unwrap();
+        assert_eq!(canonical, canonicalize(value).unwrap());
+        assert_eq!(stats.byte_len, canonical.len());
+        stats
+    }
+
+    #[test]
+    fn counts_scalars_and_containers() {
+        assert_eq!(
+            stats_of(&json!(null)),
+            CanonStats {
+                byte_len: 4,
+                ..CanonStats::default()
+            }
+        );
+        let stats = stats_of(&json!([[[]], {"a": "x", "b": [1, 2.5, true, "y"]}, -3]));
+        assert_eq!(stats.array_count, 4);
+        assert_eq!(stats.object_count, 1);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.string_count, 2);
+        assert_eq!(stats.number_count, 3);
+    }
+
+    #[test]
+    fn enum_variants_count_as_the_objects_they_write() {
+        #[derive(Serialize)]
+        enum Event {
+            Unit,
+            Newtype(u8),
+            Tuple(u8, &'static str),
+            Struct { id: u8 },
+        }
+
+        let stats = stats_of(&[
+            Event::Unit,
+            Event::Newtype(1),
+            Event::Tuple(2, "t"),
+            Event::Struct { id: 3 },
+        ]);
+        assert_eq!(stats.object_count, 4);
+        assert_eq!(stats.array_count, 2);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.string_count, 2);
+        assert_eq!(stats.number_count, 3);
+    }
+
+    #[test]
+    fn numbers_from_parsed_text_count_once() {
+        let parsed: serde_json::Value =
+            serde_json::from_str("[1e2, 123456789012345678901234]").unwrap();
+        let stats = stats_of(&parsed);
+        assert_eq!(stats.number_count, 2);
+        assert_eq!(stats.string_count, 0);
+    }
+
+    #[test]
+    fn errors_are_returned_as_from_canonicalize() {
+        assert!(matches!(
+            canonicalize_with_stats(&[1.0, f64::NAN]),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+//! File paths for a content-addressed store.
+//!
+//! A value is stored under the hex of its canonical digest, in directories
+//! named after the digest's first bytes so no directory grows too large:
+//! with the default depth of 2, a SHA-256 of `abcd12…` lives at
+//! `ab/cd/abcd12…`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::algorithm::HashAlgorithm;
+use crate::canon::canonicalize;
+use crate::encoding::{encode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::hash::digest_bytes;
+
+/// How many directory levels [`store_path`] and [`write_content`] use.
+pub const DEFAULT_SHARD_DEPTH: usize = 2;
+
+/// Returns the digest of `value` under `alg` and its path in a store, using
+/// [`DEFAULT_SHARD_DEPTH`] levels of directories.
+///
+/// ```
+/// use canonhash::{store_path, HashAlgorithm};
+///
+/// let (digest, path) = store_path(&[1, 2, 3], HashAlgorithm::Sha256).unwrap();
+/// let name = hex::encode(&digest);
+/// assert_eq!(path, std::path::Path::new(&name[..2]).join(&name[2..4]).join(&name));
+/// ```
+pub fn store_path<T>(value: &T, alg: HashAlgorithm) -> Result<(Vec<u8>, PathBuf), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    store_path_with_depth(value, alg, DEFAULT_SHARD_DEPTH)
+}
+
+/// Like [`store_path`], with `depth` levels of directories, one per leading
+/// byte of the digest. A depth of 0 puts every file in one directory.
+pub fn store_path_with_depth<T>(
+    value: &T,
+    alg: HashAlgorithm,
+    depth: usize,
+) -> Result<(Vec<u8>, PathBuf), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let digest = digest_bytes(alg, &canonicalize(value)?);
+    let path = shard_path(&digest, depth);
+    Ok((digest, path))
+}
+
+/// Returns the relative path a digest is stored under, for looking up
+/// content whose digest is already known. `depth` is capped at the digest
+/// length.
+pub fn shard_path(digest: &[u8], depth: usize) -> PathBuf {
+    let name = encode_digest(digest, DigestEncoding::Hex);
+    let mut path: PathBuf = digest
+        .iter()
+        .take(depth)
+        .map(|byte| hex::encode([*byte]))
+        .collect();
+    path.push(name);
+    path
+}
+
+/// Canonicalizes `value` and writes it into the store at `base_dir`, at the
+/// [`store_path`] of its SHA-256. Returns the digest and the full path.
+///
+/// Nothing is written if the file already exists: its name is the digest,
+/// so it already holds these bytes. A new file is written beside its final
+/// name and renamed into place, so a reader never sees it half written.
+pub fn write_content<T>(
+    base_dir: impl AsRef<Path>,
+    value: &T,
+) -> Result<(Vec<u8>, PathBuf), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let canonical = canonicalize(value)?;
+    let digest = digest_bytes(HashAlgorithm::Sha256, &canonical);
+    let path = base_dir
+        .as_ref()
+        .join(shard_path(&digest, DEFAULT_SHARD_DEPTH));
+    if path.exists() {
+        return Ok((digest, path));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension(format!("partial-{}", std::process::id()));
+    std::fs::write(&partial, &canonical)?;
+    if let Err(err) = std::fs::rename(&partial, &path) {
+        let _ = std::fs::remove_file(&partial);
+        // Another writer got there first with the same content.
+        if !path.exists() {
+            return Err(err.into());
+        }
+    }
+    Ok((digest, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn store_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("canonhash-store-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn sharding_depth_is_configurable() {
+        let digest = [0xab, 0xcd, 0xef];
+        assert_eq!(shard_path(&digest, 0), Path::new("abcdef"));
+        assert_eq!(shard_path(&digest, 2), Path::new("ab/cd/abcdef"));
+        assert_eq!(shard_path(&digest, 9), Path::new("ab/cd/ef/abcdef"));
+
+        let value = json!({"b": 1, "a": 2});
+        let (digest, path) = store_path_with_depth(&value, HashAlgorithm::Sha512, 1).unwrap();
+        assert_eq!(digest.len(), 64);
+        assert_eq!(path, shard_path(&digest, 1));
+        assert_eq!(
+            store_path(&json!({"a": 2, "b": 1}), HashAlgorithm::Sha512)
+                .unwrap()
+                .0,
+            digest
+        );
+    }
+
+    #[test]
+    fn write_content_is_idempotent() {
+        let base = store_dir("idempotent");
+        let value = json!({"b": [1, 2], "a": "x"});
+        let (digest, path) = write_content(&base, &value).unwrap();
+        assert_eq!(
+            path,
+            base.join(store_path(&value, HashAlgorithm::Sha256).unwrap().1)
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), br#"{"a":"x","b":[1,2]}"#);
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        let again = write_content(&base, &json!({"a": "x", "b": [1, 2]})).unwrap();
+        assert_eq!(again, (digest, path.clone()));
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn write_content_writes_nothing_on_error() {
+        let base = store_dir("error");
+        assert!(write_content(&base, &[f64::NAN]).is_err());
+        assert!(!base.exists());
+    }
+}
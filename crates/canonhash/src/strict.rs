@@ -0,0 +1,206 @@
+//! Checks that the `_strict` entry points run over JSON text before parsing
+//! it.
+//!
+//! RFC 8259 leaves the meaning of repeated object keys to the parser, and
+//! `serde_json` keeps the last one. Once text is parsed into a `Value` the
+//! repeat is gone, so the check runs over the text itself, as a deserializer
+//! that builds nothing and only tracks the keys of each object it is in.
+//!
+//! Lone surrogate escapes such as `"\uD800"` encode no character, so text
+//! containing them has no canonical form. `serde_json` already refuses them,
+//! but as a generic syntax error; [`check_surrogates`] names the problem.
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use core::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::error::CanonError;
+
+/// Returns [`CanonError::InvalidSurrogate`] for the first `\u` escape in
+/// `json` that is a high surrogate not followed by an escaped low surrogate,
+/// or a low surrogate not preceded by a high one.
+///
+/// Escapes only occur inside strings, so the text doesn't need parsing;
+/// anything else wrong with it is left to the parser.
+pub(crate) fn check_surrogates(json: &str) -> Result<(), CanonError> {
+    let bytes = json.as_bytes();
+    let escape_at = |i: usize| -> Option<u16> {
+        let hex = bytes.get(i..i + 6)?.strip_prefix(b"\\u")?;
+        u16::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+        match escape_at(i) {
+            Some(0xd800..=0xdbff) => match escape_at(i + 6) {
+                Some(0xdc00..=0xdfff) => i += 12,
+                _ => return Err(CanonError::InvalidSurrogate { offset: i }),
+            },
+            Some(0xdc00..=0xdfff) => return Err(CanonError::InvalidSurrogate { offset: i }),
+            Some(_) => i += 6,
+            // `\\`, `\"` and the other two-byte escapes.
+            None => i += 2,
+        }
+    }
+    Ok(())
+}
+
+/// Returns [`CanonError::DuplicateKey`] for the first object in `json` that
+/// repeats a key, comparing keys after unescaping, or [`CanonError::Parse`] if
+/// `json` is not valid JSON.
+pub(crate) fn check_duplicates(json: &str) -> Result<(), CanonError> {
+    let mut duplicate = None;
+    let mut de = serde_json::Deserializer::from_str(json);
+    let result = Check {
+        duplicate: &mut duplicate,
+    }
+    .deserialize(&mut de)
+    .and_then(|()| de.end());
+    match (result, duplicate) {
+        (_, Some(key)) => Err(CanonError::DuplicateKey { key }),
+        (Err(err), None) => Err(err.into()),
+        (Ok(()), None) => Ok(()),
+    }
+}
+
+/// Visits one JSON value. The repeated key is reported through `duplicate`
+/// so it survives the conversion to a `serde_json::Error`.
+struct Check<'a> {
+    duplicate: &'a mut Option<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for Check<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Check<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(Check {
+                duplicate: &mut *self.duplicate,
+            })?
+            .is_some()
+        {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut seen = BTreeSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if seen.contains(&key) {
+                *self.duplicate = Some(key);
+                return Err(de::Error::custom("duplicate object key"));
+            }
+            map.next_value_seed(Check {
+                duplicate: &mut *self.duplicate,
+            })?;
+            seen.insert(key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn duplicate(json: &str) -> Option<String> {
+        match check_duplicates(json) {
+            Err(CanonError::DuplicateKey { key }) => Some(key),
+            Ok(()) => None,
+            Err(err) => panic!("unexpected error for {json}: {err}"),
+        }
+    }
+
+    #[test]
+    fn finds_duplicates_at_any_depth() {
+        assert_eq!(duplicate(r#"{"a":1,"a":2}"#).as_deref(), Some("a"));
+        assert_eq!(
+            duplicate(r#"[{"x":{"b":[],"b":null}}]"#).as_deref(),
+            Some("b")
+        );
+        assert_eq!(duplicate(r#"{"k":{"a":1},"m":{"a":2}}"#), None);
+        assert_eq!(duplicate(r#"[1, "a", true, null, 1.5, [], {}]"#), None);
+    }
+
+    #[test]
+    fn compares_keys_after_unescaping() {
+        assert_eq!(duplicate(r#"{"a":1,"\u0061":2}"#).as_deref(), Some("a"));
+        assert_eq!(duplicate(r#"{"a":1,"A":2}"#), None);
+    }
+
+    #[test]
+    fn invalid_text_is_a_parse_error() {
+        for json in ["{", r#"{"a":1} x"#, r#"{"a":1,"#] {
+            assert!(
+                matches!(check_duplicates(json), Err(CanonError::Parse(_))),
+                "{json}"
+            );
+        }
+    }
+
+    fn surrogate_offset(json: &str) -> Option<usize> {
+        match check_surrogates(json) {
+            Err(CanonError::InvalidSurrogate { offset }) => Some(offset),
+            Ok(()) => None,
+            Err(err) => panic!("unexpected error for {json}: {err}"),
+        }
+    }
+
+    #[test]
+    fn finds_lone_surrogates() {
+        assert_eq!(surrogate_offset(r#""\uD800""#), Some(1));
+        assert_eq!(surrogate_offset(r#"["x", "\udc00"]"#), Some(7));
+        assert_eq!(surrogate_offset(r#"{"\uD800A": 1}"#), Some(2));
+        assert_eq!(surrogate_offset(r#""\uDC00\uD800""#), Some(1));
+        assert_eq!(surrogate_offset(r#""\uD800A""#), Some(1));
+        assert_eq!(surrogate_offset(r#""\uD83D\uDE00\uD800""#), Some(13));
+    }
+
+    #[test]
+    fn accepts_pairs_and_other_escapes() {
+        assert_eq!(surrogate_offset(r#""\uD83D\uDE00""#), None);
+        assert_eq!(surrogate_offset(r#""\\uD800""#), None);
+        assert_eq!(surrogate_offset(r#""\"\u00e9\n""#), None);
+        assert_eq!(surrogate_offset(r#""😀""#), None);
+    }
+}
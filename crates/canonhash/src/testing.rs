@@ -0,0 +1,193 @@
+//! Snapshot tests of canonical forms.
+//!
+//! [`assert_canonical_snapshot!`](crate::assert_canonical_snapshot) records
+//! the canonical form of a value in a `.jcs` file the first time it runs and
+//! fails if the form changes after that, so an accidental change to what a
+//! type hashes to shows up in CI.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::canon::canonicalize;
+use crate::error::CanonError;
+use crate::fixture::{excerpt, first_difference};
+
+/// Set to `1` to rewrite every snapshot with the current canonical form
+/// instead of comparing against it.
+pub const UPDATE_VAR: &str = "UPDATE_SNAPSHOTS";
+
+/// Why a snapshot check failed.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The value could not be canonicalized.
+    Canonicalize(CanonError),
+    /// The snapshot file could not be read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// The canonical form differs from the snapshot, first at byte `offset`.
+    Mismatch {
+        path: PathBuf,
+        offset: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Canonicalize(err) => write!(f, "failed to canonicalize value: {err}"),
+            SnapshotError::Io { path, source } => {
+                write!(f, "snapshot {}: {source}", path.display())
+            }
+            SnapshotError::Mismatch {
+                path,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "canonical form differs from snapshot {} at byte {offset}: expected {:?}, got {:?} \
+                 (rerun with {UPDATE_VAR}=1 to accept)",
+                path.display(),
+                excerpt(expected, *offset),
+                excerpt(actual, *offset),
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Canonicalize(err) => Some(err),
+            SnapshotError::Io { source, .. } => Some(source),
+            SnapshotError::Mismatch { .. } => None,
+        }
+    }
+}
+
+/// Compares the canonical form of `value` with the snapshot at `path`.
+///
+/// The snapshot is written, along with any missing parent directories, if
+/// it doesn't exist yet or if [`UPDATE_VAR`] is set to `1`. It holds exactly
+/// the canonical bytes, with no trailing newline.
+pub fn check_snapshot<T>(value: &T, path: impl AsRef<Path>) -> Result<(), SnapshotError>
+where
+    T: ?Sized + Serialize,
+{
+    let update = std::env::var_os(UPDATE_VAR).is_some_and(|v| v == "1");
+    check(value, path.as_ref(), update)
+}
+
+fn check<T>(value: &T, path: &Path, update: bool) -> Result<(), SnapshotError>
+where
+    T: ?Sized + Serialize,
+{
+    let actual = canonicalize(value).map_err(SnapshotError::Canonicalize)?;
+    let io_error = |source| SnapshotError::Io {
+        path: path.to_owned(),
+        source,
+    };
+    let expected = match std::fs::read(path) {
+        Ok(expected) if !update => expected,
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(io_error(err)),
+        _ => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(io_error)?;
+            }
+            return std::fs::write(path, &actual).map_err(io_error);
+        }
+    };
+    if actual != expected {
+        return Err(SnapshotError::Mismatch {
+            path: path.to_owned(),
+            offset: first_difference(&actual, &expected),
+            expected: String::from_utf8_lossy(&expected).into_owned(),
+            actual: String::from_utf8_lossy(&actual).into_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Asserts that the canonical form of a value matches a snapshot file,
+/// recording it on first run.
+///
+/// A relative path is taken from the calling crate's manifest directory.
+/// On a mismatch the panic message gives the first differing byte and the
+/// text around it. See [`check_snapshot`](crate::testing::check_snapshot).
+///
+/// ```no_run
+/// #[derive(serde::Serialize)]
+/// struct Config {
+///     version: u32,
+/// }
+///
+/// canonhash::assert_canonical_snapshot!(Config { version: 1 }, "tests/snapshots/config.jcs");
+/// ```
+#[macro_export]
+macro_rules! assert_canonical_snapshot {
+    ($value:expr, $path:expr $(,)?) => {
+        let path = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path);
+        if let Err(err) = $crate::testing::check_snapshot(&$value, &path) {
+            panic!("{err}");
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("canonhash-snapshots-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn records_then_compares() {
+        let path = snapshot_path("records/value.jcs");
+        let _ = std::fs::remove_file(&path);
+        check(&json!({"b": 1, "a": [true]}), &path, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), br#"{"a":[true],"b":1}"#);
+
+        check(&json!({"a": [true], "b": 1}), &path, false).unwrap();
+        match check(&json!({"a": [false], "b": 1}), &path, false).unwrap_err() {
+            SnapshotError::Mismatch { offset, .. } => assert_eq!(offset, 6),
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), br#"{"a":[true],"b":1}"#);
+    }
+
+    #[test]
+    fn update_rewrites_the_snapshot() {
+        let path = snapshot_path("update.jcs");
+        check(&[1], &path, true).unwrap();
+        check(&[2], &path, true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"[2]");
+        assert!(check(&[1], &path, false).is_err());
+    }
+
+    #[test]
+    fn mismatch_message_names_the_file_and_difference() {
+        let path = snapshot_path("message.jcs");
+        check(&"abc", &path, true).unwrap();
+        let message = check(&"abd", &path, false).unwrap_err().to_string();
+        assert!(message.contains("message.jcs"), "{message}");
+        assert!(message.contains("at byte 3"), "{message}");
+        assert!(message.contains(UPDATE_VAR), "{message}");
+    }
+
+    #[test]
+    fn macro_passes_on_a_matching_snapshot() {
+        let path = snapshot_path("macro.jcs");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"[1,2]").unwrap();
+        crate::assert_canonical_snapshot!([1, 2], &path);
+    }
+}
@@ -0,0 +1,430 @@
+//! Canonicalization of a stream of JSON tokens, for input that isn't a serde
+//! value or a whole text, such as events from a SAX-style parser or a custom
+//! transport.
+//!
+//! [`CanonTokenSink`] takes the tokens in document order and produces the
+//! same bytes [`canonicalize`](crate::canonicalize) would for the value they
+//! describe. Arrays and scalars are written as they arrive; each object's
+//! members are held until it ends, so they can be sorted.
+//!
+//! The serde serializer doesn't go through this sink. It writes straight to
+//! a digest or writer and applies the [`Canonicalizer`](crate::Canonicalizer)
+//! options, limits, stats and trace as it goes; routing it through tokens
+//! would allocate for every value and leave those features nowhere to live.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde_json::Number;
+
+use crate::error::CanonError;
+use crate::ser::{sort_members, write_str};
+use crate::value::write_number;
+
+/// One event of a JSON document, in the order a parser meets them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonToken<'a> {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// An object member's name, followed by the tokens of its value.
+    Key(&'a str),
+    /// A string value, unescaped.
+    String(&'a str),
+    /// A number as JSON text, such as `"1e2"` or `"-0.5"`. It is written as
+    /// `serde_json` would parse it, so `"1e2"` becomes `100`.
+    Number(&'a str),
+    Bool(bool),
+    Null,
+}
+
+/// Builds the canonical form of one JSON value from its [`JsonToken`]s.
+///
+/// A token that can't come next, such as a value in an object without a
+/// key before it or an `EndArray` with no array open, is
+/// [`CanonError::TokenStream`] with its position in the stream. The sink
+/// should be dropped after an error.
+///
+/// ```
+/// use canonhash::token_sink::{CanonTokenSink, JsonToken};
+///
+/// let mut sink = CanonTokenSink::new();
+/// for token in [
+///     JsonToken::StartObject,
+///     JsonToken::Key("b"),
+///     JsonToken::StartArray,
+///     JsonToken::Number("1e2"),
+///     JsonToken::Null,
+///     JsonToken::EndArray,
+///     JsonToken::Key("a"),
+///     JsonToken::String("x"),
+///     JsonToken::EndObject,
+/// ] {
+///     sink.push(token).unwrap();
+/// }
+/// assert_eq!(sink.finish().unwrap(), br#"{"a":"x","b":[100,null]}"#);
+/// ```
+#[derive(Debug, Default)]
+pub struct CanonTokenSink {
+    out: Vec<u8>,
+    /// The arrays and objects currently open, innermost last.
+    stack: Vec<Frame>,
+    /// The indices in `stack` of the open objects, innermost last.
+    objects: Vec<usize>,
+    /// How many tokens have been pushed.
+    position: usize,
+    /// Whether the root value has ended.
+    done: bool,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Array {
+        len: usize,
+    },
+    Object {
+        members: Vec<(String, Vec<u8>)>,
+        /// The key whose value is being read, if any.
+        key: Option<String>,
+        /// The canonical form of that value so far.
+        value: Vec<u8>,
+    },
+}
+
+impl CanonTokenSink {
+    /// Returns a sink expecting the first token of a value.
+    pub fn new() -> Self {
+        CanonTokenSink::default()
+    }
+
+    /// Takes the next token.
+    pub fn push(&mut self, token: JsonToken<'_>) -> Result<(), CanonError> {
+        let position = self.position;
+        self.position += 1;
+        let invalid = |message| CanonError::TokenStream { position, message };
+        match token {
+            JsonToken::Key(name) => match self.stack.last_mut() {
+                Some(Frame::Object {
+                    key: key @ None, ..
+                }) => *key = Some(name.into()),
+                Some(Frame::Object { .. }) => return Err(invalid("key where a value belongs")),
+                _ => return Err(invalid("key outside an object")),
+            },
+            JsonToken::StartArray => {
+                self.value_start().map_err(invalid)?;
+                self.target().push(b'[');
+                self.stack.push(Frame::Array { len: 0 });
+            }
+            JsonToken::EndArray => {
+                if !matches!(self.stack.last(), Some(Frame::Array { .. })) {
+                    return Err(invalid("end of an array that isn't open"));
+                }
+                self.stack.pop();
+                self.target().push(b']');
+                self.value_end();
+            }
+            JsonToken::StartObject => {
+                self.value_start().map_err(invalid)?;
+                self.objects.push(self.stack.len());
+                self.stack.push(Frame::Object {
+                    members: Vec::new(),
+                    key: None,
+                    value: Vec::new(),
+                });
+            }
+            JsonToken::EndObject => {
+                match self.stack.last() {
+                    Some(Frame::Object { key: None, .. }) => {}
+                    Some(Frame::Object { .. }) => return Err(invalid("key without a value")),
+                    _ => return Err(invalid("end of an object that isn't open")),
+                }
+                self.objects.pop();
+                let Some(Frame::Object { mut members, .. }) = self.stack.pop() else {
+                    unreachable!("checked above")
+                };
+                sort_members(&mut members)?;
+                let out = self.target();
+                out.push(b'{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.push(b',');
+                    }
+                    write_str(out, key)?;
+                    out.push(b':');
+                    out.extend_from_slice(value);
+                }
+                out.push(b'}');
+                self.value_end();
+            }
+            JsonToken::String(s) => {
+                self.value_start().map_err(invalid)?;
+                write_str(self.target(), s)?;
+                self.value_end();
+            }
+            JsonToken::Number(text) => {
+                let number = parse_number(text).ok_or_else(|| invalid("invalid number"))?;
+                self.value_start().map_err(invalid)?;
+                write_number(self.target(), &number)?;
+                self.value_end();
+            }
+            JsonToken::Bool(v) => {
+                self.value_start().map_err(invalid)?;
+                let text: &[u8] = if v { b"true" } else { b"false" };
+                self.target().extend_from_slice(text);
+                self.value_end();
+            }
+            JsonToken::Null => {
+                self.value_start().map_err(invalid)?;
+                self.target().extend_from_slice(b"null");
+                self.value_end();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the canonical form, or [`CanonError::TokenStream`] if the
+    /// value isn't complete.
+    pub fn finish(self) -> Result<Vec<u8>, CanonError> {
+        if !self.done {
+            return Err(CanonError::TokenStream {
+                position: self.position,
+                message: "stream ended inside the value",
+            });
+        }
+        Ok(self.out)
+    }
+
+    /// Checks that a value may start here, and writes the comma before it
+    /// in an array.
+    fn value_start(&mut self) -> Result<(), &'static str> {
+        match self.stack.last_mut() {
+            None if self.done => return Err("token after the end of the value"),
+            None => {}
+            Some(Frame::Array { len }) => {
+                *len += 1;
+                if *len > 1 {
+                    self.target().push(b',');
+                }
+            }
+            Some(Frame::Object { key: None, .. }) => return Err("value without a key"),
+            Some(Frame::Object { .. }) => {}
+        }
+        Ok(())
+    }
+
+    /// Records that a value has been written in full.
+    fn value_end(&mut self) {
+        match self.stack.last_mut() {
+            None => self.done = true,
+            Some(Frame::Object {
+                members,
+                key,
+                value,
+            }) => {
+                if let Some(key) = key.take() {
+                    members.push((key, core::mem::take(value)));
+                }
+            }
+            Some(Frame::Array { .. }) => {}
+        }
+    }
+
+    /// Where the current value is written: the value buffer of the
+    /// innermost open object, or the output.
+    fn target(&mut self) -> &mut Vec<u8> {
+        let Some(&index) = self.objects.last() else {
+            return &mut self.out;
+        };
+        match &mut self.stack[index] {
+            Frame::Object { value, .. } => value,
+            Frame::Array { .. } => unreachable!("`objects` only indexes object frames"),
+        }
+    }
+}
+
+/// Parses `text` if it is exactly one JSON number, with no whitespace.
+fn parse_number(text: &str) -> Option<Number> {
+    let plain = text
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'));
+    if !plain {
+        return None;
+    }
+    serde_json::from_str(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    /// Pushes the tokens a parser would produce for `value`.
+    fn tokens(sink: &mut CanonTokenSink, value: &Value) {
+        match value {
+            Value::Null => sink.push(JsonToken::Null).unwrap(),
+            Value::Bool(v) => sink.push(JsonToken::Bool(*v)).unwrap(),
+            Value::Number(n) => sink.push(JsonToken::Number(&n.to_string())).unwrap(),
+            Value::String(s) => sink.push(JsonToken::String(s)).unwrap(),
+            Value::Array(elements) => {
+                sink.push(JsonToken::StartArray).unwrap();
+                for element in elements {
+                    tokens(sink, element);
+                }
+                sink.push(JsonToken::EndArray).unwrap();
+            }
+            Value::Object(members) => {
+                sink.push(JsonToken::StartObject).unwrap();
+                for (key, value) in members {
+                    sink.push(JsonToken::Key(key)).unwrap();
+                    tokens(sink, value);
+                }
+                sink.push(JsonToken::EndObject).unwrap();
+            }
+        }
+    }
+
+    fn via_tokens(value: &Value) -> Vec<u8> {
+        let mut sink = CanonTokenSink::new();
+        tokens(&mut sink, value);
+        sink.finish().unwrap()
+    }
+
+    #[test]
+    fn matches_canonicalize() {
+        for value in [
+            json!(null),
+            json!("\u{1f600}\n"),
+            json!([]),
+            json!({}),
+            json!([[[]], {}, [{}]]),
+            json!({"b": [1, 2.5, -0.0, 1e21], "a": {"\u{e000}": 1, "\u{1f600}": 2}}),
+            json!([{"z": {"y": [true, false, null]}, "a": []}, "s"]),
+        ] {
+            assert_eq!(via_tokens(&value), canonicalize(&value).unwrap(), "{value}");
+        }
+    }
+
+    #[test]
+    fn number_text_is_normalized() {
+        for (text, expected) in [("1e2", "100"), ("-0", "0"), ("0.50", "0.5"), ("1E400", "")] {
+            let mut sink = CanonTokenSink::new();
+            match sink.push(JsonToken::Number(text)) {
+                Ok(()) => assert_eq!(sink.finish().unwrap(), expected.as_bytes()),
+                Err(err) => assert!(expected.is_empty(), "{text}: {err}"),
+            }
+        }
+        for text in ["", " 1", "01", "1.", "+1", "0x10", "NaN"] {
+            let mut sink = CanonTokenSink::new();
+            assert!(
+                matches!(
+                    sink.push(JsonToken::Number(text)),
+                    Err(CanonError::TokenStream { position: 0, .. })
+                ),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn out_of_place_tokens_are_rejected() {
+        use JsonToken::*;
+
+        for (stream, at) in [
+            (&[Key("a")][..], 0),
+            (&[StartObject, Null][..], 1),
+            (&[StartObject, Key("a"), Key("b")][..], 2),
+            (&[StartObject, Key("a"), EndObject][..], 2),
+            (&[StartArray, EndObject][..], 1),
+            (&[StartObject, EndArray][..], 1),
+            (&[Null, Null][..], 1),
+            (&[EndArray][..], 0),
+        ] {
+            let mut sink = CanonTokenSink::new();
+            let err = stream
+                .iter()
+                .find_map(|token| sink.push(*token).err())
+                .unwrap();
+            assert!(
+                matches!(err, CanonError::TokenStream { position, .. } if position == at),
+                "{stream:?}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_rejected_end_leaves_the_sink_as_it_was() {
+        use JsonToken::*;
+
+        let mut sink = CanonTokenSink::new();
+        for token in [StartArray, StartObject, Key("a")] {
+            sink.push(token).unwrap();
+        }
+        assert!(sink.push(EndObject).is_err());
+        assert!(sink.push(EndArray).is_err());
+        for token in [StartArray, EndArray, EndObject, EndArray] {
+            sink.push(token).unwrap();
+        }
+        assert_eq!(sink.finish().unwrap(), br#"[{"a":[]}]"#);
+    }
+
+    #[test]
+    fn incomplete_streams_and_duplicate_keys() {
+        let mut sink = CanonTokenSink::new();
+        sink.push(JsonToken::StartArray).unwrap();
+        assert!(matches!(
+            sink.finish(),
+            Err(CanonError::TokenStream { position: 1, .. })
+        ));
+        assert!(CanonTokenSink::new().finish().is_err());
+
+        let mut sink = CanonTokenSink::new();
+        let mut result = Ok(());
+        for token in [
+            JsonToken::StartObject,
+            JsonToken::Key("k"),
+            JsonToken::Null,
+            JsonToken::Key("k"),
+            JsonToken::Bool(true),
+            JsonToken::EndObject,
+        ] {
+            result = sink.push(token);
+        }
+        assert!(matches!(result, Err(CanonError::DuplicateKey { key }) if key == "k"));
+    }
+
+    #[test]
+    fn duplicate_keys_are_reported_like_the_serializer_does() {
+        use serde::ser::{Serialize, SerializeMap, Serializer};
+
+        // UTF-8 order would name U+E000 first; UTF-16 order names the emoji.
+        const KEYS: [&str; 4] = ["\u{e000}", "\u{1f600}", "\u{e000}", "\u{1f600}"];
+        struct Repeats;
+        impl Serialize for Repeats {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(None)?;
+                for key in KEYS {
+                    map.serialize_entry(key, &0)?;
+                }
+                map.end()
+            }
+        }
+
+        let mut sink = CanonTokenSink::new();
+        sink.push(JsonToken::StartObject).unwrap();
+        for key in KEYS {
+            sink.push(JsonToken::Key(key)).unwrap();
+            sink.push(JsonToken::Number("0")).unwrap();
+        }
+        let from_tokens = sink.push(JsonToken::EndObject).unwrap_err();
+        let from_serde = canonicalize(&Repeats).unwrap_err();
+        for err in [from_tokens, from_serde] {
+            assert!(
+                matches!(err, CanonError::DuplicateKey { ref key } if key == "\u{1f600}"),
+                "{err}"
+            );
+        }
+    }
+}
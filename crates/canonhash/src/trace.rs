@@ -0,0 +1,240 @@
+//! A record of what the serializer did, for debugging hash mismatches.
+//!
+//! [`canonicalize_traced`] returns the same bytes as
+//! [`canonicalize`](crate::canonicalize) together with an event for every
+//! place where the canonical form differs from what a naive serialization of
+//! the value would give: objects whose members were reordered and numbers
+//! that were written differently. Arrays are never reordered, so they never
+//! appear. Without the `trace` feature none of this is compiled in.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use serde::Serialize;
+
+use crate::error::{push_pointer_segment, CanonError};
+use crate::ser::{cmp_utf16, Options, Serializer};
+use crate::sink::Sink;
+
+/// One decision taken while canonicalizing. Every `path` is a JSON Pointer
+/// (RFC 6901) into the value, empty for the value itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CanonEvent {
+    /// The members of the object at `path` did not arrive in UTF-16 key order
+    /// and were reordered. `before` is the order they were serialized in.
+    KeySorted {
+        path: String,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    /// The number at `path` was written as `to` rather than `from`. For a
+    /// float `from` is its Rust `{:?}` form, for an arbitrary-precision
+    /// `serde_json::Number` its original text.
+    NumberReformatted {
+        path: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Canonicalizes `value` and returns the events describing how the output
+/// was arrived at, in the order they happened.
+///
+/// ```
+/// use canonhash::trace::{canonicalize_traced, CanonEvent};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Nested {
+///     object: f64,
+///     array: Vec<u32>,
+/// }
+///
+/// let (bytes, events) = canonicalize_traced(&Nested { object: 2.0, array: vec![3, 1, 2] }).unwrap();
+/// assert_eq!(bytes, br#"{"array":[3,1,2],"object":2}"#);
+/// assert!(matches!(&events[1], CanonEvent::KeySorted { after, .. } if after == &["array", "object"]));
+/// ```
+pub fn canonicalize_traced<T>(value: &T) -> Result<(Vec<u8>, Vec<CanonEvent>), CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let tracer = Tracer::default();
+    let mut buf = Vec::new();
+    value.serialize(Serializer::with_trace(
+        &mut buf,
+        Options::default(),
+        &tracer,
+    ))?;
+    Ok((buf, tracer.events.into_inner()))
+}
+
+/// Collects events and the path of the value being serialized.
+#[derive(Default)]
+pub(crate) struct Tracer {
+    path: RefCell<String>,
+    events: RefCell<Vec<CanonEvent>>,
+}
+
+impl Tracer {
+    /// Appends `segments` to the current path and returns its previous
+    /// length.
+    pub(crate) fn enter<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> usize {
+        let mut path = self.path.borrow_mut();
+        let mark = path.len();
+        for segment in segments {
+            push_pointer_segment(&mut path, segment);
+        }
+        mark
+    }
+
+    pub(crate) fn leave(&self, mark: usize) {
+        self.path.borrow_mut().truncate(mark);
+    }
+
+    /// Records that members arriving as `keys` were reordered.
+    pub(crate) fn keys_sorted<'k>(&self, keys: impl Iterator<Item = &'k str>) {
+        let before: Vec<String> = keys.map(ToString::to_string).collect();
+        let mut after = before.clone();
+        after.sort_by(|a, b| cmp_utf16(a, b));
+        self.push(|path| CanonEvent::KeySorted {
+            path,
+            before,
+            after,
+        });
+    }
+
+    /// Writes a number through `write`, recording an event if the result is
+    /// not `from`.
+    pub(crate) fn reformat<S: ?Sized + Sink>(
+        &self,
+        out: &mut S,
+        from: &str,
+        write: impl FnOnce(&mut Vec<u8>) -> Result<(), CanonError>,
+    ) -> Result<(), CanonError> {
+        let mut to = Vec::new();
+        write(&mut to)?;
+        if to != from.as_bytes() {
+            let text = String::from_utf8_lossy(&to).into_owned();
+            self.push(|path| CanonEvent::NumberReformatted {
+                path,
+                from: from.to_string(),
+                to: text,
+            });
+        }
+        out.write(&to)
+    }
+
+    fn push(&self, event: impl FnOnce(String) -> CanonEvent) {
+        let path = self.path.borrow().clone();
+        self.events.borrow_mut().push(event(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    #[derive(Serialize)]
+    struct Nested {
+        zeta: &'static str,
+        array: Vec<u32>,
+        alpha: &'static str,
+    }
+
+    #[test]
+    fn output_matches_canonicalize() {
+        let value = json!({"b": [1.0, 1e21, {"y": 0.5, "x": 2}], "a": null});
+        let (bytes, _) = canonicalize_traced(&value).unwrap();
+        assert_eq!(bytes, canonicalize(&value).unwrap());
+    }
+
+    #[test]
+    fn reports_sorted_objects_but_not_arrays() {
+        let value = BTreeMap::from([(
+            "nested",
+            Nested {
+                zeta: "last",
+                array: vec![3, 1, 2],
+                alpha: "first",
+            },
+        )]);
+        let (bytes, events) = canonicalize_traced(&value).unwrap();
+        assert_eq!(
+            bytes,
+            br#"{"nested":{"alpha":"first","array":[3,1,2],"zeta":"last"}}"#
+        );
+        assert_eq!(
+            events,
+            [CanonEvent::KeySorted {
+                path: "/nested".into(),
+                before: vec!["zeta".into(), "array".into(), "alpha".into()],
+                after: vec!["alpha".into(), "array".into(), "zeta".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_reformatted_numbers_with_paths() {
+        let value = (2.0_f64, 0.5_f64, [1e21_f64], 0.1_f32, 7_u8);
+        let (bytes, events) = canonicalize_traced(&value).unwrap();
+        assert_eq!(bytes, b"[2,0.5,[1e+21],0.10000000149011612,7]");
+        let reformatted = |path: &str, from: &str, to: &str| CanonEvent::NumberReformatted {
+            path: path.into(),
+            from: from.into(),
+            to: to.into(),
+        };
+        assert_eq!(
+            events,
+            [
+                reformatted("/0", "2.0", "2"),
+                reformatted("/2/0", "1e21", "1e+21"),
+                reformatted("/3", "0.1", "0.10000000149011612"),
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_name_enum_variants() {
+        #[derive(Serialize)]
+        enum Shape {
+            Rect { w: f64, h: f64 },
+            Point(f64),
+        }
+        let (_, events) =
+            canonicalize_traced(&[Shape::Rect { w: 1.0, h: 2.5 }, Shape::Point(3.0)]).unwrap();
+        assert_eq!(
+            events,
+            [
+                CanonEvent::NumberReformatted {
+                    path: "/0/Rect/w".into(),
+                    from: "1.0".into(),
+                    to: "1".into(),
+                },
+                CanonEvent::KeySorted {
+                    path: "/0/Rect".into(),
+                    before: vec!["w".into(), "h".into()],
+                    after: vec!["h".into(), "w".into()],
+                },
+                CanonEvent::NumberReformatted {
+                    path: "/1/Point".into(),
+                    from: "3.0".into(),
+                    to: "3".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn canonical_input_produces_no_events() {
+        let (_, events) = canonicalize_traced(&json!({"a": [1, "x"], "b": 0.5})).unwrap();
+        assert!(events.is_empty());
+    }
+}
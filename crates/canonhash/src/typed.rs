@@ -0,0 +1,239 @@
+//! Canonicalization specialized to one type, for hashing many values of it.
+//!
+//! The output is exactly that of [`canonicalize`](crate::canonicalize). What
+//! changes is the work per value: struct fields are written in declaration
+//! order and must be sorted, and a [`TypedCanonicalizer`] remembers the sorted
+//! order of each struct type it meets instead of recomputing it every time.
+//! Maps still sort their keys per value, since their key sets vary.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::Serialize;
+
+use crate::algorithm::HashAlgorithm;
+use crate::error::CanonError;
+use crate::hash::digest_bytes;
+use crate::ser::{LayoutCache, Options, Serializer};
+
+/// Canonicalizes values of `T`, caching the member order of every struct type
+/// reached from `T` on first use.
+///
+/// A stale cache can't change the output: each cached order is checked
+/// against the fields a struct actually serialized and recomputed if they
+/// differ. The cache uses interior mutability, so a `TypedCanonicalizer` is
+/// not `Sync`; give each thread its own.
+///
+/// ```
+/// use canonhash::TypedCanonicalizer;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Event {
+///     timestamp: u64,
+///     kind: &'static str,
+/// }
+///
+/// let canon = TypedCanonicalizer::<Event>::new();
+/// for timestamp in [1, 2] {
+///     let event = Event { timestamp, kind: "tick" };
+///     assert_eq!(
+///         canon.canonicalize(&event).unwrap(),
+///         canonhash::canonicalize(&event).unwrap()
+///     );
+/// }
+/// ```
+pub struct TypedCanonicalizer<T: ?Sized> {
+    layouts: LayoutCache,
+    /// Reused by [`hash_with`](Self::hash_with) between calls.
+    scratch: RefCell<Vec<u8>>,
+    _type: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Serialize> TypedCanonicalizer<T> {
+    /// Returns a canonicalizer with an empty cache.
+    pub fn new() -> Self {
+        TypedCanonicalizer {
+            layouts: LayoutCache::default(),
+            scratch: RefCell::new(Vec::new()),
+            _type: PhantomData,
+        }
+    }
+
+    /// Serializes `value` into its canonical form.
+    pub fn canonicalize(&self, value: &T) -> Result<Vec<u8>, CanonError> {
+        let mut buf = Vec::new();
+        self.canonicalize_into(value, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Appends the canonical form of `value` to `buf`. On error, `buf` is
+    /// left as it was.
+    pub fn canonicalize_into(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), CanonError> {
+        let start = buf.len();
+        let result = value.serialize(Serializer::with_layouts(
+            buf,
+            Options::default(),
+            &self.layouts,
+        ));
+        if result.is_err() {
+            buf.truncate(start);
+        }
+        result
+    }
+
+    /// Returns the digest of the canonical form of `value` under `alg`, the
+    /// same as [`hash_with`](crate::hash_with).
+    pub fn hash_with(&self, alg: HashAlgorithm, value: &T) -> Result<Vec<u8>, CanonError> {
+        let mut scratch = self.scratch.borrow_mut();
+        scratch.clear();
+        self.canonicalize_into(value, &mut scratch)?;
+        Ok(digest_bytes(alg, &scratch))
+    }
+}
+
+impl<T: ?Sized + Serialize> Default for TypedCanonicalizer<T> {
+    fn default() -> Self {
+        TypedCanonicalizer::new()
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for TypedCanonicalizer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedCanonicalizer")
+            .field("layouts", &self.layouts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::canon::canonicalize;
+    use crate::hash::hash_with;
+
+    #[derive(Serialize)]
+    struct Record {
+        zeta: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        middle: Option<&'static str>,
+        alpha: Inner,
+        tags: HashMap<&'static str, u32>,
+    }
+
+    #[derive(Serialize)]
+    struct Inner {
+        y: bool,
+        x: Vec<Inner>,
+    }
+
+    fn record(middle: Option<&'static str>) -> Record {
+        Record {
+            zeta: 1,
+            middle,
+            alpha: Inner {
+                y: true,
+                x: vec![Inner {
+                    y: false,
+                    x: Vec::new(),
+                }],
+            },
+            tags: HashMap::from([("b", 2), ("a", 1)]),
+        }
+    }
+
+    #[test]
+    fn matches_the_generic_path_on_repeated_use() {
+        let canon = TypedCanonicalizer::<Record>::new();
+        for _ in 0..3 {
+            let value = record(Some("m"));
+            assert_eq!(
+                canon.canonicalize(&value).unwrap(),
+                canonicalize(&value).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn recomputes_when_fields_are_skipped() {
+        let canon = TypedCanonicalizer::<Record>::new();
+        for middle in [Some("m"), None, Some("m")] {
+            let value = record(middle);
+            assert_eq!(
+                canon.canonicalize(&value).unwrap(),
+                canonicalize(&value).unwrap(),
+                "{middle:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn structs_sharing_a_name_keep_their_own_order() {
+        mod first {
+            #[derive(serde::Serialize)]
+            pub struct Same {
+                pub b: u8,
+                pub a: u8,
+            }
+        }
+        mod second {
+            #[derive(serde::Serialize)]
+            pub struct Same {
+                pub d: u8,
+                pub c: u8,
+                pub e: u8,
+            }
+        }
+        #[derive(Serialize)]
+        struct Both(Vec<first::Same>, Vec<second::Same>);
+
+        let value = Both(
+            vec![first::Same { b: 1, a: 2 }, first::Same { b: 3, a: 4 }],
+            vec![second::Same { d: 1, c: 2, e: 3 }],
+        );
+        let canon = TypedCanonicalizer::<Both>::new();
+        for _ in 0..2 {
+            assert_eq!(
+                canon.canonicalize(&value).unwrap(),
+                canonicalize(&value).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn hashes_like_hash_with() {
+        let canon = TypedCanonicalizer::<Record>::new();
+        let value = record(None);
+        for alg in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+            assert_eq!(
+                canon.hash_with(alg, &value).unwrap(),
+                hash_with(alg, &value).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn errors_leave_the_buffer_untouched() {
+        #[derive(Serialize)]
+        struct Bad {
+            b: f64,
+            a: u8,
+        }
+        let canon = TypedCanonicalizer::<Bad>::new();
+        let mut buf = b"prefix".to_vec();
+        assert!(matches!(
+            canon.canonicalize_into(&Bad { b: f64::NAN, a: 1 }, &mut buf),
+            Err(CanonError::NonFiniteNumber { .. })
+        ));
+        assert_eq!(buf, b"prefix");
+        assert!(canon
+            .hash_with(HashAlgorithm::Sha256, &Bad { b: 1.0, a: 1 })
+            .is_ok());
+    }
+}
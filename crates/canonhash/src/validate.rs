@@ -0,0 +1,392 @@
+//! Checking whether bytes are already in canonical form.
+
+use alloc::string::String;
+use core::cmp::Ordering;
+
+use serde::de::DeserializeOwned;
+
+use crate::error::CanonError;
+use crate::number;
+use crate::ser::cmp_utf16;
+
+/// Nesting deeper than this is rejected, matching `serde_json`'s parser.
+const MAX_DEPTH: usize = 128;
+
+/// Returns whether `bytes` is exactly the canonical form of some JSON value.
+///
+/// This validates stored documents in one pass without parsing them into a
+/// value and serializing again. It accepts precisely what this crate
+/// produces: no insignificant whitespace, keys in strictly increasing UTF-16
+/// order, minimal string escapes, and numbers in ECMAScript form, except that
+/// integers may have any number of digits (as written for `u64`/`i128`
+/// fields). Documents nested more than 128 levels deep are rejected.
+pub fn is_canonical(bytes: &[u8]) -> bool {
+    if core::str::from_utf8(bytes).is_err() {
+        return false;
+    }
+    let mut checker = Checker { bytes, pos: 0 };
+    checker.value(0) && checker.pos == bytes.len()
+}
+
+/// Returns whether stored JSON text is in canonical form, telling apart text
+/// that is valid but not canonical, `Ok(false)`, from text that is not JSON
+/// at all, [`CanonError::Parse`].
+///
+/// Canonical text is recognized by the same single pass as [`is_canonical`],
+/// with no re-serialization. Only text that fails it is parsed, to find out
+/// which of the two it is. For auditing stored documents for drift.
+///
+/// ```
+/// use canonhash::is_canonical_str;
+///
+/// assert!(is_canonical_str(r#"{"a":1,"b":[true]}"#).unwrap());
+/// assert!(!is_canonical_str(r#"{"b": [true], "a": 1.0}"#).unwrap());
+/// assert!(is_canonical_str(r#"{"a":"#).is_err());
+/// ```
+pub fn is_canonical_str(json: &str) -> Result<bool, CanonError> {
+    if is_canonical(json.as_bytes()) {
+        return Ok(true);
+    }
+    // Parsed as the other text entry points parse, so that a lone surrogate
+    // escape is an error here too.
+    serde_json::from_str::<serde_json::Value>(json)?;
+    Ok(false)
+}
+
+/// Deserializes a `T` from bytes that must already be in canonical form, or
+/// fails with [`CanonError::NotCanonical`] without deserializing.
+///
+/// Stored canonical bytes that turn out not to be canonical would hash
+/// differently from what was recorded; this refuses them up front. The check
+/// is [`is_canonical`], one pass over the bytes. Use
+/// [`from_canonical_slice_unchecked`] when the source is trusted.
+///
+/// ```
+/// use canonhash::{from_canonical_slice, CanonError};
+/// use std::collections::BTreeMap;
+///
+/// let map: BTreeMap<String, u32> = from_canonical_slice(br#"{"a":1,"b":2}"#).unwrap();
+/// assert_eq!(map["b"], 2);
+/// assert!(matches!(
+///     from_canonical_slice::<BTreeMap<String, u32>>(br#"{"b":2,"a":1}"#),
+///     Err(CanonError::NotCanonical)
+/// ));
+/// ```
+pub fn from_canonical_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CanonError> {
+    if !is_canonical(bytes) {
+        return Err(CanonError::NotCanonical);
+    }
+    from_canonical_slice_unchecked(bytes)
+}
+
+/// Deserializes a `T` from canonical bytes without checking that they are
+/// canonical: `serde_json::from_slice` with this crate's error type.
+pub fn from_canonical_slice_unchecked<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CanonError> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+struct Checker<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Checker<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn literal(&mut self, lit: &[u8]) -> bool {
+        if self.bytes[self.pos..].starts_with(lit) {
+            self.pos += lit.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> bool {
+        match self.peek() {
+            Some(b'{') if depth < MAX_DEPTH => self.object(depth + 1),
+            Some(b'[') if depth < MAX_DEPTH => self.array(depth + 1),
+            Some(b'"') => self.string(None),
+            Some(b't') => self.literal(b"true"),
+            Some(b'f') => self.literal(b"false"),
+            Some(b'n') => self.literal(b"null"),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => false,
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> bool {
+        self.pos += 1;
+        if self.eat(b'}') {
+            return true;
+        }
+        let mut prev: Option<String> = None;
+        loop {
+            let mut key = String::new();
+            if !self.string(Some(&mut key)) {
+                return false;
+            }
+            if let Some(prev) = &prev {
+                if cmp_utf16(prev, &key) != Ordering::Less {
+                    return false;
+                }
+            }
+            prev = Some(key);
+            if !self.eat(b':') || !self.value(depth) {
+                return false;
+            }
+            if self.eat(b'}') {
+                return true;
+            }
+            if !self.eat(b',') {
+                return false;
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> bool {
+        self.pos += 1;
+        if self.eat(b']') {
+            return true;
+        }
+        loop {
+            if !self.value(depth) {
+                return false;
+            }
+            if self.eat(b']') {
+                return true;
+            }
+            if !self.eat(b',') {
+                return false;
+            }
+        }
+    }
+
+    /// Checks a string literal, decoding it into `decoded` if given. The input
+    /// is already known to be valid UTF-8.
+    fn string(&mut self, mut decoded: Option<&mut String>) -> bool {
+        if !self.eat(b'"') {
+            return false;
+        }
+        let mut start = self.pos;
+        loop {
+            let Some(b) = self.peek() else {
+                return false;
+            };
+            match b {
+                b'"' | b'\\' => {
+                    if let Some(out) = decoded.as_deref_mut() {
+                        let run = &self.bytes[start..self.pos];
+                        out.push_str(core::str::from_utf8(run).expect("validated UTF-8"));
+                    }
+                    self.pos += 1;
+                    if b == b'"' {
+                        return true;
+                    }
+                    let Some(c) = self.escape() else {
+                        return false;
+                    };
+                    if let Some(out) = decoded.as_deref_mut() {
+                        out.push(c);
+                    }
+                    start = self.pos;
+                }
+                0x00..=0x1f => return false,
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    /// Checks the escape after a backslash and returns the character it
+    /// stands for.
+    fn escape(&mut self) -> Option<char> {
+        let b = self.peek()?;
+        self.pos += 1;
+        let c = match b {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'b' => '\x08',
+            b'f' => '\x0c',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let hex = self.bytes.get(self.pos..self.pos + 4)?;
+                self.pos += 4;
+                let lower = |h: u8| matches!(h, b'0'..=b'9' | b'a'..=b'f');
+                if &hex[..2] != b"00" || !hex.iter().copied().all(lower) {
+                    return None;
+                }
+                let code = u8::from_str_radix(core::str::from_utf8(&hex[2..]).ok()?, 16).ok()?;
+                // Only controls without a short form are written as `\u00xx`.
+                if code >= 0x20 || matches!(code, 0x08 | 0x09 | 0x0a | 0x0c | 0x0d) {
+                    return None;
+                }
+                char::from(code)
+            }
+            _ => return None,
+        };
+        Some(c)
+    }
+
+    fn number(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        let token = core::str::from_utf8(&self.bytes[start..self.pos]).expect("ASCII");
+        let digits = token.strip_prefix('-').unwrap_or(token);
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            // Integers: exact digits, no leading zeros, no negative zero.
+            if digits == "0" {
+                return token == "0";
+            }
+            return !digits.starts_with('0');
+        }
+        match token.parse::<f64>() {
+            Ok(v) if v.is_finite() => number::Buffer::new().format_finite(v) == token,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(s: &str) -> bool {
+        is_canonical(s.as_bytes())
+    }
+
+    #[test]
+    fn accepts_canonical_documents() {
+        assert!(ok(r#"{"a":[1,2.5,"x"],"b":{"c":null,"d":true}}"#));
+        assert!(ok("{}"));
+        assert!(ok("[]"));
+        assert!(ok(r#""a\u001fb\n""#));
+        assert!(ok("1e+21"));
+        assert!(ok("-0.5"));
+        assert!(ok("18446744073709551615"));
+        assert!(ok("{\"\u{1f600}\":1,\"\u{ffff}\":2}"));
+    }
+
+    #[test]
+    fn rejects_whitespace_and_trailing_bytes() {
+        assert!(!ok(r#"{"a": 1}"#));
+        assert!(!ok("[1, 2]"));
+        assert!(!ok("1 "));
+        assert!(!ok(" 1"));
+        assert!(!ok("[1]x"));
+        assert!(!ok(""));
+    }
+
+    #[test]
+    fn rejects_unsorted_or_duplicate_keys() {
+        assert!(!ok(r#"{"b":1,"a":2}"#));
+        assert!(!ok(r#"{"a":1,"a":2}"#));
+        assert!(!ok("{\"\u{ffff}\":1,\"\u{1f600}\":2}"));
+        assert!(!ok(r#"{"b":{"y":1,"x":2}}"#));
+    }
+
+    #[test]
+    fn rejects_non_minimal_escapes() {
+        assert!(!ok(r#""\/""#));
+        assert!(!ok(r#""\u0041""#));
+        assert!(!ok(r#""\u000a""#));
+        assert!(!ok(r#""\u001F""#));
+        assert!(!ok(r#""\u00e9""#));
+        assert!(ok(r#""é""#));
+        assert!(!ok("\"\t\""));
+        assert!(!ok(r#""unterminated"#));
+        assert!(!ok("{\u{e9}\":1}"));
+    }
+
+    #[test]
+    fn compares_escaped_keys_by_decoded_value() {
+        // "\n" (U+000A) sorts before "\u001f".
+        assert!(ok(r#"{"\n":1,"\u001f":2}"#));
+        assert!(!ok(r#"{"\u001f":1,"\n":2}"#));
+    }
+
+    #[test]
+    fn rejects_non_canonical_numbers() {
+        for bad in [
+            "1.0", "01", "-0", "1E21", "1e21", "0.10", "1e-7x", "1.5e+0", ".5", "-", "+1",
+        ] {
+            assert!(!ok(bad), "{bad}");
+        }
+    }
+
+    #[test]
+    fn str_form_tells_non_canonical_from_invalid() {
+        for canonical in [r#"{"a":[1,2.5],"b":null}"#, "1e+21", r#""x""#] {
+            assert!(is_canonical_str(canonical).unwrap(), "{canonical}");
+        }
+        for drifted in [
+            r#"{"b":1,"a":2}"#,
+            r#"{"a":1,"a":2}"#,
+            "[1.0]",
+            "[1, 2]\n",
+            r#""\u0041""#,
+        ] {
+            assert!(!is_canonical_str(drifted).unwrap(), "{drifted}");
+        }
+        for invalid in ["", "[1,", "{\"a\" 1}", "[1]]", "\"\\ud800\""] {
+            assert!(
+                matches!(is_canonical_str(invalid), Err(CanonError::Parse(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn typed_reads_require_canonical_bytes() {
+        #[derive(serde::Deserialize, serde::Serialize, Debug, PartialEq)]
+        struct Record {
+            id: u32,
+            tags: Vec<String>,
+        }
+
+        let record = Record {
+            id: 7,
+            tags: vec!["x".into()],
+        };
+        let canonical = crate::canonicalize(&record).unwrap();
+        assert_eq!(from_canonical_slice::<Record>(&canonical).unwrap(), record);
+
+        let drifted = br#"{"tags":["x"],"id":7}"#;
+        assert!(matches!(
+            from_canonical_slice::<Record>(drifted),
+            Err(CanonError::NotCanonical)
+        ));
+        assert_eq!(
+            from_canonical_slice_unchecked::<Record>(drifted).unwrap(),
+            record
+        );
+        // Canonical, but not a `Record`.
+        assert!(matches!(
+            from_canonical_slice::<Record>(br#"{"id":7}"#),
+            Err(CanonError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8_and_excessive_depth() {
+        assert!(!is_canonical(b"\"\xff\""));
+        let deep = "[".repeat(129) + &"]".repeat(129);
+        assert!(!ok(&deep));
+        let shallow = "[".repeat(128) + &"]".repeat(128);
+        assert!(ok(&shallow));
+    }
+}
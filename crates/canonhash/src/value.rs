@@ -0,0 +1,238 @@
+//! Canonicalization of `serde_json::Value` by walking the tree directly.
+//!
+//! [`canonicalize`](crate::canonicalize) accepts a `Value` like any other
+//! `Serialize` type and produces the same bytes. This path skips the serde
+//! layer: each object's members are sorted by reference and written once,
+//! with no per-member buffers. It is also the shortest statement of the
+//! rules: strings per [`write_str`], numbers in ES form, members in UTF-16
+//! key order.
+//!
+//! [`merge_objects`] combines objects before canonicalizing, with an
+//! explicit choice of which side wins a shared key.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde_json::{Map, Number, Value};
+
+use crate::error::{CanonError, JsonPath};
+use crate::ser::{self, cmp_utf16, write_str, Options, DEFAULT_MAX_DEPTH};
+
+/// Returns the RFC 8785 canonical form of `value`, the same bytes as
+/// [`canonicalize`](crate::canonicalize).
+///
+/// ```
+/// use serde_json::json;
+///
+/// let value = json!({"b": [1.0, "\u{1f600}"], "a": null});
+/// assert_eq!(
+///     canonhash::canonicalize_value(&value).unwrap(),
+///     canonhash::canonicalize(&value).unwrap()
+/// );
+/// ```
+pub fn canonicalize_value(value: &Value) -> Result<Vec<u8>, CanonError> {
+    let mut buf = Vec::with_capacity(128);
+    write_value(&mut buf, value, 0)?;
+    Ok(buf)
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value, depth: usize) -> Result<(), CanonError> {
+    match value {
+        Value::Null => out.extend_from_slice(b"null"),
+        Value::Bool(true) => out.extend_from_slice(b"true"),
+        Value::Bool(false) => out.extend_from_slice(b"false"),
+        Value::Number(n) => write_number(out, n)?,
+        Value::String(s) => write_str(out, s)?,
+        Value::Array(elements) => {
+            let depth = descend(depth)?;
+            out.push(b'[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_value(out, element, depth).map_err(|err| err.within_index(i))?;
+            }
+            out.push(b']');
+        }
+        Value::Object(members) => write_object(out, members, descend(depth)?)?,
+    }
+    Ok(())
+}
+
+fn write_object(
+    out: &mut Vec<u8>,
+    members: &Map<String, Value>,
+    depth: usize,
+) -> Result<(), CanonError> {
+    let mut sorted: Vec<(&String, &Value)> = members.iter().collect();
+    sorted.sort_by(|a, b| cmp_utf16(a.0, b.0));
+    out.push(b'{');
+    for (i, (key, value)) in sorted.into_iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        write_str(out, key)?;
+        out.push(b':');
+        write_value(out, value, depth).map_err(|err| err.within_key(key))?;
+    }
+    out.push(b'}');
+    Ok(())
+}
+
+/// Integers keep their digits; anything else is a double.
+pub(crate) fn write_number(out: &mut Vec<u8>, n: &Number) -> Result<(), CanonError> {
+    #[cfg(feature = "arbitrary_precision")]
+    {
+        ser::write_number_text(out, n.as_str(), Options::default())
+    }
+    #[cfg(not(feature = "arbitrary_precision"))]
+    {
+        if let Some(v) = n.as_u64() {
+            out.extend_from_slice(itoa::Buffer::new().format(v).as_bytes());
+        } else if let Some(v) = n.as_i64() {
+            out.extend_from_slice(itoa::Buffer::new().format(v).as_bytes());
+        } else {
+            let v = n.as_f64().expect("a Number is an integer or a finite f64");
+            ser::write_float(out, v, Options::default())?;
+        }
+        Ok(())
+    }
+}
+
+/// The same limit the serializer applies by default, so a `Value` that
+/// [`canonicalize`](crate::canonicalize) rejects for depth is rejected here.
+fn descend(depth: usize) -> Result<usize, CanonError> {
+    if depth == DEFAULT_MAX_DEPTH {
+        return Err(CanonError::DepthExceeded {
+            limit: DEFAULT_MAX_DEPTH,
+            path: JsonPath::default(),
+        });
+    }
+    Ok(depth + 1)
+}
+
+/// Which value [`merge_objects`] keeps for a key both objects have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DuplicateKeyPolicy {
+    /// Keep the value from the first object.
+    FirstWins,
+    /// Keep the value from the second object, as `Map::extend` would.
+    #[default]
+    LastWins,
+}
+
+/// Merges the members of `b` into `a`, resolving shared keys by `policy`.
+///
+/// The merge is shallow: a shared key takes one side's value whole, even
+/// when both values are objects. Member order is irrelevant to the
+/// canonical form, so only which value survives matters.
+///
+/// ```
+/// use canonhash::{merge_objects, DuplicateKeyPolicy};
+/// use serde_json::json;
+///
+/// let a = json!({"id": 1, "tag": "a"}).as_object().unwrap().clone();
+/// let b = json!({"tag": "b", "extra": true}).as_object().unwrap().clone();
+/// let merged = merge_objects(a, b, DuplicateKeyPolicy::FirstWins);
+/// assert_eq!(
+///     canonhash::canonicalize(&merged).unwrap(),
+///     br#"{"extra":true,"id":1,"tag":"a"}"#
+/// );
+/// ```
+pub fn merge_objects(
+    mut a: Map<String, Value>,
+    b: Map<String, Value>,
+    policy: DuplicateKeyPolicy,
+) -> Map<String, Value> {
+    match policy {
+        DuplicateKeyPolicy::FirstWins => {
+            for (key, value) in b {
+                a.entry(key).or_insert(value);
+            }
+        }
+        DuplicateKeyPolicy::LastWins => a.extend(b),
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::canon::canonicalize;
+
+    #[test]
+    fn matches_the_serializer() {
+        let values = [
+            json!(null),
+            json!([
+                true,
+                false,
+                0,
+                -1,
+                1.5,
+                1e21,
+                5e-324,
+                -0.0,
+                u64::MAX,
+                i64::MIN
+            ]),
+            json!({"\u{e000}": 1, "\u{1f600}": 2, "a\"b": "c\\d\n\u{1}"}),
+            json!({"b": {"z": [], "y": {}}, "a": [{"b": 1, "a": 2}, "x"]}),
+        ];
+        for value in &values {
+            assert_eq!(
+                canonicalize_value(value).unwrap(),
+                canonicalize(value).unwrap(),
+                "{value}"
+            );
+        }
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn big_integers_keep_their_digits() {
+        let value: Value = serde_json::from_str("[123456789012345678901234567890, 1.50]").unwrap();
+        assert_eq!(
+            canonicalize_value(&value).unwrap(),
+            b"[123456789012345678901234567890,1.5]"
+        );
+    }
+
+    #[test]
+    fn depth_is_limited_like_the_serializer() {
+        let nest = |levels: usize| {
+            let mut value = json!([]);
+            for _ in 1..levels {
+                value = json!([value]);
+            }
+            value
+        };
+        assert!(canonicalize_value(&nest(DEFAULT_MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            canonicalize_value(&nest(DEFAULT_MAX_DEPTH + 1)),
+            Err(CanonError::DepthExceeded { .. })
+        ));
+        assert!(canonicalize(&nest(DEFAULT_MAX_DEPTH + 1)).is_err());
+    }
+
+    #[test]
+    fn merge_policy_picks_the_surviving_value() {
+        let object = |value: Value| value.as_object().unwrap().clone();
+        let a = object(json!({"shared": {"x": 1}, "a": 1}));
+        let b = object(json!({"shared": {"y": 2}, "b": 2}));
+        assert_eq!(
+            Value::Object(merge_objects(
+                a.clone(),
+                b.clone(),
+                DuplicateKeyPolicy::FirstWins
+            )),
+            json!({"a": 1, "b": 2, "shared": {"x": 1}})
+        );
+        assert_eq!(
+            Value::Object(merge_objects(a, b, DuplicateKeyPolicy::default())),
+            json!({"a": 1, "b": 2, "shared": {"y": 2}})
+        );
+    }
+}
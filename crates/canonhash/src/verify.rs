@@ -0,0 +1,287 @@
+//! Checking values against known-good digests.
+//!
+//! Digests are compared in constant time so these helpers are safe to use as
+//! integrity checks on attacker-supplied payloads.
+
+use core::fmt;
+
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+
+use crate::algorithm::HashAlgorithm;
+use crate::content_id::ContentId;
+use crate::encoding::{decode_digest, DigestEncoding};
+use crate::error::CanonError;
+use crate::hash::hash_with;
+
+/// Returns whether the canonical SHA-256 of `value` equals `expected_hex`.
+///
+/// `expected_hex` may be upper- or lowercase. Malformed hex or a digest of the
+/// wrong length is an error rather than `Ok(false)`, so a corrupted reference
+/// value can't be mistaken for a mismatching payload.
+pub fn verify_sha256<T>(value: &T, expected_hex: &str) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    verify_hex(HashAlgorithm::Sha256, value, expected_hex)
+}
+
+/// Like [`verify_sha256`] for any algorithm.
+pub fn verify_hex<T>(alg: HashAlgorithm, value: &T, expected_hex: &str) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    verify_encoded(alg, value, expected_hex, DigestEncoding::Hex)
+}
+
+/// Like [`verify_hex`] for a digest written in any [`DigestEncoding`].
+///
+/// A string that isn't valid in `enc` is [`CanonError::InvalidHex`] or
+/// [`CanonError::InvalidBase64`].
+pub fn verify_encoded<T>(
+    alg: HashAlgorithm,
+    value: &T,
+    expected: &str,
+    enc: DigestEncoding,
+) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    let expected = decode_digest(expected, enc)?;
+    verify_digest(alg, value, &expected)
+}
+
+/// Returns whether the canonical digest of `value` under `alg` equals the raw
+/// `expected` bytes.
+pub fn verify_digest<T>(alg: HashAlgorithm, value: &T, expected: &[u8]) -> Result<bool, CanonError>
+where
+    T: ?Sized + Serialize,
+{
+    if expected.len() != alg.output_len() {
+        return Err(CanonError::DigestLength {
+            expected: alg.output_len(),
+            actual: expected.len(),
+        });
+    }
+    let actual = hash_with(alg, value)?;
+    Ok(actual.ct_eq(expected).into())
+}
+
+/// Checks that the canonical digest of `value` is `expected`, under the
+/// algorithm `expected` names.
+///
+/// The digests are compared in constant time. Use [`verify_document_as`]
+/// when the id comes from the same untrusted source as the payload, so that
+/// the sender can't pick the algorithm.
+///
+/// ```
+/// use canonhash::verify::{verify_document, VerifyError};
+/// use canonhash::{ContentId, HashAlgorithm};
+/// use serde_json::json;
+///
+/// let id = ContentId::of(HashAlgorithm::Sha256, &json!({"a": 1, "b": 2})).unwrap();
+/// assert!(verify_document(&json!({"b": 2, "a": 1}), &id).is_ok());
+/// assert!(matches!(
+///     verify_document(&json!({"a": 1}), &id),
+///     Err(VerifyError::DigestMismatch)
+/// ));
+/// ```
+pub fn verify_document<T>(value: &T, expected: &ContentId) -> Result<(), VerifyError>
+where
+    T: ?Sized + Serialize,
+{
+    let actual = hash_with(expected.algorithm(), value)?;
+    if bool::from(actual.as_slice().ct_eq(expected.digest())) {
+        Ok(())
+    } else {
+        Err(VerifyError::DigestMismatch)
+    }
+}
+
+/// Like [`verify_document`], but fails with
+/// [`VerifyError::AlgorithmMismatch`] before hashing if `expected` isn't an
+/// `alg` id.
+pub fn verify_document_as<T>(
+    alg: HashAlgorithm,
+    value: &T,
+    expected: &ContentId,
+) -> Result<(), VerifyError>
+where
+    T: ?Sized + Serialize,
+{
+    if expected.algorithm() != alg {
+        return Err(VerifyError::AlgorithmMismatch {
+            expected: alg,
+            actual: expected.algorithm(),
+        });
+    }
+    verify_document(value, expected)
+}
+
+/// Why [`verify_document`] or [`verify_document_as`] rejected a value.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VerifyError {
+    /// The content id was made with `actual`, not the required `expected`.
+    AlgorithmMismatch {
+        expected: HashAlgorithm,
+        actual: HashAlgorithm,
+    },
+    /// The value's digest is not the one in the content id.
+    DigestMismatch,
+    /// The value could not be canonicalized.
+    Canon(CanonError),
+}
+
+impl From<CanonError> for VerifyError {
+    fn from(err: CanonError) -> Self {
+        VerifyError::Canon(err)
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::AlgorithmMismatch { expected, actual } => {
+                write!(f, "content id uses {actual} but {expected} is required")
+            }
+            VerifyError::DigestMismatch => f.write_str("value does not match the content id"),
+            VerifyError::Canon(err) => err.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            VerifyError::Canon(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::hash::sha256_hex;
+
+    #[test]
+    fn accepts_matching_digest() {
+        let value = json!({"b": 2, "a": 1});
+        let expected = sha256_hex(&value).unwrap();
+        assert!(verify_sha256(&value, &expected).unwrap());
+        assert!(verify_sha256(&value, &expected.to_uppercase()).unwrap());
+    }
+
+    #[test]
+    fn rejects_other_value() {
+        let expected = sha256_hex(&json!({"a": 1})).unwrap();
+        assert!(!verify_sha256(&json!({"a": 2}), &expected).unwrap());
+    }
+
+    #[test]
+    fn invalid_hex_is_an_error() {
+        let err = verify_sha256(&json!(1), &"zz".repeat(32)).unwrap_err();
+        assert!(matches!(err, CanonError::InvalidHex));
+        assert!(matches!(
+            verify_sha256(&json!(1), "abc").unwrap_err(),
+            CanonError::InvalidHex
+        ));
+    }
+
+    #[test]
+    fn accepts_digest_in_other_encodings() {
+        let value = json!({"b": 2, "a": 1});
+        let digest = hash_with(HashAlgorithm::Sha256, &value).unwrap();
+        for enc in [DigestEncoding::HexUpper, DigestEncoding::Base64UrlNoPad] {
+            let expected = crate::encoding::encode_digest(&digest, enc);
+            assert!(verify_encoded(HashAlgorithm::Sha256, &value, &expected, enc).unwrap());
+        }
+        let err = verify_encoded(HashAlgorithm::Sha256, &value, "!", DigestEncoding::Base64);
+        assert!(matches!(err, Err(CanonError::InvalidBase64)));
+    }
+
+    #[test]
+    fn wrong_length_is_an_error() {
+        let err = verify_sha256(&json!(1), &"00".repeat(20)).unwrap_err();
+        assert!(matches!(
+            err,
+            CanonError::DigestLength {
+                expected: 32,
+                actual: 20
+            }
+        ));
+
+        let sha512 = hex::encode(hash_with(HashAlgorithm::Sha512, &json!(1)).unwrap());
+        assert!(verify_sha256(&json!(1), &sha512).is_err());
+        assert!(verify_hex(HashAlgorithm::Sha512, &json!(1), &sha512).unwrap());
+    }
+
+    #[test]
+    fn documents_verify_against_their_content_id() {
+        let value = json!({"b": [1, 2], "a": "x"});
+        for alg in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            let id = ContentId::of(alg, &value).unwrap();
+            assert!(verify_document(&value, &id).is_ok());
+            assert!(verify_document_as(alg, &json!({"a": "x", "b": [1, 2]}), &id).is_ok());
+        }
+    }
+
+    #[test]
+    fn other_values_are_a_digest_mismatch() {
+        let id = ContentId::of(HashAlgorithm::Sha384, &json!({"a": 1})).unwrap();
+        for other in [json!({"a": 2}), json!({"a": 1, "b": null}), json!([])] {
+            assert!(matches!(
+                verify_document(&other, &id),
+                Err(VerifyError::DigestMismatch)
+            ));
+        }
+        // The same digest bytes under a different algorithm don't match.
+        let sha512 = ContentId::of(HashAlgorithm::Sha512, &json!(1)).unwrap();
+        let relabeled = ContentId::new(HashAlgorithm::Sha512, [0; 64]).unwrap();
+        assert_ne!(sha512, relabeled);
+        assert!(matches!(
+            verify_document(&json!(1), &relabeled),
+            Err(VerifyError::DigestMismatch)
+        ));
+    }
+
+    #[test]
+    fn pinned_algorithm_rejects_other_ids_before_hashing() {
+        let id = ContentId::of(HashAlgorithm::Sha512, &json!(1)).unwrap();
+        let err = verify_document_as(HashAlgorithm::Sha256, &json!(1), &id).unwrap_err();
+        assert!(matches!(
+            err,
+            VerifyError::AlgorithmMismatch {
+                expected: HashAlgorithm::Sha256,
+                actual: HashAlgorithm::Sha512,
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "content id uses sha512 but sha256 is required"
+        );
+        // Even a value that can't be hashed fails on the algorithm first.
+        assert!(matches!(
+            verify_document_as(HashAlgorithm::Sha256, &f64::NAN, &id),
+            Err(VerifyError::AlgorithmMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn canonicalization_errors_are_passed_through() {
+        let id = ContentId::of(HashAlgorithm::Sha256, &json!(null)).unwrap();
+        let err = verify_document(&[1.0, f64::INFINITY], &id).unwrap_err();
+        assert!(matches!(
+            err,
+            VerifyError::Canon(CanonError::NonFiniteNumber { .. })
+        ));
+        assert!(core::error::Error::source(&err).is_some());
+    }
+}
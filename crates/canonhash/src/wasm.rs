@@ -0,0 +1,17 @@
+//! JavaScript bindings, enabled by the `wasm` feature.
+//!
+//! ```js
+//! import { canonicalSha256 } from "canonhash";
+//! canonicalSha256('{"b": 1, "a": 2}'); // hex digest of {"a":2,"b":1}
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+/// Returns the lowercase hex SHA-256 of the canonical form of `json`.
+///
+/// Throws a JavaScript `Error` if `json` is not valid JSON; the message
+/// includes the line and column.
+#[wasm_bindgen(js_name = canonicalSha256)]
+pub fn canonical_sha256(json: &str) -> Result<String, JsError> {
+    crate::hash::canonical_sha256_from_str(json).map_err(|err| JsError::new(&err.to_string()))
+}
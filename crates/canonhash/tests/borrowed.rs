@@ -0,0 +1,111 @@
+//! Values owned elsewhere hash through a reference, `Box`, `Cow`, `Rc` or
+//! `Arc` exactly as they do directly, and are never cloned to be hashed.
+//! `Rc` and `Arc` rely on serde's `rc` feature, which the dev-dependencies
+//! turn on.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use canonhash::{canonical_sha256, canonicalize, HashAlgorithm, TypedCanonicalizer};
+use serde::{Serialize, Serializer};
+
+#[derive(Serialize)]
+struct DataContent {
+    modules: Vec<String>,
+    nested: Nested,
+}
+
+#[derive(Serialize)]
+struct Nested {
+    array: Vec<u32>,
+    object: HashMap<String, String>,
+    child: Option<Box<Nested>>,
+}
+
+/// Fails the test if anything clones it, which a `Cow::Borrowed` would do if
+/// it were turned into an owned value to be hashed.
+struct NoClone(DataContent);
+
+impl Clone for NoClone {
+    fn clone(&self) -> Self {
+        panic!("hashing cloned the value");
+    }
+}
+
+impl Serialize for NoClone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+fn data() -> DataContent {
+    let leaf = Nested {
+        array: vec![3, 1, 2],
+        object: HashMap::from([("zeta".to_owned(), "last".to_owned())]),
+        child: None,
+    };
+    DataContent {
+        modules: ["marketIntelligence", "notifications", "automation"]
+            .map(String::from)
+            .to_vec(),
+        nested: Nested {
+            array: (0..1000).collect(),
+            object: HashMap::from([("alpha".to_owned(), "first".to_owned())]),
+            child: Some(Box::new(leaf)),
+        },
+    }
+}
+
+#[test]
+fn wrappers_hash_like_the_value_they_hold() {
+    let expected = canonical_sha256(&data()).unwrap();
+
+    let value = NoClone(data());
+    assert_eq!(canonical_sha256(&value).unwrap(), expected);
+    assert_eq!(canonical_sha256(&&value).unwrap(), expected);
+
+    let borrowed: Cow<'_, NoClone> = Cow::Borrowed(&value);
+    assert_eq!(canonical_sha256(&borrowed).unwrap(), expected);
+    let owned: Cow<'_, NoClone> = Cow::Owned(NoClone(data()));
+    assert_eq!(canonical_sha256(&owned).unwrap(), expected);
+
+    let boxed = Box::new(NoClone(data()));
+    assert_eq!(canonical_sha256(&boxed).unwrap(), expected);
+
+    let arc = Arc::new(NoClone(data()));
+    assert_eq!(canonical_sha256(&arc).unwrap(), expected);
+    assert_eq!(canonical_sha256(&*arc).unwrap(), expected);
+    assert_eq!(Arc::strong_count(&arc), 1);
+
+    let rc = Rc::new(NoClone(data()));
+    assert_eq!(canonical_sha256(&rc).unwrap(), expected);
+}
+
+#[test]
+fn unsized_values_hash_through_their_pointers() {
+    let expected = canonicalize(&[1, 2, 3]).unwrap();
+    let slice: &[u32] = &[1, 2, 3];
+    let boxed: Box<[u32]> = slice.into();
+    let arc: Arc<[u32]> = slice.into();
+    let cow: Cow<'_, [u32]> = Cow::Borrowed(slice);
+    assert_eq!(canonicalize(slice).unwrap(), expected);
+    assert_eq!(canonicalize(&boxed).unwrap(), expected);
+    assert_eq!(canonicalize(&arc).unwrap(), expected);
+    assert_eq!(canonicalize(&cow).unwrap(), expected);
+
+    let text: Arc<str> = Arc::from("shared");
+    assert_eq!(canonicalize(&text).unwrap(), br#""shared""#);
+    assert_eq!(canonicalize(&*text).unwrap(), br#""shared""#);
+}
+
+#[test]
+fn typed_canonicalizers_accept_shared_values() {
+    let shared = Arc::new(NoClone(data()));
+    let typed = TypedCanonicalizer::new();
+    assert_eq!(
+        typed.hash_with(HashAlgorithm::Sha256, &shared).unwrap(),
+        canonical_sha256(&data()).unwrap()
+    );
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const INPUT: &str = r#"{ "b": [1.0, 2], "a": "x" }"#;
+const CANONICAL: &str = r#"{"a":"x","b":[1,2]}"#;
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_canonhash"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A child that rejects its arguments exits without reading stdin.
+    let written = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    if let Err(err) = written {
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe, "{err}");
+    }
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn prints_canonical_form_from_stdin() {
+    let output = run(&["--canonical-only"], INPUT);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), CANONICAL);
+}
+
+#[test]
+fn prints_sha256_hex_by_default() {
+    let expected =
+        canonhash::sha256_hex(&serde_json::from_str::<serde_json::Value>(INPUT).unwrap()).unwrap();
+    let output = run(&[], INPUT);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{expected}\n"));
+}
+
+#[test]
+fn reads_file_argument() {
+    let path = std::env::temp_dir().join(format!("canonhash-cli-{}.json", std::process::id()));
+    std::fs::write(&path, INPUT).unwrap();
+    let output = run(&["--canonical-only", path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(stdout(&output), CANONICAL);
+}
+
+#[test]
+fn supports_other_encodings_and_algorithms() {
+    let base64 = run(&["--alg", "sha512", "--base64"], INPUT);
+    assert!(base64.status.success());
+    assert_eq!(stdout(&base64).trim_end().len(), 88);
+
+    let multibase = run(&["--multibase"], INPUT);
+    assert!(stdout(&multibase).starts_with("zQm"));
+}
+
+#[test]
+fn verify_sets_exit_status() {
+    let digest = run(&[], INPUT);
+    let digest = stdout(&digest).trim_end().to_uppercase();
+    assert!(run(&["--verify", &digest], INPUT).status.success());
+
+    let mismatch = run(&["--verify", &"0".repeat(64)], INPUT);
+    assert_eq!(mismatch.status.code(), Some(1));
+
+    let multibase = run(&["--multibase"], INPUT);
+    let multibase = stdout(&multibase).trim_end().to_owned();
+    assert!(run(&["--multibase", "--verify", &multibase], INPUT)
+        .status
+        .success());
+    assert_eq!(
+        run(&["--multibase", "--verify", &multibase], "{}")
+            .status
+            .code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn usage_errors_exit_with_status_2() {
+    assert_eq!(run(&["--alg", "md5"], INPUT).status.code(), Some(2));
+    assert_eq!(run(&["--bogus"], INPUT).status.code(), Some(2));
+    assert_eq!(run(&[], "{not json").status.code(), Some(2));
+    assert_eq!(run(&["--verify", "zz"], INPUT).status.code(), Some(2));
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "derive")]
+
+use std::collections::HashMap;
+
+use canonhash::{canonical_sha256, hash_with, CanonicalHash, HashAlgorithm};
+use serde::Serialize;
+
+#[derive(Serialize, CanonicalHash)]
+struct TestData {
+    #[serde(rename = "testId")]
+    test_id: String,
+    timestamp: String,
+    data: DataContent,
+}
+
+#[derive(Serialize)]
+struct DataContent {
+    modules: Vec<String>,
+    object: HashMap<String, String>,
+}
+
+#[derive(Serialize, CanonicalHash)]
+#[canonical(alg = "SHA-512")]
+enum Event {
+    Started { at: u64 },
+}
+
+#[derive(Serialize, CanonicalHash)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+fn test_data() -> TestData {
+    TestData {
+        test_id: "golden-hash-test-v1".to_owned(),
+        timestamp: "2026-02-12T15:50:12Z".to_owned(),
+        data: DataContent {
+            modules: vec!["automation".to_owned()],
+            object: HashMap::from([
+                ("zeta".to_owned(), "last".to_owned()),
+                ("alpha".to_owned(), "first".to_owned()),
+            ]),
+        },
+    }
+}
+
+#[test]
+fn methods_match_the_free_functions() {
+    let data = test_data();
+    assert_eq!(
+        data.canonical_sha256().unwrap(),
+        canonical_sha256(&data).unwrap()
+    );
+    assert_eq!(
+        data.canonical_hash().unwrap(),
+        hash_with(HashAlgorithm::Sha256, &data).unwrap()
+    );
+}
+
+#[test]
+fn alg_attribute_picks_the_canonical_hash_algorithm() {
+    let event = Event::Started { at: 7 };
+    let digest = event.canonical_hash().unwrap();
+    assert_eq!(digest.len(), 64);
+    assert_eq!(digest, hash_with(HashAlgorithm::Sha512, &event).unwrap());
+    assert_eq!(
+        event.canonical_sha256().unwrap(),
+        canonical_sha256(&event).unwrap()
+    );
+}
+
+#[test]
+fn generic_types_need_only_serializable_parameters() {
+    let wrapped = Wrapper { inner: test_data() };
+    assert_eq!(
+        wrapped.canonical_sha256().unwrap(),
+        canonical_sha256(&wrapped).unwrap()
+    );
+    assert!(Wrapper { inner: f64::NAN }.canonical_hash().is_err());
+}
@@ -0,0 +1,94 @@
+//! Types from other crates that serialize as strings pass through
+//! canonicalization byte for byte. Any normalization has to happen in the
+//! type, before serialization.
+
+use canonhash::canon::to_string;
+use canonhash::canonical_sha256;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+struct Event {
+    id: Uuid,
+    timestamp: DateTime<Utc>,
+}
+
+fn timestamp() -> DateTime<Utc> {
+    NaiveDate::from_ymd_opt(2026, 2, 12)
+        .unwrap()
+        .and_hms_opt(15, 50, 12)
+        .unwrap()
+        .and_utc()
+}
+
+#[test]
+fn datetimes_keep_their_rfc3339_text() {
+    let canonical = to_string(&timestamp()).unwrap();
+    assert_eq!(canonical, r#""2026-02-12T15:50:12Z""#);
+    assert_eq!(canonical, serde_json::to_string(&timestamp()).unwrap());
+
+    let precise = Utc.timestamp_opt(1_770_911_412, 123_456_789).unwrap();
+    assert_eq!(
+        to_string(&precise).unwrap(),
+        r#""2026-02-12T15:50:12.123456789Z""#
+    );
+}
+
+#[test]
+fn offsets_are_not_normalized() {
+    // The same instant with a different offset is a different string, so it
+    // hashes differently. Convert to `Utc` first to hash instants.
+    let offset = timestamp().with_timezone(&FixedOffset::east_opt(2 * 3600).unwrap());
+    assert_eq!(offset, timestamp());
+    assert_eq!(
+        to_string(&offset).unwrap(),
+        r#""2026-02-12T17:50:12+02:00""#
+    );
+    assert_ne!(
+        canonical_sha256(&offset).unwrap(),
+        canonical_sha256(&timestamp()).unwrap()
+    );
+    assert_eq!(
+        canonical_sha256(&offset.with_timezone(&Utc)).unwrap(),
+        canonical_sha256(&timestamp()).unwrap()
+    );
+}
+
+#[test]
+fn equal_uuids_hash_the_same_however_built() {
+    let text = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+    let ids = [
+        Uuid::parse_str(text).unwrap(),
+        Uuid::parse_str(&text.to_uppercase()).unwrap(),
+        Uuid::parse_str(&text.replace('-', "")).unwrap(),
+        Uuid::from_u128(0x67e5504410b1426f9247bb680e5fe0c8),
+        Uuid::from_bytes(Uuid::parse_str(text).unwrap().into_bytes()),
+    ];
+    for id in ids {
+        assert_eq!(to_string(&id).unwrap(), format!("\"{text}\""));
+        assert_eq!(
+            canonical_sha256(&Event {
+                id,
+                timestamp: timestamp()
+            })
+            .unwrap(),
+            canonical_sha256(&Event {
+                id: ids[0],
+                timestamp: timestamp()
+            })
+            .unwrap()
+        );
+    }
+}
+
+#[test]
+fn uuid_strings_are_not_lowercased() {
+    // Only `Uuid` normalizes: the same id held as an uppercase `String`
+    // canonicalizes as written.
+    let upper = "67E55044-10B1-426F-9247-BB680E5FE0C8";
+    assert_ne!(
+        canonical_sha256(&upper).unwrap(),
+        canonical_sha256(&Uuid::parse_str(upper).unwrap()).unwrap()
+    );
+}
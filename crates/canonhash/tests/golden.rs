@@ -0,0 +1,45 @@
+use canonhash::fixture::{check_fixture, GoldenFixture};
+
+#[test]
+fn ops_stack_golden_fixture() {
+    let fixture: GoldenFixture =
+        serde_json::from_str(include_str!("fixtures/golden-hash-test-v1.json")).unwrap();
+    check_fixture(&fixture).unwrap();
+}
+
+#[test]
+fn empty_containers_and_top_level_scalars() {
+    let fixtures: Vec<GoldenFixture> =
+        serde_json::from_str(include_str!("fixtures/empty-and-scalars.json")).unwrap();
+    for fixture in &fixtures {
+        check_fixture(fixture).unwrap_or_else(|err| panic!("{}: {err}", fixture.input));
+    }
+}
+
+#[test]
+fn empty_rust_values_have_no_whitespace() {
+    #[derive(serde::Serialize)]
+    struct NoFields {}
+    #[derive(serde::Serialize)]
+    struct Empties {
+        b: Vec<u8>,
+        a: NoFields,
+    }
+
+    let empty_map = std::collections::BTreeMap::<String, u8>::new();
+    assert_eq!(canonhash::canonicalize(&empty_map).unwrap(), b"{}");
+    assert_eq!(canonhash::canonicalize(&Vec::<u8>::new()).unwrap(), b"[]");
+    assert_eq!(canonhash::canonicalize(&NoFields {}).unwrap(), b"{}");
+    assert_eq!(
+        canonhash::canonicalize(&Empties {
+            b: Vec::new(),
+            a: NoFields {},
+        })
+        .unwrap(),
+        br#"{"a":{},"b":[]}"#
+    );
+    assert_eq!(
+        canonhash::canonicalize_str_strict(" { \"a\" : { } , \"b\" : [ ] } ").unwrap(),
+        br#"{"a":{},"b":[]}"#
+    );
+}
@@ -0,0 +1,52 @@
+//! Canonical output must be a fixed point: parsing it and canonicalizing again
+//! yields the same bytes, and `is_canonical` accepts it.
+
+use canonhash::{canonicalize, is_canonical};
+use proptest::prelude::*;
+use serde_json::{Map, Number, Value};
+
+fn arb_number() -> impl Strategy<Value = Number> {
+    prop_oneof![
+        any::<i64>().prop_map(Number::from),
+        any::<u64>().prop_map(Number::from),
+        any::<f64>().prop_filter_map("finite", Number::from_f64),
+        // Values near the ES exponent thresholds and with short decimals.
+        (-30i32..30, 1u32..1000).prop_filter_map("finite", |(e, m)| Number::from_f64(
+            f64::from(m) * 10f64.powi(e)
+        )),
+    ]
+}
+
+fn arb_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        arb_number().prop_map(Value::Number),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            prop::collection::vec((any::<String>(), inner), 0..8)
+                .prop_map(|members| Value::Object(members.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn canonicalization_is_idempotent(value in arb_value()) {
+        let first = canonicalize(&value).unwrap();
+        let reparsed: Value = serde_json::from_slice(&first).unwrap();
+        let second = canonicalize(&reparsed).unwrap();
+        prop_assert_eq!(&first, &second);
+        prop_assert!(is_canonical(&first));
+    }
+
+    #[test]
+    fn is_canonical_agrees_with_canonicalize(value in arb_value()) {
+        let pretty = serde_json::to_vec_pretty(&value).unwrap();
+        let canonical = canonicalize(&value).unwrap();
+        prop_assert_eq!(is_canonical(&pretty), pretty == canonical);
+    }
+}
@@ -0,0 +1,29 @@
+//! The reference test vectors from cyberphone/json-canonicalization, the
+//! RFC 8785 author's implementation. See `fixtures/jcs/README.md`.
+
+macro_rules! vectors {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                let input = include_str!(concat!("fixtures/jcs/input/", stringify!($name), ".json"));
+                let expected = include_bytes!(concat!("fixtures/jcs/output/", stringify!($name), ".json"));
+                check(input, expected);
+            }
+        )*
+    };
+}
+
+fn check(input: &str, expected: &[u8]) {
+    let value: serde_json::Value = serde_json::from_str(input).unwrap();
+    let canonical = canonhash::canonicalize(&value).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&canonical),
+        String::from_utf8_lossy(expected)
+    );
+    assert_eq!(canonical, expected);
+    assert_eq!(canonhash::canonicalize_str_strict(input).unwrap(), expected);
+    assert_eq!(canonhash::canonicalize_value(&value).unwrap(), expected);
+}
+
+vectors!(arrays, french, structures, unicode, values, weird);
@@ -0,0 +1,68 @@
+//! The map type holding a set of members never affects the canonical form:
+//! `HashMap`, `BTreeMap` and `IndexMap` iterate in different orders, and
+//! every object is sorted regardless.
+
+use std::collections::{BTreeMap, HashMap};
+
+use canonhash::{canonical_sha256, canonicalize};
+use indexmap::IndexMap;
+use serde::Serialize;
+
+/// Includes keys whose `str` order (and so `BTreeMap` order) differs from
+/// the UTF-16 order RFC 8785 sorts by.
+const PAIRS: [(&str, u32); 5] = [
+    ("zeta", 1),
+    ("alpha", 2),
+    ("\u{e000}", 3),
+    ("\u{1f600}", 4),
+    ("Beta", 5),
+];
+
+const EXPECTED: &str = "{\"Beta\":5,\"alpha\":2,\"zeta\":1,\"\u{1f600}\":4,\"\u{e000}\":3}";
+
+#[test]
+fn all_map_types_canonicalize_identically() {
+    let hash: HashMap<&str, u32> = PAIRS.into_iter().collect();
+    let btree: BTreeMap<&str, u32> = PAIRS.into_iter().collect();
+    let forward: IndexMap<&str, u32> = PAIRS.into_iter().collect();
+    let reverse: IndexMap<&str, u32> = PAIRS.into_iter().rev().collect();
+
+    let expected = canonical_sha256(&btree).unwrap();
+    assert_eq!(canonicalize(&btree).unwrap(), EXPECTED.as_bytes());
+    for canonical in [
+        canonicalize(&hash).unwrap(),
+        canonicalize(&forward).unwrap(),
+        canonicalize(&reverse).unwrap(),
+    ] {
+        assert_eq!(canonical, EXPECTED.as_bytes());
+    }
+    for digest in [
+        canonical_sha256(&hash).unwrap(),
+        canonical_sha256(&forward).unwrap(),
+        canonical_sha256(&reverse).unwrap(),
+    ] {
+        assert_eq!(digest, expected);
+    }
+}
+
+#[test]
+fn changing_a_fields_map_type_keeps_its_hash() {
+    #[derive(Serialize)]
+    struct Record<M> {
+        id: u32,
+        labels: M,
+    }
+
+    let before = Record {
+        id: 7,
+        labels: PAIRS.into_iter().collect::<HashMap<_, _>>(),
+    };
+    let after = Record {
+        id: 7,
+        labels: PAIRS.into_iter().rev().collect::<IndexMap<_, _>>(),
+    };
+    assert_eq!(
+        canonical_sha256(&before).unwrap(),
+        canonical_sha256(&after).unwrap()
+    );
+}
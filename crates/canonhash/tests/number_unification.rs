@@ -0,0 +1,85 @@
+//! RFC 8785 writes every number as ECMAScript would, so an integer-typed
+//! value and a float-typed one that hold the same number canonicalize the
+//! same, whichever serde hook the type goes through.
+
+use canonhash::canon::to_string;
+use canonhash::canonical_sha256;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Unsigned {
+    n: u64,
+}
+
+#[derive(Serialize)]
+struct Signed {
+    n: i64,
+}
+
+#[derive(Serialize)]
+struct Float {
+    n: f64,
+}
+
+#[derive(Serialize)]
+struct Narrow {
+    n: i32,
+}
+
+#[test]
+fn integer_and_float_fields_holding_100_canonicalize_the_same() {
+    let forms = [
+        to_string(&Unsigned { n: 100 }).unwrap(),
+        to_string(&Signed { n: 100 }).unwrap(),
+        to_string(&Float { n: 100.0 }).unwrap(),
+        to_string(&Narrow { n: 100 }).unwrap(),
+    ];
+    assert!(forms.iter().all(|form| form == r#"{"n":100}"#), "{forms:?}");
+    assert_eq!(
+        canonical_sha256(&Unsigned { n: 100 }).unwrap(),
+        canonical_sha256(&Float { n: 100.0 }).unwrap()
+    );
+}
+
+#[test]
+fn every_serde_number_hook_writes_the_same_integer() {
+    let expected = "[100,100,100,100,100,100,100,100,100,100,100,100]";
+    let all = (
+        100u8, 100u16, 100u32, 100u64, 100u128, 100i8, 100i16, 100i32, 100i64, 100i128, 100f32,
+        100f64,
+    );
+    assert_eq!(to_string(&all).unwrap(), expected);
+    assert_eq!(
+        to_string(&(-100i64, -100.0f64, 0u8, -0.0f64)).unwrap(),
+        "[-100,-100,0,0]"
+    );
+}
+
+#[test]
+fn exponent_and_fraction_text_normalizes_to_an_integer() {
+    for text in ["1e2", "1E2", "1e+2", "100.0", "10000e-2", "0.1e3"] {
+        let parsed: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(to_string(&parsed).unwrap(), "100", "{text}");
+        assert_eq!(
+            canonical_sha256(&parsed).unwrap(),
+            canonical_sha256(&100u64).unwrap(),
+            "{text}"
+        );
+    }
+}
+
+#[test]
+fn integers_agree_with_floats_up_to_2_pow_53() {
+    let max_safe = 1u64 << 53;
+    assert_eq!(
+        to_string(&max_safe).unwrap(),
+        to_string(&(max_safe as f64)).unwrap()
+    );
+    // Past that a float can't hold every integer, so the integer's exact
+    // digits and the nearest float's shortest form differ.
+    assert_eq!(to_string(&u64::MAX).unwrap(), "18446744073709551615");
+    assert_eq!(
+        to_string(&(u64::MAX as f64)).unwrap(),
+        "18446744073709552000"
+    );
+}
@@ -0,0 +1,75 @@
+//! `serde_json::value::RawValue` fields hold JSON text that serde never
+//! parsed. Canonicalization parses it and writes the value it holds, so the
+//! text's spacing and member order don't reach the output.
+
+use canonhash::canon::to_string;
+use canonhash::{canonical_sha256, CanonError, Canonicalizer};
+use serde::Serialize;
+use serde_json::json;
+use serde_json::value::RawValue;
+
+#[derive(Serialize)]
+struct Envelope {
+    kind: &'static str,
+    payload: Box<RawValue>,
+}
+
+fn raw(text: &str) -> Box<RawValue> {
+    RawValue::from_string(text.to_owned()).unwrap()
+}
+
+#[test]
+fn raw_text_is_reparsed_and_canonicalized() {
+    let payload = raw(r#"{ "b":1 , "a":2 }"#);
+    assert_eq!(to_string(&payload).unwrap(), r#"{"a":2,"b":1}"#);
+
+    let envelope = Envelope {
+        kind: "event",
+        payload: raw(r#"{ "b":1 , "a":2 }"#),
+    };
+    assert_eq!(
+        to_string(&envelope).unwrap(),
+        r#"{"kind":"event","payload":{"a":2,"b":1}}"#
+    );
+    assert_eq!(
+        canonical_sha256(&envelope).unwrap(),
+        canonical_sha256(&json!({"payload": {"a": 2, "b": 1}, "kind": "event"})).unwrap()
+    );
+}
+
+#[test]
+fn scalars_and_nested_text_are_normalized() {
+    assert_eq!(to_string(&raw(" 1E2 ")).unwrap(), "100");
+    assert_eq!(to_string(&raw(r#""A""#)).unwrap(), r#""A""#);
+    assert_eq!(
+        to_string(&raw(r#"[ {"z":[],"y":0.50} , null ]"#)).unwrap(),
+        r#"[{"y":0.5,"z":[]},null]"#
+    );
+    let borrowed: &RawValue = serde_json::from_str(r#"{"x": { "d":4, "c":3 }}"#).unwrap();
+    assert_eq!(to_string(borrowed).unwrap(), r#"{"x":{"c":3,"d":4}}"#);
+}
+
+#[test]
+fn canonicalizer_options_apply_inside_raw_text() {
+    let canonical = Canonicalizer::new()
+        .omit_nulls(true)
+        .canonicalize(&raw(r#"{"b":null,"a":1}"#))
+        .unwrap();
+    assert_eq!(canonical, br#"{"a":1}"#);
+
+    let nested = raw(r#"[[[1]]]"#);
+    assert!(matches!(
+        Canonicalizer::new().max_depth(2).canonicalize(&nested),
+        Err(CanonError::DepthExceeded { .. })
+    ));
+}
+
+#[test]
+fn repeated_keys_in_raw_text_are_rejected() {
+    let err = to_string(&raw(r#"{"a":1,"a":2}"#)).unwrap_err();
+    assert!(matches!(err, CanonError::DuplicateKey { key } if key == "a"));
+    assert!(matches!(
+        to_string(&raw(r#""\uD800""#)),
+        Err(CanonError::InvalidSurrogate { .. })
+    ));
+}
@@ -0,0 +1,56 @@
+//! Struct members are sorted by the names serde writes, after `rename` and
+//! `rename_all`, never by the Rust field names.
+
+use canonhash::canon::to_string;
+use canonhash::{canonical_sha256, TypedCanonicalizer};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(non_snake_case)]
+struct Person {
+    first_name: &'static str,
+    aBc: u32,
+    last_name: &'static str,
+    // Last by its Rust name, first by its wire name.
+    #[serde(rename = "Zip")]
+    zip_code: &'static str,
+}
+
+fn person() -> Person {
+    Person {
+        first_name: "Ada",
+        aBc: 1,
+        last_name: "Lovelace",
+        zip_code: "N1",
+    }
+}
+
+#[test]
+fn members_sort_by_wire_name() {
+    assert_eq!(
+        to_string(&person()).unwrap(),
+        r#"{"Zip":"N1","aBc":1,"firstName":"Ada","lastName":"Lovelace"}"#
+    );
+}
+
+#[test]
+fn hash_matches_the_wire_form_not_the_rust_form() {
+    let wire = json!({"firstName": "Ada", "aBc": 1, "lastName": "Lovelace", "Zip": "N1"});
+    let rust = json!({"first_name": "Ada", "aBc": 1, "last_name": "Lovelace", "zip_code": "N1"});
+    let digest = canonical_sha256(&person()).unwrap();
+    assert_eq!(digest, canonical_sha256(&wire).unwrap());
+    assert_ne!(digest, canonical_sha256(&rust).unwrap());
+}
+
+#[test]
+fn cached_layouts_use_wire_names_too() {
+    let canon = TypedCanonicalizer::<Person>::new();
+    for _ in 0..2 {
+        assert_eq!(
+            canon.canonicalize(&person()).unwrap(),
+            canonhash::canonicalize(&person()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! `Canonicalizer::sort_arrays_at` treats the arrays it names as sets and
+//! leaves every other array in order, using the shape of the golden-hash
+//! example record.
+
+use std::collections::HashMap;
+
+use canonhash::{CanonMode, Canonicalizer, HashAlgorithm};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct TestData {
+    #[serde(rename = "testId")]
+    test_id: String,
+    timestamp: String,
+    data: DataContent,
+}
+
+#[derive(Serialize)]
+struct DataContent {
+    modules: Vec<String>,
+    nested: Nested,
+}
+
+#[derive(Serialize)]
+struct Nested {
+    array: Vec<u32>,
+    object: HashMap<String, String>,
+}
+
+fn record(modules: [&str; 3], array: Vec<u32>) -> TestData {
+    TestData {
+        test_id: "golden-hash-test-v1".to_owned(),
+        timestamp: "2026-02-12T15:50:12Z".to_owned(),
+        data: DataContent {
+            modules: modules.map(String::from).to_vec(),
+            nested: Nested {
+                array,
+                object: HashMap::from([
+                    ("zeta".to_owned(), "last".to_owned()),
+                    ("alpha".to_owned(), "first".to_owned()),
+                ]),
+            },
+        },
+    }
+}
+
+fn sha256(canon: &Canonicalizer, value: &TestData) -> Vec<u8> {
+    canon.hash_with(HashAlgorithm::Sha256, value).unwrap()
+}
+
+#[test]
+fn reordered_modules_hash_equal_only_when_the_path_is_marked() {
+    let original = record(
+        ["marketIntelligence", "notifications", "automation"],
+        vec![3, 1, 2],
+    );
+    let reordered = record(
+        ["automation", "marketIntelligence", "notifications"],
+        vec![3, 1, 2],
+    );
+
+    let strict = Canonicalizer::new();
+    assert_ne!(sha256(&strict, &original), sha256(&strict, &reordered));
+    let elsewhere = Canonicalizer::new().sort_arrays_at(&["/data/nested/array"]);
+    assert_ne!(
+        sha256(&elsewhere, &original),
+        sha256(&elsewhere, &reordered)
+    );
+
+    let marked = Canonicalizer::new().sort_arrays_at(&["/data/modules"]);
+    assert_eq!(marked.mode(), CanonMode::Relaxed);
+    assert_eq!(sha256(&marked, &original), sha256(&marked, &reordered));
+    assert_eq!(
+        marked.to_string(&original).unwrap(),
+        r#"{"data":{"modules":["automation","marketIntelligence","notifications"],"nested":{"array":[3,1,2],"object":{"alpha":"first","zeta":"last"}}},"testId":"golden-hash-test-v1","timestamp":"2026-02-12T15:50:12Z"}"#
+    );
+}
+
+#[test]
+fn unmarked_arrays_keep_their_order() {
+    let modules = ["marketIntelligence", "notifications", "automation"];
+    let marked = Canonicalizer::new().sort_arrays_at(&["/data/modules"]);
+    assert_ne!(
+        sha256(&marked, &record(modules, vec![3, 1, 2])),
+        sha256(&marked, &record(modules, vec![1, 2, 3]))
+    );
+
+    let both = marked.sort_arrays_at(&["/data/nested/array"]);
+    assert_eq!(
+        sha256(&both, &record(modules, vec![3, 1, 2])),
+        sha256(&both, &record(modules, vec![1, 2, 3]))
+    );
+}
@@ -0,0 +1,116 @@
+//! Tag and content keys that serde injects for internally and adjacently
+//! tagged enums are ordinary members of the object: serde writes the tag
+//! first, and the canonical form sorts it among the fields like any other
+//! key.
+
+use std::collections::HashMap;
+
+use canonhash::canon::to_string;
+use canonhash::{canonical_sha256, canonicalize};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Internal {
+    Created { zeta: u32, alpha: u32 },
+    Wrapped(Fields),
+    Labels(HashMap<&'static str, u32>),
+    Cleared,
+}
+
+#[derive(Serialize)]
+struct Fields {
+    zeta: u32,
+    alpha: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data")]
+enum Adjacent {
+    Moved { zeta: u32, alpha: u32 },
+    Count(u32),
+    Reset,
+}
+
+#[test]
+fn internal_tag_sorts_among_struct_variant_fields() {
+    let event = Internal::Created { zeta: 1, alpha: 2 };
+    assert_eq!(
+        to_string(&event).unwrap(),
+        r#"{"alpha":2,"type":"Created","zeta":1}"#
+    );
+    assert_eq!(
+        canonical_sha256(&event).unwrap(),
+        canonical_sha256(&json!({"zeta": 1, "alpha": 2, "type": "Created"})).unwrap()
+    );
+}
+
+#[test]
+fn internal_tag_sorts_into_newtype_contents() {
+    assert_eq!(
+        to_string(&Internal::Wrapped(Fields {
+            zeta: 1,
+            alpha: "a"
+        }))
+        .unwrap(),
+        r#"{"alpha":"a","type":"Wrapped","zeta":1}"#
+    );
+
+    // A map goes through `serialize_map` rather than `serialize_struct`,
+    // and iterates in arbitrary order.
+    let labels = HashMap::from([("z", 1), ("u", 2), ("a", 3)]);
+    assert_eq!(
+        to_string(&Internal::Labels(labels)).unwrap(),
+        r#"{"a":3,"type":"Labels","u":2,"z":1}"#
+    );
+    assert_eq!(
+        to_string(&Internal::Cleared).unwrap(),
+        r#"{"type":"Cleared"}"#
+    );
+}
+
+#[test]
+fn adjacent_tag_and_content_keys_are_sorted() {
+    assert_eq!(
+        to_string(&Adjacent::Moved { zeta: 1, alpha: 2 }).unwrap(),
+        r#"{"data":{"alpha":2,"zeta":1},"type":"Moved"}"#
+    );
+    assert_eq!(
+        to_string(&Adjacent::Count(3)).unwrap(),
+        r#"{"data":3,"type":"Count"}"#
+    );
+    assert_eq!(to_string(&Adjacent::Reset).unwrap(), r#"{"type":"Reset"}"#);
+}
+
+#[test]
+fn tag_position_does_not_change_the_canonical_form() {
+    // The same members as the tagged enum produces, written tag last.
+    #[derive(Serialize)]
+    struct TagLast {
+        data: Fields,
+        r#type: &'static str,
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type", content = "data")]
+    enum Tagged {
+        Wrapped(Fields),
+    }
+
+    let tagged = Tagged::Wrapped(Fields {
+        zeta: 1,
+        alpha: "a",
+    });
+    let tag_last = TagLast {
+        data: Fields {
+            zeta: 1,
+            alpha: "a",
+        },
+        r#type: "Wrapped",
+    };
+    assert_eq!(
+        canonicalize(&tagged).unwrap(),
+        canonicalize(&tag_last).unwrap()
+    );
+}